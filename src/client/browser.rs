@@ -0,0 +1,108 @@
+use macroquad::prelude::*;
+
+use snake::game_core::net::{version_mismatch_text, NetOptions, NetStatus};
+use snake::game_core::{ClientMsg, MatchPhase, RoomInfo, ServerMsg};
+
+use super::transport::{browse, NetTransport};
+use super::draw_button;
+
+// Pre-connect room list, polled over its own short-lived connection
+pub struct RoomBrowser {
+    net: Box<dyn NetTransport>,
+    rooms: Vec<RoomInfo>,
+    error: Option<String>,
+    scroll: usize,
+    last_refresh: f64,
+}
+
+pub enum BrowserAction {
+    None,
+    Back,
+    Pick(String),
+}
+
+const BROWSER_REFRESH_SECS: f64 = 2.0;
+const BROWSER_ROW_H: f32 = 30.0;
+
+impl RoomBrowser {
+    pub fn open(server_addr: String, options: NetOptions) -> Self {
+        RoomBrowser { net: browse(server_addr, options), rooms: Vec::new(), error: None, scroll: 0, last_refresh: get_time() }
+    }
+
+    pub fn update(&mut self) {
+        while let Some(status) = self.net.poll_status() {
+            if let NetStatus::Failed(reason) = status {
+                self.error.get_or_insert(reason);
+            }
+        }
+        while let Some(msg) = self.net.try_recv() {
+            match msg {
+                ServerMsg::RoomList(rooms) => self.rooms = rooms,
+                ServerMsg::VersionMismatch { server_version } => self.error = Some(version_mismatch_text(server_version)),
+                ServerMsg::Kick { reason } => self.error = Some(format!("Kicked: {}", reason)),
+                _ => {}
+            }
+        }
+        if get_time() - self.last_refresh > BROWSER_REFRESH_SECS {
+            self.net.send(ClientMsg::ListRooms);
+            self.last_refresh = get_time();
+        }
+    }
+
+    pub fn draw(&mut self, panel: Rect) -> BrowserAction {
+        draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::new(0.1, 0.1, 0.1, 0.9));
+        draw_text("Rooms", panel.x + 40.0, panel.y + 50.0, 40.0, WHITE);
+
+        let list_y = panel.y + 90.0;
+        let visible = ((panel.h - 170.0) / BROWSER_ROW_H).max(1.0) as usize;
+        let max_scroll = self.rooms.len().saturating_sub(visible);
+        let (_, wheel) = mouse_wheel();
+        if wheel > 0.0 {
+            self.scroll = self.scroll.saturating_sub(1);
+        } else if wheel < 0.0 {
+            self.scroll += 1;
+        }
+        self.scroll = self.scroll.min(max_scroll);
+
+        let columns = [panel.x + 40.0, panel.x + 140.0, panel.x + panel.w - 260.0, panel.x + panel.w - 160.0];
+        for (text, x) in ["Code", "Name", "Players", "Status"].iter().zip(columns) {
+            draw_text(text, x, list_y, 22.0, GRAY);
+        }
+        if let Some(error) = &self.error {
+            draw_text(error, panel.x + 40.0, list_y + BROWSER_ROW_H, 22.0, RED);
+        } else if self.rooms.is_empty() {
+            draw_text("No rooms yet - leave the code empty to create one", panel.x + 40.0, list_y + BROWSER_ROW_H, 22.0, GRAY);
+        }
+
+        let (mx, my) = mouse_position();
+        let mut action = BrowserAction::None;
+        for (i, room) in self.rooms.iter().skip(self.scroll).take(visible).enumerate() {
+            let row = Rect { x: panel.x + 30.0, y: list_y + 8.0 + i as f32 * BROWSER_ROW_H, w: panel.w - 60.0, h: BROWSER_ROW_H };
+            let full = room.players >= room.capacity;
+            if row.contains(vec2(mx, my)) && !full {
+                draw_rectangle(row.x, row.y, row.w, row.h, Color::new(0.2, 0.3, 0.2, 1.0));
+                if is_mouse_button_pressed(MouseButton::Left) {
+                    action = BrowserAction::Pick(room.code.clone());
+                }
+            }
+            let color = if full { GRAY } else { WHITE };
+            let status = if full { "full" } else if room.phase == MatchPhase::Lobby { "lobby" } else { "playing" };
+            let players = format!("{}/{}", room.players, room.capacity);
+            let name = format!("{} - {}{}", room.name, room.mode, if room.locked { " (private)" } else { "" });
+            let y = row.y + 22.0;
+            for (text, x) in [room.code.as_str(), name.as_str(), players.as_str(), status].iter().zip(columns) {
+                draw_text(text, x, y, 22.0, color);
+            }
+        }
+        if max_scroll > 0 {
+            let hint = format!("{}-{} of {} (scroll for more)", self.scroll + 1, (self.scroll + visible).min(self.rooms.len()), self.rooms.len());
+            draw_text(&hint, panel.x + 40.0, panel.y + panel.h - 40.0, 20.0, GRAY);
+        }
+
+        let back_rect = Rect { x: panel.x + panel.w - 200.0, y: panel.y + panel.h - 70.0, w: 160.0, h: 44.0 };
+        if draw_button(back_rect, "Back") || is_key_pressed(KeyCode::Escape) {
+            action = BrowserAction::Back;
+        }
+        action
+    }
+}
@@ -0,0 +1,303 @@
+use macroquad::prelude::*;
+
+use snake::game_core::net::{version_mismatch_text, NetOptions, NetStatus, RoomChoice};
+use snake::game_core::replay::Replay;
+use snake::game_core::{normalize_name, BoardMap, ClientMsg, EmoteMsg, ErrorCode, FoodItem, GameConfig, GameEvent, GameMode, LobbyMsg, PauseMsg, PING_INTERVAL_MS, PlayerId, PlayerState, QueueMsg, RematchMsg, RoundEndMsg, ServerMsg, StateMsg};
+
+use super::hud::{kill_text, push_toast, Bubble, BUBBLE_SECS, Toast};
+use super::particles::{cell_centre, DeathFade, Particles, SHAKE_SECS};
+use super::render::{food_color, power_up_style, View};
+use super::replay_view::{record, start_recording, ReplayTransport};
+use super::sound::{self, Effect};
+use super::theme::{snake_colors, Theme};
+use super::transport::{connect, Auth, NetTransport, OfflineTransport};
+
+// Everything that belongs to one connection to the server, dropped together when it ends
+pub struct Connection {
+    pub net: Box<dyn NetTransport>,
+    // what it takes to connect again: the room is filled in by the server's Session
+    pub username: String,
+    // the id of our own snake, which the server's Session tells
+    pub me: Option<PlayerId>,
+    // when our snake last came onto the board, for the pulse that points it out
+    pub spawned_at: Option<f64>,
+    pub view: View,
+    pub server_addr: String,
+    room_code: Option<String>,
+    // how the wait for a match goes, until matchmaking finds one
+    pub queue: Option<QueueMsg>,
+    // the password of the private room we're in, or created
+    room_password: Option<String>,
+    pub game_config: GameConfig,
+    pub map: Option<BoardMap>,
+    pub latest_state: Option<StateMsg>,
+    // events not shown yet; deaths come with the snake as it was before it died
+    pending_events: Vec<(GameEvent, Option<PlayerState>)>,
+    pub particles: Particles,
+    // the board shakes until then, after the local snake died
+    pub shake_until: f64,
+    pub death_fade: Option<DeathFade>,
+    pub latest_lobby: Option<LobbyMsg>,
+    pub round_end: Option<RoundEndMsg>,
+    pub rematch: Option<RematchMsg>,
+    pub voted_rematch: bool,
+    pub pause: Option<PauseMsg>,
+    pub ready: bool,
+    // seq of the last Input sent on this connection
+    pub input_seq: u32,
+    // players steering from this window, more than one in local versus
+    pub local_players: usize,
+    // where friends can join, when we host the server
+    pub hosted_at: Option<String>,
+    // set when the server turned us away; ends the connection once it closes
+    error: Option<String>,
+    // why it did, to tell a private room's password prompt apart
+    pub refused: Option<ErrorCode>,
+    pub toasts: Vec<Toast>,
+    // recent deaths, shown apart from the toasts
+    pub kill_feed: Vec<Toast>,
+    pub bubbles: Vec<Bubble>,
+    // round trip of the last answered Ping
+    pub rtt_ms: Option<u64>,
+    last_ping: f64,
+    // where everything that arrives is written, with --record
+    recording: Option<std::fs::File>,
+    // the server took our Register or Login; the login is saved from then on
+    pub logged_in: bool,
+}
+
+impl Connection {
+    pub fn open(server_addr: String, username: String, room: RoomChoice, auth: Auth, options: NetOptions) -> Self {
+        let (room_code, queue, room_password) = match &room {
+            RoomChoice::Join { code, password } => (Some(code.clone()), None, password.clone()),
+            RoomChoice::Create { password, .. } => (None, None, password.clone()),
+            RoomChoice::Queue => (None, Some(QueueMsg::default()), None),
+        };
+        let net = connect(server_addr.clone(), username.clone(), room, auth, options);
+        let mut conn = Connection::with_transport(net, server_addr, username, room_code);
+        conn.queue = queue;
+        conn.room_password = room_password;
+        conn
+    }
+
+    // What connecting again should ask for: the room we were in, or the same as before
+    pub fn target(&self, mode: GameMode, wrap: bool) -> RoomChoice {
+        match &self.room_code {
+            Some(code) => RoomChoice::Join { code: code.clone(), password: self.room_password.clone() },
+            None if self.queue.is_some() => RoomChoice::Queue,
+            None => RoomChoice::Create { mode, wrap, password: self.room_password.clone() },
+        }
+    }
+
+    // A match that never touches the network, with a snake for each name steered from this
+    // window; the first name is ours
+    pub fn offline(names: Vec<String>, mode: GameMode, wrap: bool) -> Self {
+        let net = Box::new(OfflineTransport::new(&names, mode, wrap));
+        let me = net.state.players[0].id;
+        let mut conn = Connection::with_transport(net, String::new(), names[0].clone(), None);
+        conn.local_players = names.len();
+        conn.me = Some(me);
+        conn
+    }
+
+    // A recorded match, shown as the player who recorded it saw it
+    pub fn replay(replay: Replay) -> Self {
+        let username = replay.header.player.clone();
+        Connection::with_transport(Box::new(ReplayTransport::new(replay)), String::new(), username, None)
+    }
+
+    // Records the connection to `path` as a replay, when there is one
+    pub fn recorded(mut self, path: Option<&str>) -> Self {
+        match path.map(start_recording) {
+            Some(Ok(file)) => self.recording = Some(file),
+            Some(Err(e)) => push_toast(&mut self.toasts, e, RED),
+            None => {}
+        }
+        self
+    }
+
+    // Particles for the events since the last frame: food and power-ups bursting, snakes
+    // dissolving, and a shake and a slow fade for our own death. Our own snake's eating and
+    // dying are heard too.
+    pub fn show_events(&mut self, palette: &[(Color, Color)], theme: &Theme) {
+        for (event, victim) in std::mem::take(&mut self.pending_events) {
+            match event {
+                GameEvent::Eat { player, pos, kind } => {
+                    if Some(player) == self.me {
+                        sound::play(Effect::Eat);
+                    }
+                    let food = FoodItem { pos, kind, expires_tick: None };
+                    self.particles.burst(cell_centre(pos), 12, 4.0, 0.5, food_color(&food, 0, theme));
+                }
+                GameEvent::PowerUp { pos, kind, .. } => {
+                    self.particles.burst(cell_centre(pos), 16, 5.0, 0.6, power_up_style(kind).0);
+                }
+                GameEvent::Death(_) => {
+                    let Some(victim) = victim else { continue };
+                    let (head, body) = snake_colors(&victim, palette);
+                    for (i, &pos) in victim.snake.iter().enumerate() {
+                        self.particles.burst(cell_centre(pos), 3, 1.5, 0.9, if i == 0 { head } else { body });
+                    }
+                    if Some(victim.id) == self.me {
+                        sound::play(Effect::Death);
+                        self.shake_until = get_time() + SHAKE_SECS;
+                        self.death_fade = Some(DeathFade { snake: victim.snake, head, body, start: get_time() });
+                    }
+                }
+            }
+        }
+    }
+
+    fn with_transport(net: Box<dyn NetTransport>, server_addr: String, username: String, room_code: Option<String>) -> Self {
+        Connection {
+            net,
+            me: None,
+            spawned_at: None,
+            view: View::default(),
+            username,
+            server_addr,
+            room_code,
+            queue: None,
+            room_password: None,
+            game_config: GameConfig::default(),
+            map: None,
+            latest_state: None,
+            pending_events: Vec::new(),
+            particles: Particles::default(),
+            shake_until: 0.0,
+            death_fade: None,
+            latest_lobby: None,
+            round_end: None,
+            rematch: None,
+            voted_rematch: false,
+            pause: None,
+            ready: false,
+            input_seq: 0,
+            local_players: 1,
+            hosted_at: None,
+            error: None,
+            refused: None,
+            toasts: Vec::new(),
+            kill_feed: Vec::new(),
+            bubbles: Vec::new(),
+            rtt_ms: None,
+            last_ping: get_time(),
+            recording: None,
+            logged_in: false,
+        }
+    }
+
+    // Handles everything the server sent since the last frame. Returns the reason the
+    // connection is over, if it is.
+    pub fn update(&mut self) -> Option<String> {
+        // Keep the connection alive and measure the round trip
+        if (get_time() - self.last_ping) * 1000.0 >= PING_INTERVAL_MS as f64 {
+            self.last_ping = get_time();
+            self.net.send(ClientMsg::Ping { sent_ms: (self.last_ping * 1000.0) as u64, rtt_ms: self.rtt_ms });
+        }
+
+        while let Some(status) = self.net.poll_status() {
+            match status {
+                // the server's own explanation beats "connection closed"
+                NetStatus::Failed(reason) => return Some(self.error.take().unwrap_or(reason)),
+                NetStatus::Degraded(text) => push_toast(&mut self.toasts, text, YELLOW),
+                NetStatus::Connected => {}
+            }
+        }
+
+        // Drain any received states (keep only latest)
+        while let Some(update) = self.net.try_recv() {
+            if let Some(file) = self.recording.as_mut()
+                && let Err(e) = record(file, &update, &self.username)
+            {
+                push_toast(&mut self.toasts, format!("Recording stopped: {}", e), RED);
+                self.recording = None;
+            }
+            match update {
+                ServerMsg::VersionMismatch { server_version } => self.error = Some(version_mismatch_text(server_version)),
+                // refused before getting anywhere means the connection is over
+                ServerMsg::Error(error) if self.latest_state.is_none() && self.latest_lobby.is_none() => {
+                    self.refused = Some(error.code);
+                    self.error = Some(error.message);
+                }
+                ServerMsg::Error(error) => push_toast(&mut self.toasts, error.message, RED),
+                ServerMsg::Info(text) => push_toast(&mut self.toasts, text, WHITE),
+                ServerMsg::Kick { reason } => self.error = Some(format!("Kicked: {}", reason)),
+                ServerMsg::Pong { sent_ms } => self.rtt_ms = Some(((get_time() * 1000.0) as u64).saturating_sub(sent_ms)),
+                ServerMsg::Session(session) => {
+                    if session.name != normalize_name(&self.username) {
+                        push_toast(&mut self.toasts, format!("The name was taken, you play as {}", session.name), YELLOW);
+                    }
+                    self.room_code = Some(session.room_code);
+                    self.me = Some(session.player_id);
+                    // matchmaking readies us up in the room it found
+                    if self.queue.take().is_some() {
+                        self.ready = true;
+                    }
+                }
+                ServerMsg::Account(account) => {
+                    push_toast(&mut self.toasts, format!("Logged in as {}", account.name), GREEN);
+                    self.logged_in = true;
+                }
+                ServerMsg::Config(config) => self.game_config = config,
+                ServerMsg::Map(map) => self.map = Some(map),
+                ServerMsg::Lobby(lobby) => self.latest_lobby = Some(lobby),
+                // drop snapshots that arrive after a newer one
+                ServerMsg::State(state) if self.latest_state.as_ref().is_some_and(|s| s.seq >= state.seq) => {}
+                ServerMsg::State(state) => {
+                    // a running state after a round ended means the rematch started
+                    let rematch = !state.is_over() && self.round_end.is_some();
+                    if rematch {
+                        self.round_end = None;
+                        self.rematch = None;
+                        self.voted_rematch = false;
+                    }
+                    let alive = |s: &StateMsg| self.me.and_then(|id| s.player(id)).is_some_and(|p| !p.dead && !p.snake.is_empty());
+                    if alive(&state) && (rematch || !self.latest_state.as_ref().is_some_and(alive)) {
+                        self.spawned_at = Some(get_time());
+                    }
+                    // a beep for every second the countdown shows, and another for the start
+                    let second = |s: &StateMsg| s.countdown_ms.map(|ms| ms.div_ceil(1000));
+                    let before = self.latest_state.as_ref().and_then(second);
+                    match second(&state) {
+                        Some(now) if before != Some(now) => sound::play(Effect::Countdown),
+                        None if before.is_some() && !state.is_over() => sound::play(Effect::Go),
+                        _ => {}
+                    }
+                    self.latest_state = Some(state);
+                }
+                ServerMsg::Rematch(r) => self.rematch = Some(r),
+                ServerMsg::Queue(queue) => self.queue = Some(queue),
+                ServerMsg::Pause(pause) => {
+                    if !pause.paused && pause.votes > 0 {
+                        let text = format!("Pause requested ({}/{}), press P to agree", pause.votes, pause.needed);
+                        push_toast(&mut self.toasts, text, YELLOW);
+                    }
+                    self.pause = Some(pause);
+                }
+                ServerMsg::RoundEnd(end) => self.round_end = Some(end),
+                ServerMsg::Emote(EmoteMsg { player, emote }) => {
+                    self.bubbles.retain(|b| b.player != player);
+                    self.bubbles.push(Bubble { player, emote, until: get_time() + BUBBLE_SECS });
+                }
+                ServerMsg::Events(events) => {
+                    // the latest state is from just before these happened
+                    let players = self.latest_state.as_ref().map_or(&[][..], |s| &s.players[..]);
+                    for event in events {
+                        let victim = match event {
+                            GameEvent::Death(kill) => {
+                                let color = if kill.killer.is_some() { ORANGE } else { LIGHTGRAY };
+                                push_toast(&mut self.kill_feed, kill_text(&kill, players), color);
+                                players.iter().find(|p| p.id == kill.victim).cloned()
+                            }
+                            _ => None,
+                        };
+                        self.pending_events.push((event, victim));
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+}
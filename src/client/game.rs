@@ -0,0 +1,315 @@
+use macroquad::prelude::*;
+
+use snake::game_core::{heading, ClientMsg, Direction, Emote, PowerUpKind, StateMsg};
+
+use super::connection::Connection;
+use super::hud::{self, draw_bubbles, draw_countdown, draw_effects, draw_kill_feed, draw_pause, draw_toasts};
+use super::input::{PadInput, TouchInput};
+use super::keybinds::Action;
+use super::lobby::{draw_lobby, draw_queue, LobbyAction};
+use super::menu::Menu;
+use super::particles::{cell_centre, shake_offset};
+use super::render::{board_view, draw_body, draw_food, draw_head, draw_letterbox, draw_map, draw_minimap, draw_name_tag, draw_outline, draw_power_up, draw_rect_at, set_board_camera, visible_cells, MAX_ZOOM, MIN_ZOOM, Tile, ZOOM_STEP};
+use super::replay_view::{draw_replay_bar, REPLAY_SEEK_SECS};
+use super::results::{draw_round_end, draw_series_end, RoundEndAction};
+use super::settings::save_settings;
+use super::sound;
+use super::theme::{draw_grid, palette, snake_colors};
+use super::{App, Frame, Screen};
+
+// One frame of a connection: what arrived, what the players pressed, and the match, lobby or
+// wait drawn from it. Returns the screen to show next, the menu once the connection is over.
+pub fn show(mut c: Box<Connection>, app: &mut App, menu: &mut Menu, frame: &Frame) -> Screen {
+    if let Some(reason) = c.update() {
+        return menu.ended(&c, reason);
+    }
+    // the login is saved now, natively; the browser build has to send the password again
+    if c.logged_in && cfg!(not(target_arch = "wasm32")) {
+        menu.logged_in();
+    }
+
+    // touches steer too, though not a replay, where nothing is steered
+    let playing = c.net.replay().is_none() && c.latest_state.as_ref().is_some_and(|s| !s.is_over());
+    steer(&mut c, app, &frame.pad, playing);
+
+    // set when the results' Back to Menu button is clicked
+    let mut leave = false;
+    let theme = app.settings.theme();
+    c.show_events(palette(theme, app.colorblind), theme);
+    if c.latest_state.is_some() {
+        draw_match(&mut c, app);
+        if playing {
+            app.touch.draw(app.settings.controls);
+        }
+        leave = draw_overlay(&mut c, frame);
+    } else if let Some(lobby) = &c.latest_lobby {
+        // taller than the connect panel so a full lobby fits
+        let panel = frame.tall_panel(0.15, 0.7);
+        let action = match draw_lobby(lobby, c.game_config, c.ready, panel) {
+            LobbyAction::None if frame.pad.confirm => LobbyAction::ToggleReady,
+            action => action,
+        };
+        match action {
+            LobbyAction::ToggleReady => {
+                c.ready = !c.ready;
+                c.net.send(ClientMsg::Ready { ready: c.ready });
+            }
+            LobbyAction::NextTeam => {
+                let mine = lobby.players.iter().find(|p| Some(p.id) == c.me).and_then(|p| p.team);
+                let team = mine.map_or(0, |t| (t + 1) % lobby.teams);
+                c.net.send(ClientMsg::PickTeam { team });
+            }
+            LobbyAction::None => {}
+        }
+        if let Some(addr) = &c.hosted_at {
+            let text = format!("Hosting: friends connect to {} and join room {}", addr, lobby.room_code);
+            draw_text(&text, panel.x + 20.0, panel.y + panel.h + 30.0, 24.0, LIGHTGRAY);
+        }
+    } else if let Some(queue) = &c.queue {
+        leave = draw_queue(queue, frame.panel);
+    } else {
+        let text = "Connecting to server...";
+        let ts = measure_text(text, None, 30, 1.0);
+        draw_text(text, (screen_width() - ts.width) / 2.0, screen_height() / 2.0, 30.0, YELLOW);
+    }
+    if let Some(replay) = c.net.replay() {
+        draw_replay_bar(replay);
+    }
+    draw_toasts(&mut c.toasts);
+    draw_kill_feed(&mut c.kill_feed);
+    hud::draw_corner(c.latest_state.as_ref(), c.rtt_ms);
+
+    // Esc goes back to the menu, to reconnect from there
+    if leave || is_key_pressed(KeyCode::Escape) {
+        Screen::Menu
+    } else {
+        Screen::Game(c)
+    }
+}
+
+// Input: turns and boost, by local player, and the keys that change the view. Alone, both
+// columns of keys steer; in local versus the first column is the first player's and the
+// second the second's.
+fn steer(c: &mut Connection, app: &mut App, pad: &PadInput, playing: bool) {
+    let versus = c.local_players > 1;
+    let keys = &app.settings.keys;
+    let mut turns: Vec<(usize, Direction)> = Vec::new();
+    let mut boosts: Vec<(usize, bool)> = Vec::new();
+    let turn_actions = [(Action::Up, Direction::Up), (Action::Down, Direction::Down), (Action::Left, Direction::Left), (Action::Right, Direction::Right)];
+    // the first column last, so its turn wins when both press at once
+    for column in [1, 0] {
+        let player = if versus { column } else { 0 };
+        turns.extend(turn_actions.into_iter().filter(|(action, _)| keys.pressed(*action, column)).map(|(_, dir)| (player, dir)));
+        if keys.pressed(Action::Boost, column) {
+            boosts.push((player, true));
+        }
+        if keys.released(Action::Boost, column) {
+            boosts.push((player, false));
+        }
+    }
+
+    let (touch_turn, touch_boost) = if playing {
+        app.touch.update(app.settings.controls)
+    } else {
+        app.touch = TouchInput::default();
+        (None, None)
+    };
+    turns.extend(touch_turn.map(|dir| (0, dir)));
+    boosts.extend(touch_boost.map(|active| (0, active)));
+    // each controller steers the player it belongs to
+    turns.extend(pad.turns.iter().filter(|(player, _)| *player < c.local_players));
+    boosts.extend(pad.boosts.iter().filter(|(player, _)| *player < c.local_players));
+
+    for player in 0..c.local_players {
+        if let Some(&(_, dir)) = turns.iter().rfind(|(p, _)| *p == player) {
+            c.input_seq += 1;
+            c.net.send_as(player, ClientMsg::Input { dir, seq: c.input_seq });
+        }
+    }
+    for (player, active) in boosts {
+        c.net.send_as(player, ClientMsg::Boost { active });
+    }
+    for (action, emote) in [Action::Emote1, Action::Emote2, Action::Emote3].into_iter().zip(Emote::ALL) {
+        for column in 0..2 {
+            if keys.pressed(action, column) && c.latest_state.is_some() {
+                c.net.send_as(if versus { column } else { 0 }, ClientMsg::Emote { emote });
+            }
+        }
+    }
+    let settings = &mut app.settings;
+    if is_key_pressed(KeyCode::G) {
+        settings.hide_grid = !settings.hide_grid;
+        save_settings(settings);
+    }
+    if settings.keys.either_pressed(Action::NameTags) {
+        settings.hide_name_tags = !settings.hide_name_tags;
+        save_settings(settings);
+    }
+    if settings.keys.either_pressed(Action::Mute) {
+        settings.muted = !settings.muted;
+        sound::set_volume(settings);
+        save_settings(settings);
+    }
+    // the wheel's size differs between platforms, only which way it turned counts
+    let wheel = mouse_wheel().1;
+    let mut zoom = settings.zoom;
+    if settings.keys.either_pressed(Action::ZoomIn) || wheel > 0.0 {
+        zoom *= ZOOM_STEP;
+    }
+    if settings.keys.either_pressed(Action::ZoomOut) || wheel < 0.0 {
+        zoom /= ZOOM_STEP;
+    }
+    if zoom != settings.zoom {
+        settings.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        save_settings(settings);
+    }
+    if settings.keys.either_pressed(Action::Scoreboard) {
+        app.show_scoreboard = !app.show_scoreboard;
+    }
+    if (settings.keys.either_pressed(Action::Pause) || pad.pause) && c.latest_state.as_ref().is_some_and(|s| !s.is_over()) {
+        c.net.send(ClientMsg::Pause);
+    }
+    if let Some(replay) = c.net.replay() {
+        if is_key_pressed(KeyCode::Space) {
+            replay.paused = !replay.paused;
+        }
+        if is_key_pressed(KeyCode::Period) {
+            replay.step();
+        }
+        if is_key_pressed(KeyCode::Up) {
+            replay.faster();
+        }
+        if is_key_pressed(KeyCode::Down) {
+            replay.slower();
+        }
+        if is_key_pressed(KeyCode::Right) {
+            replay.seek(replay.frame_after(REPLAY_SEEK_SECS));
+        }
+        if is_key_pressed(KeyCode::Left) {
+            replay.seek(replay.frame_after(-REPLAY_SEEK_SECS));
+        }
+    }
+}
+
+// The board as the latest state has it, with the HUD over it
+fn draw_match(c: &mut Connection, app: &App) {
+    let Some(state) = &c.latest_state else { return };
+    let settings = &app.settings;
+    let theme = settings.theme();
+    let palette = palette(theme, app.colorblind);
+    let sprites = app.sprites.as_ref();
+    // the view stays put while our snake is off the board
+    if let Some(&head) = c.me.and_then(|id| state.player(id)).filter(|p| !p.dead).and_then(|p| p.snake.first()) {
+        c.view.follow(vec2(head.x as f32 + 0.5, head.y as f32 + 0.5), &c.game_config, get_frame_time());
+    }
+    let (cell, origin) = board_view(&c.game_config, &c.view, settings);
+    let board = vec2(c.game_config.grid_width as f32, c.game_config.grid_height as f32) * cell;
+    draw_letterbox(origin, board);
+    set_board_camera(origin + shake_offset(c.shake_until, cell));
+    if !settings.hide_grid {
+        draw_grid(&c.game_config, cell, theme);
+    }
+
+    if let Some(map) = &c.map {
+        draw_map(map, cell);
+    }
+    if !c.game_config.wrap {
+        draw_rectangle_lines(0.0, 0.0, board.x, board.y, 3.0, theme.border);
+    }
+    for wall in state.obstacles.iter() {
+        match sprites {
+            Some(atlas) => atlas.draw(Tile::Wall, *wall, cell, 0, theme.wall),
+            None => draw_rect_at(*wall, cell, theme.wall),
+        }
+    }
+
+    for power_up in state.power_ups.iter() {
+        draw_power_up(power_up.kind, power_up.pos, cell);
+    }
+
+    for p in state.players.iter() {
+        // snakes of players who are away, and ghosts, are faded out
+        let faded = !p.connected || p.has_effect(PowerUpKind::Ghost);
+        let (head, body) = snake_colors(p, palette);
+        let (head, body) = if faded { (head.with_alpha(0.35), body.with_alpha(0.35)) } else { (head, body) };
+        // a boosting snake's head lights up
+        let head = if p.boosting && !faded { SKYBLUE } else { head };
+        if let Some(atlas) = sprites {
+            atlas.draw_snake(&p.snake, p.dir, &c.game_config, cell, head, body);
+            continue;
+        }
+        draw_body(&p.snake, cell, body, app.style);
+        if let Some(&pos) = p.snake.first() {
+            draw_head(pos, heading(&p.snake, p.dir, &c.game_config), cell, head);
+        }
+    }
+    // our own snake stands out from the rest
+    if let Some(p) = c.me.and_then(|id| state.player(id)).filter(|p| !p.dead) {
+        let since_spawn = c.spawned_at.map_or(f64::INFINITY, |at| get_time() - at);
+        draw_outline(&p.snake, cell, theme.text, since_spawn);
+    }
+    // everyone's name over their head, ours in the outline's color
+    for p in state.players.iter().filter(|p| !p.dead && !settings.hide_name_tags) {
+        let Some(&head) = p.snake.first() else { continue };
+        let color = if Some(p.id) == c.me { theme.text } else { snake_colors(p, palette).0 };
+        let faded = !p.connected || p.has_effect(PowerUpKind::Ghost);
+        draw_name_tag(&p.name, head, cell, if faded { color.with_alpha(0.35) } else { color });
+    }
+
+    for food in state.food_items.iter() {
+        draw_food(food, state.tick, cell, theme, sprites);
+    }
+    // boosting snakes leave sparkles behind
+    for p in state.players.iter().filter(|p| p.boosting && !p.dead) {
+        if let Some(&tail) = p.snake.last() {
+            c.particles.burst(cell_centre(tail), 1, 1.0, 0.4, GOLD);
+        }
+    }
+    c.particles.draw(cell);
+    if c.death_fade.as_ref().is_some_and(|fade| !fade.draw(cell)) {
+        c.death_fade = None;
+    }
+    draw_bubbles(&mut c.bubbles, &state.players, cell);
+    set_default_camera();
+    draw_minimap(state, &c.game_config, visible_cells(&c.game_config, cell, origin), c.me, theme, palette);
+
+    draw_hud(state, c, app);
+}
+
+fn draw_hud(state: &StateMsg, c: &Connection, app: &App) {
+    let theme = app.settings.theme();
+    hud::draw_match(state, c.game_config.mode);
+    if app.show_scoreboard {
+        hud::draw_cards(state, c.me, palette(theme, app.colorblind), theme);
+    }
+    draw_effects(state);
+    if !state.is_over() {
+        draw_countdown(state, theme.text);
+    }
+}
+
+// The results once the round is over, or the pause screen while the match is paused.
+// Returns whether the results' Back to Menu button was clicked.
+fn draw_overlay(c: &mut Connection, frame: &Frame) -> bool {
+    if let Some(end) = &c.round_end {
+        let shown = match end.series.as_ref().filter(|s| s.is_over()) {
+            Some(series) => draw_series_end(end, series, c.me, c.rematch.as_ref(), c.voted_rematch, frame.panel),
+            None => draw_round_end(end, c.me, c.rematch.as_ref(), c.voted_rematch, frame.panel),
+        };
+        let action = match shown {
+            RoundEndAction::None if frame.pad.confirm && !c.voted_rematch => RoundEndAction::Rematch,
+            action => action,
+        };
+        match action {
+            RoundEndAction::Rematch => {
+                c.voted_rematch = true;
+                c.net.send(ClientMsg::Restart);
+            }
+            RoundEndAction::Leave => return true,
+            RoundEndAction::None => {}
+        }
+    } else if let Some(pause) = c.pause.as_ref().filter(|p| p.paused) {
+        draw_pause(pause, frame.panel);
+    }
+    false
+}
@@ -0,0 +1,323 @@
+// The heads-up display over a running match: a card for every player down the left edge,
+// the mode and clock at the top, and the tick and ping in the top-right corner; and the
+// toasts, emotes and kill feed that come and go over it
+
+use macroquad::prelude::*;
+
+use snake::game_core::{Emote, GameMode, KillEvent, MatchPhase, PauseMsg, PlayerId, PlayerState, POWER_UP_EFFECT_TICKS, SeriesScore, StateMsg};
+
+use super::render::power_up_style;
+use super::theme::{snake_colors, Theme};
+
+const CARD_W: f32 = 220.0;
+const MIN_CARD_W: f32 = 150.0;
+const CARD_H: f32 = 50.0;
+const MIN_CARD_H: f32 = 38.0;
+const GAP: f32 = 6.0;
+const PIP_GAP: f32 = 9.0;
+const TOP: f32 = 40.0;
+// kept clear at the bottom of the window for the toasts and the minimap
+const BOTTOM: f32 = 240.0;
+
+// How a player is doing, as their card puts it
+fn status(p: &PlayerState) -> String {
+    let status = match (p.connected, p.snake.is_empty()) {
+        (true, _) if p.respawn_tick.is_some() => "respawning",
+        (true, _) if p.dead => "dead",
+        (true, _) => "alive",
+        (false, false) => "away",
+        (false, true) => "left",
+    };
+    match p.lives {
+        Some(lives) => format!("{}, {} lives", status, lives),
+        None => status.to_string(),
+    }
+}
+
+// Best score first. The cards get shorter as players join, and go into more columns
+// once a column of them would run into the bottom corner
+pub fn draw_cards(state: &StateMsg, me: Option<PlayerId>, palette: &[(Color, Color)], theme: &Theme) {
+    let mut players: Vec<&PlayerState> = state.players.iter().collect();
+    if players.is_empty() {
+        return;
+    }
+    players.sort_by_key(|p| std::cmp::Reverse(p.score));
+    let room = (screen_height() - TOP - BOTTOM).max(CARD_H);
+    let per_column = (((room + GAP) / (MIN_CARD_H + GAP)) as usize).max(1);
+    let columns = players.len().div_ceil(per_column);
+    let rows = players.len().div_ceil(columns);
+    let h = ((room + GAP) / rows as f32 - GAP).clamp(MIN_CARD_H, CARD_H);
+    // no more than about a third of the window, so the board stays in view
+    let w = (screen_width() / 3.0 / columns as f32 - GAP).clamp(MIN_CARD_W, CARD_W);
+    for (i, p) in players.into_iter().enumerate() {
+        let (column, row) = (i / rows, i % rows);
+        let card = Rect { x: 10.0 + column as f32 * (w + GAP), y: TOP + row as f32 * (h + GAP), w, h };
+        draw_card(p, card, Some(p.id) == me, state.series.as_ref(), palette, theme);
+    }
+}
+
+fn draw_card(p: &PlayerState, card: Rect, mine: bool, series: Option<&SeriesScore>, palette: &[(Color, Color)], theme: &Theme) {
+    let (big, small) = ((card.h * 0.4).round(), (card.h * 0.32).round());
+    // players out of the running are greyed out
+    let alpha = if p.dead || !p.connected { 0.5 } else { 1.0 };
+    draw_rectangle(card.x, card.y, card.w, card.h, Color::new(0.0, 0.0, 0.0, 0.6));
+    if mine {
+        draw_rectangle_lines(card.x, card.y, card.w, card.h, 2.0, theme.text);
+    }
+    let (head, body) = snake_colors(p, palette);
+    let swatch = card.h - 16.0;
+    draw_rectangle(card.x + 8.0, card.y + 8.0, 12.0, swatch / 2.0, head.with_alpha(alpha));
+    draw_rectangle(card.x + 8.0, card.y + 8.0 + swatch / 2.0, 12.0, swatch / 2.0, body.with_alpha(alpha));
+
+    let score = p.score.to_string();
+    let score_w = measure_text(&score, None, big as u16, 1.0).width;
+    draw_text(&score, card.x + card.w - score_w - 8.0, card.y + big + 2.0, big, WHITE.with_alpha(alpha));
+    let name_x = card.x + 28.0;
+    let name = fit(&p.name, card.x + card.w - score_w - 16.0 - name_x, big);
+    draw_text(&name, name_x, card.y + big + 2.0, big, WHITE.with_alpha(alpha));
+    // a tick after the names of players logged in to their account
+    if p.verified && name == p.name {
+        let end = name_x + measure_text(&name, None, big as u16, 1.0).width + 6.0;
+        let y = card.y + big - 4.0;
+        draw_line(end, y - 4.0, end + 3.0, y - 1.0, 2.0, GREEN);
+        draw_line(end + 3.0, y - 1.0, end + 8.0, y - 9.0, 2.0, GREEN);
+    }
+
+    let length = if p.dead { 0 } else { p.snake.len() };
+    let mut details = format!("length {}   {}", length, status(p));
+    if let Some(ms) = p.ping_ms {
+        details += &format!("   {} ms", ms);
+    }
+    // in a series, a pip for every round it takes, filled in for the ones they won
+    let mut pips_w = 0.0;
+    if let Some(series) = series {
+        let (needed, won) = (series.needed(), series.wins_of(p.id));
+        pips_w = needed as f32 * PIP_GAP;
+        let y = card.y + card.h - 7.0 - small * 0.3;
+        for i in 0..needed {
+            let x = card.x + card.w - pips_w + i as f32 * PIP_GAP;
+            if i < won {
+                draw_circle(x, y, 3.0, GOLD.with_alpha(alpha));
+            } else {
+                draw_circle_lines(x, y, 3.0, 1.0, GRAY.with_alpha(alpha));
+            }
+        }
+    }
+    let details = fit(&details, card.w - 36.0 - pips_w, small);
+    draw_text(&details, name_x, card.y + card.h - 7.0, small, LIGHTGRAY.with_alpha(alpha));
+}
+
+// Cuts text down to fit the width, ending it with dots when anything was cut
+fn fit(text: &str, width: f32, size: f32) -> String {
+    if measure_text(text, None, size as u16, 1.0).width <= width {
+        return text.to_string();
+    }
+    let mut chars: Vec<char> = text.chars().collect();
+    while !chars.is_empty() {
+        chars.pop();
+        let cut = format!("{}..", chars.iter().collect::<String>().trim_end());
+        if measure_text(&cut, None, size as u16, 1.0).width <= width {
+            return cut;
+        }
+    }
+    String::new()
+}
+
+// The mode, with the series round and each team's total where there are those, across
+// the top centre; the round's clock goes under it when there is one
+pub fn draw_match(state: &StateMsg, mode: GameMode) {
+    let mut text = mode.to_string();
+    if let Some(series) = &state.series {
+        text += &format!("   Round {}, best of {}", series.round, series.best_of);
+    }
+    for (team, total) in team_totals(state.players.iter().map(|p| (p.team, p.score))) {
+        text += &format!("   Team {}: {}", team + 1, total);
+    }
+    let ts = measure_text(&text, None, 20, 1.0);
+    let x = (screen_width() - ts.width) / 2.0;
+    draw_rectangle(x - 10.0, 4.0, ts.width + 20.0, 24.0, Color::new(0.0, 0.0, 0.0, 0.6));
+    draw_text(&text, x, 22.0, 20.0, WHITE);
+    if let Some(left) = state.time_left_ms {
+        draw_clock(left, state.phase);
+    }
+}
+
+// The tick and game speed, then the round trip to the server, in the top-right corner;
+// either is left out when there's nothing to show for it
+pub fn draw_corner(state: Option<&StateMsg>, rtt_ms: Option<u64>) {
+    let mut lines = Vec::new();
+    if let Some(state) = state {
+        lines.push((format!("Tick {}   {:.1}/s", state.tick, 1000.0 / state.tick_ms.max(1) as f32), LIGHTGRAY));
+    }
+    if let Some(rtt) = rtt_ms {
+        let color = if rtt < 80 { GREEN } else if rtt < 200 { YELLOW } else { RED };
+        lines.push((format!("Ping: {} ms", rtt), color));
+    }
+    for (i, (text, color)) in lines.iter().enumerate() {
+        let y = 20.0 + i as f32 * 22.0;
+        let ts = measure_text(text, None, 18, 1.0);
+        let x = screen_width() - ts.width - 10.0;
+        draw_rectangle(x - 6.0, y - 16.0, ts.width + 12.0, 22.0, Color::new(0.0, 0.0, 0.0, 0.6));
+        draw_text(text, x, y, 18.0, *color);
+    }
+}
+
+// Combined score of each team that has players, by team number
+pub fn team_totals(scores: impl Iterator<Item = (Option<u8>, u32)>) -> Vec<(u8, u32)> {
+    let mut totals: Vec<(u8, u32)> = Vec::new();
+    for (team, score) in scores {
+        let Some(team) = team else { continue };
+        match totals.iter_mut().find(|(t, _)| *t == team) {
+            Some((_, total)) => *total += score,
+            None => totals.push((team, score)),
+        }
+    }
+    totals.sort_by_key(|(t, _)| *t);
+    totals
+}
+
+// Strip along the bottom-right listing every snake's running effects with the time left on them
+pub fn draw_effects(state: &StateMsg) {
+    const BAR_W: f32 = 60.0;
+    let mut y = screen_height() - 12.0;
+    for p in state.players.iter().rev() {
+        if p.effects.is_empty() {
+            continue;
+        }
+        let mut x = screen_width() - 10.0;
+        for effect in p.effects.iter().rev() {
+            let (color, label) = power_up_style(effect.kind);
+            let left = effect.until_tick.saturating_sub(state.tick) as f32 / POWER_UP_EFFECT_TICKS as f32;
+            x -= BAR_W + 10.0;
+            draw_rectangle(x, y - 14.0, BAR_W * left.min(1.0), 16.0, color.with_alpha(0.6));
+            draw_rectangle_lines(x, y - 14.0, BAR_W, 16.0, 1.0, color);
+            draw_text(label, x + 4.0, y, 16.0, WHITE);
+        }
+        let ts = measure_text(&p.name, None, 18, 1.0);
+        draw_text(&p.name, x - ts.width - 8.0, y, 18.0, WHITE);
+        y -= 22.0;
+    }
+}
+
+// A timed round's clock, top centre; it flashes red over the last ten seconds, and
+// gives way to a sudden death warning once it ran out on a tie
+fn draw_clock(time_left_ms: u64, phase: MatchPhase) {
+    let secs = time_left_ms.div_ceil(1000);
+    let text = match phase {
+        MatchPhase::SuddenDeath => "SUDDEN DEATH".to_string(),
+        _ => format!("{}:{:02}", secs / 60, secs % 60),
+    };
+    let color = if secs > 10 {
+        WHITE
+    } else if (get_time() * 4.0) as i64 % 2 == 0 {
+        RED
+    } else {
+        RED.with_alpha(0.3)
+    };
+    let ts = measure_text(&text, None, 40, 1.0);
+    draw_text(&text, (screen_width() - ts.width) / 2.0, 56.0, 40.0, color);
+}
+
+// How long "GO!" stays up once the countdown is over
+const GO_MS: u64 = 700;
+
+// 3, 2, 1 in the middle of the board before a round starts, then GO! for a moment
+pub fn draw_countdown(state: &StateMsg, color: Color) {
+    let text = match state.countdown_ms {
+        Some(ms) => ms.div_ceil(1000).to_string(),
+        None if state.phase == MatchPhase::Playing && state.tick * state.tick_ms < GO_MS => "GO!".to_string(),
+        None => return,
+    };
+    let ts = measure_text(&text, None, 120, 1.0);
+    draw_text(&text, (screen_width() - ts.width) / 2.0, screen_height() / 2.0 + ts.offset_y / 2.0, 120.0, color);
+}
+
+// Overlay while the match is paused, saying who it waits for and how to resume
+pub fn draw_pause(pause: &PauseMsg, panel: Rect) {
+    draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::new(0.1, 0.1, 0.1, 0.8));
+    let title = "Paused";
+    let ts = measure_text(title, None, 40, 1.0);
+    draw_text(title, panel.x + (panel.w - ts.width) / 2.0, panel.y + 50.0, 40.0, YELLOW);
+    for (i, name) in pause.waiting_for.iter().enumerate() {
+        let text = format!("Waiting for {} to reconnect...", name);
+        draw_text(&text, panel.x + 40.0, panel.y + 100.0 + i as f32 * 26.0, 24.0, WHITE);
+    }
+    let hint = if pause.votes > 0 {
+        format!("Press P to resume ({}/{})", pause.votes, pause.needed)
+    } else {
+        "Press P to resume".to_string()
+    };
+    draw_text(&hint, panel.x + 40.0, panel.y + panel.h - 40.0, 24.0, GRAY);
+}
+
+const TOAST_SECS: f64 = 4.0;
+pub const BUBBLE_SECS: f64 = 2.5;
+
+// A short-lived notice from the server
+pub struct Toast {
+    text: String,
+    color: Color,
+    until: f64,
+}
+
+pub fn push_toast(toasts: &mut Vec<Toast>, text: String, color: Color) {
+    toasts.push(Toast { text, color, until: get_time() + TOAST_SECS });
+}
+
+// An emote over the head of the snake that sent it
+pub struct Bubble {
+    pub player: PlayerId,
+    pub emote: Emote,
+    pub until: f64,
+}
+
+// The live emotes, each in a speech bubble above its sender's head
+pub fn draw_bubbles(bubbles: &mut Vec<Bubble>, players: &[PlayerState], cell: f32) {
+    let now = get_time();
+    bubbles.retain(|b| b.until > now);
+    for bubble in bubbles.iter() {
+        let Some(head) = players.iter().find(|p| p.id == bubble.player).and_then(|p| p.snake.first()) else { continue };
+        let text = bubble.emote.to_string();
+        let ts = measure_text(&text, None, 20, 1.0);
+        let x = (head.x as f32 + 0.5) * cell - ts.width / 2.0;
+        let y = head.y as f32 * cell - 12.0;
+        draw_rectangle(x - 8.0, y - 18.0, ts.width + 16.0, 24.0, Color::new(1.0, 1.0, 1.0, 0.9));
+        draw_triangle(vec2(x + ts.width / 2.0 - 5.0, y + 6.0), vec2(x + ts.width / 2.0 + 5.0, y + 6.0), vec2(x + ts.width / 2.0, y + 12.0), Color::new(1.0, 1.0, 1.0, 0.9));
+        draw_text(&text, x, y, 20.0, BLACK);
+    }
+}
+
+// How a death reads in the kill feed
+pub fn kill_text(kill: &KillEvent, players: &[PlayerState]) -> String {
+    let name = |id: PlayerId| players.iter().find(|p| p.id == id).map_or("?", |p| p.name.as_str());
+    match kill.killer {
+        Some(killer) => format!("{} ran into {}", name(kill.victim), name(killer)),
+        None => format!("{} crashed", name(kill.victim)),
+    }
+}
+
+// The latest deaths in the top-right corner, newest at the top
+pub fn draw_kill_feed(feed: &mut Vec<Toast>) {
+    const SHOWN: usize = 5;
+    let now = get_time();
+    feed.retain(|t| t.until > now);
+    for (i, entry) in feed.iter().rev().take(SHOWN).enumerate() {
+        let y = 90.0 + i as f32 * 26.0;
+        let ts = measure_text(&entry.text, None, 20, 1.0);
+        let x = screen_width() - ts.width - 20.0;
+        draw_rectangle(x - 10.0, y - 18.0, ts.width + 20.0, 24.0, Color::new(0.0, 0.0, 0.0, 0.6));
+        draw_text(&entry.text, x, y, 20.0, entry.color);
+    }
+}
+
+// Stacks the live toasts in the bottom-left corner, newest at the bottom
+pub fn draw_toasts(toasts: &mut Vec<Toast>) {
+    let now = get_time();
+    toasts.retain(|t| t.until > now);
+    for (i, toast) in toasts.iter().rev().enumerate() {
+        let y = screen_height() - 20.0 - i as f32 * 30.0;
+        let ts = measure_text(&toast.text, None, 24, 1.0);
+        draw_rectangle(10.0, y - 22.0, ts.width + 20.0, 28.0, Color::new(0.0, 0.0, 0.0, 0.7));
+        draw_text(&toast.text, 20.0, y, 24.0, toast.color);
+    }
+}
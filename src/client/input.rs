@@ -0,0 +1,314 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use snake::game_core::Direction;
+
+// How the snake is steered on a touch screen; the keyboard works with any of them
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Controls {
+    #[default]
+    Keyboard,
+    // a swipe anywhere turns the snake the way it went
+    Swipe,
+    // arrows drawn in the bottom right corner
+    DPad,
+}
+
+impl Controls {
+    pub const ALL: [Controls; 3] = [Controls::Keyboard, Controls::Swipe, Controls::DPad];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Controls::Keyboard => "Keyboard",
+            Controls::Swipe => "Swipe",
+            Controls::DPad => "D-pad",
+        }
+    }
+}
+
+// A swipe turns once it has gone this share of the screen's shorter side
+const SWIPE_SHARE: f32 = 0.05;
+
+// A finger on the screen, from where it last turned the snake
+struct TouchTrack {
+    id: u64,
+    anchor: Vec2,
+    // the D-pad arrow it is on
+    arrow: Option<Direction>,
+}
+
+// Turns touches into turns and boost, for the touch control schemes
+#[derive(Default)]
+pub struct TouchInput {
+    tracks: Vec<TouchTrack>,
+    boosting: bool,
+}
+
+impl TouchInput {
+    // Arrows around a centre in the bottom right corner, sized for a thumb
+    fn arrows() -> [(Rect, Direction); 4] {
+        let size = (screen_width().min(screen_height()) * 0.12).max(48.0);
+        let centre = vec2(screen_width() - size * 2.0, screen_height() - size * 2.0);
+        let at = |dx: f32, dy: f32| Rect { x: centre.x + (dx - 0.5) * size, y: centre.y + (dy - 0.5) * size, w: size, h: size };
+        [(at(0.0, -1.0), Direction::Up), (at(0.0, 1.0), Direction::Down), (at(-1.0, 0.0), Direction::Left), (at(1.0, 0.0), Direction::Right)]
+    }
+
+    // Held for boost, in the bottom left corner
+    fn boost_button() -> Rect {
+        let size = (screen_width().min(screen_height()) * 0.15).max(60.0);
+        Rect { x: size * 0.5, y: screen_height() - size * 1.5, w: size, h: size }
+    }
+
+    // The turn this frame's touches ask for, and whether boost should now be on if that changed
+    pub fn update(&mut self, controls: Controls) -> (Option<Direction>, Option<bool>) {
+        if controls == Controls::Keyboard {
+            return (None, None);
+        }
+        let mut turn = None;
+        let mut boosting = false;
+        let swipe_px = screen_width().min(screen_height()) * SWIPE_SHARE;
+        for touch in touches() {
+            if matches!(touch.phase, TouchPhase::Ended | TouchPhase::Cancelled) {
+                self.tracks.retain(|t| t.id != touch.id);
+                continue;
+            }
+            if Self::boost_button().contains(touch.position) {
+                boosting = true;
+                continue;
+            }
+            let track = match self.tracks.iter().position(|t| t.id == touch.id) {
+                Some(i) => &mut self.tracks[i],
+                None => {
+                    self.tracks.push(TouchTrack { id: touch.id, anchor: touch.position, arrow: None });
+                    self.tracks.last_mut().unwrap()
+                }
+            };
+            match controls {
+                Controls::Swipe => {
+                    let moved = touch.position - track.anchor;
+                    if moved.length() >= swipe_px {
+                        turn = Some(match (moved.x.abs() > moved.y.abs(), moved.x > 0.0, moved.y > 0.0) {
+                            (true, true, _) => Direction::Right,
+                            (true, false, _) => Direction::Left,
+                            (false, _, true) => Direction::Down,
+                            (false, _, false) => Direction::Up,
+                        });
+                        // a finger that keeps going turns again from here
+                        track.anchor = touch.position;
+                    }
+                }
+                // sliding from one arrow onto another turns too
+                Controls::DPad => {
+                    let arrow = Self::arrows().into_iter().find(|(rect, _)| rect.contains(touch.position)).map(|(_, dir)| dir);
+                    if arrow.is_some() && arrow != track.arrow {
+                        turn = arrow;
+                    }
+                    track.arrow = arrow;
+                }
+                Controls::Keyboard => {}
+            }
+        }
+        let boost = (boosting != self.boosting).then_some(boosting);
+        self.boosting = boosting;
+        (turn, boost)
+    }
+
+    pub fn draw(&self, controls: Controls) {
+        if controls == Controls::Keyboard {
+            return;
+        }
+        let faint = Color::new(1.0, 1.0, 1.0, 0.15);
+        if controls == Controls::DPad {
+            for (rect, dir) in Self::arrows() {
+                let held = self.tracks.iter().any(|t| t.arrow == Some(dir));
+                draw_rectangle(rect.x + 2.0, rect.y + 2.0, rect.w - 4.0, rect.h - 4.0, if held { faint.with_alpha(0.35) } else { faint });
+                let (c, r) = (rect.center(), rect.w * 0.25);
+                let (tip, side) = match dir {
+                    Direction::Up => (vec2(0.0, -r), vec2(r, 0.0)),
+                    Direction::Down => (vec2(0.0, r), vec2(r, 0.0)),
+                    Direction::Left => (vec2(-r, 0.0), vec2(0.0, r)),
+                    Direction::Right => (vec2(r, 0.0), vec2(0.0, r)),
+                };
+                draw_triangle(c + tip, c - tip * 0.5 + side, c - tip * 0.5 - side, WHITE.with_alpha(0.6));
+            }
+        }
+        let boost = Self::boost_button();
+        let color = if self.boosting { SKYBLUE.with_alpha(0.5) } else { faint };
+        draw_circle(boost.center().x, boost.center().y, boost.w * 0.5, color);
+        let ts = measure_text("Boost", None, 22, 1.0);
+        draw_text("Boost", boost.center().x - ts.width * 0.5, boost.center().y + 7.0, 22.0, WHITE.with_alpha(0.7));
+    }
+}
+
+// What controllers did since the last frame
+#[derive(Default)]
+pub struct PadInput {
+    // turns, by the local player whose controller made them
+    pub turns: Vec<(usize, Direction)>,
+    // boost pressed or let go, by local player
+    pub boosts: Vec<(usize, bool)>,
+    // A on any controller
+    pub confirm: bool,
+    // Start on any controller
+    pub pause: bool,
+}
+
+// Controllers, built with the gamepad feature. A controller belongs to a local player from
+// the first button it presses, taking the first player without one.
+#[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+pub mod pads {
+    use gilrs::{Axis, Button, EventType, GamepadId, Gilrs};
+    use snake::game_core::Direction;
+
+    use super::PadInput;
+
+    // How far the left stick has to lean before it turns the snake
+    const STICK_THRESHOLD: f32 = 0.5;
+
+    pub const AVAILABLE: bool = true;
+
+    pub struct Pads {
+        // None when the system has no controller support to offer
+        gilrs: Option<Gilrs>,
+        // the controller of each local player
+        owners: Vec<Option<GamepadId>>,
+        // each controller's left stick, and the way it last turned the snake
+        sticks: Vec<(GamepadId, f32, f32, Option<Direction>)>,
+    }
+
+    impl Pads {
+        pub fn new(players: usize) -> Pads {
+            let gilrs = Gilrs::new().ok();
+            Pads { gilrs, owners: vec![None; players], sticks: Vec::new() }
+        }
+
+        // The local player a controller plays for, handing it the next free one if it has none
+        fn owner(&mut self, id: GamepadId) -> Option<usize> {
+            if let Some(player) = self.owners.iter().position(|o| *o == Some(id)) {
+                return Some(player);
+            }
+            let free = self.owners.iter().position(Option::is_none)?;
+            self.owners[free] = Some(id);
+            Some(free)
+        }
+
+        pub fn poll(&mut self) -> PadInput {
+            let mut input = PadInput::default();
+            let mut events = Vec::new();
+            if let Some(gilrs) = self.gilrs.as_mut() {
+                while let Some(event) = gilrs.next_event() {
+                    events.push((event.id, event.event));
+                }
+            }
+            for (id, event) in events {
+                match event {
+                    EventType::ButtonPressed(button, _) => {
+                        let player = self.owner(id);
+                        let turn = match button {
+                            Button::DPadUp => Some(Direction::Up),
+                            Button::DPadDown => Some(Direction::Down),
+                            Button::DPadLeft => Some(Direction::Left),
+                            Button::DPadRight => Some(Direction::Right),
+                            _ => None,
+                        };
+                        match (button, player, turn) {
+                            (_, Some(player), Some(dir)) => input.turns.push((player, dir)),
+                            (Button::South, _, _) => input.confirm = true,
+                            (Button::Start, _, _) => input.pause = true,
+                            (Button::RightTrigger | Button::RightTrigger2, Some(player), _) => input.boosts.push((player, true)),
+                            _ => {}
+                        }
+                    }
+                    EventType::ButtonReleased(Button::RightTrigger | Button::RightTrigger2, _) => {
+                        if let Some(player) = self.owners.iter().position(|o| *o == Some(id)) {
+                            input.boosts.push((player, false));
+                        }
+                    }
+                    EventType::AxisChanged(axis @ (Axis::LeftStickX | Axis::LeftStickY), value, _) => {
+                        if let Some(turn) = self.lean(id, axis, value)
+                            && let Some(player) = self.owner(id)
+                        {
+                            input.turns.push((player, turn));
+                        }
+                    }
+                    EventType::Disconnected => {
+                        self.owners.iter_mut().filter(|o| **o == Some(id)).for_each(|o| *o = None);
+                        self.sticks.retain(|s| s.0 != id);
+                    }
+                    _ => {}
+                }
+            }
+            input
+        }
+
+        // Moves a controller's left stick, and returns the turn if it now leans a new way
+        fn lean(&mut self, id: GamepadId, axis: Axis, value: f32) -> Option<Direction> {
+            let i = match self.sticks.iter().position(|s| s.0 == id) {
+                Some(i) => i,
+                None => {
+                    self.sticks.push((id, 0.0, 0.0, None));
+                    self.sticks.len() - 1
+                }
+            };
+            let stick = &mut self.sticks[i];
+            if axis == Axis::LeftStickX { stick.1 = value } else { stick.2 = value }
+            let (x, y) = (stick.1, stick.2);
+            // up is positive on the stick
+            let leaning = if x.abs().max(y.abs()) < STICK_THRESHOLD {
+                None
+            } else if x.abs() > y.abs() {
+                Some(if x > 0.0 { Direction::Right } else { Direction::Left })
+            } else {
+                Some(if y > 0.0 { Direction::Up } else { Direction::Down })
+            };
+            let turn = leaning.filter(|dir| stick.3 != Some(*dir));
+            stick.3 = leaning;
+            turn
+        }
+
+        // The name of each local player's controller
+        // Whether the system let us look for controllers at all
+        pub fn supported(&self) -> bool {
+            self.gilrs.is_some()
+        }
+
+        pub fn owners(&self) -> Vec<Option<String>> {
+            let gilrs = self.gilrs.as_ref();
+            self.owners.iter().map(|o| Some(gilrs?.gamepad((*o)?).name().to_string())).collect()
+        }
+
+        // Lets go of every controller, so they are handed out again as they press something
+        pub fn reassign(&mut self) {
+            self.owners.iter_mut().for_each(|o| *o = None);
+        }
+    }
+}
+
+#[cfg(not(all(feature = "gamepad", not(target_arch = "wasm32"))))]
+pub mod pads {
+    use super::PadInput;
+
+    pub const AVAILABLE: bool = false;
+
+    pub struct Pads;
+
+    impl Pads {
+        pub fn new(_players: usize) -> Pads {
+            Pads
+        }
+
+        pub fn poll(&mut self) -> PadInput {
+            PadInput::default()
+        }
+
+        pub fn supported(&self) -> bool {
+            false
+        }
+
+        pub fn owners(&self) -> Vec<Option<String>> {
+            Vec::new()
+        }
+
+        pub fn reassign(&mut self) {}
+    }
+}
@@ -0,0 +1,207 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::settings::{save_settings, Settings};
+use super::draw_button;
+
+// What keys do in a match. Each has two keys; in local versus the first belongs to the first
+// player and the second to the second, where the action is a player's own.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Boost,
+    Emote1,
+    Emote2,
+    Emote3,
+    Pause,
+    Scoreboard,
+    NameTags,
+    ZoomIn,
+    ZoomOut,
+    Mute,
+}
+
+impl Action {
+    const ALL: [Action; 14] = [
+        Action::Up, Action::Down, Action::Left, Action::Right, Action::Boost,
+        Action::Emote1, Action::Emote2, Action::Emote3, Action::Pause, Action::Scoreboard, Action::NameTags,
+        Action::ZoomIn, Action::ZoomOut, Action::Mute,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Action::Up => "Up",
+            Action::Down => "Down",
+            Action::Left => "Left",
+            Action::Right => "Right",
+            Action::Boost => "Boost",
+            Action::Emote1 => "Emote 1",
+            Action::Emote2 => "Emote 2",
+            Action::Emote3 => "Emote 3",
+            Action::Pause => "Pause",
+            Action::Scoreboard => "Scoreboard",
+            Action::NameTags => "Name tags",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+            Action::Mute => "Mute",
+        }
+    }
+
+    fn default_keys(self) -> [Option<Key>; 2] {
+        let (first, second) = match self {
+            Action::Up => (KeyCode::W, Some(KeyCode::Up)),
+            Action::Down => (KeyCode::S, Some(KeyCode::Down)),
+            Action::Left => (KeyCode::A, Some(KeyCode::Left)),
+            Action::Right => (KeyCode::D, Some(KeyCode::Right)),
+            Action::Boost => (KeyCode::Space, Some(KeyCode::RightShift)),
+            Action::Emote1 => (KeyCode::Key1, None),
+            Action::Emote2 => (KeyCode::Key2, None),
+            Action::Emote3 => (KeyCode::Key3, None),
+            Action::Pause => (KeyCode::P, None),
+            Action::Scoreboard => (KeyCode::Tab, None),
+            Action::NameTags => (KeyCode::N, None),
+            Action::ZoomIn => (KeyCode::Equal, Some(KeyCode::KpAdd)),
+            Action::ZoomOut => (KeyCode::Minus, Some(KeyCode::KpSubtract)),
+            Action::Mute => (KeyCode::M, None),
+        };
+        [Some(Key(first)), second.map(Key)]
+    }
+}
+
+// Keys that can be bound. Escape stays for leaving, F11 for fullscreen and Backspace for
+// clearing a key.
+const BINDABLE: [KeyCode; 93] = {
+    use KeyCode::*;
+    [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+        Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+        Up, Down, Left, Right, Space, Enter, Tab, Insert, Delete, PageUp, PageDown, Home, End,
+        Apostrophe, Comma, Minus, Period, Slash, Semicolon, Equal, LeftBracket, Backslash, RightBracket, GraveAccent,
+        F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F12,
+        Kp0, Kp1, Kp2, Kp3, Kp4, Kp5, Kp6, Kp7, Kp8, Kp9, KpDecimal, KpDivide, KpMultiply, KpSubtract, KpAdd, KpEnter,
+        LeftShift, LeftControl, LeftAlt, RightShift, RightControl, RightAlt,
+    ]
+};
+
+// A bindable key, saved by its name
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+struct Key(KeyCode);
+
+impl Key {
+    fn name(self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+impl Serialize for Key {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        BINDABLE.iter().map(|&code| Key(code)).find(|key| key.name() == name)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown key: {}", name)))
+    }
+}
+
+// The keys bound to each action; actions missing from the settings file keep their defaults
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Keybinds(std::collections::BTreeMap<Action, [Option<Key>; 2]>);
+
+impl Keybinds {
+    fn keys(&self, action: Action) -> [Option<Key>; 2] {
+        self.0.get(&action).copied().unwrap_or_else(|| action.default_keys())
+    }
+
+    // The action's key in one of the two columns was pressed this frame
+    pub fn pressed(&self, action: Action, column: usize) -> bool {
+        self.keys(action)[column].is_some_and(|key| is_key_pressed(key.0))
+    }
+
+    pub fn released(&self, action: Action, column: usize) -> bool {
+        self.keys(action)[column].is_some_and(|key| is_key_released(key.0))
+    }
+
+    pub fn either_pressed(&self, action: Action) -> bool {
+        self.pressed(action, 0) || self.pressed(action, 1)
+    }
+
+    // Puts `key` on the action's column, taking it off whatever had it so a key does one thing
+    fn bind(&mut self, action: Action, column: usize, key: Option<Key>) {
+        if key.is_some() {
+            for other in Action::ALL {
+                let mut keys = self.keys(other);
+                if keys.contains(&key) {
+                    keys.iter_mut().filter(|k| **k == key).for_each(|k| *k = None);
+                    self.0.insert(other, keys);
+                }
+            }
+        }
+        let mut keys = self.keys(action);
+        keys[column] = key;
+        self.0.insert(action, keys);
+    }
+}
+
+// A small button for the key table; `waiting` marks the one taking the next key press
+fn draw_key_button(rect: Rect, label: &str, waiting: bool) -> bool {
+    let hovering = rect.contains(mouse_position().into());
+    let color = if waiting { GOLD } else if hovering { DARKGREEN } else { GREEN };
+    draw_rectangle(rect.x, rect.y, rect.w, rect.h, color);
+    let ts = measure_text(label, None, 20, 1.0);
+    draw_text(label, rect.x + (rect.w - ts.width) / 2.0, rect.y + rect.h * 0.5 + 7.0, 20.0, BLACK);
+    hovering && is_mouse_button_pressed(MouseButton::Left)
+}
+
+// The key table: clicking a key waits for the next key press to bind in its place, Backspace
+// clears it and Escape changes nothing. True once the player is done.
+pub fn draw_keys(settings: &mut Settings, waiting: &mut Option<(Action, usize)>, panel: Rect) -> bool {
+    draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::new(0.1, 0.1, 0.1, 0.9));
+    draw_text("Keys", panel.x + 40.0, panel.y + 50.0, 40.0, WHITE);
+    draw_text("Player 1", panel.x + 180.0, panel.y + 70.0, 20.0, GRAY);
+    draw_text("Player 2 in versus", panel.x + 340.0, panel.y + 70.0, 20.0, GRAY);
+    // a key that ends the wait shouldn't also act on the screen
+    let was_waiting = waiting.is_some();
+    if let Some((action, column)) = *waiting
+        && let Some(code) = get_last_key_pressed()
+    {
+        let key = match code {
+            KeyCode::Escape => Some(settings.keys.keys(action)[column]),
+            KeyCode::Backspace => Some(None),
+            code if BINDABLE.contains(&code) => Some(Some(Key(code))),
+            // not one that can be bound; keep waiting
+            _ => None,
+        };
+        if let Some(key) = key {
+            settings.keys.bind(action, column, key);
+            *waiting = None;
+            save_settings(settings);
+        }
+    }
+    for (i, action) in Action::ALL.into_iter().enumerate() {
+        let y = panel.y + 80.0 + i as f32 * 24.0;
+        draw_text(action.label(), panel.x + 40.0, y + 19.0, 22.0, WHITE);
+        for (column, key) in settings.keys.keys(action).into_iter().enumerate() {
+            let rect = Rect { x: panel.x + 180.0 + column as f32 * 160.0, y, w: 140.0, h: 23.0 };
+            let is_waiting = *waiting == Some((action, column));
+            let label = if is_waiting { "press a key".to_string() } else { key.map_or("-".to_string(), Key::name) };
+            if draw_key_button(rect, &label, is_waiting) {
+                *waiting = Some((action, column));
+            }
+        }
+    }
+    let reset = Rect { x: panel.x + panel.w - 200.0, y: panel.y + panel.h - 126.0, w: 160.0, h: 44.0 };
+    if draw_button(reset, "Defaults") {
+        settings.keys = Keybinds::default();
+        *waiting = None;
+        save_settings(settings);
+    }
+    let back = Rect { x: panel.x + panel.w - 200.0, y: panel.y + panel.h - 70.0, w: 160.0, h: 44.0 };
+    !was_waiting && (draw_button(back, "Back") || is_key_pressed(KeyCode::Escape))
+}
@@ -0,0 +1,69 @@
+use macroquad::prelude::*;
+
+use snake::game_core::{GameConfig, LobbyMsg, QueueMsg};
+
+use super::theme::team_colors;
+use super::draw_button;
+
+pub enum LobbyAction {
+    None,
+    ToggleReady,
+    NextTeam,
+}
+
+pub fn draw_lobby(lobby: &LobbyMsg, game: GameConfig, ready: bool, panel: Rect) -> LobbyAction {
+    draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::new(0.1, 0.1, 0.1, 0.9));
+    let title = format!("Lobby ({}/{})", lobby.players.len(), lobby.max_players);
+    draw_text(&title, panel.x + 40.0, panel.y + 50.0, 40.0, WHITE);
+    let code = format!("Room code: {}", lobby.room_code);
+    let cs = measure_text(&code, None, 28, 1.0);
+    draw_text(&code, panel.x + panel.w - cs.width - 40.0, panel.y + 50.0, 28.0, YELLOW);
+    let borders = if game.wrap { "wrap-around" } else { "solid walls" };
+    draw_text(&format!("Mode: {}, {}", game.mode, borders), panel.x + 40.0, panel.y + 80.0, 24.0, GRAY);
+
+    for (i, p) in lobby.players.iter().enumerate() {
+        let y = panel.y + 116.0 + i as f32 * 26.0;
+        draw_text(&p.name, panel.x + 40.0, y, 24.0, WHITE);
+        if let Some(team) = p.team {
+            draw_text(&format!("Team {}", team + 1), panel.x + panel.w - 400.0, y, 24.0, team_colors(Some(team)).0);
+        }
+        let (status, color) = if p.ready { ("ready", GREEN) } else { ("waiting", GRAY) };
+        draw_text(status, panel.x + panel.w - 240.0, y, 24.0, color);
+    }
+
+    if let Some(secs) = lobby.countdown {
+        let text = format!("Starting in {}...", secs);
+        draw_text(&text, panel.x + 40.0, panel.y + panel.h - 40.0, 28.0, YELLOW);
+    }
+
+    let btn_rect = Rect { x: panel.x + panel.w - 200.0, y: panel.y + panel.h - 70.0, w: 160.0, h: 44.0 };
+    if draw_button(btn_rect, if ready { "Not ready" } else { "Ready" }) || is_key_pressed(KeyCode::R) {
+        return LobbyAction::ToggleReady;
+    }
+    if lobby.teams > 0 {
+        let team_rect = Rect { x: btn_rect.x - 200.0, ..btn_rect };
+        if draw_button(team_rect, "Switch team") || is_key_pressed(KeyCode::T) {
+            return LobbyAction::NextTeam;
+        }
+    }
+    LobbyAction::None
+}
+
+// The wait for a match, until matchmaking puts us in a room. Returns whether Cancel was
+// clicked.
+pub fn draw_queue(queue: &QueueMsg, panel: Rect) -> bool {
+    draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::new(0.1, 0.1, 0.1, 0.8));
+    let dots = ".".repeat(get_time() as usize % 3 + 1);
+    let title = format!("Looking for a match{}", dots);
+    let ts = measure_text("Looking for a match...", None, 40, 1.0);
+    draw_text(&title, panel.x + (panel.w - ts.width) / 2.0, panel.y + 50.0, 40.0, YELLOW);
+    let waiting = match queue.waiting {
+        0 | 1 => "Nobody else is looking yet".to_string(),
+        n => format!("{} players looking", n),
+    };
+    draw_text(&waiting, panel.x + 40.0, panel.y + 110.0, 24.0, WHITE);
+    let waited = format!("Waited {}:{:02}", queue.waited_secs / 60, queue.waited_secs % 60);
+    draw_text(&waited, panel.x + 40.0, panel.y + 140.0, 24.0, LIGHTGRAY);
+    let cancel_rect = Rect { x: panel.x + (panel.w - 160.0) / 2.0, y: panel.y + panel.h - 70.0, w: 160.0, h: 44.0 };
+    draw_button(cancel_rect, "Cancel")
+}
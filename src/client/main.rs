@@ -0,0 +1,240 @@
+// The windowed client: a menu to connect from, and the match it connects to, drawn with
+// macroquad. Every frame, the screen that is up draws itself and says which screen is next.
+
+mod browser;
+mod connection;
+mod game;
+mod hud;
+mod input;
+mod keybinds;
+mod lobby;
+mod menu;
+mod particles;
+mod profile;
+mod render;
+mod replay_view;
+mod results;
+mod settings;
+mod sound;
+mod text_field;
+mod theme;
+mod transport;
+
+use std::time::Duration;
+
+use macroquad::prelude::*;
+
+use snake::game_core::net::NetOptions;
+use snake::game_core::{Compression, WireFormat};
+
+use browser::{BrowserAction, RoomBrowser};
+use connection::Connection;
+use input::{pads, PadInput, TouchInput};
+use keybinds::{draw_keys, Action};
+use menu::{Menu, PromptAction, RoomPrompt};
+use profile::Profile;
+use render::{Atlas, RenderStyle};
+use settings::{draw_settings, load_settings, save_settings, Settings, SettingsAction};
+use sound::Effect;
+
+const CELL_SIZE: f32 = 20.0; // rendering only, sizes the window for the default board
+// Smallest menu panels, below which their contents stop fitting
+const MIN_PANEL_W: f32 = 760.0;
+const MIN_PANEL_H: f32 = 420.0;
+// How long an unreachable server is tried before giving up, unless --connect-timeout says otherwise
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// Where --record writes matches and Watch replay reads them, unless --replay says otherwise
+const DEFAULT_REPLAY_PATH: &str = "snake.replay";
+
+// What the window shows
+enum Screen {
+    Menu,
+    Settings,
+    // the key table, and which key it waits for if any
+    Keys(Option<(Action, usize)>),
+    Browser(RoomBrowser),
+    Profile(Profile),
+    // a room's password, asked for before connecting to it
+    Prompt(RoomPrompt),
+    Game(Box<Connection>),
+}
+
+// What every screen shares, for as long as the client runs
+struct App {
+    settings: Settings,
+    net_options: NetOptions,
+    colorblind: bool,
+    replay_path: String,
+    // every match played is written to the replay file
+    record: bool,
+    style: RenderStyle,
+    // the atlas, when snakes are drawn with it
+    sprites: Option<Atlas>,
+    show_scoreboard: bool,
+    touch: TouchInput,
+    // a controller for each player local versus has
+    pads: pads::Pads,
+}
+
+impl App {
+    async fn new() -> Self {
+        // --json keeps the connection human-readable for debugging, --udp takes states by datagram,
+        // --compress deflate|lz4 asks for what the server sends to be compressed,
+        // --state-every N for a game state only every N ticks, on slow links,
+        // --connect-timeout SECS bounds how long an unreachable server is tried
+        let args: Vec<String> = std::env::args().collect();
+        let connect_timeout = args.iter().position(|a| a == "--connect-timeout")
+            .and_then(|i| args.get(i + 1)?.parse::<f64>().ok())
+            .filter(|secs| *secs > 0.0)
+            .map_or(DEFAULT_CONNECT_TIMEOUT, Duration::from_secs_f64);
+        let net_options = NetOptions {
+            format: if args.iter().any(|a| a == "--json") { WireFormat::Json } else { WireFormat::Binary },
+            udp: args.iter().any(|a| a == "--udp"),
+            compression: match args.iter().position(|a| a == "--compress").and_then(|i| args.get(i + 1)).map(String::as_str) {
+                Some("deflate") => Compression::Deflate,
+                Some("lz4") => Compression::Lz4,
+                _ => Compression::None,
+            },
+            state_every: args.iter().position(|a| a == "--state-every")
+                .and_then(|i| args.get(i + 1)?.parse().ok())
+                .unwrap_or(1),
+            connect_timeout,
+        };
+        // sprites when the atlas is there, unless --style asks for a flat look
+        let atlas = Atlas::load().await;
+        let style = match args.iter().position(|a| a == "--style").and_then(|i| args.get(i + 1)).map(String::as_str) {
+            Some("blocks") => RenderStyle::Blocks,
+            Some("smooth") => RenderStyle::Smooth,
+            _ if atlas.is_some() => RenderStyle::Sprites,
+            _ => RenderStyle::Smooth,
+        };
+        App {
+            settings: load_settings(),
+            net_options,
+            colorblind: args.iter().any(|a| a == "--colorblind"),
+            // --record writes every match played to the replay file, --replay PATH picks that file
+            replay_path: args.iter().position(|a| a == "--replay")
+                .and_then(|i| args.get(i + 1))
+                .map_or(DEFAULT_REPLAY_PATH.to_string(), String::clone),
+            record: args.iter().any(|a| a == "--record"),
+            style,
+            sprites: atlas.filter(|_| style == RenderStyle::Sprites),
+            show_scoreboard: true,
+            touch: TouchInput::default(),
+            pads: pads::Pads::new(2),
+        }
+    }
+
+    // Where a new connection is recorded to, if anywhere
+    fn recording(&self) -> Option<&str> {
+        self.record.then_some(self.replay_path.as_str())
+    }
+}
+
+// What a screen is given for one frame
+struct Frame {
+    // where most panels go; redone every frame as the window may have been resized, keeping
+    // a size their contents fit in
+    panel: Rect,
+    pad: PadInput,
+    alt: bool,
+}
+
+impl Frame {
+    fn new(pad: PadInput) -> Self {
+        let (screen_w, screen_h) = (screen_width(), screen_height());
+        let panel_w = (screen_w * 0.8).max(MIN_PANEL_W).min(screen_w);
+        let panel_h = (screen_h * 0.6).max(MIN_PANEL_H).min(screen_h);
+        Frame {
+            panel: Rect { x: (screen_w - panel_w) * 0.5, y: (screen_h - panel_h) * 0.5, w: panel_w, h: panel_h },
+            pad,
+            alt: is_key_down(KeyCode::LeftAlt) || is_key_down(KeyCode::RightAlt),
+        }
+    }
+
+    // The panel stretched to run from `top` down for `height`, both shares of the window's height
+    fn tall_panel(&self, top: f32, height: f32) -> Rect {
+        Rect { y: screen_height() * top, h: screen_height() * height, ..self.panel }
+    }
+}
+
+// Draws a clickable button and reports whether it was clicked this frame
+fn draw_button(rect: Rect, label: &str) -> bool {
+    let (mx, my) = mouse_position();
+    let hovering = rect.contains(vec2(mx, my));
+    draw_rectangle(rect.x, rect.y, rect.w, rect.h, if hovering { DARKGREEN } else { GREEN });
+    let bt = measure_text(label, None, 28, 1.0);
+    draw_text(label, rect.x + (rect.w - bt.width) / 2.0, rect.y + 32.0, 28.0, BLACK);
+    let clicked = hovering && is_mouse_button_pressed(MouseButton::Left);
+    if clicked {
+        sound::play(Effect::Click);
+    }
+    clicked
+}
+
+// The window opens the way the saved settings say
+fn window_conf() -> Conf {
+    let settings = load_settings();
+    let (width, height) = settings.window_size();
+    Conf {
+        window_title: "Snake (Client)".to_string(),
+        window_width: width as i32,
+        window_height: height as i32,
+        fullscreen: settings.fullscreen,
+        platform: miniquad::conf::Platform { swap_interval: Some(if settings.vsync { 1 } else { 0 }), ..Default::default() },
+        ..Default::default()
+    }
+}
+
+#[macroquad::main(window_conf)]
+async fn main() {
+    let mut app = App::new().await;
+    sound::load(&app.settings).await;
+    let mut menu = Menu::new(&app.settings);
+    let mut screen = Screen::Menu;
+
+    loop {
+        clear_background(app.settings.theme().background);
+        let frame = Frame::new(app.pads.poll());
+        if is_key_pressed(KeyCode::F11) || (frame.alt && is_key_pressed(KeyCode::Enter)) {
+            app.settings.fullscreen = !app.settings.fullscreen;
+            set_fullscreen(app.settings.fullscreen);
+            save_settings(&app.settings);
+        }
+
+        screen = match screen {
+            Screen::Menu => menu.show(&mut app, &frame),
+            Screen::Settings => match draw_settings(&mut app.settings, &mut app.pads, frame.panel) {
+                SettingsAction::None => Screen::Settings,
+                SettingsAction::Keys => Screen::Keys(None),
+                SettingsAction::Done => Screen::Menu,
+            },
+            Screen::Keys(mut waiting) => {
+                if draw_keys(&mut app.settings, &mut waiting, frame.panel) { Screen::Settings } else { Screen::Keys(waiting) }
+            }
+            Screen::Browser(mut browser) => {
+                browser.update();
+                match browser.draw(frame.tall_panel(0.1, 0.8)) {
+                    BrowserAction::None => Screen::Browser(browser),
+                    BrowserAction::Back => Screen::Menu,
+                    BrowserAction::Pick(code) => {
+                        menu.room_code.set(code);
+                        Screen::Menu
+                    }
+                }
+            }
+            Screen::Profile(mut profile) => {
+                profile.update();
+                if profile.draw(frame.panel) { Screen::Menu } else { Screen::Profile(profile) }
+            }
+            Screen::Prompt(mut prompt) => match prompt.draw(frame.panel) {
+                PromptAction::None => Screen::Prompt(prompt),
+                PromptAction::Cancel => Screen::Menu,
+                PromptAction::Connect(room_password) => menu.connect_prompted(&app, prompt, room_password),
+            },
+            Screen::Game(conn) => game::show(conn, &mut app, &mut menu, &frame),
+        };
+
+        next_frame().await;
+    }
+}
@@ -0,0 +1,396 @@
+use macroquad::prelude::*;
+
+use snake::game_core::net::RoomChoice;
+use snake::game_core::sim::MatchConfig;
+use snake::game_core::{ErrorCode, GameMode, MAX_NAME_LEN, MAX_PLAYERS, MAX_ROOM_PASSWORD_LEN, ROOM_CODE_LEN};
+
+use super::browser::RoomBrowser;
+use super::connection::Connection;
+use super::profile::Profile;
+use super::replay_view::load_replay;
+use super::settings::{save_settings, Settings};
+use super::text_field::TextField;
+use super::transport::{check_address, Auth};
+use super::{draw_button, App, Frame, Screen};
+
+// where a game hosted from the menu listens
+#[cfg(not(target_arch = "wasm32"))]
+const HOST_PORT: u16 = 4000;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Focus { None, Name, Password, Address, Room }
+
+impl Focus {
+    // The field Tab moves to, or Shift+Tab when `back`
+    fn cycle(self, back: bool) -> Focus {
+        const ORDER: [Focus; 4] = [Focus::Name, Focus::Password, Focus::Address, Focus::Room];
+        let Some(i) = ORDER.iter().position(|&f| f == self) else { return Focus::Name };
+        ORDER[if back { i + ORDER.len() - 1 } else { i + 1 } % ORDER.len()]
+    }
+}
+
+// Longest password the connect form takes
+const MAX_PASSWORD_LEN: usize = 64;
+// Longest server address: a full-length hostname with a scheme and a port
+const MAX_ADDR_LEN: usize = 270;
+
+// Starts the game server inside the client the first time, and returns the port it listens
+// on; it runs until the client quits
+#[cfg(not(target_arch = "wasm32"))]
+fn host_game(hosted: &mut Option<u16>) -> Result<u16, String> {
+    if let Some(port) = *hosted {
+        return Ok(port);
+    }
+    // friends in every slot a match has
+    let config = MatchConfig { max_players: MAX_PLAYERS, ..MatchConfig::default() };
+    let port = snake::server::host(HOST_PORT, config)?.port();
+    *hosted = Some(port);
+    Ok(port)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn host_game(_hosted: &mut Option<u16>) -> Result<u16, String> {
+    Err("The browser build can't host games".to_string())
+}
+
+// Where friends reach a game hosted here, as far as this machine can tell
+#[cfg(not(target_arch = "wasm32"))]
+fn host_address(port: u16) -> String {
+    match snake::server::lan_ip() {
+        Some(ip) => format!("{}:{}", ip, port),
+        None => format!("this machine's address, port {}", port),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn host_address(port: u16) -> String {
+    format!("port {}", port)
+}
+
+// The connect form, and what the menu remembers between connections
+pub struct Menu {
+    username: TextField,
+    // left empty to play as a guest, or with a saved login
+    password: TextField,
+    // the password registers the name rather than logging in to it
+    register: bool,
+    server_addr: TextField,
+    pub room_code: TextField,
+    // what a room we create plays, and what offline play plays
+    mode: GameMode,
+    wrap: bool,
+    // a room we create asks for a password to join it
+    private: bool,
+    focus: Focus,
+    // why the last connection ended, shown with a Retry button
+    error: Option<String>,
+    // what Retry reconnects to
+    last_target: Option<(String, String, RoomChoice)>,
+    // the port of the server Host game started, once it has
+    hosted: Option<u16>,
+}
+
+impl Menu {
+    pub fn new(settings: &Settings) -> Self {
+        Menu {
+            username: TextField::new(settings.username.clone(), MAX_NAME_LEN),
+            password: TextField::new(String::new(), MAX_PASSWORD_LEN),
+            register: false,
+            server_addr: TextField::new(settings.server_addr.clone(), MAX_ADDR_LEN),
+            room_code: TextField::new(String::new(), ROOM_CODE_LEN),
+            mode: GameMode::default(),
+            wrap: true,
+            private: false,
+            focus: Focus::Name,
+            error: None,
+            last_target: None,
+            hosted: None,
+        }
+    }
+
+    // Connects to the room the prompt asked the password for
+    pub fn connect_prompted(&mut self, app: &App, prompt: RoomPrompt, room_password: String) -> Screen {
+        let RoomPrompt { target: (addr, name, room), hosted_at, .. } = prompt;
+        let room = room.with_password(Some(room_password));
+        let mut c = Connection::open(addr, name, room, Auth::from_form(&self.password.text, self.register), app.net_options).recorded(app.recording());
+        c.hosted_at = hosted_at;
+        self.error = None;
+        Screen::Game(Box::new(c))
+    }
+
+    // Back from a connection that ended, saying why; a room that wants a password asks for it
+    pub fn ended(&mut self, c: &Connection, reason: String) -> Screen {
+        let target = (c.server_addr.clone(), c.username.clone(), c.target(self.mode, self.wrap));
+        let prompt = (c.refused == Some(ErrorCode::WrongPassword)).then(|| RoomPrompt::new(reason.clone(), target.clone()));
+        self.last_target = Some(target);
+        self.error = Some(reason);
+        prompt.map_or(Screen::Menu, Screen::Prompt)
+    }
+
+    // The server took the login, which is saved from now on
+    pub fn logged_in(&mut self) {
+        self.password.set(String::new());
+        self.register = false;
+    }
+
+    // The name players go by offline, when the form has none
+    fn name_or(&self, default: &str) -> String {
+        if self.username.text.is_empty() { default.to_string() } else { self.username.text.clone() }
+    }
+
+    // One frame of the menu; returns the screen to show next
+    pub fn show(&mut self, app: &mut App, frame: &Frame) -> Screen {
+        let Rect { x: panel_x, y: panel_y, w: panel_w, h: panel_h } = frame.panel;
+        let mut next = Screen::Menu;
+        draw_rectangle(panel_x, panel_y, panel_w, panel_h, Color::new(0.1, 0.1, 0.1, 0.9));
+        let title = "Multiplayer Snake";
+        let ts = measure_text(title, None, 40, 1.0);
+        draw_text(title, panel_x + (panel_w - ts.width) / 2.0, panel_y + 50.0, 40.0, WHITE);
+        let settings_rect = Rect { x: panel_x + panel_w - 200.0, y: panel_y + 16.0, w: 180.0, h: 44.0 };
+        if draw_button(settings_rect, "Settings") {
+            next = Screen::Settings;
+        }
+        let replay_rect = Rect { x: panel_x + 20.0, ..settings_rect };
+        if draw_button(replay_rect, "Watch replay") {
+            match load_replay(&app.replay_path) {
+                Ok(replay) => {
+                    next = Screen::Game(Box::new(Connection::replay(replay)));
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(e),
+            }
+        }
+
+        // Inputs
+        let name_rect = Rect { x: panel_x + 40.0, y: panel_y + 90.0, w: panel_w - 500.0, h: 48.0 };
+        let password_rect = Rect { x: name_rect.x + name_rect.w + 20.0, w: 240.0, ..name_rect };
+        let register_rect = Rect { x: password_rect.x + password_rect.w + 20.0, w: 140.0, ..name_rect };
+        let addr_rect = Rect { x: panel_x + 40.0, y: panel_y + 160.0, w: panel_w - 280.0, h: 48.0 };
+        let profile_rect = Rect { x: addr_rect.x + addr_rect.w + 20.0, w: 180.0, ..addr_rect };
+        let room_rect = Rect { x: panel_x + 40.0, y: panel_y + 230.0, w: panel_w - 640.0, h: 48.0 };
+        let mode_rect = Rect { x: room_rect.x + room_rect.w + 20.0, w: 240.0, ..room_rect };
+        let wrap_rect = Rect { x: mode_rect.x + mode_rect.w + 20.0, w: 120.0, ..room_rect };
+        let private_rect = Rect { x: wrap_rect.x + wrap_rect.w + 20.0, ..wrap_rect };
+
+        // Focus handling; a click in a field also puts the cursor there
+        if is_mouse_button_pressed(MouseButton::Left) {
+            let (mx, my) = mouse_position();
+            let p = vec2(mx, my);
+            if name_rect.contains(p) {
+                self.focus = Focus::Name;
+                self.username.click(name_rect, mx, false);
+            } else if password_rect.contains(p) {
+                self.focus = Focus::Password;
+                self.password.click(password_rect, mx, true);
+            } else if addr_rect.contains(p) {
+                self.focus = Focus::Address;
+                self.server_addr.click(addr_rect, mx, false);
+            } else if room_rect.contains(p) {
+                self.focus = Focus::Room;
+                self.room_code.click(room_rect, mx, false);
+            } else {
+                self.focus = Focus::None;
+            }
+        }
+        if is_key_pressed(KeyCode::Tab) {
+            self.focus = self.focus.cycle(is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift));
+        }
+
+        // Input
+        match self.focus {
+            Focus::Name => self.username.update(),
+            Focus::Password => self.password.update(),
+            Focus::Address => self.server_addr.update(),
+            Focus::Room => self.room_code.update(),
+            Focus::None => {}
+        }
+
+        self.username.draw(name_rect, "Username", self.focus == Focus::Name, false);
+        self.password.draw(password_rect, "Password", self.focus == Focus::Password, true);
+        if draw_button(register_rect, if self.register { "Register" } else { "Log in" }) {
+            self.register = !self.register;
+        }
+        self.server_addr.draw(addr_rect, "Server address (e.g., 127.0.0.1:4000)", self.focus == Focus::Address, false);
+        self.room_code.draw(room_rect, "Room code (leave empty to create one)", self.focus == Focus::Room, false);
+        // what's wrong with the address, if anything, for the buttons that connect to it
+        let address = check_address(&self.server_addr.text);
+        if draw_button(profile_rect, "Profile") && !self.username.text.is_empty() {
+            match &address {
+                Ok(()) => next = Screen::Profile(Profile::open(self.server_addr.text.clone(), self.username.text.clone(), app.net_options)),
+                Err(e) => self.error = Some(e.clone()),
+            }
+        }
+        if draw_button(mode_rect, &self.mode.to_string()) {
+            self.mode = self.mode.next();
+        }
+        if draw_button(wrap_rect, if self.wrap { "Wrap" } else { "Walls" }) {
+            self.wrap = !self.wrap;
+        }
+        if draw_button(private_rect, if self.private { "Private" } else { "Public" }) {
+            self.private = !self.private;
+        }
+
+        // Connect button
+        let btn_rect = Rect { x: panel_x + panel_w - 200.0, y: panel_y + panel_h - 70.0, w: 160.0, h: 44.0 };
+        let clicked = draw_button(btn_rect, "Connect");
+        let browse_rect = Rect { x: panel_x + 40.0, y: btn_rect.y, w: 180.0, h: 44.0 };
+        if draw_button(browse_rect, "Browse rooms") {
+            match &address {
+                Ok(()) => next = Screen::Browser(RoomBrowser::open(self.server_addr.text.clone(), app.net_options)),
+                Err(e) => self.error = Some(e.clone()),
+            }
+        }
+        let offline_rect = Rect { x: browse_rect.x + browse_rect.w + 20.0, w: 160.0, ..browse_rect };
+        if draw_button(offline_rect, "Play offline") {
+            let c = Connection::offline(vec![self.name_or("Player")], self.mode, self.wrap).recorded(app.recording());
+            next = Screen::Game(Box::new(c));
+            self.error = None;
+        }
+        // two players at one keyboard
+        let versus_rect = Rect { x: offline_rect.x + offline_rect.w + 20.0, w: 120.0, ..browse_rect };
+        if draw_button(versus_rect, "Versus") {
+            let c = Connection::offline(vec![self.name_or("Player 1"), "Player 2".to_string()], self.mode, self.wrap).recorded(app.recording());
+            next = Screen::Game(Box::new(c));
+            self.error = None;
+        }
+
+        let auth = Auth::from_form(&self.password.text, self.register);
+
+        // Why the last attempt failed, with a way to try the same thing again
+        let host_rect = Rect { y: btn_rect.y - 56.0, ..btn_rect };
+        if let Some(error) = &self.error {
+            draw_text(error, panel_x + 40.0, host_rect.y + 30.0, 24.0, RED);
+            let after_text = panel_x + 60.0 + measure_text(error, None, 24, 1.0).width;
+            let retry_rect = Rect { x: after_text.min(host_rect.x - 360.0), ..host_rect };
+            if let Some((addr, name, room)) = self.last_target.clone()
+                && draw_button(retry_rect, "Retry")
+            {
+                next = Screen::Game(Box::new(Connection::open(addr, name, room, auth.clone(), app.net_options).recorded(app.recording())));
+                self.error = None;
+            }
+        }
+
+        // a server of our own, in this process, that friends on the LAN can join
+        if cfg!(not(target_arch = "wasm32")) && draw_button(host_rect, "Host game") {
+            match host_game(&mut self.hosted) {
+                Ok(port) => {
+                    let target = (format!("127.0.0.1:{}", port), self.name_or("Player"), RoomChoice::Create { mode: self.mode, wrap: self.wrap, password: None });
+                    if self.private {
+                        let mut prompt = RoomPrompt::new("Choose a password for your room".to_string(), target);
+                        prompt.hosted_at = Some(host_address(port));
+                        next = Screen::Prompt(prompt);
+                    } else {
+                        let (addr, name, room) = target;
+                        let mut c = Connection::open(addr, name, room, auth.clone(), app.net_options).recorded(app.recording());
+                        c.hosted_at = Some(host_address(port));
+                        next = Screen::Game(Box::new(c));
+                    }
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(e),
+            }
+        }
+
+        // a room of players near our rating, found by the server
+        let quick_rect = Rect { x: host_rect.x - 180.0, ..host_rect };
+        if draw_button(quick_rect, "Quick match") && !self.username.text.is_empty() {
+            match &address {
+                Ok(()) => {
+                    let c = Connection::open(self.server_addr.text.clone(), self.username.text.clone(), RoomChoice::Queue, auth.clone(), app.net_options);
+                    next = Screen::Game(Box::new(c.recorded(app.recording())));
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(e.clone()),
+            }
+        }
+
+        let wants_connect = clicked || (is_key_pressed(KeyCode::Enter) && !frame.alt) || frame.pad.confirm;
+        if wants_connect && !self.username.text.is_empty() {
+            match &address {
+                Err(e) => self.error = Some(e.clone()),
+                Ok(()) => {
+                    let code = self.room_code.text.trim().to_uppercase();
+                    app.settings.username = self.username.text.clone();
+                    app.settings.server_addr = self.server_addr.text.clone();
+                    save_settings(&app.settings);
+                    let create = RoomChoice::Create { mode: self.mode, wrap: self.wrap, password: None };
+                    if code.is_empty() && self.private {
+                        let target = (self.server_addr.text.clone(), self.username.text.clone(), create);
+                        next = Screen::Prompt(RoomPrompt::new("Choose a password for your room".to_string(), target));
+                    } else {
+                        let room = if code.is_empty() { create } else { RoomChoice::Join { code, password: None } };
+                        // on to the game view, which shows "Connecting..." until a state arrives
+                        let c = Connection::open(self.server_addr.text.clone(), self.username.text.clone(), room, auth, app.net_options);
+                        next = Screen::Game(Box::new(c.recorded(app.recording())));
+                    }
+                    self.error = None;
+                }
+            }
+        }
+        next
+    }
+}
+
+pub enum PromptAction {
+    None,
+    Cancel,
+    Connect(String),
+}
+
+// Asks for a room's password before connecting to it: the one a private room we're joining
+// wants, or the one a room we're creating will
+pub struct RoomPrompt {
+    title: String,
+    field: TextField,
+    // server, name and room to connect to with the password
+    target: (String, String, RoomChoice),
+    // where friends can join, when the room is on the server we host
+    hosted_at: Option<String>,
+}
+
+impl RoomPrompt {
+    fn new(title: String, target: (String, String, RoomChoice)) -> Self {
+        RoomPrompt { title, field: TextField::new(String::new(), MAX_ROOM_PASSWORD_LEN), target, hosted_at: None }
+    }
+
+    pub fn draw(&mut self, panel: Rect) -> PromptAction {
+        draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::new(0.1, 0.1, 0.1, 0.9));
+        let ts = measure_text(&self.title, None, 30, 1.0);
+        draw_text(&self.title, panel.x + (panel.w - ts.width) / 2.0, panel.y + 60.0, 30.0, YELLOW);
+        let field_rect = Rect { x: panel.x + 40.0, y: panel.y + 110.0, w: panel.w - 80.0, h: 48.0 };
+        self.field.update();
+        self.field.draw(field_rect, "Room password", true, true);
+
+        let connect_rect = Rect { x: panel.x + panel.w - 200.0, y: panel.y + panel.h - 70.0, w: 160.0, h: 44.0 };
+        let cancel_rect = Rect { x: panel.x + 40.0, ..connect_rect };
+        let connect = draw_button(connect_rect, "Connect") || is_key_pressed(KeyCode::Enter);
+        if connect && !self.field.text.is_empty() {
+            PromptAction::Connect(self.field.text.clone())
+        } else if draw_button(cancel_rect, "Cancel") || is_key_pressed(KeyCode::Escape) {
+            PromptAction::Cancel
+        } else {
+            PromptAction::None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tab_goes_round_the_fields() {
+        let mut focus = Focus::Name;
+        let mut order = Vec::new();
+        for _ in 0..4 {
+            focus = focus.cycle(false);
+            order.push(focus);
+        }
+        assert_eq!(order, [Focus::Password, Focus::Address, Focus::Room, Focus::Name]);
+        assert_eq!(Focus::Name.cycle(true), Focus::Room);
+        assert_eq!(Focus::Password.cycle(true), Focus::Name);
+        // after a click outside every field, Tab starts from the first
+        assert_eq!(Focus::None.cycle(false), Focus::Name);
+        assert_eq!(Focus::None.cycle(true), Focus::Name);
+    }
+}
@@ -0,0 +1,91 @@
+use macroquad::prelude::*;
+
+use snake::game_core::Pos;
+
+use super::render::draw_rect_at;
+
+// Past this many, the oldest particles make way
+const MAX_PARTICLES: usize = 2000;
+
+// A dot flying off something that happened on the board; positions in cells
+struct Particle {
+    pos: Vec2,
+    vel: Vec2,
+    color: Color,
+    born: f64,
+    life: f64,
+}
+
+#[derive(Default)]
+pub struct Particles {
+    list: Vec<Particle>,
+}
+
+impl Particles {
+    // `count` dots flying every which way from `at`, up to `speed` cells a second
+    pub fn burst(&mut self, at: Vec2, count: usize, speed: f32, life: f64, color: Color) {
+        let now = get_time();
+        for _ in 0..count {
+            let angle = macroquad::rand::gen_range(0.0, std::f32::consts::TAU);
+            let vel = Vec2::from_angle(angle) * speed * macroquad::rand::gen_range(0.3, 1.0);
+            let life = life * macroquad::rand::gen_range(0.6, 1.0);
+            self.list.push(Particle { pos: at, vel, color, born: now, life });
+        }
+        let excess = self.list.len().saturating_sub(MAX_PARTICLES);
+        self.list.drain(..excess);
+    }
+
+    // Moves the live particles on and draws them shrinking and fading, in board pixels
+    pub fn draw(&mut self, cell: f32) {
+        let (now, dt) = (get_time(), get_frame_time());
+        self.list.retain(|p| now - p.born < p.life);
+        for p in self.list.iter_mut() {
+            p.pos += p.vel * dt;
+            let left = 1.0 - ((now - p.born) / p.life) as f32;
+            draw_circle(p.pos.x * cell, p.pos.y * cell, (cell * 0.15 * left).max(1.0), p.color.with_alpha(p.color.a * left));
+        }
+    }
+}
+
+pub fn cell_centre(pos: Pos) -> Vec2 {
+    vec2(pos.x as f32 + 0.5, pos.y as f32 + 0.5)
+}
+
+// How long the board shakes when the local snake dies, and how far at first, in cells
+pub const SHAKE_SECS: f64 = 0.35;
+const SHAKE_CELLS: f32 = 0.5;
+// The dead local snake's segments go one after another, head first, each this much later
+// than the one before and taking DEATH_FADE_SECS to fade
+const DEATH_FADE_STEP: f64 = 0.04;
+const DEATH_FADE_SECS: f64 = 0.3;
+
+// The local snake as it was when it died, coming apart
+pub struct DeathFade {
+    pub snake: Vec<Pos>,
+    pub head: Color,
+    pub body: Color,
+    pub start: f64,
+}
+
+impl DeathFade {
+    // False once the last segment is gone
+    pub fn draw(&self, cell: f32) -> bool {
+        let elapsed = get_time() - self.start;
+        for (i, &pos) in self.snake.iter().enumerate() {
+            let left = 1.0 - ((elapsed - i as f64 * DEATH_FADE_STEP) / DEATH_FADE_SECS).clamp(0.0, 1.0) as f32;
+            let color = if i == 0 { self.head } else { self.body };
+            draw_rect_at(pos, cell, color.with_alpha(color.a * left));
+        }
+        elapsed < self.snake.len() as f64 * DEATH_FADE_STEP + DEATH_FADE_SECS
+    }
+}
+
+// Where the board is pushed while it shakes, in pixels
+pub fn shake_offset(shake_until: f64, cell: f32) -> Vec2 {
+    let left = ((shake_until - get_time()) / SHAKE_SECS).max(0.0) as f32;
+    if left == 0.0 {
+        return Vec2::ZERO;
+    }
+    let amount = SHAKE_CELLS * cell * left;
+    vec2(macroquad::rand::gen_range(-amount, amount), macroquad::rand::gen_range(-amount, amount))
+}
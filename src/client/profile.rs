@@ -0,0 +1,97 @@
+use macroquad::prelude::*;
+
+use snake::game_core::net::{hello, version_mismatch_text, NetOptions, NetStatus};
+use snake::game_core::{ClientMsg, PlayerStats, ServerMsg, WireFormat};
+
+use super::transport::{open_transport, NetTransport};
+use super::draw_button;
+
+// A player's lifetime stats and the server's leaderboard, fetched over their own short-lived
+// connection
+pub struct Profile {
+    net: Box<dyn NetTransport>,
+    name: String,
+    stats: Option<PlayerStats>,
+    leaderboard: Option<Vec<PlayerStats>>,
+    error: Option<String>,
+}
+
+impl Profile {
+    pub fn open(server_addr: String, name: String, options: NetOptions) -> Self {
+        let options = NetOptions { format: WireFormat::Json, udp: false, ..options };
+        let requests = vec![hello(options), ClientMsg::GetStats { name: name.clone() }, ClientMsg::GetLeaderboard];
+        let net = open_transport(server_addr, requests, options);
+        Profile { net, name, stats: None, leaderboard: None, error: None }
+    }
+
+    pub fn update(&mut self) {
+        while let Some(status) = self.net.poll_status() {
+            // the server may hang up on an idle connection once it answered
+            if let NetStatus::Failed(reason) = status
+                && (self.stats.is_none() || self.leaderboard.is_none())
+            {
+                self.error.get_or_insert(reason);
+            }
+        }
+        while let Some(msg) = self.net.try_recv() {
+            match msg {
+                ServerMsg::Stats(stats) => self.stats = Some(stats),
+                ServerMsg::Leaderboard(list) => self.leaderboard = Some(list),
+                ServerMsg::VersionMismatch { server_version } => self.error = Some(version_mismatch_text(server_version)),
+                ServerMsg::Kick { reason } => self.error = Some(format!("Kicked: {}", reason)),
+                _ => {}
+            }
+        }
+    }
+
+    // True once the player is done looking
+    pub fn draw(&self, panel: Rect) -> bool {
+        draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::new(0.1, 0.1, 0.1, 0.9));
+        draw_text(&self.name, panel.x + 40.0, panel.y + 50.0, 40.0, WHITE);
+        let line = |i: usize| panel.y + 100.0 + i as f32 * 36.0;
+        match (&self.stats, &self.error) {
+            (_, Some(error)) => {
+                draw_text(error, panel.x + 40.0, line(0), 24.0, RED);
+            }
+            (None, None) => {
+                draw_text("Loading...", panel.x + 40.0, line(0), 24.0, GRAY);
+            }
+            (Some(stats), _) if stats.games == 0 && stats.time_alive_ms == 0 => {
+                draw_text("No games played on this server yet", panel.x + 40.0, line(0), 24.0, GRAY);
+            }
+            (Some(stats), _) => {
+                let alive_secs = stats.time_alive_ms / 1000;
+                let rows = [
+                    ("Games", stats.games.to_string()),
+                    ("Wins", stats.wins.to_string()),
+                    ("Kills", stats.kills.to_string()),
+                    ("Deaths", stats.deaths.to_string()),
+                    ("Food eaten", stats.food_eaten.to_string()),
+                    ("Time alive", format!("{}h {:02}m {:02}s", alive_secs / 3600, alive_secs / 60 % 60, alive_secs % 60)),
+                    ("Rating", stats.rating.map_or("unrated".to_string(), |r| r.to_string())),
+                ];
+                for (i, (label, value)) in rows.iter().enumerate() {
+                    draw_text(label, panel.x + 40.0, line(i), 28.0, GRAY);
+                    draw_text(value, panel.x + 260.0, line(i), 28.0, WHITE);
+                }
+            }
+        }
+        // the leaderboard down the right half
+        if let Some(list) = &self.leaderboard {
+            let x = panel.x + panel.w / 2.0 + 20.0;
+            draw_text("Top rated", x, panel.y + 50.0, 28.0, WHITE);
+            if list.is_empty() {
+                draw_text("Nobody is rated yet", x, line(0), 24.0, GRAY);
+            }
+            for (i, entry) in list.iter().enumerate() {
+                let y = panel.y + 90.0 + i as f32 * 26.0;
+                let color = if entry.name == self.name { YELLOW } else { WHITE };
+                draw_text(&format!("{}.", i + 1), x, y, 22.0, GRAY);
+                draw_text(&entry.name, x + 40.0, y, 22.0, color);
+                draw_text(&entry.rating.unwrap_or_default().to_string(), panel.x + panel.w - 100.0, y, 22.0, color);
+            }
+        }
+        let back = Rect { x: panel.x + panel.w - 200.0, y: panel.y + panel.h - 70.0, w: 160.0, h: 44.0 };
+        draw_button(back, "Back") || is_key_pressed(KeyCode::Escape)
+    }
+}
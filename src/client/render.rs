@@ -0,0 +1,371 @@
+use macroquad::prelude::*;
+
+use snake::game_core::{heading, toward, BoardMap, CORPSE_TICKS, Direction, FoodItem, FoodKind, GameConfig, PlayerId, PlayerState, Pos, PowerUpKind, StateMsg};
+
+use super::settings::Settings;
+use super::theme::{snake_colors, Theme};
+
+// Cells are never drawn smaller than this at the normal zoom. A board that would need
+// smaller ones doesn't fit in the window, and the view follows our snake around it instead.
+const MIN_CELL_PX: f32 = 12.0;
+// How far zooming goes either way, and how much one key press or wheel notch changes it
+pub const MIN_ZOOM: f32 = 0.25;
+pub const MAX_ZOOM: f32 = 4.0;
+pub const ZOOM_STEP: f32 = 1.25;
+// How quickly the view catches up with the snake it follows; higher is snappier
+const FOLLOW_RATE: f32 = 6.0;
+
+// What part of the board the window looks at
+#[derive(Default)]
+pub struct View {
+    // the cell in the middle of the window, in cells; None until there's something to look at
+    centre: Option<Vec2>,
+}
+
+impl View {
+    // Glides toward `target` a bit every frame. It jumps when the target is half a board
+    // away, which is a snake going over a wrapping edge rather than anywhere worth gliding.
+    pub fn follow(&mut self, target: Vec2, config: &GameConfig, dt: f32) {
+        let half = vec2(config.grid_width as f32, config.grid_height as f32) / 2.0;
+        self.centre = match self.centre {
+            Some(centre) if (target - centre).abs().cmplt(half).all() => Some(centre.lerp(target, 1.0 - (-FOLLOW_RATE * dt).exp())),
+            _ => Some(target),
+        };
+    }
+}
+
+// The cell size for the server's board, and where the board's top-left corner goes. A board
+// that fits sits centred, with bars on the sides that are left over; one that doesn't is
+// kept on the view's centre as far as its edges let it. Pixel-perfect keeps cells and the
+// board's corner on whole pixels, so every cell is drawn the same size.
+pub fn board_view(config: &GameConfig, view: &View, settings: &Settings) -> (f32, Vec2) {
+    let board = vec2(config.grid_width as f32, config.grid_height as f32);
+    let screen = vec2(screen_width(), screen_height());
+    let fit = (screen.x / board.x).min(screen.y / board.y);
+    // zooming out stops once the whole board is in view
+    let mut cell = (fit.max(MIN_CELL_PX) * settings.zoom.clamp(MIN_ZOOM, MAX_ZOOM)).max(fit);
+    if settings.pixel_perfect {
+        cell = cell.floor().max(1.0);
+    }
+    let centre = view.centre.unwrap_or(board / 2.0);
+    let axis = |screen: f32, size: f32, centre: f32| {
+        if size <= screen { (screen - size) / 2.0 } else { (screen / 2.0 - centre * cell).clamp(screen - size, 0.0) }
+    };
+    let origin = vec2(axis(screen.x, board.x * cell, centre.x), axis(screen.y, board.y * cell, centre.y));
+    (cell, if settings.pixel_perfect { origin.floor() } else { origin })
+}
+
+// Board drawing happens in board pixels, from the board's top-left corner; this camera
+// puts that corner at `origin` on screen. set_default_camera goes back to screen pixels.
+pub fn set_board_camera(origin: Vec2) {
+    set_camera(&Camera2D::from_display_rect(Rect::new(-origin.x, -origin.y, screen_width(), screen_height())));
+}
+
+// The part of the board on screen, in cells, when its top-left corner is at `origin`
+pub fn visible_cells(config: &GameConfig, cell: f32, origin: Vec2) -> Rect {
+    let (w, h) = (config.grid_width as f32, config.grid_height as f32);
+    let x = (-origin.x / cell).clamp(0.0, w);
+    let y = (-origin.y / cell).clamp(0.0, h);
+    let right = ((screen_width() - origin.x) / cell).clamp(x, w);
+    let bottom = ((screen_height() - origin.y) / cell).clamp(y, h);
+    Rect::new(x, y, right - x, bottom - y)
+}
+
+// Darkens the window around a board of `size` placed at `origin`
+pub fn draw_letterbox(origin: Vec2, size: Vec2) {
+    let shade = Color::new(0.0, 0.0, 0.0, 0.4);
+    let (sw, sh) = (screen_width(), screen_height());
+    draw_rectangle(0.0, 0.0, sw, origin.y, shade);
+    draw_rectangle(0.0, origin.y + size.y, sw, sh - origin.y - size.y, shade);
+    draw_rectangle(0.0, origin.y, origin.x, size.y, shade);
+    draw_rectangle(origin.x + size.x, origin.y, sw - origin.x - size.x, size.y, shade);
+}
+
+pub fn draw_rect_at(pos: Pos, cell: f32, color: Color) {
+    let x = pos.x as f32 * cell;
+    let y = pos.y as f32 * cell;
+    let gap = (cell * 0.1).min(2.0);
+    draw_rectangle(x, y, cell - gap, cell - gap, color);
+}
+
+// How snakes, food and walls are drawn, picked with --style
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RenderStyle {
+    // one square per cell, like the board's grid
+    Blocks,
+    // a rounded tube through the cell centres
+    Smooth,
+    // tiles from the texture atlas, when it could be loaded
+    Sprites,
+}
+
+const ATLAS_PATH: &str = "assets/atlas.png";
+// Size in pixels of one square tile in the atlas
+const ATLAS_TILE: f32 = 16.0;
+
+// The atlas's tiles, left to right. They're drawn white and tinted when drawn: the head
+// faces right, the body runs left to right, the corner joins left and bottom, and the
+// tail joins on its left.
+#[derive(Copy, Clone, Debug)]
+pub enum Tile {
+    Head,
+    Body,
+    Corner,
+    Tail,
+    Food,
+    Wall,
+}
+
+pub struct Atlas {
+    texture: Texture2D,
+}
+
+impl Atlas {
+    // None when the file is missing or unreadable; everything is drawn flat then
+    pub async fn load() -> Option<Atlas> {
+        let texture = load_texture(ATLAS_PATH).await.ok()?;
+        texture.set_filter(FilterMode::Nearest);
+        Some(Atlas { texture })
+    }
+
+    // `quarter_turns` turns the tile clockwise
+    pub fn draw(&self, tile: Tile, pos: Pos, cell: f32, quarter_turns: u8, color: Color) {
+        let source = Rect::new(tile as usize as f32 * ATLAS_TILE, 0.0, ATLAS_TILE, ATLAS_TILE);
+        draw_texture_ex(&self.texture, pos.x as f32 * cell, pos.y as f32 * cell, color, DrawTextureParams {
+            dest_size: Some(vec2(cell, cell)),
+            source: Some(source),
+            rotation: quarter_turns as f32 * std::f32::consts::FRAC_PI_2,
+            ..Default::default()
+        });
+    }
+
+    pub fn draw_snake(&self, snake: &[Pos], dir: Direction, config: &GameConfig, cell: f32, head: Color, body: Color) {
+        for (i, &pos) in snake.iter().enumerate().skip(1) {
+            let to_head = toward(pos, snake[i - 1], config);
+            let (tile, turns) = match (to_head, snake.get(i + 1).and_then(|&next| toward(pos, next, config))) {
+                (Some(a), None) if i == snake.len() - 1 => (Tile::Tail, (quarter_turns(a) + 2) % 4),
+                (Some(a), Some(b)) if a == b.opposite() => (Tile::Body, quarter_turns(a) % 2),
+                (Some(a), Some(b)) => (Tile::Corner, corner_turns(a, b)),
+                _ => (Tile::Body, 0),
+            };
+            self.draw(tile, pos, cell, turns, body);
+        }
+        if let Some(&pos) = snake.first() {
+            self.draw(Tile::Head, pos, cell, quarter_turns(heading(snake, dir, config)), head);
+        }
+    }
+}
+
+// Quarter turns clockwise from facing right
+fn quarter_turns(dir: Direction) -> u8 {
+    match dir {
+        Direction::Right => 0,
+        Direction::Down => 1,
+        Direction::Left => 2,
+        Direction::Up => 3,
+    }
+}
+
+// Turns for the corner tile, which joins left and bottom unturned
+fn corner_turns(a: Direction, b: Direction) -> u8 {
+    use Direction::*;
+    match (a, b) {
+        (Left, Down) | (Down, Left) => 0,
+        (Up, Left) | (Left, Up) => 1,
+        (Right, Up) | (Up, Right) => 2,
+        _ => 3,
+    }
+}
+
+// Everything behind the head
+pub fn draw_body(snake: &[Pos], cell: f32, color: Color, style: RenderStyle) {
+    let Some(body) = snake.get(1..) else { return };
+    if style == RenderStyle::Blocks {
+        for s in body {
+            draw_rect_at(*s, cell, color);
+        }
+        return;
+    }
+    let width = cell * 0.8;
+    let centre = |p: &Pos| vec2((p.x as f32 + 0.5) * cell, (p.y as f32 + 0.5) * cell);
+    // joined to the head too, which is drawn on top; nothing joins across the wrapping edge
+    for pair in snake.windows(2) {
+        if (pair[0].x - pair[1].x).abs() + (pair[0].y - pair[1].y).abs() == 1 {
+            let (a, b) = (centre(&pair[0]), centre(&pair[1]));
+            draw_line(a.x, a.y, b.x, b.y, width, color);
+        }
+    }
+    for s in body {
+        let c = centre(s);
+        draw_circle(c.x, c.y, width / 2.0, color);
+    }
+}
+
+// A head rounded off at the front, with eyes looking the way it goes
+pub fn draw_head(pos: Pos, facing: Direction, cell: f32, color: Color) {
+    let size = cell - (cell * 0.1).min(2.0);
+    let (x, y) = (pos.x as f32 * cell, pos.y as f32 * cell);
+    let (cx, cy) = (x + size / 2.0, y + size / 2.0);
+    let (fx, fy) = match facing {
+        Direction::Up => (0.0, -1.0),
+        Direction::Down => (0.0, 1.0),
+        Direction::Left => (-1.0, 0.0),
+        Direction::Right => (1.0, 0.0),
+    };
+    // the back half square, the front half round
+    let back_x = if fx < 0.0 { cx } else { x };
+    let back_y = if fy < 0.0 { cy } else { y };
+    let (back_w, back_h) = if fx != 0.0 { (size / 2.0, size) } else { (size, size / 2.0) };
+    draw_rectangle(back_x, back_y, back_w, back_h, color);
+    draw_circle(cx, cy, size / 2.0, color);
+    if cell < 6.0 {
+        return;
+    }
+    let eye_white = WHITE.with_alpha(color.a);
+    let pupil = BLACK.with_alpha(color.a);
+    for side in [-1.0, 1.0] {
+        // eyes sit towards the front, one on each side of the travel direction
+        let ex = cx + fx * size * 0.15 - fy * side * size * 0.22;
+        let ey = cy + fy * size * 0.15 + fx * side * size * 0.22;
+        draw_circle(ex, ey, size * 0.16, eye_white);
+        draw_circle(ex + fx * size * 0.06, ey + fy * size * 0.06, size * 0.08, pupil);
+    }
+}
+
+// How long the outline around our snake pulses after it spawns
+const SPAWN_PULSE_SECS: f64 = 1.5;
+
+// Traces the outside edge of a snake, to set it apart whatever its colors. The line
+// throbs for a moment after the snake spawns, so it's easy to find.
+pub fn draw_outline(snake: &[Pos], cell: f32, color: Color, since_spawn: f64) {
+    let pulse = if since_spawn < SPAWN_PULSE_SECS { (since_spawn * 10.0).sin().abs() as f32 } else { 0.0 };
+    let width = 2.0 + pulse * 3.0;
+    let color = color.with_alpha(0.8 + pulse * 0.2);
+    for pos in snake {
+        let (x, y) = (pos.x as f32 * cell, pos.y as f32 * cell);
+        // only the sides that don't join another segment of the snake
+        let sides = [
+            (0, -1, (x, y), (x + cell, y)),
+            (0, 1, (x, y + cell), (x + cell, y + cell)),
+            (-1, 0, (x, y), (x, y + cell)),
+            (1, 0, (x + cell, y), (x + cell, y + cell)),
+        ];
+        for (dx, dy, a, b) in sides {
+            if !snake.contains(&Pos { x: pos.x + dx, y: pos.y + dy }) {
+                draw_line(a.0, a.1, b.0, b.1, width, color);
+            }
+        }
+    }
+}
+
+// A name just above a snake's head, ringed in black so it reads on any background
+pub fn draw_name_tag(name: &str, head: Pos, cell: f32, color: Color) {
+    let ts = measure_text(name, None, 18, 1.0);
+    let x = (head.x as f32 + 0.5) * cell - ts.width / 2.0;
+    let y = head.y as f32 * cell - 6.0;
+    for (dx, dy) in [(-1.0, 0.0), (1.0, 0.0), (0.0, -1.0), (0.0, 1.0)] {
+        draw_text(name, x + dx, y + dy, 18.0, BLACK.with_alpha(color.a));
+    }
+    draw_text(name, x, y, 18.0, color);
+}
+
+// Colors telling teleporter pairs apart
+const TELEPORTER_COLORS: [Color; 4] = [PURPLE, ORANGE, SKYBLUE, PINK];
+
+// The map's food cells and teleporters; its walls come with every state
+pub fn draw_map(map: &BoardMap, cell: f32) {
+    for pos in map.food_zones.iter() {
+        draw_rectangle(pos.x as f32 * cell, pos.y as f32 * cell, cell, cell, Color::new(0.0, 0.25, 0.0, 1.0));
+    }
+    for (i, (a, b)) in map.teleporters.iter().enumerate() {
+        let color = TELEPORTER_COLORS[i % TELEPORTER_COLORS.len()];
+        for pos in [a, b] {
+            draw_circle_lines((pos.x as f32 + 0.5) * cell, (pos.y as f32 + 0.5) * cell, cell * 0.4, 2.0, color);
+        }
+    }
+}
+
+// Golden food pulses, and flashes quickly once it is about to disappear
+pub fn food_color(food: &FoodItem, tick: u64, theme: &Theme) -> Color {
+    match food.kind {
+        FoodKind::Normal => theme.food,
+        FoodKind::Leftover => MAROON,
+        // fades as it rots
+        FoodKind::Corpse => {
+            let left = food.expires_tick.map_or(1.0, |until| until.saturating_sub(tick) as f32 / CORPSE_TICKS as f32);
+            VIOLET.with_alpha(0.3 + 0.7 * left.min(1.0))
+        }
+        FoodKind::Poison => LIME,
+        FoodKind::Golden => {
+            let ending = food.expires_tick.is_some_and(|until| until.saturating_sub(tick) < 10);
+            let rate = if ending { 16.0 } else { 5.0 };
+            GOLD.with_alpha(0.6 + 0.4 * (get_time() * rate).sin() as f32)
+        }
+    }
+}
+
+// Poison is crossed out, so it doesn't pass for food at a glance
+pub fn draw_food(food: &FoodItem, tick: u64, cell: f32, theme: &Theme, sprites: Option<&Atlas>) {
+    let color = food_color(food, tick, theme);
+    match sprites {
+        Some(atlas) => atlas.draw(Tile::Food, food.pos, cell, 0, color),
+        None => draw_rect_at(food.pos, cell, color),
+    }
+    if food.kind == FoodKind::Poison {
+        let (x, y, gap) = (food.pos.x as f32 * cell, food.pos.y as f32 * cell, (cell * 0.1).min(2.0));
+        draw_line(x + gap, y + gap, x + cell - 2.0 * gap, y + cell - 2.0 * gap, 2.0, BLACK);
+        draw_line(x + cell - 2.0 * gap, y + gap, x + gap, y + cell - 2.0 * gap, 2.0, BLACK);
+    }
+}
+
+pub fn power_up_style(kind: PowerUpKind) -> (Color, &'static str) {
+    match kind {
+        PowerUpKind::Speed => (YELLOW, "Speed"),
+        PowerUpKind::Slow => (SKYBLUE, "Slow"),
+        PowerUpKind::Ghost => (LIGHTGRAY, "Ghost"),
+        PowerUpKind::Shrink => (MAGENTA, "Shrink"),
+    }
+}
+
+// A power-up on the board: a colored disc marked with the kind's initial
+pub fn draw_power_up(kind: PowerUpKind, pos: Pos, cell: f32) {
+    let (color, label) = power_up_style(kind);
+    let (cx, cy) = ((pos.x as f32 + 0.5) * cell, (pos.y as f32 + 0.5) * cell);
+    draw_circle(cx, cy, cell * 0.45, color);
+    let size = (cell * 0.8) as u16;
+    let ts = measure_text(&label[..1], None, size, 1.0);
+    draw_text(&label[..1], cx - ts.width / 2.0, cy + ts.height / 2.0, size as f32, BLACK);
+}
+
+// Longest side of the minimap, in pixels
+const MINIMAP_SIZE: f32 = 200.0;
+
+// The whole board in the bottom-left corner, a pixel or so a cell, with the part that's on
+// screen outlined; only drawn when some of the board is off screen
+pub fn draw_minimap(state: &StateMsg, config: &GameConfig, visible: Rect, me: Option<PlayerId>, theme: &Theme, palette: &[(Color, Color)]) {
+    let (w, h) = (config.grid_width as f32, config.grid_height as f32);
+    if visible.w >= w && visible.h >= h {
+        return;
+    }
+    let scale = (MINIMAP_SIZE / w).min(MINIMAP_SIZE / h);
+    let (x, y) = (10.0, screen_height() - h * scale - 10.0);
+    let dot = |pos: Pos, color: Color| draw_rectangle(x + pos.x as f32 * scale, y + pos.y as f32 * scale, scale.max(1.0), scale.max(1.0), color);
+    draw_rectangle(x, y, w * scale, h * scale, theme.background.with_alpha(0.8));
+    for &wall in &state.obstacles {
+        dot(wall, theme.wall);
+    }
+    for food in &state.food_items {
+        dot(food.pos, food_color(food, state.tick, theme));
+    }
+    // ours last and in the text color, so it's on top and easy to find
+    let mut players: Vec<&PlayerState> = state.players.iter().filter(|p| !p.dead).collect();
+    players.sort_by_key(|p| Some(p.id) == me);
+    for p in players {
+        let color = if Some(p.id) == me { theme.text } else { snake_colors(p, palette).0 };
+        for &pos in &p.snake {
+            dot(pos, color);
+        }
+    }
+    draw_rectangle_lines(x, y, w * scale, h * scale, 1.0, theme.border);
+    draw_rectangle_lines(x + visible.x * scale, y + visible.y * scale, visible.w * scale, visible.h * scale, 1.5, theme.text);
+}
@@ -0,0 +1,181 @@
+use std::collections::VecDeque;
+
+use macroquad::prelude::*;
+
+use snake::game_core::net::NetStatus;
+use snake::game_core::replay::{self, Replay};
+use snake::game_core::{ClientMsg, ServerMsg, StateMsg};
+
+use super::transport::NetTransport;
+
+// Playback speeds, slowest first
+const REPLAY_SPEEDS: [f64; 3] = [0.5, 1.0, 2.0];
+// How far Left and Right jump in a replay, in game time
+pub const REPLAY_SEEK_SECS: f64 = 5.0;
+
+// A recorded match played back at its own pace. Like OfflineTransport it talks like a
+// server, handing out the recorded messages as their time comes; whatever is sent to it
+// is ignored.
+pub struct ReplayTransport {
+    replay: Replay,
+    inbox: VecDeque<ServerMsg>,
+    // the frame to play next, and when, in get_time() seconds
+    next: usize,
+    next_due: f64,
+    // index into REPLAY_SPEEDS
+    speed: usize,
+    pub paused: bool,
+    // snapshots are numbered afresh as they are handed out, so the client takes the
+    // earlier ones a seek goes back to
+    seq: u64,
+}
+
+impl ReplayTransport {
+    pub fn new(replay: Replay) -> Self {
+        ReplayTransport { replay, inbox: VecDeque::new(), next: 0, next_due: get_time(), speed: 1, paused: false, seq: 0 }
+    }
+
+    fn len(&self) -> usize {
+        self.replay.frames.len()
+    }
+
+    // The frame on screen
+    fn position(&self) -> usize {
+        self.next.saturating_sub(1)
+    }
+
+    fn speed(&self) -> f64 {
+        REPLAY_SPEEDS[self.speed]
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed + 1).min(REPLAY_SPEEDS.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+
+    fn push_state(&mut self, at: usize) {
+        self.seq += 1;
+        let state = StateMsg { seq: self.seq, ..self.replay.frames[at].state.clone() };
+        self.inbox.push_back(ServerMsg::State(state));
+        self.next = at + 1;
+        self.next_due = get_time() + self.replay.frames[at].state.tick_ms as f64 / 1000.0 / self.speed();
+    }
+
+    // Plays the next frame right away, paused or not
+    pub fn step(&mut self) {
+        if self.next < self.len() {
+            self.inbox.extend(self.replay.frames[self.next].before.iter().cloned());
+            self.push_state(self.next);
+        }
+    }
+
+    // Jumps to frame `at`. The events in between are skipped; what the client keeps
+    // from earlier messages, like the board and the round's result, comes along.
+    pub fn seek(&mut self, at: usize) {
+        let at = at.min(self.len() - 1);
+        if at == self.next {
+            return self.step();
+        }
+        self.inbox.clear();
+        let kept = self.replay.frames[..=at].iter()
+            .flat_map(|frame| frame.before.iter())
+            .filter(|msg| matches!(msg, ServerMsg::Session(_) | ServerMsg::Config(_) | ServerMsg::Map(_) | ServerMsg::Pause(_) | ServerMsg::RoundEnd(_) | ServerMsg::Rematch(_)))
+            .cloned()
+            .collect::<Vec<_>>();
+        self.inbox.extend(kept);
+        self.push_state(at);
+    }
+
+    // The frame `secs` of game time away from the one on screen, back for negative `secs`
+    pub fn frame_after(&self, secs: f64) -> usize {
+        let mut at = self.position();
+        let mut left_ms = secs.abs() * 1000.0;
+        while left_ms > 0.0 {
+            if secs > 0.0 && at + 1 < self.len() {
+                left_ms -= self.replay.frames[at].state.tick_ms as f64;
+                at += 1;
+            } else if secs < 0.0 && at > 0 {
+                at -= 1;
+                left_ms -= self.replay.frames[at].state.tick_ms as f64;
+            } else {
+                break;
+            }
+        }
+        at
+    }
+}
+
+impl NetTransport for ReplayTransport {
+    fn send(&mut self, _msg: ClientMsg) {}
+
+    fn try_recv(&mut self) -> Option<ServerMsg> {
+        let now = get_time();
+        if self.paused {
+            self.next_due = now;
+        } else if now >= self.next_due {
+            self.step();
+        }
+        self.inbox.pop_front()
+    }
+
+    fn poll_status(&mut self) -> Option<NetStatus> {
+        None
+    }
+
+    fn replay(&mut self) -> Option<&mut ReplayTransport> {
+        Some(self)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_replay(path: &str) -> Result<Replay, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Can't open {}: {}", path, e))?;
+    replay::parse(&text).map_err(|e| format!("Can't play {}: {}", path, e))
+}
+
+// The header goes in once the Welcome arrives with the seed
+#[cfg(not(target_arch = "wasm32"))]
+pub fn start_recording(path: &str) -> Result<std::fs::File, String> {
+    std::fs::File::create(path).map_err(|e| format!("Can't record to {}: {}", path, e))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn load_replay(_path: &str) -> Result<Replay, String> {
+    Err("Replays can't be opened in the browser".to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn start_recording(_path: &str) -> Result<std::fs::File, String> {
+    Err("Matches can't be recorded in the browser".to_string())
+}
+
+// A replay's progress along the bottom of the window, with its controls; clicking the
+// bar jumps there
+pub fn draw_replay_bar(replay: &mut ReplayTransport) {
+    let (w, h) = (screen_width(), screen_height());
+    let bar = Rect { x: 10.0, y: h - 14.0, w: w - 20.0, h: 8.0 };
+    let last = replay.len().saturating_sub(1).max(1);
+    draw_rectangle(bar.x, bar.y, bar.w, bar.h, Color::new(1.0, 1.0, 1.0, 0.2));
+    draw_rectangle(bar.x, bar.y, bar.w * replay.position() as f32 / last as f32, bar.h, YELLOW);
+    let status = if replay.paused { "paused".to_string() } else { format!("{}x", replay.speed()) };
+    let text = format!("Replay {}   Space pause   . step   Up/Down speed   Left/Right seek", status);
+    let ts = measure_text(&text, None, 20, 1.0);
+    draw_text(&text, (w - ts.width) / 2.0, bar.y - 8.0, 20.0, WHITE);
+    let (mx, my) = mouse_position();
+    let target = Rect { y: bar.y - 6.0, h: bar.h + 12.0, ..bar };
+    if is_mouse_button_pressed(MouseButton::Left) && target.contains(vec2(mx, my)) {
+        replay.seek(((mx - bar.x) / bar.w * last as f32).round() as usize);
+    }
+}
+
+// Adds a message to a recording; pings are left out, and so are logins, which hold a token
+pub fn record(file: &mut std::fs::File, msg: &ServerMsg, player: &str) -> std::io::Result<()> {
+    match msg {
+        ServerMsg::Welcome { seed, .. } => replay::write_header(file, player, *seed),
+        ServerMsg::Pong { .. } | ServerMsg::Account(_) | ServerMsg::Queue(_) => Ok(()),
+        msg => replay::write_msg(file, msg),
+    }
+}
@@ -0,0 +1,158 @@
+use macroquad::prelude::*;
+
+use snake::game_core::{PlayerId, RematchMsg, RoundEndMsg, RoundEndReason, SeriesScore};
+
+use super::hud::team_totals;
+use super::theme::team_colors;
+use super::draw_button;
+
+// Results of a finished round, highest score first. Returns true when the rematch button is pressed.
+pub enum RoundEndAction {
+    None,
+    Rematch,
+    Leave,
+}
+
+// The results once a round is decided: who won, how long it took, and every player's score,
+// kills and peak length, best first
+pub fn draw_round_end(round_end: &RoundEndMsg, me: Option<PlayerId>, rematch: Option<&RematchMsg>, voted: bool, panel: Rect) -> RoundEndAction {
+    const COLUMNS: [(&str, f32); 4] = [("Score", 400.0), ("Kills", 310.0), ("Peak length", 220.0), ("Rounds", 100.0)];
+    draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::new(0.1, 0.1, 0.1, 0.9));
+    let winner = round_end.standings.iter().find(|s| Some(s.player) == round_end.winner);
+    let title = match (winner, round_end.winning_team, round_end.reason) {
+        (Some(s), _, RoundEndReason::ScoreTarget) => format!("{} reached the target!", s.name),
+        (Some(s), _, RoundEndReason::HighestScore) => format!("{} wins on points!", s.name),
+        (Some(s), _, RoundEndReason::SuddenDeath) => format!("{} survived sudden death!", s.name),
+        (Some(s), _, _) => format!("{} wins!", s.name),
+        (None, Some(t), RoundEndReason::ScoreTarget) => format!("Team {} reached the target!", t + 1),
+        (None, Some(t), RoundEndReason::HighestScore) => format!("Team {} wins on points!", t + 1),
+        (None, Some(t), RoundEndReason::SuddenDeath) => format!("Team {} survived sudden death!", t + 1),
+        (None, Some(t), _) => format!("Team {} wins!", t + 1),
+        (None, None, _) if round_end.standings.len() > 1 => "Draw!".to_string(),
+        (None, None, _) => "Game Over".to_string(),
+    };
+    let ts = measure_text(&title, None, 40, 1.0);
+    draw_text(&title, panel.x + (panel.w - ts.width) / 2.0, panel.y + 50.0, 40.0, YELLOW);
+    let secs = round_end.duration_ms / 1000;
+    let mut took = format!("Round took {}:{:02}", secs / 60, secs % 60);
+    if let Some(series) = &round_end.series {
+        took = format!("Round {} of a best of {}, first to {} wins   {}", series.round, series.best_of, series.needed(), took);
+    }
+    let ts = measure_text(&took, None, 22, 1.0);
+    draw_text(&took, panel.x + (panel.w - ts.width) / 2.0, panel.y + 80.0, 22.0, GRAY);
+
+    // the rows squeeze together to leave room for the buttons when the room is full
+    let top = panel.y + 115.0;
+    let rows = round_end.standings.len() + 2;
+    let row_h = ((panel.y + panel.h - 90.0 - top) / rows as f32).min(26.0);
+    let size = (row_h - 2.0).min(24.0);
+    // the rounds column is only there in a series
+    let series = round_end.series.as_ref();
+    let columns = if series.is_some() { &COLUMNS[..] } else { &COLUMNS[..3] };
+    draw_text("Player", panel.x + 80.0, top, 20.0, GRAY);
+    for (title, dx) in columns {
+        draw_text(title, panel.x + panel.w - dx, top, 20.0, GRAY);
+    }
+    for (rank, standing) in round_end.standings.iter().enumerate() {
+        let y = top + (rank + 1) as f32 * row_h;
+        if Some(standing.player) == me {
+            draw_rectangle(panel.x + 30.0, y - row_h + 6.0, panel.w - 60.0, row_h, Color::new(1.0, 1.0, 1.0, 0.1));
+        }
+        let won = round_end.winner == Some(standing.player) || (standing.team.is_some() && standing.team == round_end.winning_team);
+        let color = if won { YELLOW } else { WHITE };
+        draw_text(&format!("{}.", rank + 1), panel.x + 40.0, y, size, GRAY);
+        draw_text(&standing.name, panel.x + 80.0, y, size, color);
+        // what the round did to a logged-in player's rating
+        if let Some(change) = standing.rating_change {
+            let x = panel.x + 80.0 + measure_text(&standing.name, None, size as u16, 1.0).width + 12.0;
+            let color = if change >= 0 { GREEN } else { RED };
+            draw_text(&format!("{:+}", change), x, y, size * 0.8, color);
+        }
+        if let Some(team) = standing.team {
+            draw_text(&format!("Team {}", team + 1), panel.x + panel.w - 520.0, y, size, team_colors(Some(team)).0);
+        }
+        let rounds = series.map_or(0, |s| s.wins_of(standing.player));
+        let cells = [standing.score, standing.kills, standing.peak_length, rounds];
+        for ((_, dx), value) in columns.iter().zip(cells) {
+            draw_text(&value.to_string(), panel.x + panel.w - dx, y, size, color);
+        }
+    }
+    let totals: Vec<String> = team_totals(round_end.standings.iter().map(|s| (s.team, s.score)))
+        .iter()
+        .map(|(team, total)| format!("Team {}: {}", team + 1, total))
+        .collect();
+    if !totals.is_empty() {
+        let y = top + (round_end.standings.len() + 1) as f32 * row_h + 10.0;
+        draw_text(&totals.join("   "), panel.x + 40.0, y, size, YELLOW);
+    }
+
+    let label = match &round_end.series {
+        Some(series) if !series.is_over() => "Next Round",
+        _ => "Rematch",
+    };
+    draw_results_buttons(label, rematch, voted, panel)
+}
+
+// Back to Menu and the rematch vote along the bottom of a results panel
+fn draw_results_buttons(label: &str, rematch: Option<&RematchMsg>, voted: bool, panel: Rect) -> RoundEndAction {
+    let leave = Rect { x: panel.x + 40.0, y: panel.y + panel.h - 70.0, w: 200.0, h: 44.0 };
+    if draw_button(leave, "Back to Menu") {
+        return RoundEndAction::Leave;
+    }
+    let btn_rect = Rect { x: panel.x + panel.w - 240.0, ..leave };
+    let label = match rematch {
+        Some(r) => format!("{} ({}/{})", label, r.votes, r.needed),
+        None => label.to_string(),
+    };
+    if voted {
+        draw_rectangle(btn_rect.x, btn_rect.y, btn_rect.w, btn_rect.h, Color::new(0.2, 0.2, 0.2, 1.0));
+        let ts = measure_text(&label, None, 28, 1.0);
+        draw_text(&label, btn_rect.x + (btn_rect.w - ts.width) / 2.0, btn_rect.y + 32.0, 28.0, GRAY);
+        return RoundEndAction::None;
+    }
+    if draw_button(btn_rect, &label) || is_key_pressed(KeyCode::R) {
+        return RoundEndAction::Rematch;
+    }
+    RoundEndAction::None
+}
+
+// Once a series is decided: its champion, and every player by rounds won
+pub fn draw_series_end(round_end: &RoundEndMsg, series: &SeriesScore, me: Option<PlayerId>, rematch: Option<&RematchMsg>, voted: bool, panel: Rect) -> RoundEndAction {
+    draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::new(0.1, 0.1, 0.1, 0.9));
+    let name = |id: PlayerId| round_end.standings.iter().find(|s| s.player == id);
+    let title = match series.champions.as_slice() {
+        [one] => format!("{} wins the series!", name(*one).map_or("?", |s| s.name.as_str())),
+        [first, ..] => match name(*first).and_then(|s| s.team) {
+            Some(team) => format!("Team {} wins the series!", team + 1),
+            None => "Series over".to_string(),
+        },
+        [] => "Series over".to_string(),
+    };
+    let ts = measure_text(&title, None, 40, 1.0);
+    draw_text(&title, panel.x + (panel.w - ts.width) / 2.0, panel.y + 50.0, 40.0, GOLD);
+    let sub = format!("Best of {}, decided in round {}", series.best_of, series.round);
+    let ts = measure_text(&sub, None, 22, 1.0);
+    draw_text(&sub, panel.x + (panel.w - ts.width) / 2.0, panel.y + 80.0, 22.0, GRAY);
+
+    let mut players: Vec<_> = round_end.standings.iter().collect();
+    players.sort_by_key(|s| std::cmp::Reverse(series.wins_of(s.player)));
+    let top = panel.y + 115.0;
+    let row_h = ((panel.y + panel.h - 90.0 - top) / (players.len() + 1) as f32).min(26.0);
+    let size = (row_h - 2.0).min(24.0);
+    draw_text("Player", panel.x + 80.0, top, 20.0, GRAY);
+    draw_text("Rounds won", panel.x + panel.w - 200.0, top, 20.0, GRAY);
+    for (rank, standing) in players.iter().enumerate() {
+        let y = top + (rank + 1) as f32 * row_h;
+        if Some(standing.player) == me {
+            draw_rectangle(panel.x + 30.0, y - row_h + 6.0, panel.w - 60.0, row_h, Color::new(1.0, 1.0, 1.0, 0.1));
+        }
+        let color = if series.champions.contains(&standing.player) { GOLD } else { WHITE };
+        draw_text(&format!("{}.", rank + 1), panel.x + 40.0, y, size, GRAY);
+        draw_text(&standing.name, panel.x + 80.0, y, size, color);
+        if let Some(team) = standing.team {
+            draw_text(&format!("Team {}", team + 1), panel.x + panel.w - 380.0, y, size, team_colors(Some(team)).0);
+        }
+        draw_text(&series.wins_of(standing.player).to_string(), panel.x + panel.w - 200.0, y, size, color);
+    }
+    draw_results_buttons("New Series", rematch, voted, panel)
+}
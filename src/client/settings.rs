@@ -0,0 +1,204 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use snake::game_core::{DEFAULT_GRID_HEIGHT, DEFAULT_GRID_WIDTH};
+
+use super::input::{pads, Controls};
+use super::keybinds::Keybinds;
+use super::sound;
+use super::theme::{Theme, THEMES};
+use super::{draw_button, CELL_SIZE};
+
+// Choices kept between runs, in the platform's config directory; the browser build starts
+// from the defaults every time
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    theme: String,
+    pub hide_grid: bool,
+    pub hide_name_tags: bool,
+    // how far the game view is zoomed in, 1 being the board's normal size
+    pub zoom: f32,
+    // cells only come in whole pixels
+    pub pixel_perfect: bool,
+    pub fullscreen: bool,
+    // window size in pixels; None sizes it for the default board
+    resolution: Option<(u32, u32)>,
+    // only read at startup
+    pub vsync: bool,
+    pub controls: Controls,
+    pub keys: Keybinds,
+    // how loud sound effects and music are, 0 to 1, unless muted
+    pub effects_volume: f32,
+    pub music_volume: f32,
+    pub muted: bool,
+    // what the connect form last connected with, filled in again at startup
+    pub username: String,
+    pub server_addr: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings { theme: String::new(), hide_grid: false, hide_name_tags: false, zoom: 1.0, pixel_perfect: false, fullscreen: false, resolution: None, vsync: true, controls: Controls::Keyboard, keys: Keybinds::default(),
+            effects_volume: 0.8, music_volume: 0.4, muted: false, username: String::new(), server_addr: String::from("127.0.0.1:4000") }
+    }
+}
+
+impl Settings {
+    pub fn theme(&self) -> &'static Theme {
+        THEMES.iter().find(|t| t.name == self.theme).unwrap_or(&THEMES[0])
+    }
+
+    pub fn window_size(&self) -> (u32, u32) {
+        self.resolution.unwrap_or((DEFAULT_GRID_WIDTH as u32 * CELL_SIZE as u32, DEFAULT_GRID_HEIGHT as u32 * CELL_SIZE as u32))
+    }
+}
+
+// Window sizes the settings screen cycles through
+const RESOLUTIONS: [(u32, u32); 4] = [(1280, 720), (1600, 900), (1920, 1080), (2560, 1440)];
+
+// A bar for a share from 0 to 1, labelled with it. Returns where along it the mouse is
+// while held down on it.
+fn draw_slider(rect: Rect, label: &str, value: f32) -> Option<f32> {
+    draw_rectangle(rect.x, rect.y, rect.w, rect.h, DARKGRAY);
+    draw_rectangle(rect.x, rect.y, rect.w * value, rect.h, DARKGREEN);
+    let text = format!("{}: {}%", label, (value * 100.0).round());
+    draw_text(&text, rect.x + 10.0, rect.y + 30.0, 24.0, WHITE);
+    let (mx, my) = mouse_position();
+    (is_mouse_button_down(MouseButton::Left) && rect.contains(vec2(mx, my))).then(|| ((mx - rect.x) / rect.w).clamp(0.0, 1.0))
+}
+
+// What the player did on the settings screen
+#[derive(PartialEq)]
+pub enum SettingsAction {
+    None,
+    Keys,
+    Done,
+}
+
+// The settings screen; changes are saved as they're made
+pub fn draw_settings(settings: &mut Settings, pads: &mut pads::Pads, panel: Rect) -> SettingsAction {
+    draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::new(0.1, 0.1, 0.1, 0.9));
+    draw_text("Settings", panel.x + 40.0, panel.y + 50.0, 40.0, WHITE);
+    let row = |i: usize| Rect { x: panel.x + 40.0, y: panel.y + 80.0 + i as f32 * 56.0, w: 360.0, h: 44.0 };
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    let mut changed = false;
+    if draw_button(row(0), &format!("Theme: {}", settings.theme().name)) {
+        let i = THEMES.iter().position(|t| t.name == settings.theme().name).unwrap_or(0);
+        settings.theme = THEMES[(i + 1) % THEMES.len()].name.to_string();
+        changed = true;
+    }
+    let pixels = Rect { x: row(0).x + row(0).w + 20.0, w: 260.0, ..row(0) };
+    if draw_button(pixels, &format!("Pixel-perfect: {}", on_off(settings.pixel_perfect))) {
+        settings.pixel_perfect = !settings.pixel_perfect;
+        changed = true;
+    }
+    if draw_button(row(1), &format!("Fullscreen: {}", on_off(settings.fullscreen))) {
+        settings.fullscreen = !settings.fullscreen;
+        set_fullscreen(settings.fullscreen);
+        changed = true;
+    }
+    let (w, h) = settings.window_size();
+    if draw_button(row(2), &format!("Window: {}x{}", w, h)) {
+        // through the list, then back to the default
+        settings.resolution = match settings.resolution {
+            None => Some(RESOLUTIONS[0]),
+            Some(size) => RESOLUTIONS.iter().position(|r| *r == size).and_then(|i| RESOLUTIONS.get(i + 1)).copied(),
+        };
+        let (w, h) = settings.window_size();
+        request_new_screen_size(w as f32, h as f32);
+        changed = true;
+    }
+    if draw_button(row(3), &format!("VSync: {}", on_off(settings.vsync))) {
+        settings.vsync = !settings.vsync;
+        changed = true;
+    }
+    draw_text("VSync changes apply the next time the game starts", row(3).x + row(3).w + 20.0, row(3).y + 30.0, 20.0, GRAY);
+    if draw_button(row(4), &format!("Controls: {}", settings.controls.label())) {
+        let i = Controls::ALL.iter().position(|c| *c == settings.controls).unwrap_or(0);
+        settings.controls = Controls::ALL[(i + 1) % Controls::ALL.len()];
+        changed = true;
+    }
+    if sound::AVAILABLE {
+        let effects = Rect { x: row(2).x + row(2).w + 20.0, w: 160.0, ..row(2) };
+        let music = Rect { x: effects.x + effects.w + 20.0, ..effects };
+        let effects_volume = draw_slider(effects, "Effects", settings.effects_volume);
+        let music_volume = draw_slider(music, "Music", settings.music_volume);
+        if effects_volume.is_some() || music_volume.is_some() {
+            settings.effects_volume = effects_volume.unwrap_or(settings.effects_volume);
+            settings.music_volume = music_volume.unwrap_or(settings.music_volume);
+            sound::set_volume(settings);
+        }
+        // saved once the slider is let go of
+        let (mx, my) = mouse_position();
+        changed |= is_mouse_button_released(MouseButton::Left) && (effects.contains(vec2(mx, my)) || music.contains(vec2(mx, my)));
+    }
+    let keys = Rect { x: row(4).x + row(4).w + 20.0, w: 200.0, ..row(4) };
+    let mut action = SettingsAction::None;
+    if draw_button(keys, "Keys") {
+        action = SettingsAction::Keys;
+    }
+    if pads::AVAILABLE && !pads.supported() {
+        draw_text("No controllers: this system offers no controller support", row(5).x, row(5).y + 30.0, 20.0, GRAY);
+    } else if pads::AVAILABLE {
+        let owners: Vec<String> = pads.owners().into_iter().enumerate()
+            .map(|(i, name)| format!("P{}: {}", i + 1, name.as_deref().unwrap_or("none")))
+            .collect();
+        if draw_button(row(5), "Hand out controllers") {
+            pads.reassign();
+        }
+        draw_text(&owners.join("   "), row(5).x + row(5).w + 20.0, row(5).y + 30.0, 20.0, GRAY);
+    }
+    draw_text("F11 or Alt+Enter toggles fullscreen anywhere", row(1).x + row(1).w + 20.0, row(1).y + 30.0, 20.0, GRAY);
+    if changed {
+        save_settings(settings);
+    }
+    let back = Rect { x: panel.x + panel.w - 200.0, y: panel.y + panel.h - 70.0, w: 160.0, h: 44.0 };
+    if draw_button(back, "Back") || is_key_pressed(KeyCode::Escape) {
+        action = SettingsAction::Done;
+    }
+    action
+}
+
+// Where settings used to be kept, read if the config directory has none yet
+#[cfg(not(target_arch = "wasm32"))]
+fn old_settings_path() -> std::path::PathBuf {
+    std::env::temp_dir().join("rusty-snake-settings.json")
+}
+
+// e.g. ~/.config/rusty-snake/settings.json on Linux, falling back to the old place without a
+// home directory
+#[cfg(not(target_arch = "wasm32"))]
+fn settings_path() -> std::path::PathBuf {
+    directories::ProjectDirs::from("", "", "rusty-snake")
+        .map(|dirs| dirs.config_dir().join("settings.json"))
+        .unwrap_or_else(old_settings_path)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_settings() -> Settings {
+    std::fs::read_to_string(settings_path())
+        .or_else(|_| std::fs::read_to_string(old_settings_path()))
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_settings(settings: &Settings) {
+    let path = settings_path();
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Ok(data) = serde_json::to_string_pretty(settings) {
+        let _ = std::fs::write(path, data);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn load_settings() -> Settings {
+    Settings::default()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save_settings(_settings: &Settings) {}
@@ -0,0 +1,153 @@
+// Sound effects and music, built with the audio feature. The sounds are made up when the
+// client starts rather than loaded, so there are no files to ship with it.
+
+use super::settings::Settings;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Effect {
+    // our snake ate
+    Eat,
+    // our snake died
+    Death,
+    // each second of the countdown before a round
+    Countdown,
+    // the round starting
+    Go,
+    Click,
+}
+
+#[cfg(feature = "audio")]
+mod playing {
+    use std::cell::RefCell;
+    use std::f32::consts::TAU;
+
+    use macroquad::audio::{load_sound_from_bytes, play_sound, set_sound_volume, PlaySoundParams, Sound};
+
+    use super::{Effect, Settings};
+
+    // Samples a second, in the sounds made up here
+    const RATE: u32 = 22050;
+    // The tune, as notes in Hz a quarter of a second each, over and over
+    const TUNE: [f32; 16] = [220.0, 261.6, 329.6, 261.6, 293.7, 349.2, 440.0, 349.2, 261.6, 329.6, 392.0, 329.6, 246.9, 293.7, 392.0, 293.7];
+    const NOTE_SECS: f32 = 0.25;
+
+    pub const AVAILABLE: bool = true;
+
+    struct Sounds {
+        effects: Vec<(Effect, Sound)>,
+        music: Sound,
+        // the effects' volume, music's is set on the sound itself
+        volume: f32,
+    }
+
+    // macroquad's audio, like the rest of it, lives on the main thread
+    thread_local! {
+        static SOUNDS: RefCell<Option<Sounds>> = const { RefCell::new(None) };
+    }
+
+    fn sine(phase: f32) -> f32 {
+        (phase * TAU).sin()
+    }
+
+    fn square(phase: f32) -> f32 {
+        if phase < 0.5 { 0.5 } else { -0.5 }
+    }
+
+    fn triangle(phase: f32) -> f32 {
+        1.0 - 4.0 * (phase - 0.5).abs()
+    }
+
+    // `secs` of `wave` sliding from `from` to `to` Hz, fading out as it goes
+    fn tone(from: f32, to: f32, secs: f32, wave: fn(f32) -> f32) -> Vec<f32> {
+        let n = (secs * RATE as f32) as usize;
+        let mut phase = 0.0f32;
+        (0..n)
+            .map(|i| {
+                let t = i as f32 / n as f32;
+                phase = (phase + (from + (to - from) * t) / RATE as f32).fract();
+                wave(phase) * (1.0 - t) * (1.0 - t)
+            })
+            .collect()
+    }
+
+    // Mono 16-bit WAV, which every platform's decoder takes
+    fn wav(samples: &[f32]) -> Vec<u8> {
+        let data_len = samples.len() as u32 * 2;
+        let mut out = Vec::with_capacity(44 + data_len as usize);
+        out.extend_from_slice(b"RIFF");
+        out.extend_from_slice(&(36 + data_len).to_le_bytes());
+        out.extend_from_slice(b"WAVEfmt ");
+        out.extend_from_slice(&16u32.to_le_bytes());
+        // PCM, one channel
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&RATE.to_le_bytes());
+        out.extend_from_slice(&(RATE * 2).to_le_bytes());
+        out.extend_from_slice(&2u16.to_le_bytes());
+        out.extend_from_slice(&16u16.to_le_bytes());
+        out.extend_from_slice(b"data");
+        out.extend_from_slice(&data_len.to_le_bytes());
+        for sample in samples {
+            out.extend_from_slice(&((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes());
+        }
+        out
+    }
+
+    fn samples(effect: Effect) -> Vec<f32> {
+        match effect {
+            Effect::Eat => tone(600.0, 1200.0, 0.08, sine),
+            Effect::Death => tone(400.0, 60.0, 0.5, square),
+            Effect::Countdown => tone(660.0, 660.0, 0.15, sine),
+            Effect::Go => tone(990.0, 990.0, 0.35, sine),
+            Effect::Click => tone(1800.0, 1200.0, 0.03, sine),
+        }
+    }
+
+    // Makes up every sound and starts the music, as loud as the settings say
+    pub async fn load(settings: &Settings) {
+        let mut effects = Vec::new();
+        for effect in [Effect::Eat, Effect::Death, Effect::Countdown, Effect::Go, Effect::Click] {
+            // no sound at all, the way the client goes without sprites
+            let Ok(sound) = load_sound_from_bytes(&wav(&samples(effect))).await else { return };
+            effects.push((effect, sound));
+        }
+        let tune: Vec<f32> = TUNE.iter().flat_map(|&note| tone(note, note, NOTE_SECS, triangle)).map(|s| s * 0.5).collect();
+        let Ok(music) = load_sound_from_bytes(&wav(&tune)).await else { return };
+        play_sound(&music, PlaySoundParams { looped: true, volume: 0.0 });
+        SOUNDS.with_borrow_mut(|sounds| *sounds = Some(Sounds { effects, music, volume: 0.0 }));
+        set_volume(settings);
+    }
+
+    pub fn play(effect: Effect) {
+        SOUNDS.with_borrow(|sounds| {
+            let Some(sounds) = sounds.as_ref().filter(|s| s.volume > 0.0) else { return };
+            if let Some((_, sound)) = sounds.effects.iter().find(|(e, _)| *e == effect) {
+                play_sound(sound, PlaySoundParams { looped: false, volume: sounds.volume });
+            }
+        });
+    }
+
+    pub fn set_volume(settings: &Settings) {
+        let unmuted = if settings.muted { 0.0 } else { 1.0 };
+        SOUNDS.with_borrow_mut(|sounds| {
+            let Some(sounds) = sounds.as_mut() else { return };
+            sounds.volume = settings.effects_volume * unmuted;
+            set_sound_volume(&sounds.music, settings.music_volume * unmuted);
+        });
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+mod playing {
+    use super::{Effect, Settings};
+
+    pub const AVAILABLE: bool = false;
+
+    pub async fn load(_settings: &Settings) {}
+
+    pub fn play(_effect: Effect) {}
+
+    pub fn set_volume(_settings: &Settings) {}
+}
+
+pub use playing::{load, play, set_volume, AVAILABLE};
@@ -0,0 +1,269 @@
+use macroquad::prelude::*;
+
+const INPUT_FONT: u16 = 28;
+
+// A one-line text box's contents and cursor. The cursor and the other end of the selection
+// count characters, not bytes.
+pub struct TextField {
+    pub text: String,
+    cursor: usize,
+    // where the selection started, while there is one
+    anchor: Option<usize>,
+    max_len: usize,
+}
+
+impl TextField {
+    pub fn new(text: String, max_len: usize) -> Self {
+        let mut field = TextField { text: String::new(), cursor: 0, anchor: None, max_len };
+        field.set(text);
+        field
+    }
+
+    // Replaces the text, with the cursor at its end
+    pub fn set(&mut self, text: String) {
+        self.text = text.chars().take(self.max_len).collect();
+        self.cursor = self.len();
+        self.anchor = None;
+    }
+
+    fn len(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    fn byte(&self, i: usize) -> usize {
+        self.text.char_indices().nth(i).map_or(self.text.len(), |(b, _)| b)
+    }
+
+    fn selection(&self) -> Option<(usize, usize)> {
+        self.anchor.filter(|&a| a != self.cursor).map(|a| (a.min(self.cursor), a.max(self.cursor)))
+    }
+
+    // Takes out the selected text; false if nothing was selected
+    fn delete_selection(&mut self) -> bool {
+        let selection = self.selection();
+        self.anchor = None;
+        let Some((from, to)) = selection else { return false };
+        self.text.replace_range(self.byte(from)..self.byte(to), "");
+        self.cursor = from;
+        true
+    }
+
+    // Types `s` over the selection, as much of it as fits
+    fn insert(&mut self, s: &str) {
+        self.delete_selection();
+        let room = self.max_len.saturating_sub(self.len());
+        let s: String = s.chars().filter(|c| !c.is_control()).take(room).collect();
+        let at = self.byte(self.cursor);
+        self.text.insert_str(at, &s);
+        self.cursor += s.chars().count();
+    }
+
+    // Moves the cursor, stretching the selection when `select`
+    fn move_to(&mut self, to: usize, select: bool) {
+        if select {
+            self.anchor.get_or_insert(self.cursor);
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = to.min(self.len());
+    }
+
+    // The selected text, if any
+    fn selected(&self) -> Option<&str> {
+        self.selection().map(|(from, to)| &self.text[self.byte(from)..self.byte(to)])
+    }
+
+    fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.len();
+    }
+
+    // Pastes over the selection, leaving out the whitespace copied around the text
+    fn paste(&mut self, pasted: &str) {
+        self.insert(pasted.trim());
+    }
+
+    // Without `select`, Left first drops the selection at its start
+    fn left(&mut self, select: bool) {
+        match self.selection() {
+            Some((from, _)) if !select => self.move_to(from, false),
+            _ => self.move_to(self.cursor.saturating_sub(1), select),
+        }
+    }
+
+    // Without `select`, Right first drops the selection at its end
+    fn right(&mut self, select: bool) {
+        match self.selection() {
+            Some((_, to)) if !select => self.move_to(to, false),
+            _ => self.move_to(self.cursor + 1, select),
+        }
+    }
+
+    fn backspace(&mut self) {
+        if !self.delete_selection() && self.cursor > 0 {
+            self.cursor -= 1;
+            self.text.remove(self.byte(self.cursor));
+        }
+    }
+
+    fn delete(&mut self) {
+        if !self.delete_selection() && self.cursor < self.len() {
+            self.text.remove(self.byte(self.cursor));
+        }
+    }
+
+    // Typing, the cursor keys, Home and End, and Ctrl+A, C, X and V
+    pub fn update(&mut self) {
+        let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+        let ctrl = [KeyCode::LeftControl, KeyCode::RightControl, KeyCode::LeftSuper, KeyCode::RightSuper].into_iter().any(is_key_down);
+        while let Some(c) = get_char_pressed() {
+            // with Ctrl held the letters are shortcuts
+            if !ctrl {
+                self.insert(&c.to_string());
+            }
+        }
+        if ctrl && is_key_pressed(KeyCode::A) {
+            self.select_all();
+        }
+        if ctrl && (is_key_pressed(KeyCode::C) || is_key_pressed(KeyCode::X))
+            && let Some(selected) = self.selected()
+        {
+            miniquad::window::clipboard_set(selected);
+            if is_key_pressed(KeyCode::X) {
+                self.delete_selection();
+            }
+        }
+        if ctrl && is_key_pressed(KeyCode::V)
+            && let Some(pasted) = miniquad::window::clipboard_get()
+        {
+            self.paste(&pasted);
+        }
+        if is_key_pressed(KeyCode::Left) {
+            self.left(shift);
+        } else if is_key_pressed(KeyCode::Right) {
+            self.right(shift);
+        }
+        if is_key_pressed(KeyCode::Home) {
+            self.move_to(0, shift);
+        }
+        if is_key_pressed(KeyCode::End) {
+            self.move_to(self.len(), shift);
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            self.backspace();
+        }
+        if is_key_pressed(KeyCode::Delete) {
+            self.delete();
+        }
+    }
+
+    // What the box shows: the password field shows stars, one per character
+    fn shown(&self, masked: bool) -> String {
+        if masked { "*".repeat(self.len()) } else { self.text.clone() }
+    }
+
+    // The first character drawn in a box `width` wide, so the cursor stays in sight
+    fn scroll(shown: &str, cursor: usize, width: f32) -> usize {
+        let chars: Vec<char> = shown.chars().collect();
+        (0..cursor).find(|&first| text_width(&chars[first..cursor]) <= width).unwrap_or(cursor)
+    }
+
+    // Puts the cursor at the character boundary nearest `x`
+    pub fn click(&mut self, rect: Rect, x: f32, masked: bool) {
+        let shown = self.shown(masked);
+        let chars: Vec<char> = shown.chars().collect();
+        let first = TextField::scroll(&shown, self.cursor, rect.w - 16.0);
+        let x = x - rect.x - 8.0;
+        let to = (first..=chars.len())
+            .min_by(|&a, &b| (text_width(&chars[first..a]) - x).abs().total_cmp(&(text_width(&chars[first..b]) - x).abs()))
+            .unwrap_or(first);
+        self.move_to(to, false);
+    }
+
+    pub fn draw(&self, rect: Rect, placeholder: &str, focused: bool, masked: bool) {
+        draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, if focused { YELLOW } else { GRAY });
+        let baseline = rect.y + rect.h * 0.65;
+        if self.text.is_empty() {
+            draw_text(placeholder, rect.x + 8.0, baseline, INPUT_FONT as f32, GRAY);
+        }
+        let shown = self.shown(masked);
+        let chars: Vec<char> = shown.chars().collect();
+        let width = rect.w - 16.0;
+        let first = TextField::scroll(&shown, self.cursor, width);
+        let last = (first..=chars.len()).take_while(|&end| text_width(&chars[first..end]) <= width).last().unwrap_or(first);
+        let x_at = |i: usize| rect.x + 8.0 + text_width(&chars[first..i.clamp(first, last)]);
+        if focused && let Some((from, to)) = self.selection() {
+            draw_rectangle(x_at(from), rect.y + 8.0, x_at(to) - x_at(from), rect.h - 16.0, DARKBLUE);
+        }
+        draw_text(&chars[first..last].iter().collect::<String>(), rect.x + 8.0, baseline, INPUT_FONT as f32, WHITE);
+        // a blinking cursor
+        if focused && get_time().fract() < 0.5 {
+            let x = x_at(self.cursor);
+            draw_line(x, rect.y + 10.0, x, rect.y + rect.h - 10.0, 2.0, WHITE);
+        }
+    }
+}
+
+fn text_width(chars: &[char]) -> f32 {
+    measure_text(&chars.iter().collect::<String>(), None, INPUT_FONT, 1.0).width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_cursor_steps_over_whole_characters() {
+        let mut field = TextField::new("añ✓b".to_string(), 10);
+        assert_eq!(field.cursor, 4);
+        field.left(false);
+        field.left(false);
+        field.backspace();
+        assert_eq!(field.text, "a✓b");
+        field.insert("é");
+        assert_eq!(field.text, "aé✓b");
+        field.delete();
+        assert_eq!(field.text, "aéb");
+        field.left(true);
+        assert_eq!(field.selected(), Some("é"));
+        // Right without Shift drops the selection at its end, after the é
+        field.right(false);
+        field.insert("x");
+        assert_eq!((field.text.as_str(), field.cursor), ("aéxb", 3));
+    }
+
+    #[test]
+    fn typing_replaces_the_selection() {
+        let mut field = TextField::new("hello world".to_string(), 20);
+        for _ in 0..5 {
+            field.left(true);
+        }
+        assert_eq!(field.selected(), Some("world"));
+        field.insert("X");
+        assert_eq!((field.text.as_str(), field.cursor), ("hello X", 7));
+        assert_eq!(field.selected(), None);
+
+        field.select_all();
+        field.paste("bye");
+        assert_eq!(field.text, "bye");
+        field.select_all();
+        field.backspace();
+        assert_eq!((field.text.as_str(), field.cursor), ("", 0));
+    }
+
+    #[test]
+    fn pasting_stops_at_max_len() {
+        let mut field = TextField::new("abc".to_string(), 5);
+        field.paste("defgh");
+        assert_eq!((field.text.as_str(), field.cursor), ("abcde", 5));
+        field.paste("more");
+        assert_eq!(field.text, "abcde");
+
+        // the selection makes room for what replaces it; line breaks and the space around the
+        // pasted text are left out
+        field.select_all();
+        field.paste("  1\n23456  ");
+        assert_eq!(field.text, "12345");
+        assert_eq!(TextField::new("toolong".to_string(), 3).text, "too");
+    }
+}
@@ -0,0 +1,142 @@
+use macroquad::prelude::*;
+
+use snake::game_core::{GameConfig, MAX_PLAYERS, PlayerState};
+
+// Snake colors per team, head and body; players without a team use the first
+const TEAM_COLORS: [(Color, Color); 4] = [(BLUE, DARKBLUE), (ORANGE, BROWN), (GREEN, DARKGREEN), (PINK, PURPLE)];
+
+pub fn team_colors(team: Option<u8>) -> (Color, Color) {
+    TEAM_COLORS[team.unwrap_or(0) as usize % TEAM_COLORS.len()]
+}
+
+// The Okabe-Ito colors, told apart with any kind of color blindness
+const COLORBLIND_COLORS: [(Color, Color); MAX_PLAYERS] = [
+    (Color::new(0.0, 0.45, 0.7, 1.0), Color::new(0.0, 0.3, 0.47, 1.0)),
+    (Color::new(0.9, 0.62, 0.0, 1.0), Color::new(0.6, 0.41, 0.0, 1.0)),
+    (Color::new(0.0, 0.62, 0.45, 1.0), Color::new(0.0, 0.41, 0.3, 1.0)),
+    (Color::new(0.8, 0.47, 0.65, 1.0), Color::new(0.53, 0.31, 0.43, 1.0)),
+    (Color::new(0.94, 0.89, 0.26, 1.0), Color::new(0.63, 0.6, 0.17, 1.0)),
+    (Color::new(0.34, 0.71, 0.91, 1.0), Color::new(0.23, 0.47, 0.61, 1.0)),
+    (Color::new(0.84, 0.37, 0.0, 1.0), Color::new(0.56, 0.25, 0.0, 1.0)),
+    (WHITE, GRAY),
+];
+
+// How the board looks. Snakes take their colors from `snakes` by the color the server gave
+// the player, head and body, unless the colorblind palette is on.
+pub struct Theme {
+    pub name: &'static str,
+    pub background: Color,
+    pub wall: Color,
+    pub food: Color,
+    pub border: Color,
+    pub text: Color,
+    grid: GridStyle,
+    // drawn faintly; the background shows through
+    grid_color: Color,
+    snakes: [(Color, Color); MAX_PLAYERS],
+}
+
+// What the board's background shows of its cells
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum GridStyle {
+    Lines,
+    Checkerboard,
+}
+
+pub const THEMES: [Theme; 3] = [
+    Theme {
+        name: "Classic",
+        background: BLACK,
+        wall: GRAY,
+        food: RED,
+        border: GRAY,
+        text: WHITE,
+        grid: GridStyle::Lines,
+        grid_color: Color::new(1.0, 1.0, 1.0, 0.06),
+        snakes: [
+            (GREEN, DARKGREEN),
+            (ORANGE, BROWN),
+            (BLUE, DARKBLUE),
+            (PINK, PURPLE),
+            (YELLOW, GOLD),
+            (SKYBLUE, DARKBLUE),
+            (RED, MAROON),
+            (WHITE, LIGHTGRAY),
+        ],
+    },
+    Theme {
+        name: "Neon",
+        background: Color::new(0.04, 0.02, 0.08, 1.0),
+        wall: Color::new(0.3, 0.1, 0.5, 1.0),
+        food: Color::new(1.0, 0.2, 0.4, 1.0),
+        border: MAGENTA,
+        text: Color::new(0.0, 1.0, 1.0, 1.0),
+        grid: GridStyle::Lines,
+        grid_color: Color::new(1.0, 0.0, 1.0, 0.1),
+        snakes: [
+            (Color::new(0.0, 1.0, 1.0, 1.0), Color::new(0.0, 0.6, 0.7, 1.0)),
+            (Color::new(1.0, 0.0, 1.0, 1.0), Color::new(0.6, 0.0, 0.6, 1.0)),
+            (Color::new(0.6, 1.0, 0.0, 1.0), Color::new(0.35, 0.6, 0.0, 1.0)),
+            (Color::new(1.0, 0.5, 0.0, 1.0), Color::new(0.6, 0.3, 0.0, 1.0)),
+            (Color::new(1.0, 1.0, 0.2, 1.0), Color::new(0.6, 0.6, 0.1, 1.0)),
+            (Color::new(0.2, 0.4, 1.0, 1.0), Color::new(0.1, 0.2, 0.6, 1.0)),
+            (Color::new(1.0, 0.3, 0.6, 1.0), Color::new(0.6, 0.15, 0.35, 1.0)),
+            (WHITE, Color::new(0.6, 0.6, 0.6, 1.0)),
+        ],
+    },
+    // four shades of green, like an old handheld
+    Theme {
+        name: "Retro LCD",
+        background: Color::new(0.61, 0.74, 0.06, 1.0),
+        wall: Color::new(0.19, 0.38, 0.19, 1.0),
+        food: Color::new(0.06, 0.22, 0.06, 1.0),
+        border: Color::new(0.06, 0.22, 0.06, 1.0),
+        text: Color::new(0.06, 0.22, 0.06, 1.0),
+        grid: GridStyle::Checkerboard,
+        grid_color: Color::new(0.06, 0.22, 0.06, 0.06),
+        snakes: [
+            (Color::new(0.06, 0.22, 0.06, 1.0), Color::new(0.19, 0.38, 0.19, 1.0)),
+            (Color::new(0.19, 0.38, 0.19, 1.0), Color::new(0.06, 0.22, 0.06, 1.0)),
+            (Color::new(0.06, 0.22, 0.06, 1.0), Color::new(0.55, 0.67, 0.06, 1.0)),
+            (Color::new(0.19, 0.38, 0.19, 1.0), Color::new(0.55, 0.67, 0.06, 1.0)),
+            (Color::new(0.0, 0.1, 0.0, 1.0), Color::new(0.06, 0.22, 0.06, 1.0)),
+            (Color::new(0.0, 0.1, 0.0, 1.0), Color::new(0.19, 0.38, 0.19, 1.0)),
+            (Color::new(0.0, 0.1, 0.0, 1.0), Color::new(0.55, 0.67, 0.06, 1.0)),
+            (Color::new(0.55, 0.67, 0.06, 1.0), Color::new(0.06, 0.22, 0.06, 1.0)),
+        ],
+    },
+];
+
+// The board's cells, so distances are easy to judge
+pub fn draw_grid(config: &GameConfig, cell: f32, theme: &Theme) {
+    let (w, h) = (config.grid_width as f32 * cell, config.grid_height as f32 * cell);
+    match theme.grid {
+        GridStyle::Lines => {
+            for x in 0..=config.grid_width {
+                draw_line(x as f32 * cell, 0.0, x as f32 * cell, h, 1.0, theme.grid_color);
+            }
+            for y in 0..=config.grid_height {
+                draw_line(0.0, y as f32 * cell, w, y as f32 * cell, 1.0, theme.grid_color);
+            }
+        }
+        GridStyle::Checkerboard => {
+            for y in 0..config.grid_height {
+                for x in (y % 2..config.grid_width).step_by(2) {
+                    draw_rectangle(x as f32 * cell, y as f32 * cell, cell, cell, theme.grid_color);
+                }
+            }
+        }
+    }
+}
+
+pub fn palette(theme: &Theme, colorblind: bool) -> &[(Color, Color); MAX_PLAYERS] {
+    if colorblind { &COLORBLIND_COLORS } else { &theme.snakes }
+}
+
+// Teammates look alike; everyone else gets their own color
+pub fn snake_colors(player: &PlayerState, palette: &[(Color, Color)]) -> (Color, Color) {
+    if player.team.is_some() {
+        return team_colors(player.team);
+    }
+    palette[player.color as usize % palette.len()]
+}
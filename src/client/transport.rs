@@ -0,0 +1,311 @@
+use std::collections::VecDeque;
+
+use macroquad::prelude::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+use snake::game_core::net::tcp;
+use snake::game_core::net::{hello, join_messages, NetOptions, NetStatus, RoomChoice};
+use snake::game_core::sim::{play_tick, GameState, MatchConfig, TickResult};
+use snake::game_core::{strip_scheme, ClientMsg, Compression, Credential, EmoteMsg, GameMode, MatchPhase, PauseMsg, PROTOCOL_VERSION, ServerAddr, ServerMsg, WireFormat};
+
+use super::replay_view::ReplayTransport;
+
+// How a connection proves the name it plays under is the player's
+#[derive(Clone)]
+pub enum Auth {
+    // a login saved for the name on this server, if there is one
+    Saved,
+    Login(String),
+    Register(String),
+}
+
+impl Auth {
+    // What the connect form's password field and Register/Log in button ask for
+    pub fn from_form(password: &str, register: bool) -> Auth {
+        match (password.is_empty(), register) {
+            (true, _) => Auth::Saved,
+            (false, false) => Auth::Login(password.to_string()),
+            (false, true) => Auth::Register(password.to_string()),
+        }
+    }
+}
+
+// The game talks to the server through this; natively it is a TCP socket, in the browser a WebSocket
+pub trait NetTransport {
+    fn send(&mut self, msg: ClientMsg);
+    fn try_recv(&mut self) -> Option<ServerMsg>;
+    // the next change of the connection's status, if there was one
+    fn poll_status(&mut self) -> Option<NetStatus>;
+    // the recording being played, for its playback controls
+    fn replay(&mut self) -> Option<&mut ReplayTransport> {
+        None
+    }
+    // Sends for one of the players sharing this window; only offline play has more than one
+    fn send_as(&mut self, player: usize, msg: ClientMsg) {
+        if player == 0 {
+            self.send(msg);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl NetTransport for tcp::TcpTransport {
+    fn send(&mut self, msg: ClientMsg) {
+        tcp::TcpTransport::send(self, msg);
+    }
+
+    fn try_recv(&mut self) -> Option<ServerMsg> {
+        tcp::TcpTransport::try_recv(self)
+    }
+
+    fn poll_status(&mut self) -> Option<NetStatus> {
+        tcp::TcpTransport::poll_status(self)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_transport(server_addr: String, opening: Vec<ClientMsg>, options: NetOptions) -> Box<dyn NetTransport> {
+    Box::new(tcp::start_networking(server_addr, opening, options.connect_timeout))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn open_transport(server_addr: String, opening: Vec<ClientMsg>, options: NetOptions) -> Box<dyn NetTransport> {
+    Box::new(web_socket::WsTransport::connect(&server_addr, opening, options.connect_timeout))
+}
+
+// Browsers also take a ws:// or wss:// URL as it is
+fn is_ws_url(addr: &str) -> bool {
+    addr.starts_with("ws://") || addr.starts_with("wss://")
+}
+
+// Whether the client can try the address, and what's wrong with it if not
+pub fn check_address(addr: &str) -> Result<(), String> {
+    if cfg!(target_arch = "wasm32") && is_ws_url(addr) {
+        return Ok(());
+    }
+    ServerAddr::parse(addr).map(|_| ())
+}
+
+pub fn connect(server_addr: String, username: String, room: RoomChoice, auth: Auth, options: NetOptions) -> Box<dyn NetTransport> {
+    // datagrams aren't encrypted, so a secure connection keeps states on the stream
+    #[cfg(not(target_arch = "wasm32"))]
+    let (token, saved_login, options) = (
+        match &room {
+            RoomChoice::Join { code, .. } => tcp::load_session(&server_addr, code),
+            _ => None,
+        },
+        tcp::load_account(&server_addr, &username),
+        NetOptions { udp: options.udp && !strip_scheme(&server_addr).0, ..options },
+    );
+    #[cfg(target_arch = "wasm32")]
+    let (token, saved_login, options) = (None, None, NetOptions { udp: false, ..options });
+    let login = match auth {
+        Auth::Saved => saved_login.map(|token| ClientMsg::Login { name: username.clone(), credential: Credential::Token(token) }),
+        Auth::Login(password) => Some(ClientMsg::Login { name: username.clone(), credential: Credential::Password(password) }),
+        Auth::Register(password) => Some(ClientMsg::Register { name: username.clone(), password }),
+    };
+    open_transport(server_addr, join_messages(username, room, token, login, options), options)
+}
+
+// A match played alone on the client's own simulation. It talks like a server would, so
+// the rest of the client can't tell the difference.
+pub struct OfflineTransport {
+    pub state: GameState,
+    inbox: VecDeque<ServerMsg>,
+    // when the next tick is due, in get_time() seconds
+    next_tick: f64,
+    paused: bool,
+}
+
+impl OfflineTransport {
+    // One snake for each name, all steered from this window
+    pub fn new(names: &[String], mode: GameMode, wrap: bool) -> Self {
+        let mut config = MatchConfig::default();
+        config.game.mode = mode;
+        config.game.wrap = wrap;
+        let game = config.game;
+        let mut state = GameState::new(names, config);
+        let welcome = ServerMsg::Welcome { protocol_version: PROTOCOL_VERSION, format: WireFormat::Json, seed: state.seed, compression: Compression::None };
+        let mut inbox = VecDeque::from([welcome, ServerMsg::Config(game)]);
+        inbox.push_back(ServerMsg::State(state.snapshot()));
+        let next_tick = get_time() + state.tick_ms as f64 / 1000.0;
+        OfflineTransport { state, inbox, next_tick, paused: false }
+    }
+}
+
+impl NetTransport for OfflineTransport {
+    fn send(&mut self, msg: ClientMsg) {
+        self.send_as(0, msg);
+    }
+
+    fn send_as(&mut self, slot: usize, msg: ClientMsg) {
+        let Some(player) = self.state.players.get_mut(slot) else {
+            return;
+        };
+        let id = player.id;
+        match msg {
+            ClientMsg::Input { dir, .. } => player.queue_input(dir),
+            ClientMsg::Boost { active } => player.boosting = active,
+            ClientMsg::Restart if self.state.is_over() => {
+                self.state.reset();
+                self.next_tick = get_time() + self.state.tick_ms as f64 / 1000.0;
+                self.inbox.push_back(ServerMsg::State(self.state.snapshot()));
+            }
+            ClientMsg::Pause if !self.state.is_over() => {
+                self.paused = !self.paused;
+                let pause = PauseMsg { paused: self.paused, waiting_for: Vec::new(), votes: 0, needed: 1 };
+                self.inbox.push_back(ServerMsg::Pause(pause));
+                let mut state = self.state.snapshot();
+                if self.paused {
+                    state.phase = MatchPhase::Paused;
+                }
+                self.inbox.push_back(ServerMsg::State(state));
+            }
+            ClientMsg::Ping { sent_ms, .. } => self.inbox.push_back(ServerMsg::Pong { sent_ms }),
+            ClientMsg::Emote { emote } => self.inbox.push_back(ServerMsg::Emote(EmoteMsg { player: id, emote })),
+            _ => {}
+        }
+    }
+
+    fn try_recv(&mut self) -> Option<ServerMsg> {
+        let now = get_time();
+        if self.paused {
+            self.next_tick = now;
+        } else if now >= self.next_tick {
+            let TickResult { events, round_end, .. } = play_tick(&mut self.state, &[]);
+            if !events.is_empty() {
+                self.inbox.push_back(ServerMsg::Events(events));
+            }
+            if let Some(round_end) = round_end {
+                self.inbox.push_back(ServerMsg::RoundEnd(round_end));
+            }
+            self.inbox.push_back(ServerMsg::State(self.state.snapshot()));
+            // after a stall, carry on from now rather than catching up
+            self.next_tick = (self.next_tick + self.state.tick_ms as f64 / 1000.0).max(now);
+        }
+        self.inbox.pop_front()
+    }
+
+    fn poll_status(&mut self) -> Option<NetStatus> {
+        None
+    }
+}
+
+// A connection that only asks for the room list; send ListRooms again to refresh it
+pub fn browse(server_addr: String, options: NetOptions) -> Box<dyn NetTransport> {
+    let options = NetOptions { format: WireFormat::Json, udp: false, ..options };
+    open_transport(server_addr, vec![hello(options), ClientMsg::ListRooms], options)
+}
+
+// Browser builds can't open sockets themselves; web/snake_net.js provides a WebSocket that
+// carries the same byte stream as the TCP connection (e.g. through a websockify bridge)
+#[cfg(target_arch = "wasm32")]
+mod web_socket {
+    use std::time::Duration;
+
+    use macroquad::time::get_time;
+    use snake::game_core::codec;
+    use snake::game_core::net::NetStatus;
+    use snake::game_core::{ClientMsg, Compression, ServerAddr, ServerMsg, WireFormat};
+
+    use super::{is_ws_url, NetTransport};
+
+    unsafe extern "C" {
+        fn snake_ws_connect(url: *const u8, url_len: usize);
+        fn snake_ws_send(data: *const u8, len: usize);
+        // copies up to `cap` received bytes into `buf`, returns how many
+        fn snake_ws_recv(buf: *mut u8, cap: usize) -> usize;
+        // 0 while connecting, 1 once open, 2 after it closed or failed
+        fn snake_ws_state() -> u32;
+    }
+
+    pub struct WsTransport {
+        // None until the server's Welcome arrived
+        format: Option<WireFormat>,
+        compression: Compression,
+        inbox: Vec<u8>,
+        // messages held back until the format is known
+        pending: Vec<ClientMsg>,
+        // the last status handed out, and when connecting gives up
+        status: Option<NetStatus>,
+        deadline: f64,
+    }
+
+    impl WsTransport {
+        pub fn connect(server_addr: &str, mut opening: Vec<ClientMsg>, timeout: Duration) -> Self {
+            let url = if is_ws_url(server_addr) {
+                server_addr.to_string()
+            } else {
+                match ServerAddr::parse(server_addr) {
+                    Ok(addr) => format!("{}://{}", if addr.secure { "wss" } else { "ws" }, addr.authority()),
+                    // checked before connecting; the socket reports anything left
+                    Err(_) => format!("ws://{}", server_addr),
+                }
+            };
+            unsafe { snake_ws_connect(url.as_ptr(), url.len()) };
+            let first = opening.remove(0);
+            let deadline = get_time() + timeout.as_secs_f64();
+            let transport = WsTransport { format: None, compression: Compression::None, inbox: Vec::new(), pending: opening, status: None, deadline };
+            transport.write(WireFormat::Json, &first);
+            transport
+        }
+
+        fn write(&self, format: WireFormat, msg: &ClientMsg) {
+            if let Ok(bytes) = codec::encode(format, msg) {
+                unsafe { snake_ws_send(bytes.as_ptr(), bytes.len()) };
+            }
+        }
+    }
+
+    impl NetTransport for WsTransport {
+        fn send(&mut self, msg: ClientMsg) {
+            match self.format {
+                Some(format) => self.write(format, &msg),
+                None => self.pending.push(msg),
+            }
+        }
+
+        fn try_recv(&mut self) -> Option<ServerMsg> {
+            let mut chunk = [0u8; 4096];
+            loop {
+                let n = unsafe { snake_ws_recv(chunk.as_mut_ptr(), chunk.len()) };
+                if n == 0 {
+                    break;
+                }
+                self.inbox.extend_from_slice(&chunk[..n]);
+            }
+            loop {
+                let format = self.format.unwrap_or(WireFormat::Json);
+                let frame = codec::take_packed_frame(&mut self.inbox, format, self.compression).ok()??;
+                match codec::decode::<ServerMsg>(format, &frame) {
+                    Ok(ServerMsg::Welcome { format: chosen, protocol_version, seed, compression }) => {
+                        self.format = Some(chosen);
+                        self.compression = compression;
+                        for msg in std::mem::take(&mut self.pending) {
+                            self.write(chosen, &msg);
+                        }
+                        return Some(ServerMsg::Welcome { protocol_version, format: chosen, seed, compression });
+                    }
+                    Ok(msg) => return Some(msg),
+                    Err(_) => {}
+                }
+            }
+        }
+
+        fn poll_status(&mut self) -> Option<NetStatus> {
+            // browsers don't say why a WebSocket failed, only that it did
+            let status = match unsafe { snake_ws_state() } {
+                0 if get_time() > self.deadline => NetStatus::Failed("The server didn't answer in time".to_string()),
+                0 => return None,
+                1 => NetStatus::Connected,
+                _ if self.status.is_none() => NetStatus::Failed("Can't reach the server".to_string()),
+                _ => NetStatus::Failed("Connection lost".to_string()),
+            };
+            if self.status.as_ref() == Some(&status) {
+                return None;
+            }
+            self.status = Some(status.clone());
+            Some(status)
+        }
+    }
+}
@@ -0,0 +1,350 @@
+// Renders a recorded match to an animated GIF, or to a numbered PNG per frame, for sharing
+// a highlight without running the game. It draws what game_core::scene lists, in the
+// windowed client's Classic colors.
+
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use snake::game_core::replay::{self, ReplayFrame};
+use snake::game_core::scene::{self, Piece, Segment};
+use snake::game_core::{BoardMap, FoodKind, GameConfig, PlayerState, PowerUpKind, ServerMsg, MAX_PLAYERS};
+
+// Pixels per board cell when not given
+const DEFAULT_CELL: u32 = 8;
+// GIFs count delays in hundredths of a second, and viewers speed up anything quicker than this
+const MIN_DELAY_CS: u64 = 2;
+
+// The palette both formats index into
+const BACKGROUND: u8 = 0;
+const FOOD_ZONE: u8 = 1;
+const WALL: u8 = 2;
+const BOOSTING_HEAD: u8 = 3;
+const TELEPORTERS: u8 = 4;
+const FOODS: u8 = 8;
+const POWER_UPS: u8 = 13;
+// a head and a body color for each snake color, then each team, then all of those faded
+const SNAKES: u8 = 17;
+const SNAKE_PAIRS: u8 = (MAX_PLAYERS + TEAM_COLORS.len()) as u8;
+
+const BLACK: [u8; 3] = [0, 0, 0];
+const GRAY: [u8; 3] = [130, 130, 130];
+const SKYBLUE: [u8; 3] = [102, 191, 255];
+const TELEPORTER_COLORS: [[u8; 3]; 4] = [PURPLE, ORANGE, SKYBLUE, PINK];
+// Normal, Golden, Leftover, Corpse, Poison
+const FOOD_COLORS: [[u8; 3]; 5] = [[230, 41, 55], [255, 203, 0], [190, 33, 55], [135, 60, 190], [0, 158, 47]];
+// Speed, Slow, Ghost, Shrink
+const POWER_UP_COLORS: [[u8; 3]; 4] = [[253, 249, 0], SKYBLUE, [200, 200, 200], [255, 0, 255]];
+const GREEN: [u8; 3] = [0, 228, 48];
+const DARKGREEN: [u8; 3] = [0, 117, 44];
+const ORANGE: [u8; 3] = [255, 161, 0];
+const BROWN: [u8; 3] = [127, 106, 79];
+const BLUE: [u8; 3] = [0, 121, 241];
+const DARKBLUE: [u8; 3] = [0, 82, 172];
+const PINK: [u8; 3] = [255, 109, 194];
+const PURPLE: [u8; 3] = [200, 122, 255];
+const SNAKE_COLORS: [([u8; 3], [u8; 3]); MAX_PLAYERS] = [
+    (GREEN, DARKGREEN),
+    (ORANGE, BROWN),
+    (BLUE, DARKBLUE),
+    (PINK, PURPLE),
+    ([253, 249, 0], [255, 203, 0]),
+    (SKYBLUE, DARKBLUE),
+    ([230, 41, 55], [190, 33, 55]),
+    ([255, 255, 255], [200, 200, 200]),
+];
+const TEAM_COLORS: [([u8; 3], [u8; 3]); 4] = [(BLUE, DARKBLUE), (ORANGE, BROWN), (GREEN, DARKGREEN), (PINK, PURPLE)];
+
+fn palette() -> Vec<u8> {
+    let mut colors = vec![BLACK, [0, 64, 0], GRAY, SKYBLUE];
+    colors.extend(TELEPORTER_COLORS);
+    colors.extend(FOOD_COLORS);
+    colors.extend(POWER_UP_COLORS);
+    let pairs: Vec<[u8; 3]> = SNAKE_COLORS.iter().chain(&TEAM_COLORS).flat_map(|&(head, body)| [head, body]).collect();
+    colors.extend(&pairs);
+    // faded snakes are drawn at about a third, over the black background
+    colors.extend(pairs.iter().map(|c| c.map(|v| (v as u32 * 35 / 100) as u8)));
+    colors.concat()
+}
+
+struct ExportArgs {
+    replay: String,
+    out: Output,
+    cell: u32,
+    // keep only every this many snapshots
+    every: usize,
+    from_secs: f64,
+    to_secs: Option<f64>,
+}
+
+enum Output {
+    Gif(PathBuf),
+    // a directory that gets frame-00000.png and on
+    Png(PathBuf),
+}
+
+fn parse_args() -> Result<ExportArgs, String> {
+    let mut it = std::env::args().skip(1);
+    let mut replay = None;
+    let mut out = None;
+    let (mut cell, mut every, mut from_secs, mut to_secs) = (DEFAULT_CELL, 1, 0.0, None);
+    let secs = |flag: &str, value: Option<String>| -> Result<f64, String> {
+        let value = value.ok_or(format!("{} needs a value", flag))?;
+        value.parse().ok().filter(|s: &f64| *s >= 0.0).ok_or(format!("invalid {}: {}", flag, value))
+    };
+    let number = |flag: &str, value: Option<String>| -> Result<u32, String> {
+        let value = value.ok_or(format!("{} needs a value", flag))?;
+        value.parse().ok().filter(|n| *n > 0).ok_or(format!("invalid {}: {}", flag, value))
+    };
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--gif" => out = Some(Output::Gif(it.next().ok_or("--gif needs a file")?.into())),
+            "--png" => out = Some(Output::Png(it.next().ok_or("--png needs a directory")?.into())),
+            "--cell" => cell = number("--cell", it.next())?.min(64),
+            "--every" => every = number("--every", it.next())? as usize,
+            "--from" => from_secs = secs("--from", it.next())?,
+            "--to" => to_secs = Some(secs("--to", it.next())?),
+            other if other.starts_with("--") => return Err(format!("unknown argument: {}", other)),
+            path if replay.is_none() => replay = Some(path.to_string()),
+            extra => return Err(format!("unexpected argument: {}", extra)),
+        }
+    }
+    let replay = replay.ok_or("which replay? give its path")?;
+    // next to the replay, under the same name, unless told otherwise
+    let out = out.unwrap_or_else(|| Output::Gif(Path::new(&replay).with_extension("gif")));
+    Ok(ExportArgs { replay, out, cell, every, from_secs, to_secs })
+}
+
+// One picture of the board, a palette index per pixel
+struct Canvas {
+    width: u32,
+    height: u32,
+    cell: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(config: &GameConfig, cell: u32) -> Canvas {
+        let (width, height) = (config.grid_width.max(1) as u32 * cell, config.grid_height.max(1) as u32 * cell);
+        Canvas { width, height, cell, pixels: vec![BACKGROUND; (width * height) as usize] }
+    }
+
+    // Colors the pixels of the cell at (x, y) that `inside` picks, given their offset in it
+    fn paint(&mut self, x: i32, y: i32, color: u8, inside: impl Fn(u32, u32) -> bool) {
+        let (left, top) = (x as u32 * self.cell, y as u32 * self.cell);
+        if x < 0 || y < 0 || left >= self.width || top >= self.height {
+            return;
+        }
+        for dy in 0..self.cell {
+            for dx in (0..self.cell).filter(|&dx| inside(dx, dy)) {
+                self.pixels[((top + dy) * self.width + left + dx) as usize] = color;
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &ReplayFrame, map: Option<&BoardMap>) {
+        self.pixels.fill(BACKGROUND);
+        let cell = self.cell;
+        let last = cell - 1;
+        for (pos, piece) in scene::pieces(&frame.state, map) {
+            let (x, y) = (pos.x, pos.y);
+            match piece {
+                Piece::FoodZone => self.paint(x, y, FOOD_ZONE, |_, _| true),
+                Piece::Wall => self.paint(x, y, WALL, |_, _| true),
+                // a ring in the pair's color
+                Piece::Teleporter { pair } => {
+                    let color = TELEPORTERS + (pair % TELEPORTER_COLORS.len()) as u8;
+                    self.paint(x, y, color, |dx, dy| dx == 0 || dy == 0 || dx == last || dy == last);
+                }
+                // a diamond
+                Piece::PowerUp(kind) => {
+                    let color = POWER_UPS + power_up_index(kind);
+                    let half = cell as i32 / 2;
+                    self.paint(x, y, color, |dx, dy| (dx as i32 - half).abs() + (dy as i32 - half).abs() <= half);
+                }
+                Piece::Body(segment) => {
+                    let color = snake_color(&frame.state.players[segment.player], segment) + 1;
+                    self.paint(x, y, color, |_, _| true);
+                }
+                Piece::Head(segment) => {
+                    let player = &frame.state.players[segment.player];
+                    let color = if segment.boosting && !segment.faded { BOOSTING_HEAD } else { snake_color(player, segment) };
+                    self.paint(x, y, color, |_, _| true);
+                }
+                // a square inset from the cell's edges; poison is crossed out
+                Piece::Food(kind) => {
+                    let margin = cell / 5;
+                    let inset = move |dx: u32, dy: u32| (margin..cell - margin).contains(&dx) && (margin..cell - margin).contains(&dy);
+                    self.paint(x, y, FOODS + food_index(kind), inset);
+                    if kind == FoodKind::Poison && cell >= 4 {
+                        self.paint(x, y, BACKGROUND, move |dx, dy| inset(dx, dy) && (dx == dy || dx == last - dy));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn food_index(kind: FoodKind) -> u8 {
+    match kind {
+        FoodKind::Normal => 0,
+        FoodKind::Golden => 1,
+        FoodKind::Leftover => 2,
+        FoodKind::Corpse => 3,
+        FoodKind::Poison => 4,
+    }
+}
+
+fn power_up_index(kind: PowerUpKind) -> u8 {
+    match kind {
+        PowerUpKind::Speed => 0,
+        PowerUpKind::Slow => 1,
+        PowerUpKind::Ghost => 2,
+        PowerUpKind::Shrink => 3,
+    }
+}
+
+// The palette index of the snake's head color; its body color comes right after
+fn snake_color(player: &PlayerState, segment: Segment) -> u8 {
+    let pair = match player.team {
+        Some(team) => MAX_PLAYERS + team as usize % TEAM_COLORS.len(),
+        None => player.color as usize % MAX_PLAYERS,
+    } as u8;
+    let faded = if segment.faded { SNAKE_PAIRS * 2 } else { 0 };
+    SNAKES + faded + pair * 2
+}
+
+// Writes the frames out one by one, in whichever format was asked for
+enum Sink {
+    Gif(gif::Encoder<BufWriter<File>>),
+    Png { dir: PathBuf, written: usize },
+}
+
+impl Sink {
+    fn open(out: &Output, width: u32, height: u32) -> Result<Sink, String> {
+        match out {
+            Output::Gif(path) => {
+                let (width, height) = (u16::try_from(width), u16::try_from(height));
+                let (Ok(width), Ok(height)) = (width, height) else {
+                    return Err("the picture is too big for a GIF, try a smaller --cell".to_string());
+                };
+                let file = File::create(path).map_err(|e| format!("can't create {}: {}", path.display(), e))?;
+                let mut encoder = gif::Encoder::new(BufWriter::new(file), width, height, &palette()).map_err(|e| e.to_string())?;
+                encoder.set_repeat(gif::Repeat::Infinite).map_err(|e| e.to_string())?;
+                Ok(Sink::Gif(encoder))
+            }
+            Output::Png(dir) => {
+                fs::create_dir_all(dir).map_err(|e| format!("can't create {}: {}", dir.display(), e))?;
+                Ok(Sink::Png { dir: dir.clone(), written: 0 })
+            }
+        }
+    }
+
+    fn write(&mut self, canvas: &Canvas, delay_ms: u64) -> Result<(), String> {
+        match self {
+            Sink::Gif(encoder) => {
+                let frame = gif::Frame {
+                    width: canvas.width as u16,
+                    height: canvas.height as u16,
+                    delay: (delay_ms / 10).max(MIN_DELAY_CS).min(u16::MAX as u64) as u16,
+                    buffer: Cow::Borrowed(&canvas.pixels),
+                    ..gif::Frame::default()
+                };
+                encoder.write_frame(&frame).map_err(|e| e.to_string())
+            }
+            Sink::Png { dir, written } => {
+                let path = dir.join(format!("frame-{:05}.png", written));
+                let file = File::create(&path).map_err(|e| format!("can't create {}: {}", path.display(), e))?;
+                let mut encoder = png::Encoder::new(BufWriter::new(file), canvas.width, canvas.height);
+                encoder.set_color(png::ColorType::Indexed);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_palette(palette());
+                let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+                writer.write_image_data(&canvas.pixels).map_err(|e| e.to_string())?;
+                *written += 1;
+                Ok(())
+            }
+        }
+    }
+}
+
+// Draws the frames between `from_secs` and `to_secs` of game time and returns how many it wrote
+fn export(args: &ExportArgs) -> Result<usize, String> {
+    let text = fs::read_to_string(&args.replay).map_err(|e| format!("can't open {}: {}", args.replay, e))?;
+    let replay = replay::parse(&text)?;
+    let mut config = GameConfig::default();
+    let mut map = None;
+    let mut sink: Option<Sink> = None;
+    let mut canvas: Option<Canvas> = None;
+    // game time of the frame on the canvas, and of the end of the stretch it stands for
+    let (mut shown_ms, mut end_ms) = (None, 0);
+    let (mut elapsed_ms, mut kept, mut written) = (0, 0, 0);
+    for frame in &replay.frames {
+        for msg in &frame.before {
+            match msg {
+                ServerMsg::Config(c) => config = *c,
+                ServerMsg::Map(m) => map = Some(m.clone()),
+                _ => {}
+            }
+        }
+        let at_ms = elapsed_ms;
+        elapsed_ms += frame.state.tick_ms;
+        let at_secs = at_ms as f64 / 1000.0;
+        if at_secs < args.from_secs {
+            continue;
+        }
+        if args.to_secs.is_some_and(|to| at_secs > to) {
+            break;
+        }
+        end_ms = elapsed_ms;
+        kept += 1;
+        if (kept - 1) % args.every != 0 {
+            continue;
+        }
+        // a GIF keeps its first size, so the board stays as the first frame had it
+        let canvas = canvas.get_or_insert_with(|| Canvas::new(&config, args.cell));
+        let sink = match &mut sink {
+            Some(sink) => sink,
+            None => sink.insert(Sink::open(&args.out, canvas.width, canvas.height)?),
+        };
+        // a frame is written once the next one says how long it stays up
+        if let Some(shown) = shown_ms {
+            sink.write(canvas, at_ms - shown)?;
+        }
+        canvas.draw(frame, map.as_ref());
+        shown_ms = Some(at_ms);
+        written += 1;
+    }
+    if let (Some(sink), Some(canvas), Some(shown)) = (sink.as_mut(), canvas.as_ref(), shown_ms) {
+        sink.write(canvas, end_ms - shown)?;
+    }
+    Ok(written)
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("usage: export REPLAY [--gif FILE | --png DIR] [--cell PIXELS] [--every N] [--from SECS] [--to SECS]");
+            std::process::exit(2);
+        }
+    };
+    match export(&args) {
+        Ok(0) => {
+            eprintln!("nothing between those times");
+            std::process::exit(1);
+        }
+        Ok(frames) => {
+            let out = match &args.out {
+                Output::Gif(path) | Output::Png(path) => path.display().to_string(),
+            };
+            println!("{} frames written to {}", frames, out);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -1,13 +1,251 @@
+pub mod ai;
+pub mod codec;
+pub mod collision;
+pub mod net;
+pub mod replay;
+pub mod rng;
+pub mod scene;
+pub mod sim;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tls;
+
+use std::collections::VecDeque;
 use std::fmt;
+use std::str::FromStr;
 use serde::{Deserialize, Serialize};
 
 // Shared game constants
-pub const GRID_WIDTH: i32 = 60;
-pub const GRID_HEIGHT: i32 = 30;
+// Bumped whenever a message changes shape; client and server must agree on it
+pub const PROTOCOL_VERSION: u32 = 40;
+// Clients ping at least this often; the server hangs up on connections quiet for much longer
+pub const PING_INTERVAL_MS: u64 = 1000;
+// Board size when the server isn't told otherwise, and the range it accepts
+pub const DEFAULT_GRID_WIDTH: i32 = 60;
+pub const DEFAULT_GRID_HEIGHT: i32 = 30;
+pub const MIN_GRID_SIZE: i32 = 10;
+pub const MAX_GRID_SIZE: i32 = 200;
 // Client owns CELL_SIZE for rendering; server ticks use MOVE_INTERVAL_MS
 pub const MOVE_INTERVAL_MS: u64 = 150; // ~6.67 FPS like original 0.15s
 
-pub const MAX_PLAYERS: usize = 1;
+// Player capacity is chosen per server run; these bound what a match supports
+pub const MIN_PLAYERS: usize = 1;
+pub const MAX_PLAYERS: usize = 8;
+pub const DEFAULT_MAX_PLAYERS: usize = 2;
+// Seconds between everyone being ready and the match starting
+pub const LOBBY_COUNTDOWN_SECS: u32 = 3;
+// Seconds every round counts down on the board before the snakes move, unless the server
+// sets its own
+pub const ROUND_COUNTDOWN_SECS: u64 = 3;
+// Turns a player can have waiting for their ticks; presses beyond that are dropped
+pub const MAX_QUEUED_INPUTS: usize = 3;
+// What eating golden food scores
+pub const GOLDEN_FOOD_POINTS: u32 = 5;
+// Ticks a power-up's effect lasts once picked up
+pub const POWER_UP_EFFECT_TICKS: u64 = 50;
+// Ticks before the food a dead snake turns into rots away
+pub const CORPSE_TICKS: u64 = 100;
+// Where servers listen, and clients connect, unless told otherwise
+pub const DEFAULT_PORT: u16 = 4000;
+// Addresses starting with this are connected to over TLS (wss:// in browsers)
+pub const SECURE_SCHEME: &str = "snakes://";
+
+// Splits a `snakes://` off a server address; true if it was there
+pub fn strip_scheme(addr: &str) -> (bool, &str) {
+    match addr.strip_prefix(SECURE_SCHEME) {
+        Some(rest) => (true, rest),
+        None => (false, addr),
+    }
+}
+
+// A server address as a player types it: a host name or IP address with an optional port,
+// an IPv6 address in brackets when it has one, maybe after a snakes://
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServerAddr {
+    pub secure: bool,
+    // without brackets, even when it's an IPv6 address
+    pub host: String,
+    pub port: u16,
+}
+
+impl ServerAddr {
+    pub fn parse(addr: &str) -> Result<ServerAddr, String> {
+        let (secure, rest) = strip_scheme(addr.trim());
+        if rest.is_empty() {
+            return Err("The server address is empty".to_string());
+        }
+        let (host, port) = if let Some(bracketed) = rest.strip_prefix('[') {
+            let (host, after) = bracketed.split_once(']').ok_or_else(|| format!("{} is missing its closing ]", rest))?;
+            if host.parse::<std::net::Ipv6Addr>().is_err() {
+                return Err(format!("{} is not an IPv6 address", host));
+            }
+            let port = match after {
+                "" => None,
+                after => Some(after.strip_prefix(':').ok_or_else(|| format!("Expected :PORT after [{}]", host))?),
+            };
+            (host, port)
+        } else if rest.parse::<std::net::Ipv6Addr>().is_ok() {
+            // a bare IPv6 address: its colons aren't a port
+            (rest, None)
+        } else {
+            match rest.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (rest, None),
+            }
+        };
+        if host.is_empty() {
+            return Err(format!("{} has no host before the port", rest));
+        }
+        if !host.contains(':') && !host.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.') {
+            return Err(format!("{} is not a host name or IP address", host));
+        }
+        let port = match port {
+            None => DEFAULT_PORT,
+            Some(port) => port.parse().ok().filter(|&p| p != 0).ok_or_else(|| format!("{} is not a port number", port))?,
+        };
+        Ok(ServerAddr { secure, host: host.to_string(), port })
+    }
+
+    // HOST:PORT, with an IPv6 host in brackets, the way sockets and URLs take it
+    pub fn authority(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+impl fmt::Display for ServerAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = if self.secure { SECURE_SCHEME } else { "" };
+        write!(f, "{}{}", scheme, self.authority())
+    }
+}
+
+// Tells the players in a room apart, for as long as the room lasts. A player keeps theirs
+// through rejoins and rematches, and ids aren't handed out twice, so they don't depend on
+// where a player sits in the list.
+pub type PlayerId = u32;
+
+// Longest player name, in characters
+pub const MAX_NAME_LEN: usize = 16;
+
+// Room codes are this many letters
+pub const ROOM_CODE_LEN: usize = 4;
+
+// Longest room password, in characters
+pub const MAX_ROOM_PASSWORD_LEN: usize = 32;
+
+// Names are letters, digits, spaces and a little punctuation, so they fit on the scoreboard
+// and can't hide behind invisible characters
+pub fn check_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("A name can't be empty".to_string());
+    }
+    if name.chars().count() > MAX_NAME_LEN {
+        return Err(format!("A name can be at most {} characters long", MAX_NAME_LEN));
+    }
+    if name.trim() != name {
+        return Err("A name can't start or end with a space".to_string());
+    }
+    match name.chars().find(|c| !c.is_alphanumeric() && !" _-.'".contains(*c)) {
+        Some(c) => Err(format!("A name can't contain {:?}", c)),
+        None => Ok(()),
+    }
+}
+
+// Trims a name and squeezes the spaces inside it, before it's checked
+pub fn normalize_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// The name to give a player whose name is taken already, told apart from the others by a number
+// ("bob (2)"). Names that differ only in case count as taken, they look the same on the scoreboard.
+pub fn unique_name<'a>(name: &str, taken: impl Iterator<Item = &'a str> + Clone) -> String {
+    let is_taken = |candidate: &str| taken.clone().any(|t| t.to_lowercase() == candidate.to_lowercase());
+    if !is_taken(name) {
+        return name.to_string();
+    }
+    (2..)
+        .map(|n| {
+            // the number goes on the end, in place of what doesn't fit
+            let suffix = format!(" ({})", n);
+            let keep = MAX_NAME_LEN.saturating_sub(suffix.len());
+            let base: String = name.chars().take(keep).collect();
+            format!("{}{}", base.trim_end(), suffix)
+        })
+        .find(|candidate| !is_taken(candidate))
+        .unwrap()
+}
+
+// How a round is won
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize, Default)]
+pub enum GameMode {
+    // the score target if there is one, otherwise the last snake standing
+    #[default]
+    Classic,
+    // the highest score when the clock runs out
+    Timed,
+    // the first to the score target; dying only stops a snake from scoring
+    ScoreRace,
+    // the last snake alive, whatever the scores
+    LastSnakeStanding,
+    // the last snake alive, on a board that keeps closing in
+    BattleRoyale,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 5] = [GameMode::Classic, GameMode::Timed, GameMode::ScoreRace, GameMode::LastSnakeStanding, GameMode::BattleRoyale];
+
+    // The mode after this one, for cycling through them
+    pub fn next(self) -> GameMode {
+        let at = GameMode::ALL.iter().position(|m| *m == self).unwrap_or(0);
+        GameMode::ALL[(at + 1) % GameMode::ALL.len()]
+    }
+}
+
+// Where a room's match is at. A room starts in the Lobby and every round goes Countdown,
+// Playing, maybe SuddenDeath, then Finished until a rematch counts down again. Paused can
+// come in between anywhere before Finished.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize, Default)]
+pub enum MatchPhase {
+    // players are gathering and getting ready; there's no board yet
+    Lobby,
+    // the board is out but nothing moves yet; turns pressed now are played from the start
+    Countdown,
+    #[default]
+    Playing,
+    // the players voted for a break, or the match waits for someone to rejoin
+    Paused,
+    // a timed round ran out on a tie: no more food, the board closes in, and the first
+    // of the tied snakes to die loses
+    SuddenDeath,
+    // the round is decided; a rematch starts the next one
+    Finished,
+}
+
+// Board settings chosen by the server, sent in a Config before anything else about the match
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct GameConfig {
+    pub grid_width: i32,
+    pub grid_height: i32,
+    pub mode: GameMode,
+    // snakes going off one edge come back on the other; without it the edge is a wall
+    pub wrap: bool,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig { grid_width: DEFAULT_GRID_WIDTH, grid_height: DEFAULT_GRID_HEIGHT, mode: GameMode::default(), wrap: true }
+    }
+}
+
+impl GameConfig {
+    // The same settings on a board of another size
+    pub fn resized(self, grid_width: i32, grid_height: i32) -> GameConfig {
+        GameConfig { grid_width, grid_height, ..self }
+    }
+}
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize, Default, Hash)]
 pub struct Pos {
@@ -15,54 +253,615 @@ pub struct Pos {
     pub y: i32,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+// A board designed by the server operator, sent after the Config when the server has one.
+// Its size is the Config's grid size.
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct BoardMap {
+    pub walls: Vec<Pos>,
+    // where snakes start, by slot; empty leaves it to the server
+    pub spawns: Vec<Pos>,
+    // food only appears on these cells, anywhere if there are none
+    pub food_zones: Vec<Pos>,
+    // a head entering one end of a pair comes out of the other
+    pub teleporters: Vec<(Pos, Pos)>,
+}
+
+impl BoardMap {
+    // Where a head entering `pos` comes out
+    pub fn teleport(&self, pos: Pos) -> Pos {
+        self.teleporters.iter()
+            .find_map(|&(a, b)| if a == pos { Some(b) } else if b == pos { Some(a) } else { None })
+            .unwrap_or(pos)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize, Default)]
 pub enum Direction {
     Up,
     Down,
     Left,
+    #[default]
     Right,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum FoodKind {
+    Normal,
+    // rare and short-lived, worth GOLDEN_FOOD_POINTS
+    Golden,
+    // what is left of a snake whose player left
+    Leftover,
+    // what is left of a snake that died, if the server turns bodies into food
+    Corpse,
+    // takes segments off the snake that eats it, and kills a snake that has none to lose
+    Poison,
+}
+
+impl FoodKind {
+    pub fn points(self) -> u32 {
+        match self {
+            FoodKind::Golden => GOLDEN_FOOD_POINTS,
+            FoodKind::Normal | FoodKind::Leftover | FoodKind::Corpse => 1,
+            FoodKind::Poison => 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct FoodItem {
+    pub pos: Pos,
+    pub kind: FoodKind,
+    // the tick it disappears on, if it doesn't stay until eaten
+    pub expires_tick: Option<u64>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum PowerUpKind {
+    // the snake moves twice per tick
+    Speed,
+    // every other snake moves every other tick
+    Slow,
+    // the snake passes through snakes, its own body included
+    Ghost,
+    // the snake loses half its length at once
+    Shrink,
+}
+
+// A power-up lying on the board until it is picked up or `expires_tick` passes
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct PowerUp {
+    pub kind: PowerUpKind,
+    pub pos: Pos,
+    pub expires_tick: u64,
+}
+
+// An effect working on a snake until `until_tick`. A Slow effect is on the snakes being slowed.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ActiveEffect {
+    pub kind: PowerUpKind,
+    pub until_tick: u64,
+}
+
 #[derive(Clone, Debug,Serialize, Deserialize)]
 pub struct PlayerState {
+    pub id: PlayerId,
     pub name: String,
     pub snake: Vec<Pos>,
     pub dir: Direction,
     pub score: u32,
-    pub latest_input: Option<Direction>,
+    // snakes that ran into this one this round
+    pub kills: u32,
+    // longest the snake has been this round
+    pub peak_length: u32,
+    // turns waiting to be made, oldest first; each tick makes one
+    pub inputs: VecDeque<Direction>,
     pub dead: bool,
+    // false while the player's connection is down; the snake stays frozen until they rejoin,
+    // and is taken off the board if they don't
+    pub connected: bool,
+    pub effects: Vec<ActiveEffect>,
+    // the player holds boost: the snake moves twice per tick and sheds its tail now and then
+    pub boosting: bool,
+    // set in team rooms; teammates share a score and win together
+    pub team: Option<u8>,
+    // the tick a dead snake comes back on, when the server lets snakes respawn
+    pub respawn_tick: Option<u64>,
+    // lives left, when the room plays with lives
+    pub lives: Option<u8>,
+    // which of the MAX_PLAYERS colors the snake is drawn in, distinct within a match
+    pub color: u8,
+    // round trip time the player's client last measured, for the scoreboard
+    pub ping_ms: Option<u64>,
+    // the name is a registered account its player logged in to
+    pub verified: bool,
+    // that account, which stats and ratings are kept under; only the server knows it
+    #[serde(skip)]
+    pub account: Option<String>,
+}
+
+impl PlayerState {
+    pub fn has_effect(&self, kind: PowerUpKind) -> bool {
+        self.effects.iter().any(|e| e.kind == kind)
+    }
+
+    // Lines up a turn behind the ones already waiting, so two presses within one tick both
+    // count. Pressing the way the snake will already be going changes nothing and is dropped.
+    pub fn queue_input(&mut self, dir: Direction) {
+        let heading = self.inputs.back().copied().unwrap_or(self.dir);
+        if dir != heading && self.inputs.len() < MAX_QUEUED_INPUTS {
+            self.inputs.push_back(dir);
+        }
+    }
 }
 
 impl Default for PlayerState {
     fn default() -> Self {
         PlayerState {
+            id: 0,
             name: "".to_string(),
             snake: vec![Pos{ x: 0, y: 0}],
             dir: Default::default(),
             score: 0,
-            latest_input: None,
+            kills: 0,
+            peak_length: 0,
+            inputs: VecDeque::new(),
             dead: false,
+            connected: true,
+            effects: Vec::new(),
+            boosting: false,
+            team: None,
+            respawn_tick: None,
+            lives: None,
+            color: 0,
+            ping_ms: None,
+            verified: false,
+            account: None,
         }
     }
 }
 
-impl Default for Direction {
-    fn default() -> Self { Direction::Right }
-}
-
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct StateMsg {
+    // grows with every snapshot of a match; anything not newer than the last one seen is stale
+    pub seq: u64,
+    // seq of the receiving client's last applied Input
+    pub ack: u32,
     pub tick: u64,
+    // current length of a tick; it shrinks as the round goes on if the server speeds up
+    pub tick_ms: u64,
     pub players: Vec<PlayerState>,
-    pub food: Pos,
-    pub game_over: bool,
-    pub winner: Option<u8>,
+    pub food_items: Vec<FoodItem>,
+    // wall cells, fixed for the round; running into one kills
+    pub obstacles: Vec<Pos>,
+    pub power_ups: Vec<PowerUp>,
+    // game time left on a timed round's clock
+    pub time_left_ms: Option<u64>,
+    pub phase: MatchPhase,
+    // how long the countdown still runs, during the countdown
+    pub countdown_ms: Option<u64>,
+    pub winner: Option<PlayerId>,
+    // where the series stands, when the room plays best-of-N series
+    pub series: Option<SeriesScore>,
+}
+
+impl StateMsg {
+    // The round is decided and waits for a rematch
+    pub fn is_over(&self) -> bool {
+        self.phase == MatchPhase::Finished
+    }
+
+    pub fn player(&self, id: PlayerId) -> Option<&PlayerState> {
+        self.players.iter().find(|p| p.id == id)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum RoundEndReason {
+    // every other snake died
+    LastStanding,
+    // the winner reached the room's score target
+    ScoreTarget,
+    // the clock ran out, or every snake died where that doesn't end a round by itself,
+    // and the winner had the most points
+    HighestScore,
+    // the winner outlived the others it was tied with
+    SuddenDeath,
+    // the last snakes died on the same tick, or a solo snake died
+    Draw,
+}
+
+// Sent once when a round is decided; the final State follows in the Finished phase
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoundEndMsg {
+    pub winner: Option<PlayerId>,
+    pub reason: RoundEndReason,
+    // set instead of `winner` when a team takes the round
+    pub winning_team: Option<u8>,
+    // every player, best score first
+    pub standings: Vec<Standing>,
+    // game time the round took; pauses don't count
+    pub duration_ms: u64,
+    // the series with this round counted
+    pub series: Option<SeriesScore>,
+}
+
+// A best-of-N series: rounds are played until a player, or a team, has won more than half
+// of them. Drawn rounds don't count for anybody.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct SeriesScore {
+    pub best_of: u32,
+    // the round being played, from 1
+    pub round: u32,
+    // rounds won so far, by player; a team's win counts for each of its players
+    pub wins: Vec<(PlayerId, u32)>,
+    // whoever took the series, once it's decided: one player, or a whole team
+    pub champions: Vec<PlayerId>,
+}
+
+impl SeriesScore {
+    pub fn new(best_of: u32) -> Self {
+        SeriesScore { best_of, round: 1, wins: Vec::new(), champions: Vec::new() }
+    }
+
+    // Round wins it takes to take the series
+    pub fn needed(&self) -> u32 {
+        self.best_of / 2 + 1
+    }
+
+    pub fn wins_of(&self, player: PlayerId) -> u32 {
+        self.wins.iter().find(|(p, _)| *p == player).map_or(0, |(_, wins)| *wins)
+    }
+
+    pub fn is_over(&self) -> bool {
+        !self.champions.is_empty()
+    }
+
+    // Counts a round for the players that won it, and crowns them if that was enough
+    pub fn add_win(&mut self, winners: &[PlayerId]) {
+        for &player in winners {
+            match self.wins.iter_mut().find(|(p, _)| *p == player) {
+                Some((_, wins)) => *wins += 1,
+                None => self.wins.push((player, 1)),
+            }
+        }
+        let needed = self.needed();
+        if winners.iter().any(|&p| self.wins_of(p) >= needed) {
+            self.champions = winners.to_vec();
+        }
+    }
+}
+
+// How one player finished a round
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Standing {
+    pub player: PlayerId,
+    pub name: String,
+    pub team: Option<u8>,
+    pub score: u32,
+    pub kills: u32,
+    pub peak_length: u32,
+    // for players logged in to an account: their rating after the round, and how much the
+    // round moved it
+    pub rating: Option<i32>,
+    pub rating_change: Option<i32>,
+}
+
+// A snake died. `killer` is whose body it ran into, if anybody's.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct KillEvent {
+    pub victim: PlayerId,
+    pub killer: Option<PlayerId>,
+}
+
+// Something that happened during a tick, sent ahead of the tick's snapshot so clients
+// don't have to work it out from consecutive states
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum GameEvent {
+    Eat { player: PlayerId, pos: Pos, kind: FoodKind },
+    Death(KillEvent),
+    PowerUp { player: PlayerId, pos: Pos, kind: PowerUpKind },
+}
+
+// Canned messages players can send each other mid-match
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Emote {
+    Gg,
+    Oops,
+    Taunt,
+}
+
+impl Emote {
+    pub const ALL: [Emote; 3] = [Emote::Gg, Emote::Oops, Emote::Taunt];
+}
+
+// Someone sent an emote
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct EmoteMsg {
+    pub player: PlayerId,
+    pub emote: Emote,
+}
+
+// How a player waiting for matchmaking is doing, sent every second until a match is found
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueMsg {
+    // players in the queue, this one included
+    pub waiting: u32,
+    pub waited_secs: u64,
+}
+
+// How many players asked for a rematch after the round ended
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RematchMsg {
+    pub votes: u8,
+    pub needed: u8,
+}
+
+// Sent whenever a match is paused or resumed, or the votes to do so change
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PauseMsg {
+    pub paused: bool,
+    // dropped players the match waits for; it resumes once they are back or gone
+    pub waiting_for: Vec<String>,
+    // players asking to flip the pause, and how many it takes
+    pub votes: u8,
+    pub needed: u8,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LobbyPlayer {
+    pub id: PlayerId,
+    pub name: String,
+    pub ready: bool,
+    pub team: Option<u8>,
+}
+
+// Sent by the server while players gather before a match
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LobbyMsg {
+    pub room_code: String,
+    pub players: Vec<LobbyPlayer>,
+    pub max_players: u8,
+    pub countdown: Option<u32>,
+    // number of teams to pick from; 0 when every snake is on its own
+    pub teams: u8,
+}
+
+// Handed out on Join; presenting it again after a dropped connection reclaims the same snake
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct SessionToken(pub String);
+
+// Handed out on logging in to an account; logging in with it again skips the password
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct AccountToken(pub String);
+
+// What a Login proves the account with
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Credential {
+    Password(String),
+    Token(AccountToken),
+}
+
+// Sent once a Register or Login went through
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AccountMsg {
+    pub name: String,
+    pub token: AccountToken,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionMsg {
+    pub room_code: String,
+    pub token: SessionToken,
+    // what the player is called in the room, which differs from the name they joined with
+    // when someone there had it already
+    pub name: String,
+    // the player's id in the room, the one their snake has on the board
+    pub player_id: PlayerId,
+}
+
+// One entry of the server's room list
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoomInfo {
+    pub code: String,
+    pub name: String,
+    pub players: u8,
+    pub capacity: u8,
+    // Lobby while the room gathers players, Playing once its match started
+    pub phase: MatchPhase,
+    pub mode: GameMode,
+    // joining takes the room's password
+    #[serde(default)]
+    pub locked: bool,
+}
+
+// What the server has counted for a player name over every round played on it
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct PlayerStats {
+    pub name: String,
+    // rounds played to the end
+    pub games: u32,
+    pub wins: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub food_eaten: u32,
+    // game time spent with a snake on the board
+    pub time_alive_ms: u64,
+    // Elo rating, from the first round played logged in to the name's account
+    #[serde(default)]
+    pub rating: Option<i32>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ErrorCode {
+    RoomFull,
+    NoSuchRoom,
+    NameTaken,
+    BadRequest,
+    // a wrong password or token
+    AuthFailed,
+    // the name belongs to an account, or the server only lets registered players in
+    AuthRequired,
+    // the room is private and the password was missing or didn't match
+    WrongPassword,
+}
+
+// Something the client asked for was refused; `message` is meant for the player
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ErrorMsg {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+// How frames are encoded on a connection, proposed by the client's Hello
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+pub enum WireFormat {
+    #[default]
+    Json,
+    Binary,
+}
+
+// How the server compresses the frames it sends, proposed by the client's Hello; see codec::pack
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+pub enum Compression {
+    #[default]
+    None,
+    Deflate,
+    Lz4,
+}
+
+// Everything the server sends. The first message on a connection is always a Json line answering
+// the client's Hello: a Welcome, or a VersionMismatch before the server hangs up. Those two keep
+// their shape across protocol versions so any build can read them. `seed` is what the server's
+// rooms seed their matches from, each mixed with its room code by sim::room_seed. Unless the
+// Welcome's `compression` is None, every frame after it comes packed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ServerMsg {
+    Welcome { protocol_version: u32, format: WireFormat, #[serde(default)] seed: u64, #[serde(default)] compression: Compression },
+    VersionMismatch { server_version: u32 },
+    // answers a request for UDP: sending the ticket's 8 little-endian bytes to the server's UDP port
+    // from the client's socket makes State messages arrive there instead
+    Udp { ticket: u64 },
+    Config(GameConfig),
+    Map(BoardMap),
+    Session(SessionMsg),
+    Account(AccountMsg),
+    Lobby(LobbyMsg),
+    State(StateMsg),
+    RoomList(Vec<RoomInfo>),
+    Stats(PlayerStats),
+    // the rated players with the best ratings, best first
+    Leaderboard(Vec<PlayerStats>),
+    RoundEnd(RoundEndMsg),
+    Rematch(RematchMsg),
+    Queue(QueueMsg),
+    Pause(PauseMsg),
+    // what happened in the tick whose State comes next, when anything did
+    Events(Vec<GameEvent>),
+    Emote(EmoteMsg),
+    Error(ErrorMsg),
+    // news for the player, like someone joining or leaving
+    Info(String),
+    // the server is about to close the connection for good
+    Kick { reason: String },
+    // echoes a Ping right away
+    Pong { sent_ms: u64 },
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ClientMsg {
+    // always the first message, as a Json line; proposes the format for everything after it,
+    // `udp` asks for snapshots by datagram and `compression` for what the server sends to be packed
+    Hello { protocol_version: u32, #[serde(default)] format: WireFormat, #[serde(default)] udp: bool, #[serde(default)] compression: Compression },
+    // Register and Login come before Join, only before a room is picked. A name that has an
+    // account can only be played by someone logged in to it.
+    Register { name: String, password: String },
+    Login { name: String, credential: Credential },
     Join { name: String },
-    Input { dir: Direction },
+    Rejoin { token: SessionToken },
+    // pick a room after Join/Rejoin; nothing else is handled until one is chosen.
+    // A new room plays `mode` and wraps around by `wrap`, or the server's defaults without them;
+    // with a password, only those who give it can join.
+    CreateRoom { mode: Option<GameMode>, wrap: Option<bool>, #[serde(default)] password: Option<String> },
+    JoinRoom { code: String, #[serde(default)] password: Option<String> },
+    // wait for matchmaking to pick a room instead, with players close in rating and ping;
+    // once it has, the player is in it as if they'd joined it and readied up
+    QueueForMatch,
+    // answered with a RoomList, only before a room is picked
+    ListRooms,
+    // answered with the Stats kept for `name`, only before a room is picked
+    GetStats { name: String },
+    // answered with the Leaderboard, only before a room is picked
+    GetLeaderboard,
+    Ready { ready: bool },
+    // receive a State only every `every` ticks rather than every one the room sends, to save
+    // bandwidth on a slow link; Events still come every tick
+    StateRate { every: u64 },
+    // move to another team in the lobby, in rooms played in teams
+    PickTeam { team: u8 },
+    // seq counts up per connection; the server ignores inputs older than one it already took
+    Input { dir: Direction, seq: u32 },
+    // held down to move faster at the cost of length
+    Boost { active: bool },
+    // vote to play another round once the current one is over
+    Restart,
+    // vote to pause a running match, or to resume a paused one; the host's vote decides alone
+    Pause,
+    // keeps the connection alive; `sent_ms` is the client's clock and comes back in the Pong.
+    // `rtt_ms` is the round trip the client measured last, shown to the other players.
+    Ping { sent_ms: u64, rtt_ms: Option<u64> },
+    // passed on to everyone in the match
+    Emote { emote: Emote },
+}
+
+impl fmt::Display for Emote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Emote::Gg => "gg",
+            Emote::Oops => "oops!",
+            Emote::Taunt => "catch me!",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+impl fmt::Display for GameMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            GameMode::Classic => "Classic",
+            GameMode::Timed => "Timed",
+            GameMode::ScoreRace => "Score Race",
+            GameMode::LastSnakeStanding => "Last Snake Standing",
+            GameMode::BattleRoyale => "Battle Royale",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Reads the names used in config files, e.g. `score_race`
+impl FromStr for GameMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "classic" => Ok(GameMode::Classic),
+            "timed" => Ok(GameMode::Timed),
+            "score_race" => Ok(GameMode::ScoreRace),
+            "last_snake_standing" => Ok(GameMode::LastSnakeStanding),
+            "battle_royale" => Ok(GameMode::BattleRoyale),
+            _ => Err(format!("unknown game mode: {}", s)),
+        }
+    }
+}
+
+impl Direction {
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
 }
 
 impl fmt::Display for Direction {
@@ -78,17 +877,135 @@ impl fmt::Display for Direction {
 }
 
 
-// Helpers shared by server for wrapping and stepping
-pub fn step_head(mut head: Pos, dir: Direction) -> Pos {
+// Helpers shared by server for wrapping and stepping. With solid borders the head can end
+// up off the board, which is a crash.
+pub fn step_head(mut head: Pos, dir: Direction, config: &GameConfig) -> Pos {
     match dir {
         Direction::Up => head.y -= 1,
         Direction::Down => head.y += 1,
         Direction::Left => head.x -= 1,
         Direction::Right => head.x += 1,
     }
-    if head.x < 0 { head.x = GRID_WIDTH - 1; }
-    else if head.x >= GRID_WIDTH { head.x = 0; }
-    if head.y < 0 { head.y = GRID_HEIGHT - 1; }
-    else if head.y >= GRID_HEIGHT { head.y = 0; }
+    if !config.wrap { return head; }
+    if head.x < 0 { head.x = config.grid_width - 1; }
+    else if head.x >= config.grid_width { head.x = 0; }
+    if head.y < 0 { head.y = config.grid_height - 1; }
+    else if head.y >= config.grid_height { head.y = 0; }
     head
 }
+
+// The way from a cell to the one next to it, across the edge when the board wraps; None for
+// cells that aren't neighbours, like the two ends of a teleporter
+pub fn toward(from: Pos, to: Pos, config: &GameConfig) -> Option<Direction> {
+    let step = |d: i32, size: i32| if config.wrap && d.abs() == size - 1 { -d.signum() } else { d };
+    match (step(to.x - from.x, config.grid_width), step(to.y - from.y, config.grid_height)) {
+        (1, 0) => Some(Direction::Right),
+        (-1, 0) => Some(Direction::Left),
+        (0, 1) => Some(Direction::Down),
+        (0, -1) => Some(Direction::Up),
+        _ => None,
+    }
+}
+
+// Which way a snake actually travels, from its first two segments. `dir` can already point
+// elsewhere before the snake moves; snakes without a neck to tell by fall back to it.
+pub fn heading(snake: &[Pos], dir: Direction, config: &GameConfig) -> Direction {
+    let (Some(&head), Some(&neck)) = (snake.first(), snake.get(1)) else { return dir };
+    toward(neck, head, config).unwrap_or(dir)
+}
+
+// Whether a snake may turn `to`: anywhere but straight back into its own neck
+pub fn can_turn(snake: &[Pos], dir: Direction, to: Direction, config: &GameConfig) -> bool {
+    to != heading(snake, dir, config).opposite()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server_addresses_parse_with_a_default_port() {
+        let parsed = |addr: &str| ServerAddr::parse(addr).map(|a| (a.secure, a.host, a.port));
+        assert_eq!(parsed("example.com"), Ok((false, "example.com".to_string(), DEFAULT_PORT)));
+        assert_eq!(parsed(" 10.0.0.2:5000 "), Ok((false, "10.0.0.2".to_string(), 5000)));
+        assert_eq!(parsed("snakes://play.example.com:443"), Ok((true, "play.example.com".to_string(), 443)));
+        assert_eq!(parsed("[::1]:4001"), Ok((false, "::1".to_string(), 4001)));
+        assert_eq!(parsed("[fe80::1]"), Ok((false, "fe80::1".to_string(), DEFAULT_PORT)));
+        assert_eq!(parsed("::1"), Ok((false, "::1".to_string(), DEFAULT_PORT)));
+        for bad in ["", "snakes://", ":4000", "host:port", "host:0", "host:70000", "[::1", "[::1]4000", "[nope]:1", "bad host:1"] {
+            assert!(ServerAddr::parse(bad).is_err(), "{:?} parsed", bad);
+        }
+        assert_eq!(ServerAddr::parse("[::1]").unwrap().to_string(), "[::1]:4000");
+        assert_eq!(ServerAddr::parse("snakes://a.b").unwrap().to_string(), "snakes://a.b:4000");
+    }
+
+    #[test]
+    fn turns_follow_where_the_snake_goes() {
+        let config = GameConfig::default();
+        let right = [Pos { x: 5, y: 5 }, Pos { x: 4, y: 5 }, Pos { x: 3, y: 5 }];
+        // turned up, but hasn't moved yet: going left would still fold it over
+        assert!(!can_turn(&right, Direction::Up, Direction::Left, &config));
+        assert!(can_turn(&right, Direction::Up, Direction::Down, &config));
+        assert!(can_turn(&right, Direction::Right, Direction::Up, &config));
+
+        // across the edge of a board that wraps
+        let wrapped = [Pos { x: 0, y: 5 }, Pos { x: config.grid_width - 1, y: 5 }];
+        assert_eq!(heading(&wrapped, Direction::Up, &config), Direction::Right);
+        let solid = GameConfig { wrap: false, ..config };
+        assert_eq!(heading(&wrapped, Direction::Up, &solid), Direction::Up);
+
+        // out of a teleporter, or just spawned on one cell, there's only `dir` to go by
+        let teleported = [Pos { x: 20, y: 5 }, Pos { x: 3, y: 5 }];
+        assert_eq!(heading(&teleported, Direction::Down, &config), Direction::Down);
+        let spawned = [Pos { x: 7, y: 7 }; 3];
+        assert!(!can_turn(&spawned, Direction::Left, Direction::Right, &config));
+    }
+
+    #[test]
+    fn names_are_checked() {
+        assert!(check_name("alice").is_ok());
+        assert!(check_name("Bot-1").is_ok());
+        assert!(check_name("Zoë O'Neil").is_ok());
+        assert!(check_name("").is_err());
+        assert!(check_name("   ").is_err());
+        assert!(check_name(" bob").is_err());
+        assert!(check_name("bob\u{200b}").is_err());
+        assert!(check_name("<script>").is_err());
+        assert!(check_name(&"a".repeat(MAX_NAME_LEN)).is_ok());
+        assert!(check_name(&"a".repeat(MAX_NAME_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn names_are_tidied_and_kept_apart() {
+        assert_eq!(normalize_name("  bob \t the   snake "), "bob the snake");
+        assert!(check_name(&normalize_name("   ")).is_err());
+
+        let taken = ["alice", "Bob", "bob (2)"];
+        assert_eq!(unique_name("carol", taken.iter().copied()), "carol");
+        assert_eq!(unique_name("bob", taken.iter().copied()), "bob (3)");
+        assert_eq!(unique_name("ALICE", taken.iter().copied()), "ALICE (2)");
+        let long = "a".repeat(MAX_NAME_LEN);
+        let renamed = unique_name(&long, [long.as_str()].into_iter());
+        assert_eq!(renamed, format!("{} (2)", "a".repeat(MAX_NAME_LEN - 4)));
+    }
+
+    #[test]
+    fn quick_presses_queue_up() {
+        let mut player = PlayerState { dir: Direction::Right, ..Default::default() };
+        player.queue_input(Direction::Right);
+        assert!(player.inputs.is_empty());
+        player.queue_input(Direction::Up);
+        player.queue_input(Direction::Up);
+        player.queue_input(Direction::Left);
+        assert_eq!(player.inputs, [Direction::Up, Direction::Left]);
+        player.queue_input(Direction::Down);
+        player.queue_input(Direction::Right);
+        assert_eq!(player.inputs.len(), MAX_QUEUED_INPUTS);
+    }
+
+    #[test]
+    fn secure_addresses_are_told_apart() {
+        assert_eq!(strip_scheme("snakes://example.com:4000"), (true, "example.com:4000"));
+        assert_eq!(strip_scheme("127.0.0.1:4000"), (false, "127.0.0.1:4000"));
+    }
+}
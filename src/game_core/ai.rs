@@ -0,0 +1,284 @@
+// Bots: snakes steered by the program instead of a player. Each tick a bot looks at the
+// match and picks the way it turns, which goes in as its input like a player's would.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::str::FromStr;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use super::sim::GameState;
+use super::{heading, step_head, Direction, FoodKind, Pos};
+
+// How well bots play
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BotDifficulty {
+    // wanders about, only steering clear of what is right in front of it
+    Random,
+    // heads straight for the nearest food
+    #[default]
+    Greedy,
+    // finds its way to the nearest food around whatever is in the way
+    Pathfinding,
+    // like Pathfinding, but only takes food it can get away from again, keeps clear of
+    // other heads and follows its own tail when there is nothing safe to go for
+    Hard,
+}
+
+// Reads the names used in config files
+impl FromStr for BotDifficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(BotDifficulty::Random),
+            "greedy" => Ok(BotDifficulty::Greedy),
+            "pathfinding" => Ok(BotDifficulty::Pathfinding),
+            "hard" => Ok(BotDifficulty::Hard),
+            _ => Err(format!("unknown bot difficulty: {}", s)),
+        }
+    }
+}
+
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+pub fn bot_name(n: usize) -> String {
+    format!("Bot-{}", n)
+}
+
+// Where a head at `from` ends up going `dir`, through teleporters; None off a board
+// without wrap-around
+fn next_cell(state: &GameState, from: Pos, dir: Direction) -> Option<Pos> {
+    let game = &state.config.game;
+    let pos = step_head(from, dir, game);
+    if !(0..game.grid_width).contains(&pos.x) || !(0..game.grid_height).contains(&pos.y) {
+        return None;
+    }
+    Some(state.config.map.as_ref().map_or(pos, |map| map.teleport(pos)))
+}
+
+// Walls and the other snakes
+fn others(state: &GameState, slot: usize) -> HashSet<Pos> {
+    let mut blocked: HashSet<Pos> = state.obstacles.iter().copied().collect();
+    for (_, player) in state.players.iter().enumerate().filter(|(i, _)| *i != slot) {
+        blocked.extend(player.snake.iter().copied());
+    }
+    blocked
+}
+
+// Cells a head can't go into: walls, and every snake but the tail of `body`, which
+// moves out of the way
+fn with_body(mut blocked: HashSet<Pos>, body: &[Pos]) -> HashSet<Pos> {
+    blocked.extend(body[..body.len().saturating_sub(1)].iter().copied());
+    blocked
+}
+
+// Steps between two cells, the short way round on a board that wraps
+fn distance(state: &GameState, a: Pos, b: Pos) -> i32 {
+    let game = &state.config.game;
+    let (dx, dy) = ((a.x - b.x).abs(), (a.y - b.y).abs());
+    if game.wrap {
+        dx.min(game.grid_width - dx) + dy.min(game.grid_height - dy)
+    } else {
+        dx + dy
+    }
+}
+
+// Food worth going for; poison isn't
+fn targets(state: &GameState) -> HashSet<Pos> {
+    state.food.iter().filter(|f| f.kind != FoodKind::Poison).map(|f| f.pos).collect()
+}
+
+// The shortest way from a head at `from`, going `facing`, to the nearest of `goals`
+// around `blocked`, by A*. The steps along it, first step first.
+fn find_path(state: &GameState, from: Pos, facing: Direction, goals: &HashSet<Pos>, blocked: &HashSet<Pos>) -> Option<Vec<(Direction, Pos)>> {
+    let estimate = |pos: Pos| goals.iter().map(|goal| distance(state, pos, *goal)).min();
+    let mut open = BinaryHeap::from([Reverse((estimate(from)?, 0, from.x, from.y))]);
+    let mut cost = HashMap::from([(from, 0)]);
+    let mut came_from: HashMap<Pos, (Pos, Direction)> = HashMap::new();
+    while let Some(Reverse((_, steps, x, y))) = open.pop() {
+        let pos = Pos { x, y };
+        if steps > 0 && goals.contains(&pos) {
+            let mut path = vec![];
+            let mut at = pos;
+            while let Some(&(prev, dir)) = came_from.get(&at) {
+                path.push((dir, at));
+                at = prev;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if cost.get(&pos).is_some_and(|best| *best < steps) {
+            continue;
+        }
+        for dir in DIRECTIONS {
+            // a snake can't turn right round
+            if pos == from && dir == facing.opposite() {
+                continue;
+            }
+            let Some(next) = next_cell(state, pos, dir).filter(|p| !blocked.contains(p) && *p != from) else { continue };
+            if cost.get(&next).is_none_or(|best| steps + 1 < *best) {
+                cost.insert(next, steps + 1);
+                came_from.insert(next, (pos, dir));
+                open.push(Reverse((steps + 1 + estimate(next).unwrap_or(0), steps + 1, next.x, next.y)));
+            }
+        }
+    }
+    None
+}
+
+// Whether a snake that followed `path` and ate at its end could still reach its own
+// tail, so it doesn't shut itself in getting there
+fn escapes_after(state: &GameState, walls: &HashSet<Pos>, snake: &[Pos], path: &[(Direction, Pos)]) -> bool {
+    let body: Vec<Pos> = path.iter().rev().map(|(_, pos)| *pos).chain(snake.iter().copied()).take(snake.len() + 1).collect();
+    let (Some(&head), Some(&tail), Some(&(facing, _))) = (body.first(), body.last(), path.last()) else { return false };
+    find_path(state, head, facing, &HashSet::from([tail]), &with_body(walls.clone(), &body)).is_some()
+}
+
+// How many cells can be reached from `from`
+fn room(state: &GameState, from: Pos, blocked: &HashSet<Pos>) -> usize {
+    let mut seen = HashSet::from([from]);
+    let mut stack = vec![from];
+    while let Some(pos) = stack.pop() {
+        for dir in DIRECTIONS {
+            if let Some(next) = next_cell(state, pos, dir).filter(|p| !blocked.contains(p) && seen.insert(*p)) {
+                stack.push(next);
+            }
+        }
+    }
+    seen.len()
+}
+
+fn hard(state: &GameState, slot: usize, safe: &[(Direction, Pos)]) -> Option<Direction> {
+    let player = &state.players[slot];
+    let walls = others(state, slot);
+    let blocked = with_body(walls.clone(), &player.snake);
+    // another head could move into these this tick, which kills both
+    let contested: HashSet<Pos> = state.players.iter().enumerate()
+        .filter(|(i, p)| *i != slot && !p.dead)
+        .filter_map(|(_, p)| p.snake.first())
+        .flat_map(|head| DIRECTIONS.iter().filter_map(|dir| next_cell(state, *head, *dir)))
+        .collect();
+    let calm = |path: &[(Direction, Pos)]| path.first().filter(|(_, pos)| !contested.contains(pos)).map(|(dir, _)| *dir);
+    let facing = heading(&player.snake, player.dir, &state.config.game);
+
+    let food = find_path(state, player.snake[0], facing, &targets(state), &blocked)
+        .filter(|path| escapes_after(state, &walls, &player.snake, path));
+    if let Some(dir) = food.as_deref().and_then(calm) {
+        return Some(dir);
+    }
+    // nothing safe to eat: going after its own tail always leaves it a way out
+    if let Some(&tail) = player.snake.last().filter(|_| player.snake.len() > 1)
+        && let Some(dir) = find_path(state, player.snake[0], facing, &HashSet::from([tail]), &blocked).as_deref().and_then(calm)
+    {
+        return Some(dir);
+    }
+    // otherwise wherever leaves the most room, away from other heads if it can
+    safe.iter()
+        .max_by_key(|(_, pos)| (!contested.contains(pos), room(state, *pos, &blocked)))
+        .map(|(dir, _)| *dir)
+}
+
+// Which way the bot in `slot` turns this tick; None carries on as it goes
+pub fn choose(state: &GameState, slot: usize, difficulty: BotDifficulty) -> Option<Direction> {
+    let player = &state.players[slot];
+    let head = *player.snake.first().filter(|_| !player.dead)?;
+    let blocked = with_body(others(state, slot), &player.snake);
+    let facing = heading(&player.snake, player.dir, &state.config.game);
+    let safe: Vec<(Direction, Pos)> = DIRECTIONS.iter()
+        .filter(|dir| **dir != facing.opposite())
+        .filter_map(|dir| next_cell(state, head, *dir).filter(|p| !blocked.contains(p)).map(|p| (*dir, p)))
+        .collect();
+    let food = targets(state);
+    let greedy = || {
+        safe.iter()
+            .min_by_key(|(_, pos)| food.iter().map(|f| distance(state, *pos, *f)).min().unwrap_or(0))
+            .map(|(dir, _)| *dir)
+    };
+    match difficulty {
+        BotDifficulty::Random => {
+            let mut rng = rand::thread_rng();
+            let keeps_going = safe.iter().any(|(dir, _)| *dir == player.dir);
+            if keeps_going && rng.gen_range(0..4) != 0 {
+                return None;
+            }
+            safe.choose(&mut rng).map(|(dir, _)| *dir)
+        }
+        BotDifficulty::Greedy => greedy(),
+        BotDifficulty::Pathfinding => find_path(state, head, facing, &food, &blocked)
+            .and_then(|path| path.first().map(|(dir, _)| *dir))
+            .or_else(greedy),
+        BotDifficulty::Hard => hard(state, slot, &safe),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_core::sim::MatchConfig;
+    use crate::game_core::{FoodItem, PlayerState};
+
+    fn cells(cells: &[(i32, i32)]) -> Vec<Pos> {
+        cells.iter().map(|&(x, y)| Pos { x, y }).collect()
+    }
+
+    fn state_with(snake: &[(i32, i32)], dir: Direction, food: (i32, i32)) -> GameState {
+        let mut state = GameState::new(&["Bot-1".to_string()], MatchConfig::default());
+        state.players[0] = PlayerState { snake: cells(snake), dir, ..Default::default() };
+        state.food = vec![FoodItem { pos: Pos { x: food.0, y: food.1 }, kind: FoodKind::Normal, expires_tick: None }];
+        state.obstacles.clear();
+        state
+    }
+
+    #[test]
+    fn greedy_turns_towards_food() {
+        let state = state_with(&[(5, 5), (4, 5)], Direction::Right, (5, 1));
+        assert_eq!(choose(&state, 0, BotDifficulty::Greedy), Some(Direction::Up));
+    }
+
+    #[test]
+    fn pathfinding_goes_around_walls() {
+        // food straight ahead behind a wall; the way round starts by turning
+        let mut state = state_with(&[(5, 5), (4, 5)], Direction::Right, (8, 5));
+        state.obstacles = (3..8).map(|y| Pos { x: 6, y }).collect();
+        let dir = choose(&state, 0, BotDifficulty::Pathfinding);
+        assert!(matches!(dir, Some(Direction::Up) | Some(Direction::Down)));
+    }
+
+    #[test]
+    fn never_picks_a_deadly_move_when_there_is_another() {
+        let mut state = state_with(&[(5, 5), (4, 5)], Direction::Right, (9, 5));
+        state.obstacles = vec![Pos { x: 6, y: 5 }, Pos { x: 5, y: 4 }];
+        for difficulty in [BotDifficulty::Random, BotDifficulty::Greedy, BotDifficulty::Pathfinding, BotDifficulty::Hard] {
+            assert_eq!(choose(&state, 0, difficulty), Some(Direction::Down));
+        }
+    }
+
+    #[test]
+    fn paths_never_start_by_turning_around() {
+        // the food is right behind the head, past the tail
+        let state = state_with(&[(5, 5), (4, 5)], Direction::Right, (3, 5));
+        let path = find_path(&state, Pos { x: 5, y: 5 }, Direction::Right, &targets(&state), &HashSet::new()).unwrap();
+        assert_ne!(path[0].0, Direction::Left);
+    }
+
+    #[test]
+    fn hard_skips_food_in_a_dead_end() {
+        // the food sits at the closed end of a one-cell pocket the snake wouldn't get out of
+        let mut state = state_with(&[(5, 5), (4, 5), (3, 5), (2, 5)], Direction::Right, (7, 2));
+        state.config.game.wrap = false;
+        state.obstacles = cells(&[(6, 2), (6, 3), (6, 4), (8, 2), (8, 3), (8, 4), (7, 1)]);
+        assert_eq!(choose(&state, 0, BotDifficulty::Pathfinding), Some(Direction::Right));
+        assert_ne!(choose(&state, 0, BotDifficulty::Hard), Some(Direction::Right));
+    }
+
+    #[test]
+    fn hard_keeps_clear_of_other_heads() {
+        // food straight ahead, with another snake's head next to the cell in between
+        let mut state = state_with(&[(5, 5), (4, 5)], Direction::Right, (8, 5));
+        state.players.push(PlayerState { snake: cells(&[(6, 4), (6, 3)]), dir: Direction::Down, ..Default::default() });
+        assert_eq!(choose(&state, 0, BotDifficulty::Greedy), Some(Direction::Right));
+        assert_ne!(choose(&state, 0, BotDifficulty::Hard), Some(Direction::Right));
+    }
+}
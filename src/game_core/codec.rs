@@ -0,0 +1,307 @@
+// Wire encoding shared by server and client.
+//
+// Json frames are one JSON document per line, handy for debugging with netcat.
+// Binary frames are a little-endian u32 length followed by the message in postcard: varint
+// integers, length-prefixed strings/sequences, enum variants by index and struct fields in
+// declaration order (so both sides must run the same build).
+//
+// When the handshake agreed on compression, the server's frames travel packed: a little-endian
+// u32 length, a byte telling how the rest is packed, then a frame in the connection's format.
+
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::{Compression, WireFormat};
+
+// Refuse absurd frames instead of allocating whatever a peer claims
+pub const MAX_FRAME_LEN: usize = 1 << 20;
+
+// How a packed frame's contents are stored
+const STORED: u8 = 0;
+const DEFLATE: u8 = 1;
+const LZ4: u8 = 2;
+// Frames shorter than this gain too little to be worth compressing
+const COMPRESS_MIN_LEN: usize = 128;
+
+#[derive(Debug)]
+pub struct CodecError(String);
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+pub fn encode<T: Serialize>(format: WireFormat, msg: &T) -> Result<Vec<u8>, CodecError> {
+    match format {
+        WireFormat::Json => {
+            let mut out = serde_json::to_vec(msg).map_err(|e| CodecError(e.to_string()))?;
+            out.push(b'\n');
+            Ok(out)
+        }
+        WireFormat::Binary => {
+            let body = postcard::to_allocvec(msg).map_err(|e| CodecError(e.to_string()))?;
+            let mut out = Vec::with_capacity(4 + body.len());
+            out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            out.extend_from_slice(&body);
+            Ok(out)
+        }
+    }
+}
+
+pub fn decode<T: DeserializeOwned>(format: WireFormat, frame: &[u8]) -> Result<T, CodecError> {
+    match format {
+        WireFormat::Json => serde_json::from_slice(frame).map_err(|e| CodecError(e.to_string())),
+        WireFormat::Binary => {
+            let (value, rest) = postcard::take_from_bytes(frame).map_err(|e| CodecError(e.to_string()))?;
+            if !rest.is_empty() {
+                return Err(CodecError("trailing bytes in frame".into()));
+            }
+            Ok(value)
+        }
+    }
+}
+
+pub fn write_msg<W: Write, T: Serialize>(w: &mut W, format: WireFormat, msg: &T) -> io::Result<()> {
+    let bytes = encode(format, msg).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    w.write_all(&bytes)?;
+    w.flush()
+}
+
+// Reads the next frame payload; Ok(None) means the peer closed the connection.
+// Blank JSON lines are skipped.
+pub fn read_frame<R: BufRead>(r: &mut R, format: WireFormat) -> io::Result<Option<Vec<u8>>> {
+    read_frame_limited(r, format, MAX_FRAME_LEN)
+}
+
+// Like read_frame, for peers that have no business sending frames of more than `max_len`
+// bytes; a JSON line counts its newline
+pub fn read_frame_limited<R: BufRead>(r: &mut R, format: WireFormat, max_len: usize) -> io::Result<Option<Vec<u8>>> {
+    let too_large = || io::Error::new(io::ErrorKind::InvalidData, "frame too large");
+    match format {
+        WireFormat::Json => loop {
+            let mut line = Vec::new();
+            // reading one byte past the limit is enough to tell the line is too long
+            if (&mut *r).take(max_len as u64 + 1).read_until(b'\n', &mut line)? == 0 {
+                return Ok(None);
+            }
+            if line.len() > max_len {
+                return Err(too_large());
+            }
+            while line.last().is_some_and(|b| b.is_ascii_whitespace()) {
+                line.pop();
+            }
+            if !line.is_empty() {
+                return Ok(Some(line));
+            }
+        },
+        WireFormat::Binary => {
+            let mut len = [0u8; 4];
+            match r.read_exact(&mut len) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e),
+            }
+            let len = u32::from_le_bytes(len) as usize;
+            if len > max_len {
+                return Err(too_large());
+            }
+            let mut payload = vec![0u8; len];
+            r.read_exact(&mut payload)?;
+            Ok(Some(payload))
+        }
+    }
+}
+
+// Splits one complete frame off the front of a buffer, for transports that hand over
+// arbitrary chunks of the byte stream. Ok(None) means more bytes are needed.
+pub fn take_frame(buf: &mut Vec<u8>, format: WireFormat) -> Result<Option<Vec<u8>>, CodecError> {
+    match format {
+        WireFormat::Json => loop {
+            let Some(end) = buf.iter().position(|b| *b == b'\n') else {
+                return if buf.len() > MAX_FRAME_LEN { Err(CodecError("frame too large".into())) } else { Ok(None) };
+            };
+            let mut line: Vec<u8> = buf.drain(..=end).collect();
+            while line.last().is_some_and(|b| b.is_ascii_whitespace()) {
+                line.pop();
+            }
+            if !line.is_empty() {
+                return Ok(Some(line));
+            }
+        },
+        WireFormat::Binary => {
+            if buf.len() < 4 {
+                return Ok(None);
+            }
+            let len = u32::from_le_bytes(buf[..4].try_into().unwrap()) as usize;
+            if len > MAX_FRAME_LEN {
+                return Err(CodecError("frame too large".into()));
+            }
+            if buf.len() < 4 + len {
+                return Ok(None);
+            }
+            let frame = buf[4..4 + len].to_vec();
+            buf.drain(..4 + len);
+            Ok(Some(frame))
+        }
+    }
+}
+
+// Packs an encoded frame; one that doesn't shrink is stored as it is
+pub fn pack(compression: Compression, frame: &[u8]) -> Vec<u8> {
+    let compressed = match compression {
+        _ if frame.len() < COMPRESS_MIN_LEN => None,
+        Compression::None => None,
+        Compression::Deflate => {
+            let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::fast());
+            // writing to a Vec can't fail
+            encoder.write_all(frame).unwrap();
+            Some((DEFLATE, encoder.finish().unwrap()))
+        }
+        Compression::Lz4 => Some((LZ4, lz4_flex::compress_prepend_size(frame))),
+    };
+    let mut out = vec![0; 5];
+    match compressed.filter(|(_, body)| body.len() < frame.len()) {
+        Some((method, body)) => {
+            out[4] = method;
+            out.extend_from_slice(&body);
+        }
+        None => {
+            out[4] = STORED;
+            out.extend_from_slice(frame);
+        }
+    }
+    let len = (out.len() - 4) as u32;
+    out[..4].copy_from_slice(&len.to_le_bytes());
+    out
+}
+
+// The payload of the one frame inside a packed one, refusing anything that would unpack
+// to more than a frame may hold
+fn unpack(packed: &[u8], format: WireFormat) -> Result<Vec<u8>, CodecError> {
+    // room for the frame's own length or newline
+    let limit = MAX_FRAME_LEN + 4;
+    let too_large = || CodecError("frame too large".into());
+    let (&method, body) = packed.split_first().ok_or_else(|| CodecError("empty packed frame".into()))?;
+    let mut frame = match method {
+        STORED => body.to_vec(),
+        DEFLATE => {
+            let mut frame = Vec::new();
+            DeflateDecoder::new(body).take(limit as u64 + 1).read_to_end(&mut frame).map_err(|e| CodecError(e.to_string()))?;
+            frame
+        }
+        LZ4 => {
+            let len = body.get(..4).ok_or_else(|| CodecError("truncated lz4 frame".into()))?;
+            if u32::from_le_bytes(len.try_into().unwrap()) as usize > limit {
+                return Err(too_large());
+            }
+            lz4_flex::decompress_size_prepended(body).map_err(|e| CodecError(e.to_string()))?
+        }
+        other => return Err(CodecError(format!("unknown packing {}", other))),
+    };
+    if frame.len() > limit {
+        return Err(too_large());
+    }
+    take_frame(&mut frame, format)?.ok_or_else(|| CodecError("incomplete packed frame".into()))
+}
+
+// read_frame for a connection whose frames come packed with `compression`
+pub fn read_packed_frame<R: BufRead>(r: &mut R, format: WireFormat, compression: Compression) -> io::Result<Option<Vec<u8>>> {
+    if compression == Compression::None {
+        return read_frame(r, format);
+    }
+    let Some(packed) = read_frame(r, WireFormat::Binary)? else { return Ok(None) };
+    unpack(&packed, format).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+// take_frame for a connection whose frames come packed with `compression`
+pub fn take_packed_frame(buf: &mut Vec<u8>, format: WireFormat, compression: Compression) -> Result<Option<Vec<u8>>, CodecError> {
+    if compression == Compression::None {
+        return take_frame(buf, format);
+    }
+    let Some(packed) = take_frame(buf, WireFormat::Binary)? else { return Ok(None) };
+    unpack(&packed, format).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_frames_are_refused() {
+        let mut lines: &[u8] = b"{\"Ping\":1}\n\n0123456789012345\n";
+        assert_eq!(read_frame_limited(&mut lines, WireFormat::Json, 12).unwrap().unwrap(), b"{\"Ping\":1}");
+        assert_eq!(read_frame_limited(&mut lines, WireFormat::Json, 12).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let mut frames: &[u8] = &[3, 0, 0, 0, 1, 2, 3, 40, 0, 0, 0];
+        assert_eq!(read_frame_limited(&mut frames, WireFormat::Binary, 12).unwrap().unwrap(), [1, 2, 3]);
+        assert!(read_frame_limited(&mut frames, WireFormat::Binary, 12).is_err());
+
+        let mut buf = vec![b'x'; MAX_FRAME_LEN + 1];
+        assert!(take_frame(&mut buf, WireFormat::Json).is_err());
+    }
+
+    #[test]
+    fn binary_messages_round_trip() {
+        use crate::game_core::{ClientMsg, ErrorCode, ErrorMsg, GameMode, ServerMsg};
+        let msgs = [
+            ServerMsg::Error(ErrorMsg { code: ErrorCode::WrongPassword, message: "Wrong password for room ABCD".to_string() }),
+            ServerMsg::Pong { sent_ms: 1 << 40 },
+        ];
+        for msg in msgs {
+            let frame = encode(WireFormat::Binary, &msg).unwrap();
+            let payload = read_frame(&mut &frame[..], WireFormat::Binary).unwrap().unwrap();
+            let back: ServerMsg = decode(WireFormat::Binary, &payload).unwrap();
+            assert_eq!(encode(WireFormat::Binary, &back).unwrap(), frame);
+        }
+        let msg = ClientMsg::CreateRoom { mode: Some(GameMode::Timed), wrap: None, password: Some("hunter2".to_string()) };
+        let frame = encode(WireFormat::Binary, &msg).unwrap();
+        let mut payload = frame[4..].to_vec();
+        assert!(matches!(decode(WireFormat::Binary, &payload).unwrap(), ClientMsg::CreateRoom { mode: Some(GameMode::Timed), wrap: None, password: Some(_) }));
+        payload.push(0);
+        assert!(decode::<ClientMsg>(WireFormat::Binary, &payload).is_err());
+    }
+
+    #[test]
+    fn packed_frames_round_trip() {
+        let long: Vec<u32> = (0..500).map(|i| i % 7).collect();
+        for format in [WireFormat::Json, WireFormat::Binary] {
+            for compression in [Compression::Deflate, Compression::Lz4] {
+                let frame = encode(format, &long).unwrap();
+                let packed = pack(compression, &frame);
+                assert!(packed.len() < frame.len() / 2, "{:?} barely shrank", compression);
+                let short = encode(format, &"hi").unwrap();
+                let mut stream = [packed.clone(), pack(compression, &short)].concat();
+
+                let mut reader = &stream[..];
+                let payload = read_packed_frame(&mut reader, format, compression).unwrap().unwrap();
+                assert_eq!(decode::<Vec<u32>>(format, &payload).unwrap(), long);
+                let payload = read_packed_frame(&mut reader, format, compression).unwrap().unwrap();
+                assert_eq!(decode::<String>(format, &payload).unwrap(), "hi");
+                assert!(read_packed_frame(&mut reader, format, compression).unwrap().is_none());
+
+                // chunks arriving one at a time, as over a WebSocket
+                let mut buf = stream[..10].to_vec();
+                assert!(take_packed_frame(&mut buf, format, compression).unwrap().is_none());
+                buf.extend_from_slice(&stream[10..]);
+                assert!(take_packed_frame(&mut buf, format, compression).unwrap().is_some());
+                assert!(take_packed_frame(&mut buf, format, compression).unwrap().is_some());
+                assert!(buf.is_empty());
+
+                // a frame that claims to unpack to more than a frame may hold is refused
+                stream.truncate(packed.len());
+                if compression == Compression::Lz4 {
+                    stream[5..9].copy_from_slice(&u32::MAX.to_le_bytes());
+                    assert!(read_packed_frame(&mut &stream[..], format, compression).is_err());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+// Collision rules for one tick of movement.
+//
+// All snakes move at once. A snake that doesn't eat this tick drags its tail along,
+// so the cell its tail leaves is free to enter (chasing your own or someone else's
+// tail is fine). Bodies of dead snakes stay on the board as walls.
+//
+// - self-collision: the new head lands on the snake's own body
+// - head-to-body: the new head lands on another snake's body
+// - head-to-head: two new heads meet in one cell, or two heads swap cells; both die
+// - simultaneous food: two heads reaching the same food together is a head-to-head, nobody eats
+//
+// `resolve_with` can exempt pairs of snakes from hurting each other, e.g. teammates.
+
+use super::{PlayerState, Pos};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TickOutcome {
+    // per player, true if it dies this tick
+    pub dead: Vec<bool>,
+    // per player, whose body it ran into if that is what killed it
+    pub killers: Vec<Option<usize>>,
+    // the players that eat a food this tick
+    pub eaters: Vec<usize>,
+}
+
+// Whether any snake, alive or dead, covers the cell
+pub fn occupied(players: &[PlayerState], pos: &Pos) -> bool {
+    players.iter().any(|player| player.snake.contains(pos))
+}
+
+// The cells a snake will still cover after everyone moved, not counting its new head
+fn remaining_body(player: &PlayerState, moves: bool, grows: bool) -> &[Pos] {
+    if moves && !grows {
+        &player.snake[..player.snake.len().saturating_sub(1)]
+    } else {
+        &player.snake
+    }
+}
+
+pub fn hits_self(player: &PlayerState, new_head: Pos, grows: bool) -> bool {
+    remaining_body(player, true, grows).contains(&new_head)
+}
+
+pub fn hits_body(other: &PlayerState, new_head: Pos, other_moves: bool, other_grows: bool) -> bool {
+    remaining_body(other, other_moves, other_grows).contains(&new_head)
+}
+
+pub fn head_to_head(a_head: Pos, a_new: Pos, b_head: Pos, b_new: Pos) -> bool {
+    a_new == b_new || (a_new == b_head && b_new == a_head)
+}
+
+// Resolves where every snake ends up. `new_heads[i]` is None for snakes that don't move.
+pub fn resolve(players: &[PlayerState], new_heads: &[Option<Pos>], food: &[Pos]) -> TickOutcome {
+    resolve_with(players, new_heads, food, |_, _| true)
+}
+
+// Like `resolve`, but snake i only dies from running into snake j if `collides(i, j)`
+pub fn resolve_with(players: &[PlayerState], new_heads: &[Option<Pos>], food: &[Pos], collides: impl Fn(usize, usize) -> bool) -> TickOutcome {
+    let grows: Vec<bool> = new_heads.iter().map(|head| head.is_some_and(|h| food.contains(&h))).collect();
+    let mut dead = vec![false; players.len()];
+    let mut killers = vec![None; players.len()];
+
+    for (i, new_head) in new_heads.iter().enumerate() {
+        let Some(new_head) = *new_head else { continue };
+        let head = players[i].snake[0];
+        let mut dies = hits_self(&players[i], new_head, grows[i]);
+        for (j, other) in players.iter().enumerate() {
+            if j == i || !collides(i, j) {
+                continue;
+            }
+            let into_body = match new_heads[j] {
+                Some(other_new) => {
+                    dies |= head_to_head(head, new_head, other.snake[0], other_new);
+                    hits_body(other, new_head, true, grows[j])
+                }
+                None => hits_body(other, new_head, false, false),
+            };
+            if into_body {
+                dies = true;
+                killers[i].get_or_insert(j);
+            }
+        }
+        dead[i] = dies;
+    }
+
+    let eaters = (0..players.len()).filter(|i| grows[*i] && !dead[*i]).collect();
+    TickOutcome { dead, killers, eaters }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_core::Direction;
+
+    fn snake(cells: &[(i32, i32)]) -> PlayerState {
+        PlayerState {
+            snake: cells.iter().map(|&(x, y)| Pos { x, y }).collect(),
+            dir: Direction::Right,
+            ..Default::default()
+        }
+    }
+
+    fn at(x: i32, y: i32) -> Option<Pos> {
+        Some(Pos { x, y })
+    }
+
+    const NO_FOOD: &[Pos] = &[];
+
+    #[test]
+    fn occupied_finds_any_snake_cell() {
+        let players = vec![snake(&[(1, 1), (0, 1)]), snake(&[(5, 5)])];
+        assert!(occupied(&players, &Pos { x: 0, y: 1 }));
+        assert!(occupied(&players, &Pos { x: 5, y: 5 }));
+        assert!(!occupied(&players, &Pos { x: 2, y: 2 }));
+    }
+
+    #[test]
+    fn turning_into_own_body_kills() {
+        // heading left from (2,1) runs into the body at (1,1), which stays put
+        let players = vec![snake(&[(2, 1), (2, 2), (1, 2), (1, 1), (0, 1)])];
+        let out = resolve(&players, &[at(1, 1)], NO_FOOD);
+        assert_eq!(out.dead, vec![true]);
+    }
+
+    #[test]
+    fn chasing_own_tail_is_safe() {
+        let players = vec![snake(&[(2, 1), (2, 2), (1, 2), (1, 1)])];
+        let out = resolve(&players, &[at(1, 1)], NO_FOOD);
+        assert_eq!(out.dead, vec![false]);
+    }
+
+    #[test]
+    fn tail_stays_when_eating() {
+        // the food sits where the tail is about to leave, so the tail doesn't leave
+        let players = vec![snake(&[(2, 1), (2, 2), (1, 2), (1, 1)])];
+        let out = resolve(&players, &[at(1, 1)], &[Pos { x: 1, y: 1 }]);
+        assert_eq!(out.dead, vec![true]);
+        assert!(out.eaters.is_empty());
+    }
+
+    #[test]
+    fn head_into_other_body_kills_only_the_mover() {
+        let players = vec![snake(&[(4, 5), (3, 5)]), snake(&[(5, 7), (5, 6), (5, 5), (5, 4)])];
+        let out = resolve(&players, &[at(5, 5), at(5, 8)], NO_FOOD);
+        assert_eq!(out.dead, vec![true, false]);
+        assert_eq!(out.killers, vec![Some(1), None]);
+    }
+
+    #[test]
+    fn dead_snake_body_is_solid() {
+        let players = vec![snake(&[(4, 5)]), PlayerState { dead: true, ..snake(&[(5, 5), (6, 5)]) }];
+        let out = resolve(&players, &[at(6, 5), None], NO_FOOD);
+        assert_eq!(out.dead, vec![true, false]);
+    }
+
+    #[test]
+    fn heads_meeting_in_one_cell_both_die() {
+        let players = vec![snake(&[(4, 5)]), snake(&[(6, 5)])];
+        let out = resolve(&players, &[at(5, 5), at(5, 5)], NO_FOOD);
+        assert_eq!(out.dead, vec![true, true]);
+        assert_eq!(out.killers, vec![None, None]);
+    }
+
+    #[test]
+    fn heads_swapping_cells_both_die() {
+        let players = vec![snake(&[(4, 5), (3, 5)]), snake(&[(5, 5), (6, 5)])];
+        let out = resolve(&players, &[at(5, 5), at(4, 5)], NO_FOOD);
+        assert_eq!(out.dead, vec![true, true]);
+    }
+
+    #[test]
+    fn contested_food_goes_to_nobody() {
+        let food = [Pos { x: 5, y: 5 }];
+        let players = vec![snake(&[(4, 5)]), snake(&[(5, 4)]), snake(&[(9, 9)])];
+        let out = resolve(&players, &[at(5, 5), at(5, 5), at(10, 9)], &food);
+        assert_eq!(out.dead, vec![true, true, false]);
+        assert!(out.eaters.is_empty());
+    }
+
+    #[test]
+    fn single_snake_reaching_food_eats_it() {
+        let food = [Pos { x: 5, y: 5 }];
+        let players = vec![snake(&[(4, 5)]), snake(&[(9, 9)])];
+        let out = resolve(&players, &[at(5, 5), at(10, 9)], &food);
+        assert_eq!(out.dead, vec![false, false]);
+        assert_eq!(out.eaters, vec![0]);
+    }
+
+    #[test]
+    fn exempt_snakes_pass_through_each_other() {
+        // 0 and 1 are teammates, 2 is not
+        let teammates = |i: usize, j: usize| !(i < 2 && j < 2);
+        let players = vec![snake(&[(4, 5), (3, 5)]), snake(&[(5, 7), (5, 6), (5, 5), (5, 4)]), snake(&[(9, 9)])];
+        let out = resolve_with(&players, &[at(5, 5), at(5, 8), at(9, 8)], NO_FOOD, teammates);
+        assert_eq!(out.dead, vec![false, false, false]);
+        let out = resolve_with(&players, &[at(5, 5), at(5, 8), at(9, 8)], NO_FOOD, |_, _| true);
+        assert_eq!(out.dead, vec![true, false, false]);
+    }
+
+    #[test]
+    fn snakes_can_eat_different_food_on_one_tick() {
+        let food = [Pos { x: 5, y: 5 }, Pos { x: 10, y: 9 }];
+        let players = vec![snake(&[(4, 5)]), snake(&[(9, 9)])];
+        let out = resolve(&players, &[at(5, 5), at(10, 9)], &food);
+        assert_eq!(out.dead, vec![false, false]);
+        assert_eq!(out.eaters, vec![0, 1]);
+    }
+}
@@ -0,0 +1,87 @@
+// What clients need to talk to a server, whatever they draw with: the opening messages and,
+// natively, the TCP connection itself. Browsers bring their own socket.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tcp;
+
+use std::time::Duration;
+
+use super::{ClientMsg, Compression, GameMode, SessionToken, WireFormat, PROTOCOL_VERSION};
+
+// What a connection asks the server for during the handshake
+#[derive(Copy, Clone)]
+pub struct NetOptions {
+    pub format: WireFormat,
+    // receive states by UDP datagram, only honored by native builds
+    pub udp: bool,
+    // how the server is asked to compress what it sends
+    pub compression: Compression,
+    // take a state only every this many ticks
+    pub state_every: u64,
+    pub connect_timeout: Duration,
+}
+
+pub fn version_mismatch_text(server_version: u32) -> String {
+    if server_version > PROTOCOL_VERSION {
+        format!("The server is newer (protocol {}), please update the client", server_version)
+    } else {
+        format!("The server is older (protocol {}), it needs an update", server_version)
+    }
+}
+
+// Every connection opens with this, sent as a Json line
+pub fn hello(options: NetOptions) -> ClientMsg {
+    ClientMsg::Hello { protocol_version: PROTOCOL_VERSION, format: options.format, udp: options.udp, compression: options.compression }
+}
+
+// Which room a player connection asks for
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RoomChoice {
+    // with the password, for a private room
+    Join { code: String, password: Option<String> },
+    // a fresh one, playing `mode` on a board that wraps around by `wrap`, private with a password
+    Create { mode: GameMode, wrap: bool, password: Option<String> },
+    // whichever one matchmaking puts the player in
+    Queue,
+}
+
+impl RoomChoice {
+    // The same room, with `password` for it instead
+    pub fn with_password(self, password: Option<String>) -> RoomChoice {
+        match self {
+            RoomChoice::Join { code, .. } => RoomChoice::Join { code, password },
+            RoomChoice::Create { mode, wrap, .. } => RoomChoice::Create { mode, wrap, password },
+            RoomChoice::Queue => RoomChoice::Queue,
+        }
+    }
+}
+
+// What a player connection opens with. Logging in comes before anything else, and a Rejoin
+// before the Join so the server can ignore the Join if it works.
+pub fn join_messages(username: String, room: RoomChoice, token: Option<SessionToken>, login: Option<ClientMsg>, options: NetOptions) -> Vec<ClientMsg> {
+    let mut msgs = vec![hello(options)];
+    msgs.extend(login);
+    if options.state_every > 1 {
+        msgs.push(ClientMsg::StateRate { every: options.state_every });
+    }
+    if let Some(token) = token {
+        msgs.push(ClientMsg::Rejoin { token });
+    }
+    msgs.push(ClientMsg::Join { name: username });
+    msgs.push(match room {
+        RoomChoice::Join { code, password } => ClientMsg::JoinRoom { code, password },
+        RoomChoice::Create { mode, wrap, password } => ClientMsg::CreateRoom { mode: Some(mode), wrap: Some(wrap), password },
+        RoomChoice::Queue => ClientMsg::QueueForMatch,
+    });
+    msgs
+}
+
+// How the connection itself is doing, apart from the messages on it
+#[derive(Clone, Debug, PartialEq)]
+pub enum NetStatus {
+    Connected,
+    // the connection still works, but not the way it was asked to; the text is for the player
+    Degraded(String),
+    // the connection is gone or never came up; the text is meant for the player
+    Failed(String),
+}
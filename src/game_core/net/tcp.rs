@@ -0,0 +1,278 @@
+// The native client's connection to a server: a thread writing what the player sends, one
+// reading what the server sends, and optionally one taking states by UDP. Sessions and
+// account logins are saved as they arrive, so a restarted client can pick up where it was.
+
+use std::fs::OpenOptions;
+use std::io::{BufReader, ErrorKind, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::NetStatus;
+use crate::game_core::codec;
+use crate::game_core::tls::{self, Stream, TlsStream};
+use crate::game_core::{AccountMsg, AccountToken, ClientMsg, Compression, ServerAddr, ServerMsg, SessionMsg, SessionToken, WireFormat};
+
+// A server that sends nothing for this long is gone; it answers our pings well within it
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+// PEM certificates to trust on top of the public authorities, for servers with their own
+const CA_ENV: &str = "SNAKE_CA";
+
+// The last session token is kept on disk so a restarted client can reclaim its snake
+#[derive(Serialize, Deserialize)]
+struct SavedSession {
+    server_addr: String,
+    room_code: String,
+    token: SessionToken,
+}
+
+// The client's own data directory, e.g. ~/.local/share/rusty-snake on Linux. Tokens are as
+// good as a password, so without a home directory nothing is saved rather than leaving them
+// in a directory every user can read.
+fn data_path(file: &str) -> Option<PathBuf> {
+    directories::ProjectDirs::from("", "", "rusty-snake").map(|dirs| dirs.data_dir().join(file))
+}
+
+// Writes `data` where only this user can read it
+fn write_private(path: &Path, data: &str) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(data.as_bytes())
+}
+
+fn session_path() -> Option<PathBuf> {
+    data_path("session.json")
+}
+
+pub fn load_session(server_addr: &str, room_code: &str) -> Option<SessionToken> {
+    let data = std::fs::read_to_string(session_path()?).ok()?;
+    let saved: SavedSession = serde_json::from_str(&data).ok()?;
+    (saved.server_addr == server_addr && saved.room_code.eq_ignore_ascii_case(room_code)).then_some(saved.token)
+}
+
+fn save_session(server_addr: &str, session: SessionMsg) {
+    let saved = SavedSession { server_addr: server_addr.to_string(), room_code: session.room_code, token: session.token };
+    if let (Some(path), Ok(data)) = (session_path(), serde_json::to_string(&saved)) {
+        let _ = write_private(&path, &data);
+    }
+}
+
+// Logins to accounts, one per server and name, so the password is only needed once
+#[derive(Serialize, Deserialize)]
+struct SavedAccount {
+    server_addr: String,
+    name: String,
+    token: AccountToken,
+}
+
+fn accounts_path() -> Option<PathBuf> {
+    data_path("accounts.json")
+}
+
+fn saved_accounts() -> Vec<SavedAccount> {
+    accounts_path().and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+pub fn load_account(server_addr: &str, name: &str) -> Option<AccountToken> {
+    saved_accounts().into_iter().find(|a| a.server_addr == server_addr && a.name == name).map(|a| a.token)
+}
+
+fn save_account(server_addr: &str, account: AccountMsg) {
+    let mut saved = saved_accounts();
+    saved.retain(|a| a.server_addr != server_addr || a.name != account.name);
+    saved.push(SavedAccount { server_addr: server_addr.to_string(), name: account.name, token: account.token });
+    if let (Some(path), Ok(data)) = (accounts_path(), serde_json::to_string(&saved)) {
+        let _ = write_private(&path, &data);
+    }
+}
+
+// Messages go out and come in through channels, so none of these block
+pub struct TcpTransport {
+    tx: mpsc::Sender<ClientMsg>,
+    rx: mpsc::Receiver<ServerMsg>,
+    status: mpsc::Receiver<NetStatus>,
+}
+
+impl TcpTransport {
+    pub fn send(&mut self, msg: ClientMsg) {
+        let _ = self.tx.send(msg);
+    }
+
+    pub fn try_recv(&mut self) -> Option<ServerMsg> {
+        self.rx.try_recv().ok()
+    }
+
+    // the next change of the connection's status, if there was one
+    pub fn poll_status(&mut self) -> Option<NetStatus> {
+        self.status.try_recv().ok()
+    }
+}
+
+// Looks the host up, then tries every address it resolves to until one answers within the
+// timeout. Each step has its own error, so the player can tell a typo from a server that's down.
+fn open_socket(addr: &ServerAddr, timeout: Duration) -> Result<TcpStream, String> {
+    let addrs: Vec<SocketAddr> = (addr.host.as_str(), addr.port).to_socket_addrs()
+        .map_err(|e| format!("Can't find the server {}: {}", addr.host, e))?
+        .collect();
+    let mut last_error = format!("{} resolves to no address", addr.host);
+    for socket_addr in addrs {
+        match TcpStream::connect_timeout(&socket_addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) if e.kind() == ErrorKind::TimedOut => last_error = format!("{} didn't answer in time", addr.authority()),
+            Err(e) => last_error = format!("Can't reach {}: {}", addr.authority(), e),
+        }
+    }
+    Err(last_error)
+}
+
+// Connects, over TLS for a snakes:// address, and says where to
+fn open_stream(server_addr: &str, timeout: Duration) -> Result<(Stream, SocketAddr), String> {
+    let addr = ServerAddr::parse(server_addr)?;
+    let socket = open_socket(&addr, timeout)?;
+    let peer = socket.peer_addr().map_err(|e| format!("Connection lost: {}", e))?;
+    socket.set_nodelay(true).ok();
+    socket.set_read_timeout(Some(READ_TIMEOUT)).ok();
+    if !addr.secure {
+        return Ok((Stream::Plain(socket), peer));
+    }
+    let extra_roots = std::env::var_os(CA_ENV).map(PathBuf::from);
+    let config = tls::client_config(extra_roots.as_deref())?;
+    // the certificate has to be for the host as typed
+    let stream = TlsStream::connect(socket, &addr.host, config)?;
+    Ok((Stream::Tls(stream), peer))
+}
+
+// Registers with the server's UDP port and forwards the states that arrive there.
+// The ticket is sent again until the first datagram shows it got through.
+fn start_udp(
+    server_addr: SocketAddr,
+    ticket: u64,
+    format: WireFormat,
+    tx_states: mpsc::Sender<ServerMsg>,
+    tx_status: mpsc::Sender<NetStatus>,
+    closed: Arc<AtomicBool>,
+) {
+    thread::spawn(move || {
+        // from the address family the server was reached by
+        let local: SocketAddr = if server_addr.is_ipv6() { (Ipv6Addr::UNSPECIFIED, 0).into() } else { (Ipv4Addr::UNSPECIFIED, 0).into() };
+        let socket = match UdpSocket::bind(local).and_then(|s| s.connect(server_addr).map(|_| s)) {
+            Ok(s) => s,
+            Err(e) => {
+                let _ = tx_status.send(NetStatus::Degraded(format!("UDP unavailable, states stay on TCP: {}", e)));
+                return;
+            }
+        };
+        socket.set_read_timeout(Some(Duration::from_millis(500))).ok();
+        let mut registered = false;
+        let mut buf = vec![0u8; 64 * 1024];
+        while !closed.load(Ordering::Relaxed) {
+            if !registered {
+                let _ = socket.send(&ticket.to_le_bytes());
+            }
+            let Ok(n) = socket.recv(&mut buf) else { continue };
+            registered = true;
+            let mut frame = buf[..n].to_vec();
+            let Ok(Some(frame)) = codec::take_frame(&mut frame, format) else { continue };
+            if let Ok(msg) = codec::decode::<ServerMsg>(format, &frame)
+                && tx_states.send(msg).is_err()
+            {
+                break;
+            }
+        }
+    });
+}
+
+// Connects in the background and sends `opening` first, its first message as a Json line
+pub fn start_networking(server_addr: String, opening: Vec<ClientMsg>, connect_timeout: Duration) -> TcpTransport {
+    let (tx_ui_to_net, rx_ui_to_net) = mpsc::channel::<ClientMsg>();
+    let (tx_net_to_ui, rx_net_to_ui) = mpsc::channel::<ServerMsg>();
+    let (tx_status, rx_status) = mpsc::channel::<NetStatus>();
+
+    thread::spawn(move || {
+        // Connect to server
+        let (stream, peer) = match open_stream(&server_addr, connect_timeout) {
+            Ok(s) => s,
+            Err(e) => {
+                let _ = tx_status.send(NetStatus::Failed(e));
+                return;
+            }
+        };
+        let _ = tx_status.send(NetStatus::Connected);
+        let mut writer = stream.try_clone().expect("clone stream");
+        let reader_stream = stream;
+
+        // The first message goes out as a Json line, the rest wait for the server's answer
+        let mut pending = opening;
+        if pending.is_empty() || codec::write_msg(&mut writer, WireFormat::Json, &pending.remove(0)).is_err() {
+            return;
+        }
+
+        // Reader thread: receive states
+        let (tx_format, rx_format) = mpsc::channel::<WireFormat>();
+        let tx_states = tx_net_to_ui.clone();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(reader_stream);
+            // stops the UDP receiver along with the connection
+            let closed = Arc::new(AtomicBool::new(false));
+            // the server answers with a Json Welcome line before anything else
+            let mut format = WireFormat::Json;
+            let mut compression = Compression::None;
+            let reason = loop {
+                let frame = match codec::read_packed_frame(&mut reader, format, compression) {
+                    Ok(Some(frame)) => frame,
+                    Ok(None) => break "The server closed the connection".to_string(),
+                    Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                        break "The server stopped responding".to_string();
+                    }
+                    Err(e) => break format!("Connection lost: {}", e),
+                };
+                match codec::decode::<ServerMsg>(format, &frame) {
+                    Ok(ServerMsg::Welcome { format: chosen, protocol_version, seed, compression: packed }) => {
+                        format = chosen;
+                        compression = packed;
+                        let _ = tx_format.send(chosen);
+                        let _ = tx_states.send(ServerMsg::Welcome { protocol_version, format, seed, compression });
+                    }
+                    Ok(ServerMsg::Udp { ticket }) => {
+                        start_udp(peer, ticket, format, tx_states.clone(), tx_status.clone(), closed.clone());
+                    }
+                    Ok(ServerMsg::Session(session)) => {
+                        save_session(&server_addr, session.clone());
+                        let _ = tx_states.send(ServerMsg::Session(session));
+                    }
+                    Ok(ServerMsg::Account(account)) => {
+                        save_account(&server_addr, account.clone());
+                        let _ = tx_states.send(ServerMsg::Account(account));
+                    }
+                    Ok(msg) => { let _ = tx_states.send(msg); }
+                    Err(_) => {}
+                }
+            };
+            closed.store(true, Ordering::Relaxed);
+            let _ = tx_status.send(NetStatus::Failed(reason));
+        });
+
+        // Writer loop: forward UI inputs to server once the format is settled
+        // ends when the UI drops its sender
+        let Ok(format) = rx_format.recv() else { return };
+        for msg in pending.into_iter().chain(rx_ui_to_net.iter()) {
+            if codec::write_msg(&mut writer, format, &msg).is_err() {
+                break;
+            }
+        }
+    });
+
+    TcpTransport { tx: tx_ui_to_net, rx: rx_net_to_ui, status: rx_status }
+}
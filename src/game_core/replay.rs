@@ -0,0 +1,119 @@
+// Recorded matches, played back by the client without a server.
+//
+// A replay file is Json lines: a ReplayHeader, then every ServerMsg the recording client
+// received after the Welcome, in the order it arrived. Messages change shape between
+// protocol versions, so a replay only plays on a build with the version it was recorded with.
+
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use super::codec;
+use super::{ServerMsg, StateMsg, WireFormat, PROTOCOL_VERSION};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReplayHeader {
+    pub protocol_version: u32,
+    // whose client recorded it; playback shows the match as they saw it
+    pub player: String,
+    // the seed from the server's Welcome
+    pub seed: u64,
+}
+
+// One snapshot of a replay and whatever arrived since the one before it
+#[derive(Clone, Debug)]
+pub struct ReplayFrame {
+    pub before: Vec<ServerMsg>,
+    pub state: StateMsg,
+}
+
+#[derive(Clone, Debug)]
+pub struct Replay {
+    pub header: ReplayHeader,
+    pub frames: Vec<ReplayFrame>,
+}
+
+pub fn write_header<W: Write>(w: &mut W, player: &str, seed: u64) -> io::Result<()> {
+    let header = ReplayHeader { protocol_version: PROTOCOL_VERSION, player: player.to_string(), seed };
+    codec::write_msg(w, WireFormat::Json, &header)
+}
+
+pub fn write_msg<W: Write>(w: &mut W, msg: &ServerMsg) -> io::Result<()> {
+    codec::write_msg(w, WireFormat::Json, msg)
+}
+
+// Reads a whole replay file. Snapshots that arrived after a newer one are dropped, like
+// the client drops them, and so is anything after the last snapshot.
+pub fn parse(text: &str) -> Result<Replay, String> {
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let (_, first) = lines.next().ok_or("the replay is empty")?;
+    let header: ReplayHeader = serde_json::from_str(first).map_err(|e| format!("not a replay: {}", e))?;
+    if header.protocol_version != PROTOCOL_VERSION {
+        return Err(format!("recorded with protocol {}, this build plays {}", header.protocol_version, PROTOCOL_VERSION));
+    }
+    let mut frames = Vec::new();
+    let mut before = Vec::new();
+    let mut last_seq = None;
+    for (n, line) in lines {
+        let msg: ServerMsg = serde_json::from_str(line).map_err(|e| format!("line {}: {}", n + 1, e))?;
+        match msg {
+            ServerMsg::State(state) if last_seq.is_some_and(|seq| seq >= state.seq) => {}
+            ServerMsg::State(state) => {
+                last_seq = Some(state.seq);
+                frames.push(ReplayFrame { before: std::mem::take(&mut before), state });
+            }
+            msg => before.push(msg),
+        }
+    }
+    if frames.is_empty() {
+        return Err("the replay has no game in it".to_string());
+    }
+    Ok(Replay { header, frames })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_core::sim::{GameState, MatchConfig};
+
+    fn record(msgs: &[ServerMsg]) -> String {
+        let mut out = Vec::new();
+        write_header(&mut out, "alice", 7).unwrap();
+        for msg in msgs {
+            write_msg(&mut out, msg).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn splits_recording_into_frames() {
+        let mut game = GameState::new(&["alice".to_string()], MatchConfig::default());
+        let first = game.snapshot();
+        let second = game.snapshot();
+        let text = record(&[
+            ServerMsg::Config(game.config.game),
+            ServerMsg::State(first.clone()),
+            ServerMsg::Info("bob joined".to_string()),
+            ServerMsg::State(second.clone()),
+            // arrived late, dropped
+            ServerMsg::State(first),
+            ServerMsg::Info("trailing".to_string()),
+        ]);
+        let replay = parse(&text).unwrap();
+        assert_eq!(replay.header.player, "alice");
+        assert_eq!(replay.header.seed, 7);
+        assert_eq!(replay.frames.len(), 2);
+        assert!(matches!(replay.frames[0].before.as_slice(), [ServerMsg::Config(_)]));
+        assert!(matches!(replay.frames[1].before.as_slice(), [ServerMsg::Info(_)]));
+        assert_eq!(replay.frames[1].state.seq, second.seq);
+    }
+
+    #[test]
+    fn refuses_other_versions_and_empty_games() {
+        let header = ReplayHeader { protocol_version: PROTOCOL_VERSION + 1, player: String::new(), seed: 0 };
+        let text = serde_json::to_string(&header).unwrap();
+        assert!(parse(&text).unwrap_err().contains("protocol"));
+        assert!(parse("").is_err());
+        assert!(parse(&record(&[ServerMsg::Info("hi".to_string())])).unwrap_err().contains("no game"));
+    }
+}
@@ -0,0 +1,84 @@
+// The match simulation's source of randomness: a small xorshift64* generator, so a match
+// started from the same seed and fed the same inputs plays out the same everywhere.
+
+use std::ops::Range;
+
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift is stuck at zero, and seeds a bit apart should start far apart, so the
+        // seed goes through a splitmix64 step first
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        Rng { state: if z == 0 { 0x9e37_79b9_7f4a_7c15 } else { z } }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    // A number below `n`, which must not be 0
+    fn below(&mut self, n: u64) -> u64 {
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+
+    // A number in `range`, which must not be empty
+    pub fn range(&mut self, range: Range<i32>) -> i32 {
+        range.start + self.below((range.end - range.start) as u64) as i32
+    }
+
+    // An index into something `len` long, which must not be 0
+    pub fn index(&mut self, len: usize) -> usize {
+        self.below(len as u64) as usize
+    }
+
+    pub fn coin(&mut self) -> bool {
+        self.next_u64() >> 63 == 1
+    }
+
+    // True with a chance of one in `n`
+    pub fn one_in(&mut self, n: u32) -> bool {
+        self.below(n as u64) == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_numbers() {
+        let (mut a, mut b) = (Rng::new(42), Rng::new(42));
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        let (mut a, mut b) = (Rng::new(1), Rng::new(2));
+        assert_ne!(a.next_u64(), b.next_u64());
+        // zero is a seed like any other
+        assert_ne!(Rng::new(0).next_u64(), 0);
+    }
+
+    #[test]
+    fn stays_in_range() {
+        let mut rng = Rng::new(7);
+        let mut seen = [false; 5];
+        for _ in 0..1000 {
+            let n = rng.range(-2..3);
+            assert!((-2..3).contains(&n));
+            seen[(n + 2) as usize] = true;
+            assert!(rng.index(3) < 3);
+        }
+        assert!(seen.iter().all(|s| *s));
+    }
+}
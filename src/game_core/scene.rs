@@ -0,0 +1,81 @@
+// What a snapshot puts on the board, apart from how a frontend draws it: a piece for every
+// cell something covers, back to front, so drawing them in order leaves the right thing on top.
+// The windowed client draws whole snakes itself; the terminal client and the exporter go by this.
+
+use super::{BoardMap, FoodKind, Pos, PowerUpKind, StateMsg};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Piece {
+    // food only appears on these, where the map says so
+    FoodZone,
+    // both ends of a pair share the index of the pair in the map
+    Teleporter { pair: usize },
+    Wall,
+    PowerUp(PowerUpKind),
+    Body(Segment),
+    Head(Segment),
+    Food(FoodKind),
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Segment {
+    // index into the snapshot's players
+    pub player: usize,
+    // the player is away, or the snake is a ghost
+    pub faded: bool,
+    pub boosting: bool,
+}
+
+pub fn pieces(state: &StateMsg, map: Option<&BoardMap>) -> Vec<(Pos, Piece)> {
+    let mut pieces = Vec::new();
+    if let Some(map) = map {
+        pieces.extend(map.food_zones.iter().map(|&pos| (pos, Piece::FoodZone)));
+        for (pair, &(a, b)) in map.teleporters.iter().enumerate() {
+            pieces.extend([a, b].map(|pos| (pos, Piece::Teleporter { pair })));
+        }
+    }
+    // the map's walls are among the obstacles
+    pieces.extend(state.obstacles.iter().map(|&pos| (pos, Piece::Wall)));
+    pieces.extend(state.power_ups.iter().map(|p| (p.pos, Piece::PowerUp(p.kind))));
+    for (player, p) in state.players.iter().enumerate() {
+        let segment = Segment { player, faded: !p.connected || p.has_effect(PowerUpKind::Ghost), boosting: p.boosting };
+        if let Some((&head, body)) = p.snake.split_first() {
+            pieces.extend(body.iter().rev().map(|&pos| (pos, Piece::Body(segment))));
+            pieces.push((head, Piece::Head(segment)));
+        }
+    }
+    // food goes on top, so a snake about to eat it doesn't hide it
+    pieces.extend(state.food_items.iter().map(|f| (f.pos, Piece::Food(f.kind))));
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_core::sim::{GameState, MatchConfig};
+    use crate::game_core::{FoodItem, PowerUp};
+
+    #[test]
+    fn lists_the_board_back_to_front() {
+        let mut game = GameState::new(&["alice".to_string()], MatchConfig::default());
+        let mut state = game.snapshot();
+        state.players[0].snake = vec![Pos { x: 3, y: 1 }, Pos { x: 2, y: 1 }, Pos { x: 1, y: 1 }];
+        state.players[0].connected = false;
+        state.food_items = vec![FoodItem { pos: Pos { x: 3, y: 1 }, kind: FoodKind::Golden, expires_tick: None }];
+        state.power_ups = vec![PowerUp { pos: Pos { x: 5, y: 5 }, kind: PowerUpKind::Slow, expires_tick: 100 }];
+        state.obstacles = vec![Pos { x: 0, y: 0 }];
+        let map = BoardMap { teleporters: vec![(Pos { x: 7, y: 7 }, Pos { x: 8, y: 8 })], ..BoardMap::default() };
+
+        let segment = Segment { player: 0, faded: true, boosting: false };
+        assert_eq!(pieces(&state, Some(&map)), [
+            (Pos { x: 7, y: 7 }, Piece::Teleporter { pair: 0 }),
+            (Pos { x: 8, y: 8 }, Piece::Teleporter { pair: 0 }),
+            (Pos { x: 0, y: 0 }, Piece::Wall),
+            (Pos { x: 5, y: 5 }, Piece::PowerUp(PowerUpKind::Slow)),
+            (Pos { x: 1, y: 1 }, Piece::Body(segment)),
+            (Pos { x: 2, y: 1 }, Piece::Body(segment)),
+            (Pos { x: 3, y: 1 }, Piece::Head(segment)),
+            (Pos { x: 3, y: 1 }, Piece::Food(FoodKind::Golden)),
+        ]);
+    }
+}
@@ -0,0 +1,1472 @@
+// The rules of a match, played out one tick at a time. The server runs one per room;
+// the client runs one of its own for offline play.
+
+use std::sync::Arc;
+
+use rand::Rng as _;
+
+use super::ai::{self, BotDifficulty};
+use super::collision;
+use super::rng::Rng;
+use super::{ActiveEffect, BoardMap, Direction, FoodItem, FoodKind, GameConfig, GameEvent, GameMode, KillEvent, MatchPhase, PlayerId, PlayerState, Pos, PowerUp, PowerUpKind, RoundEndMsg, RoundEndReason, SeriesScore, Standing, StateMsg};
+use super::{can_turn, step_head, CORPSE_TICKS, DEFAULT_MAX_PLAYERS, MAX_GRID_SIZE, MAX_PLAYERS, MIN_GRID_SIZE, MIN_PLAYERS, MOVE_INTERVAL_MS, POWER_UP_EFFECT_TICKS, ROUND_COUNTDOWN_SECS};
+
+// How a round gets faster: the tick shrinks by `step_ms` every `every_food` food eaten
+// and every `every_ticks` ticks, down to `min_tick_ms`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpeedUp {
+    pub every_food: Option<u32>,
+    pub every_ticks: Option<u64>,
+    pub step_ms: u64,
+    pub min_tick_ms: u64,
+}
+
+impl Default for SpeedUp {
+    fn default() -> Self {
+        SpeedUp { every_food: None, every_ticks: None, step_ms: 5, min_tick_ms: 60 }
+    }
+}
+
+// Rules a match is played with, the same for every room on a server
+#[derive(Clone, Debug)]
+pub struct MatchConfig {
+    pub tick_ms: u64,
+    // a state goes out every this many ticks, events every tick; clients can ask for fewer
+    pub state_every: u64,
+    pub max_players: usize,
+    // food items on the board at any time
+    pub food_count: usize,
+    // first snake to reach this score wins the round
+    pub score_target: Option<u32>,
+    // carry scores over into rematches instead of starting from zero
+    pub keep_scores: bool,
+    // play rounds as a best-of-N series, None for round after round with no end
+    pub best_of: Option<u32>,
+    // the body of a player who left for good turns into leftovers instead of just vanishing
+    pub leave_food: bool,
+    // dead snakes turn into food that rots away, instead of lying there as walls
+    pub corpses: bool,
+    // ticks before a dead snake comes back, None to stay dead for the round
+    pub respawn_ticks: Option<u64>,
+    // deaths a snake can take before it is out of the round, None for no lives
+    pub lives: Option<u8>,
+    // short-lived golden food shows up now and then
+    pub golden_food: bool,
+    // ticks between poison showing up, None for no poison
+    pub poison_interval: Option<u64>,
+    // wall segments scattered over the board at the start of each round
+    pub obstacles: usize,
+    // power-ups show up on the board now and then
+    pub power_ups: bool,
+    pub speedup: SpeedUp,
+    // how long a timed round lasts
+    pub time_limit_secs: u64,
+    // how long every round counts down before the snakes move, 0 to start right away
+    pub countdown_secs: u64,
+    // number of teams players are split into, 0 to play every snake for itself
+    pub teams: u8,
+    // teammates can run into each other; otherwise they pass through
+    pub friendly_fire: bool,
+    // snakes the server steers itself, filling slots players leave empty
+    pub bots: usize,
+    pub bot_difficulty: BotDifficulty,
+    // what clients are told in their Config
+    pub game: GameConfig,
+    // the board a map file describes, shared by every room
+    pub map: Option<Arc<BoardMap>>,
+    // what the match's randomness starts from; None picks one at random
+    pub seed: Option<u64>,
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        MatchConfig {
+            tick_ms: MOVE_INTERVAL_MS,
+            state_every: 1,
+            max_players: DEFAULT_MAX_PLAYERS,
+            food_count: 1,
+            score_target: None,
+            keep_scores: false,
+            best_of: None,
+            leave_food: true,
+            corpses: true,
+            respawn_ticks: None,
+            lives: None,
+            golden_food: false,
+            poison_interval: None,
+            obstacles: 0,
+            power_ups: false,
+            speedup: SpeedUp::default(),
+            time_limit_secs: 120,
+            countdown_secs: ROUND_COUNTDOWN_SECS,
+            teams: 0,
+            friendly_fire: false,
+            bots: 0,
+            bot_difficulty: BotDifficulty::default(),
+            game: GameConfig::default(),
+            map: None,
+            seed: None,
+        }
+    }
+}
+
+impl MatchConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_TICK_MS..=MAX_TICK_MS).contains(&self.tick_ms) {
+            return Err(format!("tick_ms must be between {} and {}", MIN_TICK_MS, MAX_TICK_MS));
+        }
+        if !(1..=MAX_STATE_EVERY).contains(&self.state_every) {
+            return Err(format!("state_every must be between 1 and {}", MAX_STATE_EVERY));
+        }
+        if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&self.max_players) {
+            return Err(format!("player count must be between {} and {}", MIN_PLAYERS, MAX_PLAYERS));
+        }
+        if !(1..=MAX_FOOD).contains(&self.food_count) {
+            return Err(format!("food count must be between 1 and {}", MAX_FOOD));
+        }
+        if self.obstacles > MAX_OBSTACLES {
+            return Err(format!("obstacle count must be at most {}", MAX_OBSTACLES));
+        }
+        if let Some(map) = &self.map {
+            if !map.spawns.is_empty() && map.spawns.len() < self.max_players {
+                return Err(format!("the map has {} spawn points for {} players", map.spawns.len(), self.max_players));
+            }
+            let cells = map.walls.iter().chain(&map.spawns).chain(&map.food_zones);
+            if cells.chain(map.teleporters.iter().flat_map(|(a, b)| [a, b])).any(|p| p.x >= self.game.grid_width || p.y >= self.game.grid_height) {
+                return Err("the map doesn't fit the grid size, leave out --grid when using a map".to_string());
+            }
+        }
+        if self.speedup.every_food == Some(0) || self.speedup.every_ticks == Some(0) {
+            return Err("speed-up intervals must be at least 1".to_string());
+        }
+        let speeds_up = self.speedup.every_food.is_some() || self.speedup.every_ticks.is_some();
+        if speeds_up && !(MIN_TICK_MS..=self.tick_ms).contains(&self.speedup.min_tick_ms) {
+            return Err(format!("speed-up min_tick_ms must be between {} and tick_ms", MIN_TICK_MS));
+        }
+        if self.lives.is_some_and(|lives| !(1..=MAX_LIVES).contains(&lives)) {
+            return Err(format!("lives must be between 1 and {}", MAX_LIVES));
+        }
+        if self.teams == 1 || self.teams > MAX_TEAMS {
+            return Err(format!("team count must be 0 or between 2 and {}", MAX_TEAMS));
+        }
+        if self.bots > 0 && self.bots >= self.max_players {
+            return Err("there have to be fewer bots than players, to leave a slot for a person".to_string());
+        }
+        if self.teams as usize > self.max_players {
+            return Err("there can't be more teams than players".to_string());
+        }
+        if !(MIN_TIME_LIMIT_SECS..=MAX_TIME_LIMIT_SECS).contains(&self.time_limit_secs) {
+            return Err(format!("time limit must be between {} and {} seconds", MIN_TIME_LIMIT_SECS, MAX_TIME_LIMIT_SECS));
+        }
+        if self.countdown_secs > MAX_COUNTDOWN_SECS {
+            return Err(format!("countdown must be at most {} seconds", MAX_COUNTDOWN_SECS));
+        }
+        if self.poison_interval == Some(0) {
+            return Err("poison interval must be at least 1".to_string());
+        }
+        if self.best_of.is_some_and(|n| !(1..=MAX_BEST_OF).contains(&n)) {
+            return Err(format!("best of must be between 1 and {} rounds", MAX_BEST_OF));
+        }
+        if self.score_target == Some(0) {
+            return Err("score target must be at least 1".to_string());
+        }
+        let range = MIN_GRID_SIZE..=MAX_GRID_SIZE;
+        if !range.contains(&self.game.grid_width) || !range.contains(&self.game.grid_height) {
+            return Err(format!("grid sides must be between {} and {}", MIN_GRID_SIZE, MAX_GRID_SIZE));
+        }
+        Ok(())
+    }
+}
+
+const MIN_TICK_MS: u64 = 20;
+const MAX_TICK_MS: u64 = 2000;
+// Fewest states a client gets: one every this many ticks
+pub const MAX_STATE_EVERY: u64 = 10;
+const MAX_FOOD: usize = 50;
+const MAX_TEAMS: u8 = 4;
+const MAX_LIVES: u8 = 9;
+const MIN_TIME_LIMIT_SECS: u64 = 10;
+const MAX_TIME_LIMIT_SECS: u64 = 3600;
+const MAX_COUNTDOWN_SECS: u64 = 10;
+const MAX_BEST_OF: u32 = 15;
+// Ticks before leftovers rot away
+const LEFTOVER_TICKS: u64 = 60;
+// While there is none on the board, golden food shows up with a chance of one in
+// GOLDEN_FOOD_CHANCE per tick and lasts GOLDEN_FOOD_TICKS
+const GOLDEN_FOOD_CHANCE: u32 = 150;
+const GOLDEN_FOOD_TICKS: u64 = 40;
+// Poison lies around this long, there is never more than MAX_POISON of it, and eating
+// it takes POISON_SHRINK segments off
+const POISON_TICKS: u64 = 150;
+const MAX_POISON: usize = 3;
+const POISON_SHRINK: usize = 3;
+const MAX_OBSTACLES: usize = 40;
+// Every tick is split into this many sub-steps, so some snakes can move faster than others
+const SUB_STEPS: u64 = 2;
+// A boosting snake loses its last segment every this many ticks
+const BOOST_SHED_TICKS: u64 = 5;
+// A power-up appears every this many ticks, while there are fewer than MAX_POWER_UPS,
+// and lies there for POWER_UP_TICKS
+const POWER_UP_INTERVAL: u64 = 60;
+const MAX_POWER_UPS: usize = 2;
+const POWER_UP_TICKS: u64 = 100;
+// Wall segments are this many cells long at most
+const MAX_OBSTACLE_LEN: i32 = 6;
+// Walls keep this far from where snakes start, and off the rows they start on
+const SPAWN_CLEARANCE: i32 = 4;
+// Random guesses at a free cell before going through the whole board for one
+const RANDOM_CELL_TRIES: usize = 200;
+// What a score race is played to when the server sets no score target
+const SCORE_RACE_TARGET: u32 = 20;
+// A battle royale board closes in by one ring this often, a board in sudden death every
+// SUDDEN_DEATH_CLOSE_IN_TICKS, until its sides are down to MIN_ARENA_SIZE
+const CLOSE_IN_TICKS: u64 = 100;
+const SUDDEN_DEATH_CLOSE_IN_TICKS: u64 = 20;
+const MIN_ARENA_SIZE: i32 = 10;
+// What a snake gets for another snake running into its body
+const KILL_POINTS: u32 = 1;
+const KILL_GROWTH: usize = 2;
+// A respawned snake starts this long, and can't be hurt by snakes for RESPAWN_GHOST_MS
+const RESPAWN_LENGTH: usize = 3;
+const RESPAWN_GHOST_MS: u64 = 2000;
+// Respawn delay for rooms with lives but no respawn_ticks of their own
+const LIVES_RESPAWN_TICKS: u64 = 20;
+
+// The seed a room's matches play from, given the server's seed
+pub fn room_seed(server_seed: u64, room_code: &str) -> u64 {
+    // FNV-1a over the code, mixed into the server's seed
+    let code = room_code.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100_0000_01b3));
+    server_seed ^ code
+}
+
+// The team with the fewest members, where newcomers are put; None when the room has no teams
+pub fn smallest_team(teams: u8, taken: impl Iterator<Item = Option<u8>>) -> Option<u8> {
+    let mut sizes = vec![0usize; teams as usize];
+    for team in taken.flatten() {
+        sizes[team as usize] += 1;
+    }
+    (0..teams).min_by_key(|t| sizes[*t as usize])
+}
+
+// Spread starting positions over a grid of rows/columns so snakes don't start on top of each other
+fn spawn_player(slot: usize, count: usize, grid: &GameConfig) -> PlayerState {
+    let cols = count.div_ceil(2).max(1) as i32;
+    let rows = if count > 1 { 2 } else { 1 };
+    let col = (slot as i32) % cols;
+    let row = (slot as i32) / cols;
+    let x = grid.grid_width * (2 * col + 1) / (2 * cols);
+    let y = grid.grid_height * (2 * row + 1) / (2 * rows);
+    // top row heads right, bottom row heads left
+    let dir = if row == 0 { Direction::Right } else { Direction::Left };
+    PlayerState {
+        snake: vec![Pos { x, y }],
+        dir,
+        ..Default::default()
+    }
+}
+
+// A fresh snake on a given cell, heading towards the wider side of the board
+fn spawn_at(pos: Pos, grid: &GameConfig) -> PlayerState {
+    let dir = if pos.x < grid.grid_width / 2 { Direction::Right } else { Direction::Left };
+    PlayerState {
+        snake: vec![pos],
+        dir,
+        ..Default::default()
+    }
+}
+
+pub struct GameState {
+    // snapshots handed out so far, kept across rematches
+    pub seq: u64,
+    pub tick: u64,
+    // current tick length, starting from the configured one
+    pub tick_ms: u64,
+    // food eaten this round, which speeds things up
+    pub food_eaten: u32,
+    // game time played this round; pauses don't count
+    pub elapsed_ms: u64,
+    // rings of the board walled off so far in a battle royale or sudden death
+    pub shrunk: i32,
+    pub phase: MatchPhase,
+    // what's left of the countdown, in game time
+    pub countdown_ms: u64,
+    // in sudden death, the sides still tied for the win
+    pub contenders: Vec<usize>,
+    // what happened since the last take_events
+    pub events: Vec<GameEvent>,
+    pub players: Vec<PlayerState>,
+    // where every random choice comes from, started from `seed`; rematches carry on with it
+    pub seed: u64,
+    rng: Rng,
+    pub food: Vec<FoodItem>,
+    pub obstacles: Vec<Pos>,
+    pub power_ups: Vec<PowerUp>,
+    pub winner: Option<PlayerId>,
+    // how the round went, once it's over
+    pub result: Option<RoundEndMsg>,
+    // the series the round belongs to, carried over by rematches until it's decided
+    pub series: Option<SeriesScore>,
+    pub config: MatchConfig,
+}
+
+impl GameState {
+    pub fn new(names: &[String], config: MatchConfig) -> Self {
+        let seed = config.seed.unwrap_or_else(|| rand::thread_rng().r#gen());
+        Self::start(names, config, seed, Rng::new(seed))
+    }
+
+    fn start(names: &[String], config: MatchConfig, seed: u64, rng: Rng) -> Self {
+        let mut s = Self {
+            seq: 0,
+            tick: 0,
+            tick_ms: config.tick_ms,
+            food_eaten: 0,
+            elapsed_ms: 0,
+            shrunk: 0,
+            phase: if config.countdown_secs > 0 { MatchPhase::Countdown } else { MatchPhase::Playing },
+            countdown_ms: config.countdown_secs * 1000,
+            contenders: Vec::new(),
+            events: Vec::new(),
+            players: names.iter().enumerate()
+                .map(|(slot, name)| {
+                    let spawn = match config.map.as_ref().and_then(|map| map.spawns.get(slot)) {
+                        Some(&pos) => spawn_at(pos, &config.game),
+                        None => spawn_player(slot, names.len(), &config.game),
+                    };
+                    PlayerState { id: slot as PlayerId + 1, name: name.clone(), lives: config.lives, color: slot as u8, ..spawn }
+                })
+                .collect(),
+            seed,
+            rng,
+            food: Vec::new(),
+            obstacles: config.map.as_ref().map(|map| map.walls.clone()).unwrap_or_default(),
+            power_ups: Vec::new(),
+            winner: None,
+            result: None,
+            series: config.best_of.map(SeriesScore::new),
+            config,
+        };
+        s.place_obstacles();
+        s.respawn_food();
+        s
+    }
+
+    // Scatters straight wall segments over the board, away from the snakes
+    fn place_obstacles(&mut self) {
+        let heads: Vec<Pos> = self.players.iter().filter_map(|p| p.snake.first().copied()).collect();
+        let teleporters = self.config.map.as_ref().map(|map| map.teleporters.clone()).unwrap_or_default();
+        let blocked = |pos: &Pos| {
+            heads.iter().any(|h| h.y == pos.y || ((h.x - pos.x).abs() <= SPAWN_CLEARANCE && (h.y - pos.y).abs() <= SPAWN_CLEARANCE))
+                || teleporters.iter().any(|(a, b)| a == pos || b == pos)
+        };
+        // a crowded board may not have room for every segment
+        let mut placed = 0;
+        for _ in 0..self.config.obstacles * 20 {
+            if placed == self.config.obstacles {
+                break;
+            }
+            let len = self.rng.range(2..MAX_OBSTACLE_LEN + 1);
+            let (dx, dy) = if self.rng.coin() { (1, 0) } else { (0, 1) };
+            let start = Pos {
+                x: self.rng.range(0..self.config.game.grid_width - len * dx),
+                y: self.rng.range(0..self.config.game.grid_height - len * dy),
+            };
+            let segment: Vec<Pos> = (0..len).map(|i| Pos { x: start.x + i * dx, y: start.y + i * dy }).collect();
+            if segment.iter().any(|pos| blocked(pos) || self.obstacles.contains(pos)) {
+                continue;
+            }
+            self.obstacles.extend(segment);
+            placed += 1;
+        }
+    }
+
+    // The round is decided
+    pub fn is_over(&self) -> bool {
+        self.phase == MatchPhase::Finished
+    }
+
+    // Sets up a fresh round for the same players, in the same slots.
+    // Players who are away stay away.
+    pub fn reset(&mut self) {
+        let before = std::mem::take(&mut self.players);
+        let names: Vec<String> = before.iter().map(|p| p.name.clone()).collect();
+        let rng = self.rng.clone();
+        // the next round of a series that's still open; a decided one starts over
+        let series = self.series.take().filter(|s| !s.is_over());
+        *self = GameState { seq: self.seq, ..GameState::start(&names, self.config.clone(), self.seed, rng) };
+        if let Some(series) = series {
+            self.series = Some(SeriesScore { round: series.round + 1, ..series });
+        }
+        for (player, old) in self.players.iter_mut().zip(before) {
+            player.id = old.id;
+            if self.config.keep_scores {
+                player.score = old.score;
+            }
+            player.connected = old.connected;
+            player.team = old.team;
+            player.color = old.color;
+            player.verified = old.verified;
+            player.account = old.account;
+        }
+    }
+
+    // Takes a player's snake off the board for good. It counts as dead, and its body
+    // becomes leftovers if the server is set up for that.
+    pub fn remove_player(&mut self, slot: usize) {
+        let expires_tick = Some(self.tick + LEFTOVER_TICKS);
+        let player = &mut self.players[slot];
+        let body = std::mem::take(&mut player.snake);
+        player.dead = true;
+        player.connected = false;
+        player.respawn_tick = None;
+        if self.config.leave_food && !self.is_over() {
+            self.food.extend(body.into_iter().map(|pos| FoodItem { pos, kind: FoodKind::Leftover, expires_tick }));
+        }
+    }
+
+    // Whether a snake or a wall covers the cell
+    fn contains_any(&self, pos: &Pos) -> bool {
+        collision::occupied(&self.players, pos) || self.obstacles.contains(pos)
+    }
+
+    // A random cell out of those `free` accepts, found by a few guesses and then by going
+    // through the whole board; None when there's no such cell
+    fn random_cell(&mut self, free: impl Fn(&Self, &Pos) -> bool) -> Option<Pos> {
+        let (width, height) = (self.config.game.grid_width, self.config.game.grid_height);
+        for _ in 0..RANDOM_CELL_TRIES {
+            let pos = Pos { x: self.rng.range(0..width), y: self.rng.range(0..height) };
+            if free(self, &pos) {
+                return Some(pos);
+            }
+        }
+        let cells: Vec<Pos> = (0..height).flat_map(|y| (0..width).map(move |x| Pos { x, y })).filter(|pos| free(self, pos)).collect();
+        (!cells.is_empty()).then(|| cells[self.rng.index(cells.len())])
+    }
+
+    // Looks for a cell whose surroundings are clear of snakes so a newcomer isn't
+    // spawned into an instant collision. Falls back to any free cell on a crowded board,
+    // and to None on a full one.
+    fn find_spawn(&mut self) -> Option<Pos> {
+        const CLEARANCE: i32 = 3;
+        for _ in 0..200 {
+            let pos = Pos {
+                x: self.rng.range(0..self.config.game.grid_width),
+                y: self.rng.range(0..self.config.game.grid_height),
+            };
+            let crowded = (-CLEARANCE..=CLEARANCE).any(|dy| {
+                (-CLEARANCE..=CLEARANCE).any(|dx| {
+                    let p = Pos {
+                        x: (pos.x + dx).rem_euclid(self.config.game.grid_width),
+                        y: (pos.y + dy).rem_euclid(self.config.game.grid_height),
+                    };
+                    self.contains_any(&p)
+                })
+            });
+            if !crowded && !self.food.iter().any(|f| f.pos == pos) {
+                return Some(pos);
+            }
+        }
+        self.random_cell(|s, pos| !s.contains_any(pos) && !s.food.iter().any(|f| f.pos == *pos))
+    }
+
+    // Adds a player to a running match and returns its slot. On a board with no room left the
+    // snake waits to respawn until there is.
+    pub fn add_player(&mut self, name: String) -> usize {
+        // the map's spawn point for the slot if it is free, somewhere quiet otherwise
+        let slot = self.players.len();
+        let pos = self.config.map.as_ref()
+            .and_then(|map| map.spawns.get(slot).copied())
+            .filter(|pos| !self.contains_any(pos))
+            .or_else(|| self.find_spawn());
+        let team = smallest_team(self.config.teams, self.players.iter().map(|p| p.team));
+        let lives = self.config.lives;
+        // the first color nobody still playing has
+        let color = (0..MAX_PLAYERS as u8)
+            .find(|c| !self.players.iter().any(|p| p.connected && p.color == *c))
+            .unwrap_or((slot % MAX_PLAYERS) as u8);
+        let id = self.new_player_id();
+        let mut player = PlayerState { id, name, team, lives, color, ..spawn_at(pos.unwrap_or_default(), &self.config.game) };
+        if pos.is_none() {
+            player.snake.clear();
+            player.dead = true;
+            player.respawn_tick = Some(self.tick + 1);
+        }
+        self.players.push(player);
+        slot
+    }
+
+    // An id none of the players has had, for one who comes in now
+    pub fn new_player_id(&self) -> PlayerId {
+        self.players.iter().map(|p| p.id).max().unwrap_or(0) + 1
+    }
+
+    // Where the player with the id is in the list
+    pub fn slot_of(&self, id: PlayerId) -> Option<usize> {
+        self.players.iter().position(|p| p.id == id)
+    }
+
+    pub fn player(&self, id: PlayerId) -> Option<&PlayerState> {
+        self.players.iter().find(|p| p.id == id)
+    }
+
+    // Tops the board back up to the configured amount of normal food
+    fn respawn_food(&mut self) {
+        if self.phase == MatchPhase::SuddenDeath {
+            return;
+        }
+        while self.food.iter().filter(|f| f.kind == FoodKind::Normal).count() < self.config.food_count {
+            if !self.spawn_food(FoodKind::Normal, None) {
+                break;
+            }
+        }
+    }
+
+    // Puts one food item on a free cell, on the map's food cells if it has any. Returns
+    // false when the board has no free cell left, and the food is skipped.
+    fn spawn_food(&mut self, kind: FoodKind, expires_tick: Option<u64>) -> bool {
+        let zones = self.config.map.as_ref().map(|map| map.food_zones.clone()).unwrap_or_default();
+        let tries = if zones.is_empty() { 0 } else { RANDOM_CELL_TRIES };
+        let mut in_zone = None;
+        for _ in 0..tries {
+            let pos = zones[self.rng.index(zones.len())];
+            if self.is_free(&pos) {
+                in_zone = Some(pos);
+                break;
+            }
+        }
+        // snakes may cover every food cell; food goes anywhere then
+        let Some(pos) = in_zone.or_else(|| self.random_cell(|s, pos| s.is_free(pos))) else { return false };
+        self.food.push(FoodItem { pos, kind, expires_tick });
+        true
+    }
+
+    // Whether nothing at all is on the cell, so food or a power-up can go there
+    fn is_free(&self, pos: &Pos) -> bool {
+        let teleporter = self.config.map.as_ref().is_some_and(|map| map.teleporters.iter().any(|(a, b)| a == pos || b == pos));
+        !self.contains_any(pos)
+            && !teleporter
+            && !self.food.iter().any(|f| f.pos == *pos)
+            && !self.power_ups.iter().any(|p| p.pos == *pos)
+    }
+
+    // Whether moving on from `head` leaves the board, which kills when wrap-around is off
+    fn hits_border(&self, head: Pos, dir: Direction) -> bool {
+        !self.config.game.wrap && match dir {
+            Direction::Up => head.y == 0,
+            Direction::Down => head.y == self.config.game.grid_height - 1,
+            Direction::Left => head.x == 0,
+            Direction::Right => head.x == self.config.game.grid_width - 1,
+        }
+    }
+
+    fn apply_inputs(&mut self) {
+
+        let game = self.config.game;
+        for player in self.players.iter_mut() {
+            // one turn per tick; a 180 deg turn is dropped so the next one in line gets its place
+            while let Some(dir) = player.inputs.pop_front() {
+                if can_turn(&player.snake, player.dir, dir, &game) {
+                    player.dir = dir;
+                    break;
+                }
+            }
+        }
+    }
+
+    // Whether snake `i` moves in sub-step `sub` of the current tick. Snakes normally move
+    // in the first sub-step only; sped-up and boosting ones move in both and slowed ones
+    // every other tick.
+    fn moves(&self, i: usize, sub: u64) -> bool {
+        let player = &self.players[i];
+        if player.dead || !player.connected {
+            return false;
+        }
+        if player.has_effect(PowerUpKind::Speed) || self.boosts(i) {
+            return true;
+        }
+        sub == 0 && (!player.has_effect(PowerUpKind::Slow) || self.tick.is_multiple_of(2))
+    }
+
+    fn boosts(&self, i: usize) -> bool {
+        let player = &self.players[i];
+        player.boosting && player.snake.len() > 1
+    }
+
+    // Advances one tick; returns the result when this tick decided the round
+    pub fn step(&mut self) -> Option<RoundEndMsg> {
+        if self.is_over() {
+            return None;
+        }
+        // the countdown runs on ticks too, so it waits out pauses like the round does
+        if self.phase == MatchPhase::Countdown {
+            self.countdown_ms = self.countdown_ms.saturating_sub(self.tick_ms);
+            if self.countdown_ms == 0 {
+                self.phase = MatchPhase::Playing;
+            }
+            return None;
+        }
+
+        self.tick += 1;
+        self.respawn_due();
+        self.apply_inputs();
+        let tick = self.tick;
+        self.food.retain(|f| f.expires_tick.is_none_or(|until| until > tick));
+        self.power_ups.retain(|p| p.expires_tick > tick);
+        for player in self.players.iter_mut() {
+            player.effects.retain(|e| e.until_tick > tick);
+        }
+
+        // boosting costs length; a snake with nothing left to shed can't boost
+        for i in 0..self.players.len() {
+            if self.boosts(i) && tick.is_multiple_of(BOOST_SHED_TICKS) {
+                self.players[i].snake.pop();
+            }
+        }
+
+        let mut eaters = Vec::new();
+        for sub in 0..SUB_STEPS {
+            let moving: Vec<bool> = (0..self.players.len()).map(|i| self.moves(i, sub)).collect();
+            if moving.contains(&true) {
+                eaters.extend(self.advance(&moving));
+            }
+        }
+        for player in self.players.iter_mut() {
+            player.peak_length = player.peak_length.max(player.snake.len() as u32);
+        }
+        let sudden_death = self.phase == MatchPhase::SuddenDeath;
+        let golden = self.food.iter().any(|f| f.kind == FoodKind::Golden);
+        if self.config.golden_food && !sudden_death && !golden && self.rng.one_in(GOLDEN_FOOD_CHANCE) {
+            self.spawn_food(FoodKind::Golden, Some(tick + GOLDEN_FOOD_TICKS));
+        }
+        let poison = self.food.iter().filter(|f| f.kind == FoodKind::Poison).count();
+        if let Some(interval) = self.config.poison_interval
+            && tick.is_multiple_of(interval)
+            && poison < MAX_POISON
+            && !sudden_death
+        {
+            self.spawn_food(FoodKind::Poison, Some(tick + POISON_TICKS));
+        }
+        if self.config.power_ups && !sudden_death && tick.is_multiple_of(POWER_UP_INTERVAL) && self.power_ups.len() < MAX_POWER_UPS {
+            self.spawn_power_up();
+        }
+
+        self.speed_up(&eaters);
+        if self.config.corpses {
+            self.decompose();
+        }
+        self.elapsed_ms += self.tick_ms;
+        if self.config.game.mode == GameMode::BattleRoyale && tick.is_multiple_of(CLOSE_IN_TICKS) {
+            self.close_in();
+        }
+        if sudden_death && tick.is_multiple_of(SUDDEN_DEATH_CLOSE_IN_TICKS) {
+            self.close_in();
+        }
+        if self.phase == MatchPhase::Playing && self.time_left_ms() == Some(0) && self.leader().is_none() {
+            self.start_sudden_death();
+        }
+
+        let (winner, winning_team, reason) = self.round_result(&eaters)?;
+        Some(self.finish(winner, winning_team, reason))
+    }
+
+    // Ends a running round right away, decided on points
+    pub fn end_round(&mut self) -> Option<RoundEndMsg> {
+        if self.is_over() {
+            return None;
+        }
+        let (winner, winning_team, reason) = match self.leader() {
+            Some(i) => self.won_by(i, RoundEndReason::HighestScore),
+            None => (None, None, RoundEndReason::Draw),
+        };
+        Some(self.finish(winner, winning_team, reason))
+    }
+
+    // Every player's results so far, best score first
+    fn standings(&self) -> Vec<Standing> {
+        let mut standings: Vec<Standing> = self.players.iter()
+            .map(|p| Standing { player: p.id, name: p.name.clone(), team: p.team, score: p.score, kills: p.kills, peak_length: p.peak_length, rating: None, rating_change: None })
+            .collect();
+        standings.sort_by_key(|s| std::cmp::Reverse(s.score));
+        standings
+    }
+
+    fn finish(&mut self, winner: Option<usize>, winning_team: Option<u8>, reason: RoundEndReason) -> RoundEndMsg {
+        self.phase = MatchPhase::Finished;
+        self.winner = winner.map(|i| self.players[i].id);
+        if let Some(series) = self.series.as_mut() {
+            let winners: Vec<PlayerId> = match (self.winner, winning_team) {
+                (Some(id), _) => vec![id],
+                (None, Some(team)) => self.players.iter().filter(|p| p.team == Some(team)).map(|p| p.id).collect(),
+                (None, None) => Vec::new(),
+            };
+            series.add_win(&winners);
+        }
+        let round_end = RoundEndMsg {
+            winner: self.winner,
+            reason,
+            winning_team,
+            standings: self.standings(),
+            duration_ms: self.elapsed_ms,
+            series: self.series.clone(),
+        };
+        self.result = Some(round_end.clone());
+        round_end
+    }
+
+    // Moves the snakes marked in `moving` one cell; returns the ones that ate
+    fn advance(&mut self, moving: &[bool]) -> Vec<usize> {
+        // snakes running into the border die where they are
+        for (i, &moves) in moving.iter().enumerate() {
+            if moves && self.hits_border(self.players[i].snake[0], self.players[i].dir) {
+                self.kill(i, None);
+            }
+        }
+
+        // calculate new positions, the snakes that don't move stay where they are
+        let mut new_positions: Vec<Option<Pos>> = self.players.iter().enumerate()
+            .map(|(i, player)| {
+                if !moving[i] || player.dead { return None; }
+                let snake_head = *player.snake.first().unwrap();
+                let new_head = step_head(snake_head, player.dir, &self.config.game);
+                Some(self.config.map.as_ref().map_or(new_head, |map| map.teleport(new_head)))
+            })
+            .collect();
+
+        // like the border, a wall stops a snake where it is
+        let mut crashed = Vec::new();
+        for (i, pos) in new_positions.iter_mut().enumerate() {
+            if pos.is_some_and(|p| self.obstacles.contains(&p)) {
+                *pos = None;
+                crashed.push(i);
+            }
+        }
+        for i in crashed {
+            self.kill(i, None);
+        }
+
+        // detect collisions and derive player status; ghosts survive running into snakes.
+        // Poison doesn't make a snake grow, so it isn't food as far as collisions go.
+        let edible: Vec<Pos> = self.food.iter().filter(|f| f.kind != FoodKind::Poison).map(|f| f.pos).collect();
+        let players = &self.players;
+        let friendly_fire = self.config.friendly_fire;
+        let outcome = collision::resolve_with(players, &new_positions, &edible, |i, j| {
+            friendly_fire || players[i].team.is_none() || players[i].team != players[j].team
+        });
+        let mut killers = Vec::new();
+        for (i, dies) in outcome.dead.iter().enumerate() {
+            if *dies && !self.players[i].has_effect(PowerUpKind::Ghost) {
+                killers.extend(self.kill(i, outcome.killers[i]));
+            }
+        }
+
+        //process next steps for player's snake
+        for (i, pos) in new_positions.iter().enumerate() {
+            let Some(pos) = pos else { continue };
+            if !self.players[i].dead {
+                self.players[i].snake.insert(0, *pos);
+                if outcome.eaters.contains(&i)
+                    && let Some(at) = self.food.iter().position(|f| f.pos == *pos)
+                {
+                    let kind = self.food.remove(at).kind;
+                    self.players[i].score += kind.points();
+                    self.events.push(GameEvent::Eat { player: self.players[i].id, pos: *pos, kind });
+                }
+                else { self.players[i].snake.pop(); }
+                if let Some(at) = self.food.iter().position(|f| f.pos == *pos && f.kind == FoodKind::Poison) {
+                    self.food.remove(at);
+                    self.events.push(GameEvent::Eat { player: self.players[i].id, pos: *pos, kind: FoodKind::Poison });
+                    self.poison(i);
+                }
+                if let Some(at) = self.power_ups.iter().position(|p| p.pos == *pos) {
+                    let power_up = self.power_ups.remove(at);
+                    self.events.push(GameEvent::PowerUp { player: self.players[i].id, pos: *pos, kind: power_up.kind });
+                    self.apply_power_up(i, power_up.kind);
+                }
+            }
+        }
+        // killers grow only now: grown before moving, a tail could stay on a cell another
+        // snake was let into because it was about to be free
+        for k in killers {
+            let player = &mut self.players[k];
+            if !player.dead
+                && let Some(&tail) = player.snake.last()
+            {
+                player.snake.extend([tail; KILL_GROWTH]);
+            }
+        }
+        // respawn once every snake has moved so the new food can't land under a fresh head
+        if !outcome.eaters.is_empty() {
+            self.respawn_food();
+        }
+        outcome.eaters
+    }
+
+    // Shortens the tick once enough food was eaten or enough ticks went by
+    fn speed_up(&mut self, eaters: &[usize]) {
+        let speedup = self.config.speedup;
+        let mut steps = 0;
+        if let Some(every) = speedup.every_food {
+            let before = self.food_eaten / every;
+            self.food_eaten += eaters.len() as u32;
+            steps += self.food_eaten / every - before;
+        }
+        if speedup.every_ticks.is_some_and(|every| self.tick.is_multiple_of(every)) {
+            steps += 1;
+        }
+        if steps > 0 {
+            self.tick_ms = self.tick_ms.saturating_sub(steps as u64 * speedup.step_ms).max(speedup.min_tick_ms);
+        }
+    }
+
+    // Marks a snake dead and reports it. Running into another snake's body credits that
+    // snake with a point, and returns it to be given some length.
+    fn kill(&mut self, victim: usize, killer: Option<usize>) -> Option<usize> {
+        if self.players[victim].dead {
+            return None;
+        }
+        let player = &mut self.players[victim];
+        player.dead = true;
+        if let Some(lives) = player.lives.as_mut() {
+            *lives = lives.saturating_sub(1);
+        }
+        // with lives, a snake comes back until it runs out of them
+        let delay = match player.lives {
+            Some(0) => None,
+            Some(_) => Some(self.config.respawn_ticks.unwrap_or(LIVES_RESPAWN_TICKS)),
+            None => self.config.respawn_ticks,
+        };
+        if self.phase == MatchPhase::Playing {
+            player.respawn_tick = delay.map(|ticks| self.tick + ticks);
+        }
+        let killer = killer.filter(|k| *k != victim);
+        if let Some(k) = killer {
+            self.players[k].score += KILL_POINTS;
+            self.players[k].kills += 1;
+        }
+        let kill = KillEvent { victim: self.players[victim].id, killer: killer.map(|k| self.players[k].id) };
+        self.events.push(GameEvent::Death(kill));
+        killer
+    }
+
+    // Turns the bodies of snakes that died into food. Done once everyone moved, so a body
+    // still counts as a wall on the tick its snake dies.
+    fn decompose(&mut self) {
+        let expires_tick = Some(self.tick + CORPSE_TICKS);
+        for i in 0..self.players.len() {
+            if !self.players[i].dead || self.players[i].snake.is_empty() {
+                continue;
+            }
+            let body = std::mem::take(&mut self.players[i].snake);
+            // a snake that moved into the tail of one dying on the same tick is already there
+            for pos in body {
+                if !self.obstacles.contains(&pos) && !self.food.iter().any(|f| f.pos == pos) && !collision::occupied(&self.players, &pos) {
+                    self.food.push(FoodItem { pos, kind: FoodKind::Corpse, expires_tick });
+                }
+            }
+        }
+    }
+
+    // Brings back the snakes whose respawn tick came, somewhere quiet and briefly
+    // invulnerable
+    fn respawn_due(&mut self) {
+        let tick = self.tick;
+        for i in 0..self.players.len() {
+            if self.players[i].respawn_tick.is_none_or(|at| at > tick) {
+                continue;
+            }
+            // a full board puts the respawn off until there's room
+            let Some(pos) = self.find_spawn() else {
+                self.players[i].respawn_tick = Some(tick + 1);
+                continue;
+            };
+            let ghost_ticks = (RESPAWN_GHOST_MS / self.tick_ms.max(1)).max(1);
+            let fresh = spawn_at(pos, &self.config.game);
+            let player = &mut self.players[i];
+            player.snake = vec![pos; RESPAWN_LENGTH];
+            player.dir = fresh.dir;
+            player.inputs.clear();
+            player.boosting = false;
+            player.dead = false;
+            player.respawn_tick = None;
+            player.effects = vec![ActiveEffect { kind: PowerUpKind::Ghost, until_tick: tick + ghost_ticks }];
+        }
+    }
+
+    // Dead for the rest of the round, not just waiting to respawn
+    fn is_out(&self, i: usize) -> bool {
+        self.players[i].dead && self.players[i].respawn_tick.is_none()
+    }
+
+    // What happened since the last call, oldest first
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    // A snake that ate poison loses segments, or dies if it has nothing but its head
+    fn poison(&mut self, slot: usize) {
+        let player = &mut self.players[slot];
+        if player.snake.len() == 1 {
+            self.kill(slot, None);
+        } else {
+            let len = player.snake.len().saturating_sub(POISON_SHRINK).max(1);
+            player.snake.truncate(len);
+        }
+    }
+
+    fn apply_power_up(&mut self, slot: usize, kind: PowerUpKind) {
+        let until_tick = self.tick + POWER_UP_EFFECT_TICKS;
+        let effect = ActiveEffect { kind, until_tick };
+        match kind {
+            PowerUpKind::Shrink => {
+                let snake = &mut self.players[slot].snake;
+                snake.truncate(snake.len().div_ceil(2));
+            }
+            PowerUpKind::Slow => {
+                for (i, player) in self.players.iter_mut().enumerate() {
+                    if i != slot && !player.dead {
+                        player.effects.retain(|e| e.kind != kind);
+                        player.effects.push(effect);
+                    }
+                }
+            }
+            PowerUpKind::Speed | PowerUpKind::Ghost => {
+                let player = &mut self.players[slot];
+                player.effects.retain(|e| e.kind != kind);
+                player.effects.push(effect);
+            }
+        }
+    }
+
+    fn spawn_power_up(&mut self) {
+        const KINDS: [PowerUpKind; 4] = [PowerUpKind::Speed, PowerUpKind::Slow, PowerUpKind::Ghost, PowerUpKind::Shrink];
+        // give up quietly on a crowded board, the next interval tries again
+        for _ in 0..100 {
+            let pos = Pos {
+                x: self.rng.range(0..self.config.game.grid_width),
+                y: self.rng.range(0..self.config.game.grid_height),
+            };
+            if self.is_free(&pos) {
+                let kind = KINDS[self.rng.index(KINDS.len())];
+                self.power_ups.push(PowerUp { kind, pos, expires_tick: self.tick + POWER_UP_TICKS });
+                return;
+            }
+        }
+    }
+
+    // The combined score of a team's members
+    fn team_score(&self, team: u8) -> u32 {
+        self.players.iter().filter(|p| p.team == Some(team)).map(|p| p.score).sum()
+    }
+
+    // Who player `i` plays for: their team in a team room, themselves otherwise
+    fn side(&self, i: usize) -> usize {
+        match self.players[i].team {
+            Some(team) if self.config.teams > 0 => team as usize,
+            _ => i,
+        }
+    }
+
+    fn side_score(&self, i: usize) -> u32 {
+        match self.players[i].team {
+            Some(team) if self.config.teams > 0 => self.team_score(team),
+            _ => self.players[i].score,
+        }
+    }
+
+    // The round as won by player `i`, or by their team
+    fn won_by(&self, i: usize, reason: RoundEndReason) -> (Option<usize>, Option<u8>, RoundEndReason) {
+        match self.players[i].team {
+            Some(team) if self.config.teams > 0 => (None, Some(team), reason),
+            _ => (Some(i), None, reason),
+        }
+    }
+
+    // What is left on the clock of a timed round
+    fn time_left_ms(&self) -> Option<u64> {
+        (self.config.game.mode == GameMode::Timed).then(|| (self.config.time_limit_secs * 1000).saturating_sub(self.elapsed_ms))
+    }
+
+    // A player of the side with the most points, None while sides are tied for it
+    fn leader(&self) -> Option<usize> {
+        let best = (0..self.players.len()).max_by_key(|i| self.side_score(*i))?;
+        let tied = (0..self.players.len()).any(|i| self.side(i) != self.side(best) && self.side_score(i) == self.side_score(best));
+        (!tied).then_some(best)
+    }
+
+    // Decides the round if it is over: the winning player or team, and why. What ends a
+    // round depends on the mode; a round where the last snakes die together (or a solo
+    // snake dies) is a draw unless points decide it.
+    fn round_result(&mut self, eaters: &[usize]) -> Option<(Option<usize>, Option<u8>, RoundEndReason)> {
+        if self.phase == MatchPhase::SuddenDeath {
+            return self.sudden_death_result();
+        }
+        let mode = self.config.game.mode;
+        let target = match mode {
+            GameMode::Classic => self.config.score_target,
+            GameMode::ScoreRace => Some(self.config.score_target.unwrap_or(SCORE_RACE_TARGET)),
+            GameMode::Timed | GameMode::LastSnakeStanding | GameMode::BattleRoyale => None,
+        };
+        if let Some(target) = target
+            && let Some(&i) = eaters.iter().filter(|i| self.side_score(**i) >= target).max_by_key(|i| self.side_score(**i))
+        {
+            return Some(self.won_by(i, RoundEndReason::ScoreTarget));
+        }
+        let on_points = || match self.leader() {
+            Some(i) => self.won_by(i, RoundEndReason::HighestScore),
+            None => (None, None, RoundEndReason::Draw),
+        };
+        if self.time_left_ms() == Some(0) {
+            return Some(on_points());
+        }
+
+        let sides_of = |alive_only: bool| {
+            let mut sides: Vec<usize> = (0..self.players.len())
+                .filter(|i| !alive_only || !self.is_out(*i))
+                .map(|i| self.side(i))
+                .collect();
+            sides.sort_unstable();
+            sides.dedup();
+            sides
+        };
+        let last_standing = matches!(mode, GameMode::Classic | GameMode::LastSnakeStanding | GameMode::BattleRoyale);
+        match sides_of(true).as_slice() {
+            [] if last_standing => Some((None, None, RoundEndReason::Draw)),
+            [] => Some(on_points()),
+            [_] if last_standing && sides_of(false).len() > 1 => {
+                let survivor = (0..self.players.len()).find(|i| !self.is_out(*i))?;
+                Some(self.won_by(survivor, RoundEndReason::LastStanding))
+            }
+            _ => None,
+        }
+    }
+
+    // The clock ran out on a tie: the tied sides with a snake still alive play on
+    fn start_sudden_death(&mut self) {
+        let top = (0..self.players.len()).map(|i| self.side_score(i)).max().unwrap_or(0);
+        let mut contenders: Vec<usize> = (0..self.players.len())
+            .filter(|i| !self.players[*i].dead && self.side_score(*i) == top)
+            .map(|i| self.side(i))
+            .collect();
+        contenders.sort_unstable();
+        contenders.dedup();
+        self.contenders = contenders;
+        self.phase = MatchPhase::SuddenDeath;
+        // nobody comes back from here on
+        for player in self.players.iter_mut() {
+            player.respawn_tick = None;
+        }
+    }
+
+    // A contending side is out as soon as one of its snakes dies; the last one left wins
+    fn sudden_death_result(&mut self) -> Option<(Option<usize>, Option<u8>, RoundEndReason)> {
+        let lost: Vec<usize> = (0..self.players.len())
+            .filter(|i| self.players[*i].dead)
+            .map(|i| self.side(i))
+            .collect();
+        self.contenders.retain(|side| !lost.contains(side));
+        match self.contenders.as_slice() {
+            [] => Some((None, None, RoundEndReason::Draw)),
+            [side] => {
+                let survivor = (0..self.players.len()).find(|i| self.side(*i) == *side)?;
+                Some(self.won_by(survivor, RoundEndReason::SuddenDeath))
+            }
+            _ => None,
+        }
+    }
+
+    // Battle royale and sudden death: walls take the outermost free ring of the board. Snakes with their
+    // head in it die, and whatever lay there is gone.
+    fn close_in(&mut self) {
+        let (w, h) = (self.config.game.grid_width, self.config.game.grid_height);
+        let k = self.shrunk;
+        if (w - 2 * (k + 1)).min(h - 2 * (k + 1)) < MIN_ARENA_SIZE {
+            return;
+        }
+        self.shrunk += 1;
+        let ring = |p: &Pos| {
+            (p.x == k || p.x == w - 1 - k || p.y == k || p.y == h - 1 - k)
+                && (k..w - k).contains(&p.x)
+                && (k..h - k).contains(&p.y)
+        };
+        for x in k..w - k {
+            for y in k..h - k {
+                let pos = Pos { x, y };
+                if ring(&pos) && !self.obstacles.contains(&pos) {
+                    self.obstacles.push(pos);
+                }
+            }
+        }
+        for i in 0..self.players.len() {
+            if self.players[i].snake.first().is_some_and(ring) {
+                self.kill(i, None);
+            }
+        }
+        self.food.retain(|f| !ring(&f.pos));
+        self.power_ups.retain(|p| !ring(&p.pos));
+        self.respawn_food();
+    }
+
+    pub fn snapshot(&mut self) -> StateMsg {
+        self.seq += 1;
+        StateMsg {
+            seq: self.seq,
+            ack: 0,
+            tick: self.tick,
+            tick_ms: self.tick_ms,
+            players: self.players.clone(),
+            food_items: self.food.clone(),
+            obstacles: self.obstacles.clone(),
+            power_ups: self.power_ups.clone(),
+            time_left_ms: self.time_left_ms(),
+            phase: self.phase,
+            countdown_ms: (self.phase == MatchPhase::Countdown).then_some(self.countdown_ms),
+            winner: self.winner,
+            series: self.series.clone(),
+        }
+    }
+}
+
+// What one tick of a match came to
+pub struct TickResult {
+    pub events: Vec<GameEvent>,
+    // set when the tick decided the round
+    pub round_end: Option<RoundEndMsg>,
+    // the tick was part of the countdown, so nothing in it counts toward stats
+    pub counting_down: bool,
+}
+
+// Moves a match on by a tick: the bots in `bot_slots` pick their turns and the board steps.
+// The server and offline play both tick through here, and since it neither waits nor sends
+// anything a match can be played out tick by tick in a test.
+pub fn play_tick(state: &mut GameState, bot_slots: &[usize]) -> TickResult {
+    // nothing moves during the countdown, and bots wait for it like players
+    let counting_down = state.phase == MatchPhase::Countdown;
+    for &slot in bot_slots.iter().filter(|_| !counting_down) {
+        if let Some(dir) = ai::choose(state, slot, state.config.bot_difficulty) {
+            state.players[slot].queue_input(dir);
+        }
+    }
+    let round_end = state.step();
+    TickResult { events: state.take_events(), round_end, counting_down }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn cells(cells: &[(i32, i32)]) -> Vec<Pos> {
+        cells.iter().map(|&(x, y)| Pos { x, y }).collect()
+    }
+
+    // A match between the given snakes on an empty board, with its one food at `food`
+    fn state_with(snakes: &[(&[(i32, i32)], Direction)], food: (i32, i32), wrap: bool) -> GameState {
+        let names: Vec<String> = (0..snakes.len()).map(|i| format!("p{}", i)).collect();
+        let mut config = MatchConfig { max_players: snakes.len().max(2), countdown_secs: 0, ..MatchConfig::default() };
+        config.game.wrap = wrap;
+        let mut state = GameState::new(&names, config);
+        for (player, (snake, dir)) in state.players.iter_mut().zip(snakes) {
+            player.snake = cells(snake);
+            player.dir = *dir;
+        }
+        state.food = vec![FoodItem { pos: Pos { x: food.0, y: food.1 }, kind: FoodKind::Normal, expires_tick: None }];
+        state.obstacles.clear();
+        state
+    }
+
+    #[test]
+    fn snakes_move_and_turn() {
+        let mut state = state_with(&[(&[(5, 5), (4, 5), (3, 5)], Direction::Right)], (30, 20), true);
+        state.step();
+        assert_eq!(state.players[0].snake, cells(&[(6, 5), (5, 5), (4, 5)]));
+
+        // two presses within a tick are both played, one a tick
+        state.players[0].queue_input(Direction::Up);
+        state.players[0].queue_input(Direction::Left);
+        state.step();
+        state.step();
+        assert_eq!(state.players[0].snake, cells(&[(5, 4), (6, 4), (6, 5)]));
+        assert_eq!(state.players[0].dir, Direction::Left);
+
+        // straight back is never taken
+        state.players[0].queue_input(Direction::Right);
+        state.step();
+        assert_eq!(state.players[0].snake[0], Pos { x: 4, y: 4 });
+    }
+
+    // Walls on every cell but `keep`
+    fn fill_board(state: &mut GameState, keep: &[Pos]) {
+        let (width, height) = (state.config.game.grid_width, state.config.game.grid_height);
+        state.obstacles = (0..height).flat_map(|y| (0..width).map(move |x| Pos { x, y })).filter(|pos| !keep.contains(pos)).collect();
+    }
+
+    #[test]
+    fn food_is_skipped_on_a_full_board() {
+        let mut state = state_with(&[(&[(5, 5), (4, 5)], Direction::Right)], (30, 20), true);
+        state.config.food_count = 3;
+        fill_board(&mut state, &cells(&[(5, 5), (4, 5), (30, 20)]));
+        state.respawn_food();
+        assert_eq!(state.food.len(), 1);
+        assert!(!state.spawn_food(FoodKind::Golden, None));
+
+        // the one cell that frees up is found without guessing it
+        state.obstacles.retain(|pos| *pos != Pos { x: 59, y: 29 });
+        state.respawn_food();
+        assert_eq!(state.food.iter().map(|f| f.pos).collect::<Vec<_>>(), cells(&[(30, 20), (59, 29)]));
+    }
+
+    #[test]
+    fn spawns_wait_for_room_on_a_full_board() {
+        let config = MatchConfig { respawn_ticks: Some(1), countdown_secs: 0, ..MatchConfig::default() };
+        let mut state = GameState::new(&["alice".to_string()], config);
+        state.food.clear();
+        let alice = state.players[0].snake.clone();
+        fill_board(&mut state, &alice);
+        assert_eq!(state.find_spawn(), None);
+
+        // a newcomer waits off the board for a cell to free up
+        let bob = state.add_player("bob".to_string());
+        assert!(state.players[bob].snake.is_empty() && state.players[bob].dead);
+        state.respawn_due();
+        assert!(state.players[bob].respawn_tick.is_some_and(|at| at > state.tick));
+
+        state.obstacles.retain(|pos| *pos != Pos { x: 0, y: 0 });
+        state.tick += 1;
+        state.respawn_due();
+        assert_eq!(state.players[bob].snake.first(), Some(&Pos { x: 0, y: 0 }));
+        assert!(!state.players[bob].dead);
+    }
+
+    #[test]
+    fn players_keep_their_ids() {
+        let names = ["alice".to_string(), "bob".to_string()];
+        let mut state = GameState::new(&names, MatchConfig::default());
+        state.players[0].id = 7;
+        let carol = state.add_player("carol".to_string());
+        assert_eq!(state.players[carol].id, 8);
+        state.remove_player(carol);
+        assert_eq!(state.new_player_id(), 9);
+
+        // a rematch changes nothing about who is who
+        state.reset();
+        assert_eq!(state.players.iter().map(|p| p.id).collect::<Vec<_>>(), [7, 2, 8]);
+        assert_eq!(state.slot_of(2), Some(1));
+        assert_eq!(state.player(8).map(|p| p.name.as_str()), Some("carol"));
+    }
+
+    #[test]
+    fn rounds_count_down_before_anything_moves() {
+        let config = MatchConfig { countdown_secs: 1, tick_ms: 250, ..MatchConfig::default() };
+        let mut state = GameState::new(&["alice".to_string()], config);
+        let start = state.players[0].snake.clone();
+        assert_eq!(state.snapshot().countdown_ms, Some(1000));
+        // a turn pressed during the countdown waits for the start
+        state.players[0].queue_input(Direction::Up);
+        for left in [750, 500, 250] {
+            state.step();
+            assert_eq!((state.phase, state.countdown_ms, state.tick), (MatchPhase::Countdown, left, 0));
+            assert_eq!(state.players[0].snake, start);
+        }
+        state.step();
+        assert_eq!(state.phase, MatchPhase::Playing);
+        assert_eq!(state.snapshot().countdown_ms, None);
+        state.step();
+        assert_eq!(state.players[0].snake[0], Pos { x: start[0].x, y: start[0].y - 1 });
+
+        // and so do rematches
+        state.reset();
+        assert_eq!(state.phase, MatchPhase::Countdown);
+    }
+
+    #[test]
+    fn eating_grows_and_scores() {
+        let mut state = state_with(&[(&[(5, 5), (4, 5)], Direction::Right)], (6, 5), true);
+        state.step();
+        let player = &state.players[0];
+        assert_eq!(player.snake, cells(&[(6, 5), (5, 5), (4, 5)]));
+        assert_eq!(player.score, 1);
+        assert!(state.take_events().iter().any(|e| matches!(e, GameEvent::Eat { player: 1, kind: FoodKind::Normal, .. })));
+        // new food shows up somewhere the snake isn't
+        assert_eq!(state.food.len(), 1);
+        assert!(!state.players[0].snake.contains(&state.food[0].pos));
+    }
+
+    #[test]
+    fn edges_wrap_or_kill() {
+        let width = GameConfig::default().grid_width;
+        let snake: &[(i32, i32)] = &[(width - 1, 5), (width - 2, 5)];
+        let mut state = state_with(&[(snake, Direction::Right)], (30, 20), true);
+        state.step();
+        assert_eq!(state.players[0].snake[0], Pos { x: 0, y: 5 });
+        assert!(!state.players[0].dead);
+
+        let mut state = state_with(&[(snake, Direction::Right)], (30, 20), false);
+        state.step();
+        assert!(state.players[0].dead);
+    }
+
+    #[test]
+    fn collisions_kill() {
+        // head on: both die and nobody gets the credit
+        let mut state = state_with(&[(&[(10, 5), (9, 5)], Direction::Right), (&[(12, 5), (13, 5)], Direction::Left)], (30, 20), true);
+        state.step();
+        assert!(state.players.iter().all(|p| p.dead));
+        assert!(state.players.iter().all(|p| p.score == 0));
+
+        // into another snake's body: the other one scores the kill
+        let mut state = state_with(&[(&[(10, 5), (9, 5)], Direction::Right), (&[(11, 4), (11, 5), (11, 6)], Direction::Up)], (30, 20), true);
+        state.step();
+        assert!(state.players[0].dead);
+        assert!(!state.players[1].dead);
+        assert_eq!(state.players[1].score, KILL_POINTS);
+
+        // and into its own
+        let mut state = state_with(&[(&[(5, 5), (6, 5), (6, 6), (5, 6), (4, 6)], Direction::Down)], (30, 20), true);
+        state.step();
+        assert!(state.players[0].dead);
+    }
+
+    #[test]
+    fn rounds_end_with_standings() {
+        let mut state = state_with(&[(&[(10, 5), (9, 5)], Direction::Right), (&[(11, 4), (11, 5), (11, 6)], Direction::Up)], (30, 20), true);
+        let end = state.step().expect("the round is over");
+        assert_eq!((end.winner, end.duration_ms), (Some(2), state.tick_ms));
+        let rows: Vec<(PlayerId, u32, u32)> = end.standings.iter().map(|s| (s.player, s.score, s.kills)).collect();
+        assert_eq!(rows, [(2, KILL_POINTS, 1), (1, 0, 0)]);
+        assert_eq!(end.standings[0].peak_length, state.players[1].snake.len() as u32);
+        // kept for whoever turns up after the round ended
+        assert_eq!(state.result.as_ref().map(|r| r.standings.clone()), Some(end.standings));
+
+        state.reset();
+        assert!(state.result.is_none());
+        assert_eq!(state.players[1].kills, 0);
+    }
+
+    #[test]
+    fn series_carry_over_until_someone_takes_them() {
+        let names = ["alice".to_string(), "bob".to_string()];
+        let mut state = GameState::new(&names, MatchConfig { best_of: Some(3), ..MatchConfig::default() });
+        // alice, a draw, bob, then alice again
+        for (round, winner) in [(1, Some(0)), (2, None), (3, Some(1)), (4, Some(0))] {
+            assert_eq!(state.snapshot().series.map(|s| s.round), Some(round));
+            if let Some(slot) = winner {
+                state.players[slot].score = 1;
+            }
+            let end = state.end_round().unwrap();
+            let series = end.series.unwrap();
+            assert_eq!(series.is_over(), round == 4);
+            state.reset();
+        }
+        let series = state.series.as_ref().unwrap();
+        assert_eq!((series.round, series.wins.len()), (1, 0));
+    }
+
+    #[test]
+    fn team_wins_count_for_every_teammate() {
+        let mut series = SeriesScore::new(3);
+        series.add_win(&[1, 3]);
+        series.add_win(&[2]);
+        assert!(!series.is_over());
+        series.add_win(&[1, 3]);
+        assert_eq!((series.wins_of(1), series.wins_of(2), series.wins_of(3)), (2, 1, 2));
+        assert_eq!(series.champions, [1, 3]);
+    }
+
+    const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+    // What has to hold after every tick, whatever the players do
+    fn check_invariants(before: &[PlayerState], state: &GameState) -> Result<(), TestCaseError> {
+        let game = &state.config.game;
+        let alive: Vec<(usize, &PlayerState)> = state.players.iter().enumerate().filter(|(_, p)| !p.dead).collect();
+        let killers: Vec<usize> = state.events.iter()
+            .filter_map(|e| match e {
+                GameEvent::Death(KillEvent { killer: Some(k), .. }) => state.slot_of(*k),
+                _ => None,
+            })
+            .collect();
+        for &(i, player) in &alive {
+            for pos in &player.snake {
+                prop_assert!((0..game.grid_width).contains(&pos.x) && (0..game.grid_height).contains(&pos.y), "snake {} left the board at {:?}", i, pos);
+            }
+            // a one cell snake that scores a kill grows by stacking segments on its head
+            let mut body = player.snake.iter().skip_while(|&&pos| pos == player.snake[0]);
+            prop_assert!(!body.any(|&pos| pos == player.snake[0]), "snake {} runs through itself", i);
+            for &(j, other) in &alive {
+                if i != j {
+                    prop_assert!(!player.snake.iter().any(|pos| other.snake.contains(pos)), "snakes {} and {} overlap", i, j);
+                }
+            }
+            // a kill adds a few segments at once; otherwise a tick grows or moves a snake by one
+            if !killers.contains(&i) {
+                let change = player.snake.len() as i64 - before[i].snake.len() as i64;
+                prop_assert!(change.abs() <= 1, "snake {} changed length by {}", i, change);
+            }
+        }
+        for food in &state.food {
+            prop_assert!((0..game.grid_width).contains(&food.pos.x) && (0..game.grid_height).contains(&food.pos.y), "food off the board at {:?}", food.pos);
+            prop_assert!(!alive.iter().any(|(_, p)| p.snake.contains(&food.pos)), "food under a snake at {:?}", food.pos);
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn ticks_keep_the_board_sound(
+            seed in any::<u64>(),
+            size in 10..30i32,
+            players in 1..5usize,
+            food_count in 1..6usize,
+            wrap in any::<bool>(),
+            // per tick, what each player presses, if anything
+            presses in proptest::collection::vec(proptest::collection::vec(proptest::option::of(0..4usize), 4), 1..150),
+        ) {
+            let mut config = MatchConfig { max_players: players.max(2), food_count, seed: Some(seed), countdown_secs: 0, ..MatchConfig::default() };
+            config.game = GameConfig { wrap, ..config.game.resized(size, size) };
+            let names: Vec<String> = (0..players).map(|i| format!("p{}", i)).collect();
+            let mut state = GameState::new(&names, config);
+            for tick in presses {
+                for (player, press) in state.players.iter_mut().zip(tick) {
+                    if let Some(dir) = press {
+                        player.queue_input(DIRECTIONS[dir]);
+                    }
+                }
+                let before = state.players.clone();
+                state.step();
+                check_invariants(&before, &state)?;
+                state.take_events();
+            }
+        }
+    }
+
+    #[test]
+    fn bots_wait_out_the_countdown() {
+        let config = MatchConfig { countdown_secs: 1, tick_ms: 500, ..MatchConfig::default() };
+        let mut state = GameState::new(&["alice".to_string(), ai::bot_name(1)], config);
+        let start = state.players[1].snake.clone();
+        for _ in 0..2 {
+            let tick = play_tick(&mut state, &[1]);
+            assert!(tick.counting_down && tick.events.is_empty() && tick.round_end.is_none());
+            assert_eq!(state.players[1].snake, start);
+        }
+        let tick = play_tick(&mut state, &[1]);
+        assert!(!tick.counting_down);
+        assert_ne!(state.players[1].snake, start);
+    }
+
+    #[test]
+    fn the_tick_that_decides_the_round_says_so() {
+        let config = MatchConfig { countdown_secs: 0, score_target: Some(1), ..MatchConfig::default() };
+        let mut state = GameState::new(&["alice".to_string(), "bob".to_string()], config);
+        state.players[0].snake = vec![Pos { x: 5, y: 5 }];
+        state.players[0].dir = Direction::Right;
+        state.obstacles.clear();
+        state.food = vec![FoodItem { pos: Pos { x: 6, y: 5 }, kind: FoodKind::Normal, expires_tick: None }];
+
+        let tick = play_tick(&mut state, &[]);
+        assert!(tick.events.iter().any(|e| matches!(e, GameEvent::Eat { player, .. } if *player == state.players[0].id)));
+        let round_end = tick.round_end.expect("alice reached the target");
+        assert_eq!(round_end.winner, Some(state.players[0].id));
+        // and the round stays decided
+        assert!(play_tick(&mut state, &[]).round_end.is_none());
+    }
+}
@@ -1 +1,3 @@
 pub mod game_core;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod server;
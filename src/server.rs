@@ -0,0 +1,957 @@
+// The game server: rooms, matches and the connections that play them. The server binary runs
+// it on its own; a client hosting a game runs it on a background thread.
+
+mod accounts;
+mod bans;
+pub mod config;
+mod console;
+mod limits;
+mod lobby;
+pub mod map;
+mod matchmaking;
+mod metrics;
+mod rating;
+mod rooms;
+mod stats;
+mod udp;
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::iter;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, UdpSocket};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use socket2::{Domain, SockRef, Socket, Type};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::runtime::Runtime;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::time::{self, Interval, MissedTickBehavior};
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, info, info_span, warn, Instrument};
+use accounts::Accounts;
+use bans::Bans;
+use console::AdminCommand;
+use limits::RateLimit;
+use metrics::METRICS;
+use stats::Stats;
+use udp::UdpPeer;
+use crate::game_core::{ai, codec, unique_name};
+use crate::game_core::sim::{play_tick, smallest_team, GameState, MatchConfig, TickResult, MAX_STATE_EVERY};
+use crate::game_core::{BoardMap, ClientMsg, EmoteMsg, ErrorCode, ErrorMsg, MatchPhase, PauseMsg, PlayerId, RematchMsg, RoundEndMsg, ServerMsg, SessionMsg, SessionToken, StateMsg, WireFormat, Compression, PROTOCOL_VERSION};
+
+// Everything the connection tasks report back to the router and the rooms
+pub enum NetEvent {
+    Connected(u32, Outbox, IpAddr),
+    // the connection's wire format is settled, always before its first Message
+    Handshake(u32, WireFormat),
+    // the client's UDP address is known, states can go there from now on
+    UdpReady(u32, UdpPeer),
+    // the account the connection logged in to, always before its first Message
+    LoggedIn(u32, String),
+    Message(u32, ClientMsg),
+    Disconnected(u32),
+    // typed into the server's console
+    Admin(AdminCommand),
+}
+
+// Encoded frames queued for a connection's writer task, so a slow socket never stalls the tick loop
+pub type Outbox = mpsc::UnboundedSender<Arc<[u8]>>;
+
+// What a room hears: the events of its own connections, and the console commands the
+// router passes on to every room
+pub struct Inbox {
+    events: mpsc::UnboundedReceiver<NetEvent>,
+    admin: broadcast::Receiver<AdminCommand>,
+}
+
+impl Inbox {
+    pub fn new(events: mpsc::UnboundedReceiver<NetEvent>, admin: broadcast::Receiver<AdminCommand>) -> Self {
+        Inbox { events, admin }
+    }
+
+    // Waits for the next event; None once the router closed the room
+    pub async fn recv(&mut self) -> Option<NetEvent> {
+        loop {
+            tokio::select! {
+                event = self.events.recv() => return event,
+                command = self.admin.recv() => match command {
+                    Ok(command) => return Some(NetEvent::Admin(command)),
+                    // commands a busy room missed are missed
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                },
+            }
+        }
+    }
+
+    // An event that's already there, without waiting
+    pub fn try_recv(&mut self) -> Option<NetEvent> {
+        self.events.try_recv().ok().or_else(|| self.admin.try_recv().ok().map(NetEvent::Admin))
+    }
+}
+
+pub struct Client {
+    pub id: u32,
+    pub outbox: Outbox,
+    pub ip: IpAddr,
+    pub name: Option<String>,
+    // given out in the lobby with the name, and kept for the match
+    pub player_id: PlayerId,
+    pub ready: bool,
+    pub token: Option<SessionToken>,
+    // None until the client's first message picked one; nothing is sent before that
+    pub format: Option<WireFormat>,
+    // set once the client registered for states by UDP
+    pub udp: Option<UdpPeer>,
+    // picked in the lobby when the room plays in teams
+    pub team: Option<u8>,
+    // asked for a state only every this many ticks
+    pub state_every: u64,
+    // the account it logged in to, None for guests
+    pub account: Option<String>,
+}
+
+impl Client {
+    pub fn new(id: u32, outbox: Outbox, ip: IpAddr) -> Self {
+        Client { id, outbox, ip, name: None, player_id: 0, ready: false, token: None, format: None, udp: None, team: None, state_every: 1, account: None }
+    }
+}
+
+// Connections that send nothing for this long are considered dead, clients ping every PING_INTERVAL_MS
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Emotes from one player closer together than this are dropped
+const EMOTE_COOLDOWN: Duration = Duration::from_secs(1);
+
+// How long a dropped player's slot is held for a Rejoin
+pub const REJOIN_GRACE: Duration = Duration::from_secs(30);
+
+pub fn new_session_token() -> SessionToken {
+    SessionToken(format!("{:016x}", rand::thread_rng().r#gen::<u64>()))
+}
+
+// Queue one message for a single client, false if its connection is gone
+pub fn send(client: &mut Client, msg: &ServerMsg) -> bool {
+    let Some(format) = client.format else { return true };
+    client.outbox.send(codec::encode(format, msg).unwrap().into()).is_ok()
+}
+
+// Tells a client what board the match is played on, false if its connection is gone
+pub fn send_board(client: &mut Client, config: &MatchConfig) -> bool {
+    send(client, &ServerMsg::Config(config.game))
+        && config.map.as_ref().is_none_or(|map| send(client, &ServerMsg::Map(BoardMap::clone(map))))
+}
+
+// Queue one message for every client, encoded once per format, dropping those whose connection is gone
+pub fn broadcast(clients: &mut Vec<Client>, msg: &ServerMsg) {
+    let mut encoded: HashMap<WireFormat, Arc<[u8]>> = HashMap::new();
+    clients.retain_mut(|c| {
+        let Some(format) = c.format else { return true };
+        let bytes = encoded.entry(format).or_insert_with(|| codec::encode(format, msg).unwrap().into());
+        c.outbox.send(bytes.clone()).is_ok()
+    });
+}
+
+// Sends a snapshot to every client, each with the ack for its own inputs. With `every`, it only
+// goes to clients whose rate comes round on this tick: every that many ticks, or fewer if they
+// asked. Clients registered for UDP get it as a datagram when it fits in one. Snapshots are
+// always whole, never deltas, so any of them can be skipped or lost.
+fn broadcast_state(clients: &mut Vec<Client>, snapshot: StateMsg, acks: &HashMap<u32, u32>, every: Option<u64>) {
+    clients.retain_mut(|c| {
+        if every.is_some_and(|every| !snapshot.tick.is_multiple_of(every.max(c.state_every))) {
+            return true;
+        }
+        let ack = acks.get(&c.id).copied().unwrap_or(0);
+        let msg = ServerMsg::State(StateMsg { ack, ..snapshot.clone() });
+        if let (Some(peer), Some(format)) = (&c.udp, c.format) {
+            let frame = codec::encode(format, &msg).unwrap();
+            if frame.len() <= udp::MAX_DATAGRAM {
+                peer.send(&frame);
+                return true;
+            }
+        }
+        send(c, &msg)
+    });
+}
+
+// Writes queued frames until the socket fails or the Client is dropped, then tells the reader
+// by dropping `closed`, so the connection ends either way. Frames are packed here, so
+// compressing never holds up a room's ticks.
+fn spawn_writer<W: AsyncWrite + Unpin + Send + 'static>(mut writer: W, compression: Compression, closed: oneshot::Sender<()>) -> Outbox {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Arc<[u8]>>();
+    tokio::spawn(async move {
+        while let Some(frame) = rx.recv().await {
+            let packed;
+            let bytes = match compression {
+                Compression::None => &frame[..],
+                compression => {
+                    packed = codec::pack(compression, &frame);
+                    &packed[..]
+                }
+            };
+            if writer.write_all(bytes).await.and(writer.flush().await).is_err() {
+                break;
+            }
+            METRICS.sent(bytes.len());
+        }
+        let _ = writer.shutdown().await;
+        drop(closed);
+    });
+    tx
+}
+
+// What the server offers a connection during the handshake
+#[derive(Clone)]
+struct Handshake {
+    force_json: bool,
+    // whether clients asking for compression get it
+    compress: bool,
+    // the server's seed, told to every client
+    seed: u64,
+    // None when UDP is turned off
+    udp: Option<udp::Tickets>,
+}
+
+// Reads one frame of at most MAX_CLIENT_FRAME_LEN bytes, as codec::read_frame_limited does
+// for blocking readers; Ok(None) once the client hung up
+async fn read_frame<R: AsyncBufRead + Unpin>(reader: &mut R, format: WireFormat) -> io::Result<Option<Vec<u8>>> {
+    let max_len = limits::MAX_CLIENT_FRAME_LEN;
+    let too_large = || io::Error::new(io::ErrorKind::InvalidData, "frame too large");
+    match format {
+        WireFormat::Json => loop {
+            let mut line = Vec::new();
+            // reading one byte past the limit is enough to tell the line is too long
+            if (&mut *reader).take(max_len as u64 + 1).read_until(b'\n', &mut line).await? == 0 {
+                return Ok(None);
+            }
+            METRICS.received(line.len());
+            if line.len() > max_len {
+                return Err(too_large());
+            }
+            while line.last().is_some_and(|b| b.is_ascii_whitespace()) {
+                line.pop();
+            }
+            if !line.is_empty() {
+                return Ok(Some(line));
+            }
+        },
+        WireFormat::Binary => {
+            let len = match reader.read_u32_le().await {
+                Ok(len) => len as usize,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            if len > max_len {
+                return Err(too_large());
+            }
+            let mut payload = vec![0u8; len];
+            reader.read_exact(&mut payload).await?;
+            METRICS.received(4 + len);
+            Ok(Some(payload))
+        }
+    }
+}
+
+// Reads the client's Hello and answers it; returns the format and compression for the rest
+// of the connection, or None when the client can't be served
+async fn accept_hello<R, W>(reader: &mut R, writer: &mut W, handshake: &Handshake) -> Option<(WireFormat, Compression, bool)>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let frame = time::timeout(IDLE_TIMEOUT, read_frame(reader, WireFormat::Json)).await.ok()?.ok()??;
+    let (format, udp, compression) = match codec::decode::<ClientMsg>(WireFormat::Json, &frame) {
+        Ok(ClientMsg::Hello { protocol_version, format, udp, compression }) if protocol_version == PROTOCOL_VERSION => (format, udp, compression),
+        // anything else comes from a build that speaks another protocol
+        _ => {
+            warn!("Rejected: protocol mismatch");
+            let mismatch = codec::encode(WireFormat::Json, &ServerMsg::VersionMismatch { server_version: PROTOCOL_VERSION }).ok()?;
+            let _ = writer.write_all(&mismatch).await;
+            let _ = writer.shutdown().await;
+            return None;
+        }
+    };
+    let format = if handshake.force_json { WireFormat::Json } else { format };
+    let compression = if handshake.compress { compression } else { Compression::None };
+    let welcome = ServerMsg::Welcome { protocol_version: PROTOCOL_VERSION, format, seed: handshake.seed, compression };
+    writer.write_all(&codec::encode(WireFormat::Json, &welcome).ok()?).await.ok()?;
+    writer.flush().await.ok()?;
+    Some((format, compression, udp && handshake.udp.is_some()))
+}
+
+// Plays one connection through: the handshake, then its messages to the router until it
+// hangs up, goes quiet, floods or is dropped by the server
+async fn serve_connection<S>(stream: S, client_id: u32, ip: IpAddr, tx_events: mpsc::UnboundedSender<NetEvent>, handshake: Handshake)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = tokio::io::BufReader::new(reader);
+    let Some((format, compression, udp)) = accept_hello(&mut reader, &mut writer, &handshake).await else { return };
+    let (closed_tx, mut closed) = oneshot::channel();
+    let outbox = spawn_writer(writer, compression, closed_tx);
+    // the ticket goes out once the router knows the connection, so its UdpReady can't come first
+    let ticket = handshake.udp.as_ref().filter(|_| udp).map(|tickets| tickets.issue(client_id));
+    if tx_events.send(NetEvent::Connected(client_id, outbox.clone(), ip)).is_err() {
+        return;
+    }
+    let _ = tx_events.send(NetEvent::Handshake(client_id, format));
+    if let Some(ticket) = ticket
+        && let Ok(frame) = codec::encode(format, &ServerMsg::Udp { ticket })
+    {
+        let _ = outbox.send(frame.into());
+    }
+    drop(outbox);
+
+    let mut rate = RateLimit::new(limits::MESSAGES_PER_SEC, limits::MESSAGE_BURST, Instant::now());
+    loop {
+        let read = tokio::select! {
+            read = time::timeout(IDLE_TIMEOUT, read_frame(&mut reader, format)) => read,
+            // the server let go of the connection
+            _ = &mut closed => break,
+        };
+        match read {
+            Ok(Ok(Some(frame))) => {
+                if !rate.allow(Instant::now()) {
+                    warn!("Disconnected: sending too fast");
+                    METRICS.dropped();
+                    break;
+                }
+                let Ok(msg) = codec::decode::<ClientMsg>(format, &frame) else { continue };
+                let _ = tx_events.send(NetEvent::Message(client_id, msg));
+            }
+            Ok(Ok(None)) => break,
+            Ok(Err(e)) if e.kind() == io::ErrorKind::InvalidData => {
+                warn!("Disconnected: {}", e);
+                METRICS.dropped();
+                break;
+            }
+            Ok(Err(_)) => {
+                METRICS.dropped();
+                break;
+            }
+            // a peer that went away without closing the socket only shows up as silence
+            Err(_) => {
+                info!("Timed out");
+                METRICS.dropped();
+                break;
+            }
+        }
+    }
+    let _ = tx_events.send(NetEvent::Disconnected(client_id));
+}
+
+// Turns away a banned address. Its Hello is read first, so the socket isn't reset
+// before the Kick with the reason gets through.
+async fn refuse<S: AsyncRead + AsyncWrite + Unpin>(stream: S, reason: String) {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let _ = time::timeout(IDLE_TIMEOUT, read_frame(&mut tokio::io::BufReader::new(reader), WireFormat::Json)).await;
+    if let Ok(kick) = codec::encode(WireFormat::Json, &ServerMsg::Kick { reason }) {
+        let _ = writer.write_all(&kick).await;
+    }
+    let _ = writer.shutdown().await;
+}
+
+// Turns a new connection away if its address is banned, and lets it play otherwise
+async fn admit<S>(stream: S, client_id: u32, ip: IpAddr, tx_events: mpsc::UnboundedSender<NetEvent>, handshake: Handshake, bans: Bans)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    match bans.find(None, ip) {
+        Some(ban) => {
+            warn!(reason = %ban.reason, "Rejected: banned");
+            refuse(stream, ban.reason).await;
+        }
+        None => serve_connection(stream, client_id, ip, tx_events, handshake).await,
+    }
+}
+
+// Takes connections for as long as the server runs, each on a task of its own
+async fn accept_connections(listener: tokio::net::TcpListener, tx_events: mpsc::UnboundedSender<NetEvent>, handshake: Handshake, bans: Bans, tls: Option<TlsAcceptor>) {
+    let mut next_id = 1;
+    loop {
+        let Ok((socket, addr)) = listener.accept().await else { continue };
+        // an IPv4 client of a dual-stack listener shows up as ::ffff:a.b.c.d; bans and
+        // limits go by the plain IPv4 address
+        let ip = addr.ip().to_canonical();
+        let _ = socket.set_nodelay(true);
+        info!(id = next_id, %addr, "Client connected");
+        METRICS.connected();
+        let (tx_events, handshake, bans, tls) = (tx_events.clone(), handshake.clone(), bans.clone(), tls.clone());
+        let id = next_id;
+        tokio::spawn(async move {
+            // the TLS handshake happens on the connection's own task, so a slow one holds up nobody
+            match tls {
+                Some(acceptor) => {
+                    if let Ok(Ok(stream)) = time::timeout(IDLE_TIMEOUT, acceptor.accept(socket)).await {
+                        admit(stream, id, ip, tx_events, handshake, bans).await;
+                    }
+                }
+                None => admit(socket, id, ip, tx_events, handshake, bans).await,
+            }
+            METRICS.disconnected();
+        }.instrument(info_span!("conn", id, %addr)));
+        next_id += 1;
+    }
+}
+
+// What the clients are told about the pause. The match is paused by vote, and on its own
+// while it waits for dropped players to rejoin. A finished round has nothing to pause.
+fn pause_msg(state: &GameState, paused_by_vote: bool, dropped: &HashMap<usize, Instant>, votes: &HashSet<usize>, slots: &HashMap<u32, usize>) -> PauseMsg {
+    let mut waiting_for: Vec<String> = dropped.keys().map(|slot| state.players[*slot].name.clone()).collect();
+    waiting_for.sort();
+    PauseMsg {
+        paused: !state.is_over() && (paused_by_vote || !dropped.is_empty()),
+        waiting_for,
+        votes: slots.values().filter(|slot| votes.contains(slot)).count() as u8,
+        needed: (slots.len() / 2 + 1) as u8,
+    }
+}
+
+// A snapshot the way the clients see it, which says so in its phase while the match is paused
+fn snapshot(state: &mut GameState, paused: bool) -> StateMsg {
+    let mut msg = state.snapshot();
+    if paused {
+        msg.phase = MatchPhase::Paused;
+    }
+    msg
+}
+
+// The slots whose players' stats are counted: everyone but the bots and those who left
+fn counted_slots(state: &GameState, bot_slots: &[usize], gone: &HashSet<usize>) -> Vec<usize> {
+    (0..state.players.len()).filter(|slot| !bot_slots.contains(slot) && !gone.contains(slot)).collect()
+}
+
+// Tells everyone how the round went and counts it in the players' stats and ratings
+fn announce_round_end(clients: &mut Vec<Client>, state: &mut GameState, mut round_end: RoundEndMsg, stats: &Stats, counted: &[usize]) {
+    match (round_end.winner, round_end.winning_team) {
+        (Some(w), _) => info!(winner = %state.player(w).map_or("?", |p| p.name.as_str()), "Round over"),
+        (None, Some(t)) => info!(team = t + 1, "Round over"),
+        (None, None) => info!("Round over, no winner"),
+    }
+    stats.count_round(state, &mut round_end, counted);
+    stats.save();
+    // with the ratings in, for whoever joins before the rematch
+    state.result = Some(round_end.clone());
+    broadcast(clients, &ServerMsg::RoundEnd(round_end));
+}
+
+// A timer for the match's ticks, the first of them a whole tick from now
+fn tick_timer(tick_ms: u64) -> Interval {
+    let period = Duration::from_millis(tick_ms);
+    let mut ticker = time::interval_at(time::Instant::now() + period, period);
+    // a tick that ran late is caught up on, so the match keeps its pace
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Burst);
+    ticker
+}
+
+// Ticks one match until every client has left
+pub async fn run_match(inbox: &mut Inbox, room_code: &str, mut clients: Vec<Client>, config: MatchConfig, stats: &Stats) {
+    // bots take the slots after the players', as many as are left free
+    let bot_count = config.bots.min(config.max_players.saturating_sub(clients.len()));
+    let bot_slots: Vec<usize> = (clients.len()..clients.len() + bot_count).collect();
+    let names: Vec<String> = clients.iter()
+        .map(|c| c.name.clone().unwrap_or_default())
+        .chain((1..=bot_count).map(ai::bot_name))
+        .collect();
+    let mut slots: HashMap<u32, usize> = clients.iter().enumerate().map(|(slot, c)| (c.id, slot)).collect();
+    let mut sessions: HashMap<SessionToken, usize> = clients.iter().enumerate()
+        .filter_map(|(slot, c)| c.token.clone().map(|t| (t, slot)))
+        .collect();
+    // slots whose connection dropped, waiting for a Rejoin
+    let mut dropped: HashMap<usize, Instant> = HashMap::new();
+    let mut state = GameState::new(&names, config.clone());
+    for (player, client) in state.players.iter_mut().zip(&clients) {
+        player.id = client.player_id;
+        player.team = client.team;
+        player.account = client.account.clone();
+        player.verified = client.account.is_some();
+    }
+    // the bots come after the players, with ids none of them has
+    for &slot in &bot_slots {
+        state.players[slot].id = state.new_player_id();
+        state.players[slot].team = smallest_team(config.teams, state.players.iter().map(|p| p.team));
+    }
+    // slots that want another round
+    let mut rematch_votes: HashSet<usize> = HashSet::new();
+    // last input seq taken from each connection
+    let mut input_seqs: HashMap<u32, u32> = HashMap::new();
+    // when each slot last sent an emote that got passed on
+    let mut last_emotes: HashMap<usize, Instant> = HashMap::new();
+    // slots of players who left for good; they sit out the rest of the match
+    let mut gone: HashSet<usize> = HashSet::new();
+    // slots asking to flip the pause; a majority, or the host in slot 0, flips it
+    let mut pause_votes: HashSet<usize> = HashSet::new();
+    let mut paused_by_vote = false;
+    let mut last_pause = pause_msg(&state, paused_by_vote, &dropped, &pause_votes, &slots);
+
+    let mut ticker = tick_timer(state.tick_ms);
+
+    loop {
+        let mut votes_changed = false;
+        let mut pause_changed = false;
+        // wait for the next tick unless the network has something for us, then drain the rest
+        let mut due = false;
+        let first = tokio::select! {
+            event = inbox.recv() => match event {
+                Some(event) => Some(event),
+                None => return, // room closed
+            },
+            _ = ticker.tick() => {
+                due = true;
+                None
+            }
+        };
+        for event in first.into_iter().chain(iter::from_fn(|| inbox.try_recv())) {
+            match event {
+                // latecomers get a snake once they send Join
+                NetEvent::Connected(id, outbox, ip) => {
+                    // past the players here, only as many as could be coming back for a held slot
+                    if clients.len() >= config.max_players + dropped.len() {
+                        info!(id, "Rejected: match is full");
+                    } else {
+                        clients.push(Client { ready: true, ..Client::new(id, outbox, ip) });
+                    }
+                }
+                NetEvent::Handshake(id, format) => {
+                    if let Some(c) = clients.iter_mut().find(|c| c.id == id) {
+                        c.format = Some(format);
+                    }
+                }
+                NetEvent::UdpReady(id, peer) => {
+                    if let Some(c) = clients.iter_mut().find(|c| c.id == id) {
+                        c.udp = Some(peer);
+                    }
+                }
+                NetEvent::LoggedIn(id, account) => {
+                    if let Some(c) = clients.iter_mut().find(|c| c.id == id) {
+                        c.account = Some(account);
+                    }
+                }
+                NetEvent::Disconnected(id) => {
+                    clients.retain(|c| c.id != id);
+                    input_seqs.remove(&id);
+                    if let Some(slot) = slots.remove(&id) {
+                        info!(id, name = %state.players[slot].name, "Dropped, holding the slot for a rejoin");
+                        dropped.insert(slot, Instant::now());
+                        state.players[slot].connected = false;
+                        state.players[slot].boosting = false;
+                        rematch_votes.remove(&slot);
+                        pause_votes.remove(&slot);
+                        votes_changed = true;
+                        pause_changed = true;
+                        broadcast(&mut clients, &ServerMsg::Info(format!("{} lost connection", state.players[slot].name)));
+                    }
+                }
+                NetEvent::Message(id, ClientMsg::Rejoin { token, .. }) => {
+                    let Some(client) = clients.iter_mut().find(|c| c.id == id) else { continue };
+                    let Some(&slot) = sessions.get(&token) else { continue };
+                    if client.name.is_some() || dropped.remove(&slot).is_none() {
+                        continue;
+                    }
+                    info!(id, name = %state.players[slot].name, "Rejoined");
+                    client.name = Some(state.players[slot].name.clone());
+                    client.token = Some(token.clone());
+                    slots.insert(id, slot);
+                    state.players[slot].connected = true;
+                    pause_changed = true;
+                    // the same session again, which tells the client the name it plays under
+                    let session = SessionMsg { room_code: room_code.to_string(), token, name: state.players[slot].name.clone(), player_id: state.players[slot].id };
+                    let sent = send_board(client, &config)
+                        && send(client, &ServerMsg::Session(session))
+                        && send(client, &ServerMsg::State(snapshot(&mut state, last_pause.paused)))
+                        && state.result.as_ref().is_none_or(|r| send(client, &ServerMsg::RoundEnd(r.clone())));
+                    if !sent {
+                        clients.retain(|c| c.id != id);
+                    }
+                    broadcast(&mut clients, &ServerMsg::Info(format!("{} is back", state.players[slot].name)));
+                }
+                NetEvent::Message(id, ClientMsg::Join { name, .. }) => {
+                    let Some(client) = clients.iter_mut().find(|c| c.id == id) else { continue };
+                    if client.name.is_some() {
+                        continue;
+                    }
+                    // slots held for a rejoin are as taken as the ones being played
+                    if slots.len() + dropped.len() >= config.max_players {
+                        info!(id, "Rejected: match is full");
+                        send(client, &ServerMsg::Error(ErrorMsg { code: ErrorCode::RoomFull, message: format!("Room {} is full", room_code) }));
+                        clients.retain(|c| c.id != id);
+                        continue;
+                    }
+                    let name = unique_name(&name, state.players.iter().map(|p| p.name.as_str()));
+                    info!(id, %name, "Joined in progress");
+                    let info = ServerMsg::Info(format!("{} joined", name));
+                    let token = new_session_token();
+                    let slot = state.add_player(name.clone());
+                    state.players[slot].account = client.account.clone();
+                    state.players[slot].verified = client.account.is_some();
+                    client.name = Some(name.clone());
+                    client.token = Some(token.clone());
+                    sessions.insert(token.clone(), slot);
+                    slots.insert(id, slot);
+                    // don't make them wait a tick for the first picture
+                    let session = SessionMsg { room_code: room_code.to_string(), token, name, player_id: state.players[slot].id };
+                    let sent = send_board(client, &config)
+                        && send(client, &ServerMsg::Session(session))
+                        && send(client, &ServerMsg::State(snapshot(&mut state, last_pause.paused)))
+                        && (!last_pause.paused || send(client, &ServerMsg::Pause(last_pause.clone())))
+                        // a round that's already over shows its results
+                        && state.result.as_ref().is_none_or(|r| send(client, &ServerMsg::RoundEnd(r.clone())));
+                    if !sent {
+                        clients.retain(|c| c.id != id);
+                    }
+                    broadcast(&mut clients, &info);
+                }
+                NetEvent::Message(id, ClientMsg::Input { dir, seq }) => {
+                    let last = input_seqs.get(&id).copied().unwrap_or(0);
+                    if let Some(&slot) = slots.get(&id)
+                        && seq > last
+                    {
+                        input_seqs.insert(id, seq);
+                        state.players[slot].queue_input(dir);
+                        debug!(name = %state.players[slot].name, %dir, "Input")
+                    }
+                }
+                NetEvent::Message(id, ClientMsg::Ping { sent_ms, rtt_ms }) => {
+                    if let Some(c) = clients.iter_mut().find(|c| c.id == id) {
+                        send(c, &ServerMsg::Pong { sent_ms });
+                    }
+                    if let Some(&slot) = slots.get(&id) {
+                        state.players[slot].ping_ms = rtt_ms;
+                    }
+                }
+                NetEvent::Message(id, ClientMsg::StateRate { every }) => {
+                    if let Some(c) = clients.iter_mut().find(|c| c.id == id) {
+                        c.state_every = every.clamp(1, MAX_STATE_EVERY);
+                    }
+                }
+                NetEvent::Message(id, ClientMsg::Boost { active }) => {
+                    if let Some(&slot) = slots.get(&id) {
+                        state.players[slot].boosting = active;
+                    }
+                }
+                NetEvent::Message(id, ClientMsg::Emote { emote }) => {
+                    if let Some(&slot) = slots.get(&id)
+                        && last_emotes.get(&slot).is_none_or(|at| at.elapsed() >= EMOTE_COOLDOWN)
+                    {
+                        last_emotes.insert(slot, Instant::now());
+                        broadcast(&mut clients, &ServerMsg::Emote(EmoteMsg { player: state.players[slot].id, emote }));
+                    }
+                }
+                NetEvent::Message(id, ClientMsg::Restart) => {
+                    if let Some(&slot) = slots.get(&id)
+                        && state.is_over()
+                        && rematch_votes.insert(slot)
+                    {
+                        debug!(name = %state.players[slot].name, "Wants a rematch");
+                        votes_changed = true;
+                    }
+                }
+                NetEvent::Message(id, ClientMsg::Pause) => {
+                    let Some(&slot) = slots.get(&id) else { continue };
+                    if state.is_over() {
+                        continue;
+                    }
+                    if !pause_votes.remove(&slot) {
+                        pause_votes.insert(slot);
+                    }
+                    let votes = slots.values().filter(|slot| pause_votes.contains(slot)).count();
+                    if slot == 0 || votes > slots.len() / 2 {
+                        paused_by_vote = !paused_by_vote;
+                        pause_votes.clear();
+                        info!(by = %state.players[slot].name, "{}", if paused_by_vote { "Paused" } else { "Resumed" });
+                    }
+                    pause_changed = true;
+                }
+                NetEvent::Message(_, _) => {}
+                NetEvent::Admin(AdminCommand::Players) => {
+                    for (slot, player) in state.players.iter().enumerate().filter(|(slot, _)| !gone.contains(slot)) {
+                        let status = if bot_slots.contains(&slot) { "bot" } else if player.connected { "playing" } else { "away" };
+                        println!("[{}] {} ({}, {} points)", room_code, player.name, status, player.score);
+                    }
+                }
+                NetEvent::Admin(AdminCommand::Kick { target, reason }) => {
+                    // connections that haven't joined yet are hung up on too
+                    let (targeted, rest): (Vec<Client>, Vec<Client>) = std::mem::take(&mut clients).into_iter()
+                        .partition(|c| bans::targets(&target, c.name.as_deref(), c.ip));
+                    clients = rest;
+                    let mut kicked: Vec<usize> = targeted.iter().filter_map(|c| slots.get(&c.id).copied()).collect();
+                    for mut c in targeted {
+                        // dropping the client closes the connection once the Kick is out
+                        send(&mut c, &ServerMsg::Kick { reason: reason.clone() });
+                        slots.remove(&c.id);
+                        input_seqs.remove(&c.id);
+                    }
+                    // a dropped player only has a name left to be found by
+                    kicked.extend(dropped.keys().filter(|slot| state.players[**slot].name == target));
+                    for slot in kicked {
+                        dropped.remove(&slot);
+                        sessions.retain(|_, s| *s != slot);
+                        state.remove_player(slot);
+                        gone.insert(slot);
+                        rematch_votes.remove(&slot);
+                        pause_votes.remove(&slot);
+                        votes_changed = true;
+                        pause_changed = true;
+                        let name = state.players[slot].name.clone();
+                        info!(%name, "Kicked");
+                        broadcast(&mut clients, &ServerMsg::Info(format!("{} was kicked", name)));
+                    }
+                }
+                NetEvent::Admin(AdminCommand::TickMs { ms, .. }) => {
+                    let changed = MatchConfig { tick_ms: ms, ..state.config.clone() };
+                    match changed.validate() {
+                        Ok(()) => {
+                            state.config.tick_ms = ms;
+                            state.tick_ms = ms;
+                            info!(tick_ms = ms, "Tick length changed");
+                        }
+                        Err(e) => println!("[{}] {}", room_code, e),
+                    }
+                }
+                NetEvent::Admin(AdminCommand::EndRound { .. }) => {
+                    let Some(round_end) = state.end_round() else {
+                        println!("[{}] The round is already over", room_code);
+                        continue;
+                    };
+                    let counted = counted_slots(&state, &bot_slots, &gone);
+                    announce_round_end(&mut clients, &mut state, round_end, stats, &counted);
+                    broadcast_state(&mut clients, state.snapshot(), &input_seqs, None);
+                    pause_changed = true;
+                }
+                NetEvent::Admin(AdminCommand::Say(text)) => broadcast(&mut clients, &ServerMsg::Info(format!("Server: {}", text))),
+                NetEvent::Admin(AdminCommand::Rooms | AdminCommand::Ban { .. } | AdminCommand::Unban(_) | AdminCommand::Bans) => {}
+            }
+        }
+
+        // give up on players that didn't come back in time and clear their snake away
+        let expired: Vec<usize> = dropped.iter()
+            .filter(|(_, since)| since.elapsed() >= REJOIN_GRACE)
+            .map(|(slot, _)| *slot)
+            .collect();
+        for slot in expired {
+            dropped.remove(&slot);
+            sessions.retain(|_, s| *s != slot);
+            state.remove_player(slot);
+            gone.insert(slot);
+            pause_changed = true;
+            info!(name = %state.players[slot].name, "Left the match");
+            broadcast(&mut clients, &ServerMsg::Info(format!("{} left the game", state.players[slot].name)));
+        }
+
+        // everyone still connected has to agree before the next round starts
+        if state.is_over() && votes_changed && !slots.is_empty() {
+            if slots.values().all(|slot| rematch_votes.contains(slot)) {
+                info!("Rematch");
+                state.reset();
+                for slot in &gone {
+                    state.remove_player(*slot);
+                }
+                rematch_votes.clear();
+                paused_by_vote = false;
+                pause_votes.clear();
+                pause_changed = true;
+                broadcast_state(&mut clients, state.snapshot(), &input_seqs, None);
+                ticker = tick_timer(state.tick_ms);
+            } else {
+                let votes = slots.values().filter(|slot| rematch_votes.contains(slot)).count();
+                let rematch = RematchMsg { votes: votes as u8, needed: slots.len() as u8 };
+                broadcast(&mut clients, &ServerMsg::Rematch(rematch));
+            }
+        }
+
+        if pause_changed {
+            let pause = pause_msg(&state, paused_by_vote, &dropped, &pause_votes, &slots);
+            if pause.paused != last_pause.paused || pause.votes != last_pause.votes || pause.waiting_for != last_pause.waiting_for {
+                broadcast(&mut clients, &ServerMsg::Pause(pause.clone()));
+            }
+            // the phase flips along with the pause
+            if pause.paused != last_pause.paused {
+                broadcast_state(&mut clients, snapshot(&mut state, pause.paused), &input_seqs, None);
+            }
+            last_pause = pause;
+        }
+
+        // the tick waits out the pause and runs a full interval after it ends
+        if last_pause.paused {
+            ticker = tick_timer(state.tick_ms);
+        } else if due {
+            let started = Instant::now();
+            let tick_ms = state.tick_ms;
+            let TickResult { events, round_end, counting_down } = play_tick(&mut state, &bot_slots);
+            let counted = counted_slots(&state, &bot_slots, &gone);
+            if !counting_down {
+                stats.count_tick(&state, &events, tick_ms, &counted);
+            }
+            if !events.is_empty() {
+                broadcast(&mut clients, &ServerMsg::Events(events));
+            }
+            // everyone sees the tick that ends the round
+            let every = round_end.is_none().then_some(config.state_every);
+            if let Some(round_end) = round_end {
+                announce_round_end(&mut clients, &mut state, round_end, stats, &counted);
+            }
+            broadcast_state(&mut clients, state.snapshot(), &input_seqs, every);
+            METRICS.tick(started.elapsed());
+            // the tick can speed up as the round goes on, or be changed from the console
+            if ticker.period() != Duration::from_millis(state.tick_ms) {
+                ticker = tick_timer(state.tick_ms);
+            }
+        }
+
+        if clients.is_empty() && dropped.is_empty() {
+            break;
+        }
+    }
+    stats.save();
+}
+
+// What a server runs with, whether on its own or inside a client hosting a game
+pub struct Setup {
+    pub config: MatchConfig,
+    // only speak newline-delimited JSON, whatever clients ask for
+    pub json: bool,
+    // offer states by UDP to clients that ask
+    pub udp: bool,
+    // offer compressed frames to clients that ask
+    pub compress: bool,
+    pub stats: PathBuf,
+    pub accounts: PathBuf,
+    pub bans: PathBuf,
+    // players who aren't logged in to an account may join
+    pub guests: bool,
+    // where the Prometheus endpoint listens, if anywhere
+    pub metrics: Option<String>,
+    pub tls: Option<Arc<rustls::ServerConfig>>,
+    // commands typed into the terminal reach the server
+    pub console: bool,
+}
+
+// A server taking connections, with its rooms yet to run
+pub struct Server {
+    runtime: Runtime,
+    rx_events: mpsc::UnboundedReceiver<NetEvent>,
+    config: MatchConfig,
+    stats: Stats,
+    accounts: Accounts,
+    bans: Bans,
+}
+
+impl Server {
+    // Loads what the server keeps on disk and starts taking connections on `listener`
+    pub fn start(listener: TcpListener, setup: Setup) -> Result<Server, String> {
+        // every room's matches are seeded from this, so a run can be played again
+        let mut config = setup.config;
+        let seed = *config.seed.get_or_insert_with(|| rand::thread_rng().r#gen());
+        info!(seed, "Seeded");
+        let stats = Stats::load(&setup.stats)?;
+        let accounts = Accounts::load(&setup.accounts, setup.guests)?;
+        let bans = Bans::load(&setup.bans)?;
+
+        // port 0 picks a free one, so ask the socket what it got
+        let addr = listener.local_addr().map_err(|e| e.to_string())?;
+        info!(tls = setup.tls.is_some(), "Server listening on {}", addr);
+
+        // connections, rooms and their ticks are tasks on this, whichever thread runs the server
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("server")
+            .build()
+            .map_err(|e| format!("can't start the server: {}", e))?;
+        let entered = runtime.enter();
+        let (tx_events, rx_events) = mpsc::unbounded_channel::<NetEvent>();
+        let mut handshake = Handshake { force_json: setup.json, compress: setup.compress, seed, udp: None };
+        // datagrams aren't encrypted, so over TLS states stay on the connection
+        if setup.udp && setup.tls.is_none() {
+            // states go out from the same port number the TCP listener uses
+            let tickets = udp::Tickets::default();
+            // and take the same address families
+            let only_v6 = addr.is_ipv6() && SockRef::from(&listener).only_v6().unwrap_or(true);
+            let socket = bind_udp(addr, only_v6).map_err(|e| format!("can't open UDP port {}: {}", addr, e))?;
+            udp::spawn_listener(socket, tickets.clone(), tx_events.clone()).map_err(|e| format!("can't open UDP port {}: {}", addr, e))?;
+            handshake.udp = Some(tickets);
+        }
+        if let Some(addr) = &setup.metrics {
+            let listener = TcpListener::bind(addr).map_err(|e| format!("can't serve metrics on {}: {}", addr, e))?;
+            metrics::spawn_endpoint(listener);
+            info!("Metrics at http://{}/metrics", addr);
+        }
+        if setup.console {
+            console::spawn_console(tx_events.clone());
+        }
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        let listener = tokio::net::TcpListener::from_std(listener).map_err(|e| e.to_string())?;
+        let tls = setup.tls.map(TlsAcceptor::from);
+        tokio::spawn(accept_connections(listener, tx_events, handshake, bans.clone(), tls));
+        drop(entered);
+        Ok(Server { runtime, rx_events, config, stats, accounts, bans })
+    }
+
+    // Runs the rooms, for as long as the process does
+    pub fn run(self) {
+        self.runtime.block_on(rooms::run_router(self.rx_events, self.config, self.stats, self.accounts, self.bans));
+    }
+}
+
+// Listens on `addr`. An IPv6 address such as :: takes IPv4 connections as well unless
+// `ipv6_only`, whatever the platform would do by default; an IPv4 address only takes IPv4.
+pub fn listen(addr: SocketAddr, ipv6_only: bool) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(ipv6_only)?;
+    }
+    // as std's own bind does, so a restarted server gets its port straight back
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(128)?;
+    Ok(socket.into())
+}
+
+fn bind_udp(addr: SocketAddr, ipv6_only: bool) -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, None)?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(ipv6_only)?;
+    }
+    socket.bind(&addr.into())?;
+    Ok(socket.into())
+}
+
+// Starts a server on a background thread for a client hosting a game, open to the LAN on
+// `port`, and returns the address it listens on. Its files go to the temp directory.
+pub fn host(port: u16, config: MatchConfig) -> Result<SocketAddr, String> {
+    // both IPv4 and IPv6 where the machine has IPv6 at all
+    let listener = listen((Ipv6Addr::UNSPECIFIED, port).into(), false)
+        .or_else(|_| TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)))
+        .map_err(|e| format!("Can't host on port {}: {}", port, e))?;
+    let addr = listener.local_addr().map_err(|e| e.to_string())?;
+    let file = |name: &str| std::env::temp_dir().join(format!("rusty-snake-host-{}", name));
+    let setup = Setup {
+        config,
+        json: false,
+        udp: true,
+        compress: true,
+        stats: file("stats.json"),
+        accounts: file("accounts.json"),
+        bans: file("bans.json"),
+        guests: true,
+        metrics: None,
+        tls: None,
+        console: false,
+    };
+    let server = Server::start(listener, setup)?;
+    thread::spawn(move || server.run());
+    Ok(addr)
+}
+
+// This machine's address on the LAN, for telling friends where to connect. Connecting a UDP
+// socket sends nothing; it only picks the interface traffic would leave by.
+pub fn lan_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(1, 1, 1, 1), 80)).ok()?;
+    Some(socket.local_addr().ok()?.ip())
+}
@@ -0,0 +1,184 @@
+// Player accounts. A name registered with a password can only be played by someone logged
+// in to it; other names are open to guests if the server allows them. Passwords are kept
+// as salted PBKDF2 hashes and login tokens as SHA-256 hashes, in a Json file.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::game_core::{check_name, normalize_name, AccountToken, Credential, ErrorCode};
+
+const HASH_ROUNDS: u32 = 10_000;
+// tokens kept per account, the oldest go first; one per device is plenty
+const MAX_TOKENS: usize = 8;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Account {
+    name: String,
+    salt: String,
+    hash: String,
+    tokens: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct Accounts {
+    path: PathBuf,
+    // players without an account may join
+    guests: bool,
+    // by account_key, the account keeping the name as it was registered
+    accounts: Arc<Mutex<HashMap<String, Account>>>,
+}
+
+// Why a Register or Login was turned down
+pub type Refusal = (ErrorCode, String);
+
+// Names that differ only in case or spacing are the same account, as they're the same name
+// on the scoreboard
+pub fn account_key(name: &str) -> String {
+    normalize_name(name).to_lowercase()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn random_hex() -> String {
+    hex(&rand::thread_rng().r#gen::<[u8; 16]>())
+}
+
+fn hash_password(password: &str, salt: &str) -> String {
+    let mut out = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt.as_bytes(), HASH_ROUNDS, &mut out);
+    hex(&out)
+}
+
+fn hash_token(token: &AccountToken) -> String {
+    hex(&Sha256::digest(token.0.as_bytes()))
+}
+
+// Compares every byte, so how long it takes doesn't tell how much of a guess was right
+pub(super) fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+impl Accounts {
+    // Without the file nobody has an account yet
+    pub fn load(path: &Path, guests: bool) -> Result<Accounts, String> {
+        let list: Vec<Account> = match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("can't read {}: {}", path.display(), e)),
+        };
+        let accounts = list.into_iter().map(|account| (account_key(&account.name), account)).collect();
+        Ok(Accounts { path: path.to_path_buf(), guests, accounts: Arc::new(Mutex::new(accounts)) })
+    }
+
+    pub fn guests_allowed(&self) -> bool {
+        self.guests
+    }
+
+    pub fn registered(&self, name: &str) -> bool {
+        self.accounts.lock().unwrap().contains_key(&account_key(name))
+    }
+
+    // Both hand back the account's name as it was registered, with the token
+    pub fn register(&self, name: &str, password: &str) -> Result<(String, AccountToken), Refusal> {
+        check_name(name).map_err(|message| (ErrorCode::BadRequest, message))?;
+        if password.is_empty() {
+            return Err((ErrorCode::BadRequest, "An account needs a password".to_string()));
+        }
+        let mut accounts = self.accounts.lock().unwrap();
+        let key = account_key(name);
+        if accounts.contains_key(&key) {
+            return Err((ErrorCode::NameTaken, format!("{} is already registered", name)));
+        }
+        let salt = random_hex();
+        let hash = hash_password(password, &salt);
+        let account = accounts.entry(key).or_insert(Account { name: name.to_string(), salt, hash, tokens: Vec::new() });
+        let token = issue_token(account);
+        let name = account.name.clone();
+        self.save(&accounts);
+        Ok((name, token))
+    }
+
+    // A password login hands out a new token; a token login keeps using the one it has
+    pub fn login(&self, name: &str, credential: &Credential) -> Result<(String, AccountToken), Refusal> {
+        let mut accounts = self.accounts.lock().unwrap();
+        let refused = || (ErrorCode::AuthFailed, format!("Wrong name or password for {}", name));
+        let account = accounts.get_mut(&account_key(name)).ok_or_else(refused)?;
+        let registered = account.name.clone();
+        match credential {
+            Credential::Password(password) => {
+                if !same(&hash_password(password, &account.salt), &account.hash) {
+                    return Err(refused());
+                }
+                let token = issue_token(account);
+                self.save(&accounts);
+                Ok((registered, token))
+            }
+            Credential::Token(token) => {
+                let hash = hash_token(token);
+                if !account.tokens.iter().any(|t| same(t, &hash)) {
+                    return Err((ErrorCode::AuthFailed, format!("The saved login for {} expired, enter the password", name)));
+                }
+                Ok((registered, token.clone()))
+            }
+        }
+    }
+
+    // Writes the file; failing only loses what changed since the last save
+    fn save(&self, accounts: &HashMap<String, Account>) {
+        let mut list: Vec<&Account> = accounts.values().collect();
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        let written = serde_json::to_string_pretty(&list).map_err(io::Error::other).and_then(|text| fs::write(&self.path, text));
+        if let Err(e) = written {
+            tracing::error!("Can't save accounts to {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+fn issue_token(account: &mut Account) -> AccountToken {
+    let token = AccountToken(random_hex());
+    account.tokens.push(hash_token(&token));
+    if account.tokens.len() > MAX_TOKENS {
+        account.tokens.remove(0);
+    }
+    token
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passwords_and_tokens_log_in() {
+        let path = std::env::temp_dir().join(format!("snake-accounts-test-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let accounts = Accounts::load(&path, true).unwrap();
+        let (_, token) = accounts.register("alice", "hunter2").unwrap();
+        assert_eq!(accounts.register("alice", "again").unwrap_err().0, ErrorCode::NameTaken);
+        assert_eq!(accounts.register("ALICE", "again").unwrap_err().0, ErrorCode::NameTaken);
+        assert!(accounts.register("bob", "").is_err());
+        assert_eq!(accounts.register("bob\n", "hunter2").unwrap_err().0, ErrorCode::BadRequest);
+
+        // what was saved works after a restart, and the password itself isn't in there
+        let accounts = Accounts::load(&path, true).unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains("hunter2"));
+        assert!(accounts.registered("alice"));
+        assert!(accounts.registered(" Alice "));
+        assert!(!accounts.registered("bob"));
+        assert!(accounts.login("alice", &Credential::Password("hunter2".to_string())).is_ok());
+        assert_eq!(accounts.login("alice", &Credential::Token(token.clone())).unwrap(), ("alice".to_string(), token.clone()));
+        // any spelling logs in to the account as it was registered
+        assert_eq!(accounts.login("ALICE", &Credential::Token(token.clone())).unwrap().0, "alice");
+        assert_eq!(accounts.login("alice", &Credential::Password("hunter3".to_string())).unwrap_err().0, ErrorCode::AuthFailed);
+        assert!(accounts.login("alice", &Credential::Token(AccountToken("forged".to_string()))).is_err());
+        assert!(accounts.login("bob", &Credential::Password("hunter2".to_string())).is_err());
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,116 @@
+// Names and addresses that aren't let in, each with the reason they're told. Banned addresses
+// are turned away as they connect, banned names when they try to enter a room. The list is
+// kept in a Json file so it outlasts the server.
+
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Ban {
+    // a player name or an IP address
+    pub target: String,
+    pub reason: String,
+}
+
+#[derive(Clone)]
+pub struct Bans {
+    path: PathBuf,
+    list: Arc<Mutex<Vec<Ban>>>,
+}
+
+// Whether `target` names this player or their address
+pub fn targets(target: &str, name: Option<&str>, ip: IpAddr) -> bool {
+    name == Some(target) || target.parse::<IpAddr>() == Ok(ip)
+}
+
+impl Bans {
+    // Without the file nobody is banned
+    pub fn load(path: &Path) -> Result<Bans, String> {
+        let list: Vec<Ban> = match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("can't read {}: {}", path.display(), e)),
+        };
+        Ok(Bans { path: path.to_path_buf(), list: Arc::new(Mutex::new(list)) })
+    }
+
+    // The ban keeping this player or address out, if there is one
+    pub fn find(&self, name: Option<&str>, ip: IpAddr) -> Option<Ban> {
+        self.list.lock().unwrap().iter().find(|ban| targets(&ban.target, name, ip)).cloned()
+    }
+
+    pub fn list(&self) -> Vec<Ban> {
+        self.list.lock().unwrap().clone()
+    }
+
+    // False if the target was already banned; the reason is updated either way
+    pub fn ban(&self, target: &str, reason: &str) -> bool {
+        let mut list = self.list.lock().unwrap();
+        let added = match list.iter_mut().find(|ban| ban.target == target) {
+            Some(ban) => {
+                ban.reason = reason.to_string();
+                false
+            }
+            None => {
+                list.push(Ban { target: target.to_string(), reason: reason.to_string() });
+                true
+            }
+        };
+        self.save(&list);
+        added
+    }
+
+    // False if the target wasn't banned
+    pub fn unban(&self, target: &str) -> bool {
+        let mut list = self.list.lock().unwrap();
+        let before = list.len();
+        list.retain(|ban| ban.target != target);
+        if list.len() == before {
+            return false;
+        }
+        self.save(&list);
+        true
+    }
+
+    // Writes the file; failing only loses what changed since the last save
+    fn save(&self, list: &[Ban]) {
+        let written = serde_json::to_string_pretty(list).map_err(io::Error::other).and_then(|text| fs::write(&self.path, text));
+        if let Err(e) = written {
+            tracing::error!("Can't save bans to {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bans_names_and_addresses() {
+        let path = std::env::temp_dir().join(format!("snake-bans-test-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let home: IpAddr = "127.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.7".parse().unwrap();
+        let bans = Bans::load(&path).unwrap();
+        assert!(bans.ban("mallory", "cheating"));
+        assert!(bans.ban("10.0.0.7", "spam"));
+        assert!(!bans.ban("mallory", "still cheating"));
+
+        // what was saved holds after a restart
+        let bans = Bans::load(&path).unwrap();
+        assert_eq!(bans.find(Some("mallory"), home).unwrap().reason, "still cheating");
+        assert_eq!(bans.find(Some("alice"), other).unwrap().target, "10.0.0.7");
+        assert_eq!(bans.find(None, other).unwrap().reason, "spam");
+        assert!(bans.find(Some("alice"), home).is_none());
+        assert!(bans.unban("mallory"));
+        assert!(!bans.unban("mallory"));
+        assert!(bans.find(Some("mallory"), home).is_none());
+        assert_eq!(Bans::load(&path).unwrap().list().len(), 1);
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,276 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::game_core::GameConfig;
+
+use super::{map, MatchConfig};
+
+// The server config file, as TOML. Every setting is optional: those left out keep their
+// defaults, and keys nobody reads are an error rather than a typo that silently does nothing.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    tick_ms: Option<u64>,
+    state_every: Option<u64>,
+    max_players: Option<u64>,
+    food_count: Option<u64>,
+    wrap: Option<bool>,
+    // seconds, for rooms playing Timed
+    time_limit: Option<u64>,
+    // 0 starts rounds right away
+    countdown: Option<u64>,
+    mode: Option<String>,
+    leave_food: Option<bool>,
+    corpses: Option<bool>,
+    // 0 keeps dead snakes dead for the rest of the round
+    respawn_ticks: Option<u64>,
+    // 0 plays without lives
+    lives: Option<u64>,
+    obstacles: Option<u64>,
+    power_ups: Option<bool>,
+    golden_food: Option<bool>,
+    // 0 turns poison off
+    poison_interval: Option<u64>,
+    // leave it out for a different game every run
+    seed: Option<u64>,
+    // the map's size replaces the grid settings
+    map: Option<String>,
+    #[serde(default)]
+    grid: GridSection,
+    #[serde(default)]
+    win: WinSection,
+    #[serde(default)]
+    teams: TeamsSection,
+    #[serde(default)]
+    bots: BotsSection,
+    #[serde(default)]
+    speedup: SpeedUpSection,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GridSection {
+    width: Option<u64>,
+    height: Option<u64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WinSection {
+    // without a target the last snake standing wins
+    score_target: Option<u64>,
+    keep_scores: Option<bool>,
+    // 0 plays round after round with no series
+    best_of: Option<u64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TeamsSection {
+    // 0 plays every snake for itself
+    count: Option<u64>,
+    friendly_fire: Option<bool>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BotsSection {
+    count: Option<u64>,
+    difficulty: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpeedUpSection {
+    // 0 leaves that kind of speed-up off
+    every_food: Option<u64>,
+    every_ticks: Option<u64>,
+    step_ms: Option<u64>,
+    min_tick_ms: Option<u64>,
+}
+
+// Reads the server config file
+pub fn load(path: &Path) -> Result<MatchConfig, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+    parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// A setting's value in the type it's kept in, or an error naming it when it doesn't fit
+fn fit<T: TryFrom<u64>>(key: &str, value: u64) -> Result<T, String> {
+    T::try_from(value).map_err(|_| format!("{} = {} is out of range", key, value))
+}
+
+fn parse(text: &str) -> Result<MatchConfig, String> {
+    let file: ConfigFile = toml::from_str(text).map_err(|e| e.to_string())?;
+    let mut config = MatchConfig::default();
+    config.tick_ms = file.tick_ms.unwrap_or(config.tick_ms);
+    config.state_every = file.state_every.unwrap_or(config.state_every);
+    if let Some(n) = file.max_players {
+        config.max_players = fit("max_players", n)?;
+    }
+    if let Some(n) = file.food_count {
+        config.food_count = fit("food_count", n)?;
+    }
+    config.game.wrap = file.wrap.unwrap_or(config.game.wrap);
+    config.time_limit_secs = file.time_limit.unwrap_or(config.time_limit_secs);
+    config.countdown_secs = file.countdown.unwrap_or(config.countdown_secs);
+    if let Some(name) = file.mode {
+        config.game.mode = name.parse()?;
+    }
+    config.leave_food = file.leave_food.unwrap_or(config.leave_food);
+    config.corpses = file.corpses.unwrap_or(config.corpses);
+    if let Some(ticks) = file.respawn_ticks {
+        config.respawn_ticks = Some(ticks).filter(|ticks| *ticks > 0);
+    }
+    if let Some(lives) = file.lives {
+        config.lives = Some(fit("lives", lives)?).filter(|lives| *lives > 0);
+    }
+    if let Some(n) = file.obstacles {
+        config.obstacles = fit("obstacles", n)?;
+    }
+    config.power_ups = file.power_ups.unwrap_or(config.power_ups);
+    config.golden_food = file.golden_food.unwrap_or(config.golden_food);
+    if let Some(ticks) = file.poison_interval {
+        config.poison_interval = Some(ticks).filter(|ticks| *ticks > 0);
+    }
+    config.seed = file.seed.or(config.seed);
+    if let Some(path) = file.map {
+        let ((width, height), board) = map::load(Path::new(&path))?;
+        config.game = config.game.resized(width, height);
+        config.map = Some(Arc::new(board));
+    }
+    if let Some(width) = file.grid.width {
+        config.game = GameConfig { grid_width: fit("grid.width", width)?, ..config.game };
+    }
+    if let Some(height) = file.grid.height {
+        config.game = GameConfig { grid_height: fit("grid.height", height)?, ..config.game };
+    }
+    if let Some(target) = file.win.score_target {
+        config.score_target = Some(fit("win.score_target", target)?);
+    }
+    config.keep_scores = file.win.keep_scores.unwrap_or(config.keep_scores);
+    if let Some(n) = file.win.best_of {
+        config.best_of = Some(fit("win.best_of", n)?).filter(|n| *n > 0);
+    }
+    if let Some(n) = file.teams.count {
+        config.teams = fit("teams.count", n)?;
+    }
+    config.friendly_fire = file.teams.friendly_fire.unwrap_or(config.friendly_fire);
+    if let Some(n) = file.bots.count {
+        config.bots = fit("bots.count", n)?;
+    }
+    if let Some(name) = file.bots.difficulty {
+        config.bot_difficulty = name.parse()?;
+    }
+    if let Some(n) = file.speedup.every_food {
+        config.speedup.every_food = Some(fit("speedup.every_food", n)?).filter(|n| *n > 0);
+    }
+    if let Some(n) = file.speedup.every_ticks {
+        config.speedup.every_ticks = Some(n).filter(|n| *n > 0);
+    }
+    config.speedup.step_ms = file.speedup.step_ms.unwrap_or(config.speedup.step_ms);
+    config.speedup.min_tick_ms = file.speedup.min_tick_ms.unwrap_or(config.speedup.min_tick_ms);
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_core::GameMode;
+    use crate::game_core::ai::BotDifficulty;
+
+    #[test]
+    fn empty_file_gives_defaults() {
+        let config = parse("# nothing set\n\n").unwrap();
+        assert_eq!(config.tick_ms, MatchConfig::default().tick_ms);
+        assert_eq!(config.game, GameConfig::default());
+    }
+
+    #[test]
+    fn reads_sections_and_values() {
+        let text = r#"
+            tick_ms = 100
+            state_every = 3
+            wrap = false   # solid borders
+            mode = "score_race"
+            time_limit = 90
+            food_count = 3
+            leave_food = false
+            corpses = false
+            respawn_ticks = 30
+            lives = 3
+            obstacles = 5
+            seed = 99
+
+            [grid]
+            width = 120
+            height = 60
+
+            [win]
+            score_target = 25
+            best_of = 5
+
+            [speedup]
+            every_food = 3
+            every_ticks = 0
+
+            [teams]
+            count = 2
+            friendly_fire = true
+
+            [bots]
+            count = 1
+            difficulty = "pathfinding"
+        "#;
+        let config = parse(text).unwrap();
+        assert_eq!(config.tick_ms, 100);
+        assert_eq!(config.state_every, 3);
+        assert_eq!(config.food_count, 3);
+        assert!(!config.leave_food);
+        assert!(!config.corpses);
+        assert_eq!(config.respawn_ticks, Some(30));
+        assert_eq!(config.lives, Some(3));
+        assert_eq!(config.obstacles, 5);
+        assert_eq!(config.seed, Some(99));
+        assert_eq!(config.game, GameConfig { grid_width: 120, grid_height: 60, mode: GameMode::ScoreRace, wrap: false });
+        assert_eq!(config.score_target, Some(25));
+        assert_eq!(config.best_of, Some(5));
+        assert_eq!(config.speedup.every_food, Some(3));
+        assert_eq!(config.speedup.every_ticks, None);
+        assert_eq!(config.time_limit_secs, 90);
+        assert_eq!(config.teams, 2);
+        assert!(config.friendly_fire);
+        assert_eq!(config.bots, 1);
+        assert_eq!(config.bot_difficulty, BotDifficulty::Pathfinding);
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_bad_values() {
+        assert!(parse("speed = 3").unwrap_err().contains("speed"));
+        assert!(parse("[grid]\ndepth = 3").unwrap_err().contains("depth"));
+        assert!(parse("wrap = 1").is_err());
+        assert!(parse("tick_ms = 1.5").is_err());
+        assert!(parse("tick_ms = -1").is_err());
+        assert!(parse("mode = \"tag\"").unwrap_err().contains("game mode"));
+        assert!(parse("[grid\nwidth = 10").is_err());
+    }
+
+    #[test]
+    fn out_of_range_values_are_errors() {
+        assert!(parse("lives = 256").unwrap_err().contains("lives"));
+        assert!(parse("[win]\nbest_of = 4294967297").unwrap_err().contains("win.best_of"));
+        assert!(parse("[grid]\nwidth = 2147483648").unwrap_err().contains("grid.width"));
+        assert_eq!(parse("lives = 255").unwrap().lives, Some(255));
+    }
+
+    #[test]
+    fn strings_are_full_toml() {
+        // escapes, and a # inside a string after an escaped quote
+        let config = parse("mode = \"score\\u005frace\" # \"quoted\"").unwrap();
+        assert_eq!(config.game.mode, GameMode::ScoreRace);
+        assert!(parse("mode = \"x\\\"#\"").unwrap_err().contains("x\"#"));
+    }
+}
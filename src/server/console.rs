@@ -0,0 +1,122 @@
+// Commands typed into the server's terminal while it runs. They go to the router like
+// network events do, and on to the rooms they concern; answers are printed.
+
+use std::io::{self, BufRead};
+use std::thread;
+
+use tokio::sync::mpsc;
+
+use super::NetEvent;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AdminCommand {
+    Rooms,
+    Players,
+    // a player name or an IP address
+    Kick { target: String, reason: String },
+    Ban { target: String, reason: String },
+    Unban(String),
+    Bans,
+    TickMs { room: String, ms: u64 },
+    EndRound { room: String },
+    Say(String),
+}
+
+const HELP: &str = "\
+rooms                 list the rooms
+players               list everyone in every room
+kick NAME|IP [REASON] disconnect a player
+ban NAME|IP [REASON]  kick a player and keep them out
+unban NAME|IP         let a banned player back in
+bans                  list the bans
+tick ROOM MS          change how long a tick lasts in a room's match
+end ROOM              end the round being played in a room
+say TEXT              tell every player something";
+
+// The NAME|IP [REASON] a kick or ban takes, with a reason if none was given
+fn target(rest: &str, usage: &str, default_reason: &str) -> Result<(String, String), String> {
+    let (target, reason) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    if target.is_empty() {
+        return Err(usage.to_string());
+    }
+    let reason = if reason.trim().is_empty() { default_reason } else { reason.trim() };
+    Ok((target.to_string(), reason.to_string()))
+}
+
+// None for commands answered right here, like help
+fn parse(line: &str) -> Result<Option<AdminCommand>, String> {
+    let line = line.trim();
+    let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+    let room = || Some(rest.split_whitespace().next()?.to_uppercase());
+    let command = match word {
+        "" => return Ok(None),
+        "help" => {
+            println!("{}", HELP);
+            return Ok(None);
+        }
+        "rooms" => AdminCommand::Rooms,
+        "players" => AdminCommand::Players,
+        "kick" => {
+            let (target, reason) = target(rest, "usage: kick NAME|IP [REASON]", "kicked by the server")?;
+            AdminCommand::Kick { target, reason }
+        }
+        "ban" => {
+            let (target, reason) = target(rest, "usage: ban NAME|IP [REASON]", "banned from this server")?;
+            AdminCommand::Ban { target, reason }
+        }
+        "unban" if !rest.is_empty() => AdminCommand::Unban(rest.to_string()),
+        "unban" => return Err("usage: unban NAME|IP".to_string()),
+        "bans" => AdminCommand::Bans,
+        "tick" => {
+            let usage = || "usage: tick ROOM MS".to_string();
+            let ms = rest.split_whitespace().nth(1).ok_or_else(usage)?;
+            AdminCommand::TickMs { room: room().ok_or_else(usage)?, ms: ms.parse().map_err(|_| usage())? }
+        }
+        "end" => AdminCommand::EndRound { room: room().ok_or("usage: end ROOM")? },
+        "say" if !rest.is_empty() => AdminCommand::Say(rest.to_string()),
+        "say" => return Err("usage: say TEXT".to_string()),
+        other => return Err(format!("unknown command: {} (try help)", other)),
+    };
+    Ok(Some(command))
+}
+
+// Reads commands from stdin until it closes, on a thread of its own since reading stdin blocks
+pub fn spawn_console(tx_events: mpsc::UnboundedSender<NetEvent>) {
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            match parse(&line) {
+                Ok(Some(command)) => {
+                    if tx_events.send(NetEvent::Admin(command)).is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => println!("{}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_commands() {
+        assert_eq!(parse("rooms"), Ok(Some(AdminCommand::Rooms)));
+        assert_eq!(parse("  "), Ok(None));
+        assert_eq!(parse("kick bob  spamming the chat"), Ok(Some(AdminCommand::Kick { target: "bob".to_string(), reason: "spamming the chat".to_string() })));
+        assert_eq!(parse("kick bob"), Ok(Some(AdminCommand::Kick { target: "bob".to_string(), reason: "kicked by the server".to_string() })));
+        assert_eq!(parse("ban 10.0.0.7"), Ok(Some(AdminCommand::Ban { target: "10.0.0.7".to_string(), reason: "banned from this server".to_string() })));
+        assert_eq!(parse("unban bob"), Ok(Some(AdminCommand::Unban("bob".to_string()))));
+        assert_eq!(parse("tick abcd 80"), Ok(Some(AdminCommand::TickMs { room: "ABCD".to_string(), ms: 80 })));
+        assert_eq!(parse("end ABCD"), Ok(Some(AdminCommand::EndRound { room: "ABCD".to_string() })));
+        assert_eq!(parse("say back in 5"), Ok(Some(AdminCommand::Say("back in 5".to_string()))));
+        assert!(parse("tick ABCD fast").is_err());
+        assert!(parse("kick").is_err());
+        assert!(parse("unban").is_err());
+        assert!(parse("reboot").is_err());
+    }
+}
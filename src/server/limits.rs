@@ -0,0 +1,128 @@
+// What a single connection is allowed to send. A client sends an input per key press and a
+// ping a second, so these are far above anything a player does, and far below a flood.
+// Guessing room passwords is limited per address instead, since reconnecting is cheap.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+// Longest frame a client may send; names, passwords and room codes all fit easily
+pub const MAX_CLIENT_FRAME_LEN: usize = 4 * 1024;
+// Messages per second a connection may keep up, and how many it may send at once
+pub const MESSAGES_PER_SEC: f64 = 40.0;
+pub const MESSAGE_BURST: f64 = 80.0;
+// Wrong room passwords an address may give before it has to wait, and how long it waits then;
+// every wrong one after that doubles the wait, up to MAX_LOCKOUT
+pub const WRONG_PASSWORDS: u32 = 5;
+pub const LOCKOUT: Duration = Duration::from_secs(30);
+pub const MAX_LOCKOUT: Duration = Duration::from_secs(15 * 60);
+// An address that guessed nothing for this long starts over
+const GUESSES_KEPT: Duration = Duration::from_secs(60 * 60);
+
+// A token bucket: every message takes a token, and tokens come back at a steady rate
+pub struct RateLimit {
+    per_sec: f64,
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimit {
+    pub fn new(per_sec: f64, burst: f64, now: Instant) -> Self {
+        RateLimit { per_sec, burst, tokens: burst, last: now }
+    }
+
+    // False once the connection sends faster than it's allowed to
+    pub fn allow(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_sec).min(self.burst);
+        self.last = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+// Wrong room passwords by address, so a private room's password can't be guessed at speed
+#[derive(Default)]
+pub struct PasswordGuesses {
+    by_ip: HashMap<IpAddr, Guesses>,
+}
+
+struct Guesses {
+    wrong: u32,
+    last: Instant,
+}
+
+impl PasswordGuesses {
+    // How long `ip` still has to wait before it may try a password, None if it may now
+    pub fn locked_out(&self, ip: IpAddr, now: Instant) -> Option<Duration> {
+        let guesses = self.by_ip.get(&ip)?;
+        let over = guesses.wrong.checked_sub(WRONG_PASSWORDS)?;
+        let lockout = LOCKOUT.saturating_mul(1 << over.min(16)).min(MAX_LOCKOUT);
+        (guesses.last + lockout).checked_duration_since(now).filter(|left| !left.is_zero())
+    }
+
+    pub fn wrong(&mut self, ip: IpAddr, now: Instant) {
+        let guesses = self.by_ip.entry(ip).or_insert(Guesses { wrong: 0, last: now });
+        guesses.wrong += 1;
+        guesses.last = now;
+    }
+
+    // Drops addresses that have been quiet long enough to start over
+    pub fn forget_quiet(&mut self, now: Instant) {
+        self.by_ip.retain(|_, guesses| now.saturating_duration_since(guesses.last) < GUESSES_KEPT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn bursts_pass_and_floods_dont() {
+        let start = Instant::now();
+        let mut limit = RateLimit::new(10.0, 5.0, start);
+        assert!((0..5).all(|_| limit.allow(start)));
+        assert!(!limit.allow(start));
+        // a tenth of a second buys one more message
+        assert!(limit.allow(start + Duration::from_millis(100)));
+        assert!(!limit.allow(start + Duration::from_millis(100)));
+        // a long quiet spell only refills up to the burst
+        let later = start + Duration::from_secs(60);
+        assert_eq!((0..10).filter(|_| limit.allow(later)).count(), 5);
+    }
+
+    #[test]
+    fn password_guessers_wait_longer_and_longer() {
+        let start = Instant::now();
+        let (guesser, other) = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)));
+        let mut guesses = PasswordGuesses::default();
+        for _ in 0..WRONG_PASSWORDS - 1 {
+            guesses.wrong(guesser, start);
+        }
+        assert_eq!(guesses.locked_out(guesser, start), None);
+        guesses.wrong(guesser, start);
+        assert_eq!(guesses.locked_out(guesser, start), Some(LOCKOUT));
+        assert_eq!(guesses.locked_out(other, start), None);
+
+        // one more wrong guess once the wait is over doubles the next one
+        let later = start + LOCKOUT;
+        assert_eq!(guesses.locked_out(guesser, later), None);
+        guesses.wrong(guesser, later);
+        assert_eq!(guesses.locked_out(guesser, later), Some(LOCKOUT * 2));
+        for _ in 0..20 {
+            guesses.wrong(guesser, later);
+        }
+        assert_eq!(guesses.locked_out(guesser, later), Some(MAX_LOCKOUT));
+
+        // and after a quiet spell it's forgotten
+        let much_later = later + Duration::from_secs(2 * 60 * 60);
+        guesses.forget_quiet(much_later);
+        guesses.wrong(guesser, much_later);
+        assert_eq!(guesses.locked_out(guesser, much_later), None);
+    }
+}
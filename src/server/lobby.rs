@@ -0,0 +1,158 @@
+use std::time::{Duration, Instant};
+
+use tokio::time;
+use tracing::info;
+
+use crate::game_core::{unique_name, ClientMsg, LobbyMsg, LobbyPlayer, PlayerId, ServerMsg, SessionMsg, LOBBY_COUNTDOWN_SECS};
+use crate::game_core::sim::{smallest_team, MAX_STATE_EVERY};
+
+use super::bans;
+use super::console::AdminCommand;
+use super::{broadcast, new_session_token, send, send_board, Client, Inbox, MatchConfig, NetEvent};
+
+fn lobby_snapshot(clients: &[Client], room_code: &str, config: &MatchConfig, countdown: Option<u32>) -> LobbyMsg {
+    LobbyMsg {
+        room_code: room_code.to_string(),
+        players: clients.iter()
+            .filter_map(|c| c.name.as_ref().map(|name| LobbyPlayer { id: c.player_id, name: name.clone(), ready: c.ready, team: c.team }))
+            .collect(),
+        max_players: config.max_players as u8,
+        countdown,
+        teams: config.teams,
+    }
+}
+
+// A team match needs someone on at least two teams, unless a single player is trying it out
+fn teams_filled(clients: &[Client], teams: u8) -> bool {
+    let mut picked: Vec<u8> = clients.iter().filter_map(|c| c.team).collect();
+    picked.sort_unstable();
+    picked.dedup();
+    teams == 0 || clients.len() == 1 || picked.len() >= 2
+}
+
+// Collects players until everyone who joined is ready and the countdown ran out.
+// Returns the clients that take part in the match, or None once the room is closed.
+pub async fn run_lobby(inbox: &mut Inbox, room_code: &str, config: MatchConfig) -> Option<Vec<Client>> {
+    let mut clients: Vec<Client> = Vec::new();
+    let mut countdown_end: Option<Instant> = None;
+    let mut last_countdown: Option<u32> = None;
+    // ids aren't reused, even after their player left
+    let mut next_player_id: PlayerId = 1;
+
+    loop {
+        let mut changed = false;
+        // the countdown is looked at between events, and every so often when there are none
+        match time::timeout(Duration::from_millis(100), inbox.recv()).await {
+            Ok(Some(NetEvent::Connected(id, outbox, ip))) => {
+                if clients.len() >= config.max_players {
+                    // dropping the outbox closes the connection
+                    info!(id, "Rejected: lobby is full");
+                } else {
+                    clients.push(Client::new(id, outbox, ip));
+                    changed = true;
+                }
+            }
+            Ok(Some(NetEvent::Handshake(id, format))) => {
+                if let Some(c) = clients.iter_mut().find(|c| c.id == id) {
+                    c.format = Some(format);
+                }
+            }
+            Ok(Some(NetEvent::UdpReady(id, peer))) => {
+                if let Some(c) = clients.iter_mut().find(|c| c.id == id) {
+                    c.udp = Some(peer);
+                }
+            }
+            Ok(Some(NetEvent::LoggedIn(id, account))) => {
+                if let Some(c) = clients.iter_mut().find(|c| c.id == id) {
+                    c.account = Some(account);
+                }
+            }
+            Ok(Some(NetEvent::Disconnected(id))) => {
+                if let Some(c) = clients.iter().find(|c| c.id == id) {
+                    info!(id, name = %c.name.as_deref().unwrap_or("?"), "Left the lobby");
+                }
+                clients.retain(|c| c.id != id);
+                changed = true;
+            }
+            Ok(Some(NetEvent::Message(id, msg))) => {
+                let newcomer_team = smallest_team(config.teams, clients.iter().map(|c| c.team));
+                let names: Vec<String> = clients.iter().filter_map(|c| c.name.clone()).collect();
+                if let Some(c) = clients.iter_mut().find(|c| c.id == id) {
+                    match msg {
+                        ClientMsg::Join { name, .. } if c.name.is_none() => {
+                            let name = unique_name(&name, names.iter().map(String::as_str));
+                            info!(id, %name, "Joined the lobby");
+                            let token = new_session_token();
+                            c.name = Some(name.clone());
+                            c.player_id = next_player_id;
+                            next_player_id += 1;
+                            c.team = newcomer_team;
+                            c.token = Some(token.clone());
+                            send_board(c, &config);
+                            send(c, &ServerMsg::Session(SessionMsg { room_code: room_code.to_string(), token, name, player_id: c.player_id }));
+                            changed = true;
+                        }
+                        ClientMsg::Ping { sent_ms, .. } => {
+                            send(c, &ServerMsg::Pong { sent_ms });
+                        }
+                        ClientMsg::Ready { ready } if c.name.is_some() => {
+                            c.ready = ready;
+                            changed = true;
+                        }
+                        // kept for the match
+                        ClientMsg::StateRate { every } => c.state_every = every.clamp(1, MAX_STATE_EVERY),
+                        ClientMsg::PickTeam { team } if c.name.is_some() && team < config.teams => {
+                            c.team = Some(team);
+                            changed = true;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Ok(Some(NetEvent::Admin(command))) => match command {
+                AdminCommand::Players => {
+                    for (name, c) in clients.iter().filter_map(|c| Some((c.name.as_ref()?, c))) {
+                        println!("[{}] {} (in the lobby{})", room_code, name, if c.ready { ", ready" } else { "" });
+                    }
+                }
+                AdminCommand::Kick { target, reason } => {
+                    while let Some(i) = clients.iter().position(|c| bans::targets(&target, c.name.as_deref(), c.ip)) {
+                        // dropping the client closes the connection once the Kick is out
+                        let mut c = clients.remove(i);
+                        send(&mut c, &ServerMsg::Kick { reason: reason.clone() });
+                        info!(id = c.id, name = %c.name.as_deref().unwrap_or("?"), "Kicked");
+                        changed = true;
+                    }
+                }
+                AdminCommand::Say(text) => broadcast(&mut clients, &ServerMsg::Info(format!("Server: {}", text))),
+                AdminCommand::TickMs { .. } | AdminCommand::EndRound { .. } => println!("[{}] No match is being played", room_code),
+                AdminCommand::Rooms | AdminCommand::Ban { .. } | AdminCommand::Unban(_) | AdminCommand::Bans => {}
+            },
+            Ok(None) => return None, // room closed
+            Err(_) => {}
+        }
+
+        // any change to the roster restarts the countdown
+        let all_ready = !clients.is_empty() && clients.iter().all(|c| c.ready) && teams_filled(&clients, config.teams);
+        if changed || !all_ready {
+            countdown_end = if all_ready {
+                Some(Instant::now() + Duration::from_secs(LOBBY_COUNTDOWN_SECS as u64))
+            } else {
+                None
+            };
+        }
+
+        let countdown = countdown_end.map(|end| {
+            let left = end.saturating_duration_since(Instant::now());
+            left.as_millis().div_ceil(1000) as u32
+        });
+        if countdown == Some(0) {
+            return Some(clients);
+        }
+        if changed || countdown != last_countdown {
+            let snapshot = lobby_snapshot(&clients, room_code, &config, countdown);
+            broadcast(&mut clients, &ServerMsg::Lobby(snapshot));
+            last_countdown = countdown;
+        }
+    }
+}
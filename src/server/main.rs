@@ -0,0 +1,255 @@
+// The server binary: reads the config file and command line, then runs snake::server until
+// it is killed.
+
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tracing::Level;
+use snake::game_core::sim::MatchConfig;
+use snake::game_core::{tls, DEFAULT_PORT};
+use snake::server::{self, config, map, Server, Setup};
+
+// read at startup when no --config is given, if it exists
+const DEFAULT_CONFIG_PATH: &str = "server.toml";
+// where the server listens unless --bind/--port or SNAKE_BIND/SNAKE_PORT say otherwise;
+// the loopback address keeps it off the network until asked
+const DEFAULT_BIND: IpAddr = IpAddr::V4(std::net::Ipv4Addr::LOCALHOST);
+// where players' lifetime stats are kept unless --stats says otherwise
+const DEFAULT_STATS_PATH: &str = "stats.json";
+// where player accounts are kept unless --accounts says otherwise
+const DEFAULT_ACCOUNTS_PATH: &str = "accounts.json";
+// where banned names and addresses are kept unless --bans says otherwise
+const DEFAULT_BANS_PATH: &str = "bans.json";
+
+struct ServerArgs {
+    config: MatchConfig,
+    // TCP and UDP both use this address
+    bind: SocketAddr,
+    // an IPv6 bind address leaves IPv4 clients out
+    ipv6_only: bool,
+    // only speak newline-delimited JSON, whatever clients ask for
+    json: bool,
+    // turn down clients asking for state by UDP
+    no_udp: bool,
+    // turn down clients asking for compressed frames
+    no_compress: bool,
+    stats: PathBuf,
+    accounts: PathBuf,
+    bans: PathBuf,
+    // only players logged in to an account may join
+    no_guests: bool,
+    // where the Prometheus endpoint listens, if anywhere
+    metrics: Option<String>,
+    // PEM files with the certificate chain and private key to serve TLS with
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
+    log_level: Level,
+    // one Json object per log line, for log collectors
+    log_json: bool,
+}
+
+// Settings come from the config file first; flags on the command line override it
+fn parse_args() -> Result<ServerArgs, String> {
+    let argv: Vec<String> = std::env::args().skip(1).collect();
+    let config = match argv.iter().position(|a| a == "--config") {
+        Some(i) => {
+            let path = argv.get(i + 1).ok_or("--config needs a value")?;
+            config::load(Path::new(path))?
+        }
+        None if Path::new(DEFAULT_CONFIG_PATH).exists() => config::load(Path::new(DEFAULT_CONFIG_PATH))?,
+        None => MatchConfig::default(),
+    };
+    let env = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+    let bind = match env("SNAKE_BIND") {
+        Some(value) => parse_bind(&value)?,
+        None => DEFAULT_BIND,
+    };
+    let port = match env("SNAKE_PORT") {
+        Some(value) => parse_port(&value)?,
+        None => DEFAULT_PORT,
+    };
+    let mut args = ServerArgs {
+        config,
+        bind: SocketAddr::new(bind, port),
+        ipv6_only: false,
+        json: false,
+        no_udp: false,
+        no_compress: false,
+        stats: PathBuf::from(DEFAULT_STATS_PATH),
+        accounts: PathBuf::from(DEFAULT_ACCOUNTS_PATH),
+        bans: PathBuf::from(DEFAULT_BANS_PATH),
+        no_guests: false,
+        metrics: None,
+        cert: None,
+        key: None,
+        log_level: Level::INFO,
+        log_json: false,
+    };
+
+    let mut it = argv.into_iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--config" => {
+                it.next();
+            }
+            "--bind" => {
+                let value = it.next().ok_or("--bind needs a value")?;
+                args.bind.set_ip(parse_bind(&value)?);
+            }
+            "--port" => {
+                let value = it.next().ok_or("--port needs a value")?;
+                args.bind.set_port(parse_port(&value)?);
+            }
+            "--ipv6-only" => args.ipv6_only = true,
+            "--state-every" => {
+                let value = it.next().ok_or("--state-every needs a value")?;
+                args.config.state_every = value.parse().map_err(|_| format!("invalid tick count: {}", value))?;
+            }
+            "--max-players" => {
+                let value = it.next().ok_or("--max-players needs a value")?;
+                args.config.max_players = value.parse().map_err(|_| format!("invalid player count: {}", value))?;
+            }
+            "--score-target" => {
+                let value = it.next().ok_or("--score-target needs a value")?;
+                let n: u32 = value.parse().map_err(|_| format!("invalid score target: {}", value))?;
+                args.config.score_target = Some(n);
+            }
+            "--grid" => {
+                let value = it.next().ok_or("--grid needs a value")?;
+                let (width, height) = parse_grid(&value)?;
+                args.config.game = args.config.game.resized(width, height);
+            }
+            "--map" => {
+                let path = it.next().ok_or("--map needs a value")?;
+                let ((width, height), board) = map::load(Path::new(&path))?;
+                args.config.game = args.config.game.resized(width, height);
+                args.config.map = Some(Arc::new(board));
+            }
+            "--mode" => {
+                let value = it.next().ok_or("--mode needs a value")?;
+                args.config.game.mode = value.parse()?;
+            }
+            "--seed" => {
+                let value = it.next().ok_or("--seed needs a value")?;
+                args.config.seed = Some(value.parse().map_err(|_| format!("invalid seed: {}", value))?);
+            }
+            "--bots" => {
+                let value = it.next().ok_or("--bots needs a value")?;
+                args.config.bots = value.parse().map_err(|_| format!("invalid bot count: {}", value))?;
+            }
+            "--bot-difficulty" => {
+                let value = it.next().ok_or("--bot-difficulty needs a value")?;
+                args.config.bot_difficulty = value.parse()?;
+            }
+            "--stats" => {
+                let path = it.next().ok_or("--stats needs a value")?;
+                args.stats = PathBuf::from(path);
+            }
+            "--accounts" => {
+                let path = it.next().ok_or("--accounts needs a value")?;
+                args.accounts = PathBuf::from(path);
+            }
+            "--bans" => {
+                let path = it.next().ok_or("--bans needs a value")?;
+                args.bans = PathBuf::from(path);
+            }
+            "--cert" => args.cert = Some(PathBuf::from(it.next().ok_or("--cert needs a value")?)),
+            "--key" => args.key = Some(PathBuf::from(it.next().ok_or("--key needs a value")?)),
+            "--metrics" => args.metrics = Some(it.next().ok_or("--metrics needs a value")?),
+            "--log-level" => {
+                let value = it.next().ok_or("--log-level needs a value")?;
+                args.log_level = value.parse().map_err(|_| format!("invalid log level: {} (expected error, warn, info, debug or trace)", value))?;
+            }
+            "--log-json" => args.log_json = true,
+            "--no-guests" => args.no_guests = true,
+            "--keep-scores" => args.config.keep_scores = true,
+            "--best-of" => {
+                let value = it.next().ok_or("--best-of needs a value")?;
+                args.config.best_of = Some(value.parse().map_err(|_| format!("invalid round count: {}", value))?);
+            }
+            "--json" => args.json = true,
+            "--no-udp" => args.no_udp = true,
+            "--no-compress" => args.no_compress = true,
+            other => return Err(format!("unknown argument: {}", other)),
+        }
+    }
+    if args.cert.is_some() != args.key.is_some() {
+        return Err("--cert and --key go together".to_string());
+    }
+    args.config.validate()?;
+    Ok(args)
+}
+
+// The address to listen on, e.g. 0.0.0.0 to be reachable from the LAN, or :: to be reachable
+// over IPv6 as well; IPv6 addresses may come in brackets
+fn parse_bind(value: &str) -> Result<IpAddr, String> {
+    let value = value.trim();
+    let bare = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')).unwrap_or(value);
+    bare.parse().map_err(|_| format!("invalid bind address: {} (expected e.g. 0.0.0.0 or ::)", value))
+}
+
+fn parse_port(value: &str) -> Result<u16, String> {
+    value.trim().parse().map_err(|_| format!("invalid port: {}", value))
+}
+
+// Reads a board size written as WIDTHxHEIGHT
+fn parse_grid(value: &str) -> Result<(i32, i32), String> {
+    let invalid = || format!("invalid grid size: {} (expected e.g. 60x30)", value);
+    let (w, h) = value.split_once(['x', 'X']).ok_or_else(invalid)?;
+    let grid_width: i32 = w.trim().parse().map_err(|_| invalid())?;
+    let grid_height: i32 = h.trim().parse().map_err(|_| invalid())?;
+    Ok((grid_width, grid_height))
+}
+
+fn main() -> std::io::Result<()> {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("usage: server [--bind ADDR] [--port N] [--ipv6-only] [--cert PATH --key PATH] [--config PATH] [--state-every N] [--max-players N] [--grid WxH] [--map PATH] [--mode NAME] [--score-target N] [--seed N] [--bots N] [--bot-difficulty NAME] [--stats PATH] [--accounts PATH] [--bans PATH] [--no-guests] [--metrics ADDR] [--log-level LEVEL] [--log-json] [--keep-scores] [--best-of N] [--json] [--no-udp] [--no-compress]");
+            std::process::exit(2);
+        }
+    };
+
+    let logs = tracing_subscriber::fmt().with_max_level(args.log_level).with_target(false);
+    if args.log_json {
+        logs.json().init();
+    } else {
+        logs.init();
+    }
+
+    let tls = match (&args.cert, &args.key) {
+        (Some(cert), Some(key)) => match tls::server_config(cert, key) {
+            Ok(config) => Some(config),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        },
+        _ => None,
+    };
+    let setup = Setup {
+        config: args.config,
+        json: args.json,
+        udp: !args.no_udp,
+        compress: !args.no_compress,
+        stats: args.stats,
+        accounts: args.accounts,
+        bans: args.bans,
+        guests: !args.no_guests,
+        metrics: args.metrics,
+        tls,
+        console: true,
+    };
+
+    let listener = server::listen(args.bind, args.ipv6_only)?;
+    match Server::start(listener, setup) {
+        Ok(server) => server.run(),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::game_core::{BoardMap, Pos, MAX_GRID_SIZE, MIN_GRID_SIZE};
+
+// Reads a map file. A map is drawn as text, one line per row of the board:
+//
+//   .  empty cell        #  wall
+//   S  spawn point       F  cell food may appear on
+//   1-9  teleporter, each digit used for exactly one pair of cells
+//
+// Lines starting with `;` are comments. Every row must be as wide as the first.
+// Returns the map's width and height along with it.
+pub fn load(path: &Path) -> Result<((i32, i32), BoardMap), String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+    parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn parse(text: &str) -> Result<((i32, i32), BoardMap), String> {
+    let rows: Vec<&str> = text.lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty() && !line.starts_with(';'))
+        .collect();
+    let width = rows.first().map_or(0, |row| row.chars().count()) as i32;
+    let height = rows.len() as i32;
+    let range = MIN_GRID_SIZE..=MAX_GRID_SIZE;
+    if !range.contains(&width) || !range.contains(&height) {
+        return Err(format!("map is {}x{}, sides must be between {} and {}", width, height, MIN_GRID_SIZE, MAX_GRID_SIZE));
+    }
+
+    let mut map = BoardMap::default();
+    let mut ends: BTreeMap<char, Vec<Pos>> = BTreeMap::new();
+    for (y, row) in rows.iter().enumerate() {
+        if row.chars().count() as i32 != width {
+            return Err(format!("row {} is not {} cells wide", y + 1, width));
+        }
+        for (x, c) in row.chars().enumerate() {
+            let pos = Pos { x: x as i32, y: y as i32 };
+            match c {
+                '.' => {}
+                '#' => map.walls.push(pos),
+                'S' => map.spawns.push(pos),
+                'F' => map.food_zones.push(pos),
+                '1'..='9' => ends.entry(c).or_default().push(pos),
+                _ => return Err(format!("row {}: unknown cell '{}'", y + 1, c)),
+            }
+        }
+    }
+    for (digit, cells) in ends {
+        match cells[..] {
+            [a, b] => map.teleporters.push((a, b)),
+            _ => return Err(format!("teleporter {} needs exactly two ends, found {}", digit, cells.len())),
+        }
+    }
+    Ok(((width, height), map))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARENA: &str = "\
+; a small test arena
+..........
+.S......S.
+..######..
+.1......1.
+..FF..FF..
+..........
+.2......2.
+..........
+..........
+..........
+";
+
+    #[test]
+    fn reads_every_kind_of_cell() {
+        let (size, map) = parse(ARENA).unwrap();
+        assert_eq!(size, (10, 10));
+        assert_eq!(map.spawns, vec![Pos { x: 1, y: 1 }, Pos { x: 8, y: 1 }]);
+        assert_eq!(map.walls.len(), 6);
+        assert_eq!(map.food_zones.len(), 4);
+        assert_eq!(map.teleporters, vec![
+            (Pos { x: 1, y: 3 }, Pos { x: 8, y: 3 }),
+            (Pos { x: 1, y: 6 }, Pos { x: 8, y: 6 }),
+        ]);
+        assert_eq!(map.teleport(Pos { x: 8, y: 6 }), Pos { x: 1, y: 6 });
+    }
+
+    #[test]
+    fn rejects_broken_maps() {
+        assert!(parse(&ARENA.replacen("..FF..FF..\n", "..FF..FF.\n", 1)).unwrap_err().contains("wide"));
+        assert!(parse(&ARENA.replacen(".2......2.", ".2......3.", 1)).unwrap_err().contains("teleporter"));
+        assert!(parse(&ARENA.replacen("..FF", "..XF", 1)).unwrap_err().contains("unknown cell"));
+        assert!(parse("....\n....\n").is_err());
+    }
+}
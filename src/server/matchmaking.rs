@@ -0,0 +1,105 @@
+// The matchmaking pool. Players who asked for a match wait here until there are enough of them
+// close enough in rating and ping to fill a room; what counts as close widens the longer they
+// wait, and after a while a smaller room will do.
+
+use std::time::{Duration, Instant};
+
+// rating points apart that are always close enough, and how much that grows every second waited
+const RATING_BAND: f64 = 100.0;
+const RATING_BAND_PER_SEC: f64 = 10.0;
+// the same for pings, in milliseconds
+const PING_BAND_MS: f64 = 80.0;
+const PING_BAND_PER_SEC: f64 = 5.0;
+// after this long two players make a match, even if the room isn't full
+const SETTLE_AFTER: Duration = Duration::from_secs(30);
+
+pub struct Seeker {
+    pub id: u32,
+    pub rating: i32,
+    // round trip the client last measured, once it has
+    pub ping_ms: Option<u64>,
+    pub since: Instant,
+}
+
+// Seekers by how long they've waited, longest first
+#[derive(Default)]
+pub struct Pool {
+    seekers: Vec<Seeker>,
+}
+
+impl Pool {
+    pub fn add(&mut self, seeker: Seeker) {
+        self.seekers.retain(|s| s.id != seeker.id);
+        self.seekers.push(seeker);
+    }
+
+    pub fn retain(&mut self, keep: impl Fn(u32) -> bool) {
+        self.seekers.retain(|s| keep(s.id));
+    }
+
+    pub fn seekers(&self) -> &[Seeker] {
+        &self.seekers
+    }
+
+    pub fn set_ping(&mut self, id: u32, ping_ms: u64) {
+        if let Some(seeker) = self.seekers.iter_mut().find(|s| s.id == id) {
+            seeker.ping_ms = Some(ping_ms);
+        }
+    }
+
+    // Takes out the players of a match, if one can be made: for the longest waiting seeker
+    // that has them, `size` seekers close to it, or at least two once it has settled
+    pub fn find_match(&mut self, size: usize, now: Instant) -> Option<Vec<u32>> {
+        for first in &self.seekers {
+            let waited = now.duration_since(first.since);
+            let secs = waited.as_secs_f64();
+            let (rating_band, ping_band) = (RATING_BAND + RATING_BAND_PER_SEC * secs, PING_BAND_MS + PING_BAND_PER_SEC * secs);
+            let mut close: Vec<&Seeker> = self.seekers.iter()
+                .filter(|s| s.id != first.id)
+                .filter(|s| ((s.rating - first.rating).abs() as f64) <= rating_band)
+                // an unknown ping is taken to be close to anything
+                .filter(|s| match (s.ping_ms, first.ping_ms) {
+                    (Some(a), Some(b)) => a.abs_diff(b) as f64 <= ping_band,
+                    _ => true,
+                })
+                .collect();
+            close.sort_by_key(|s| (s.rating - first.rating).abs());
+            close.truncate(size.saturating_sub(1));
+            if close.len() + 1 >= size || (waited >= SETTLE_AFTER && !close.is_empty()) {
+                let ids: Vec<u32> = std::iter::once(first.id).chain(close.iter().map(|s| s.id)).collect();
+                self.seekers.retain(|s| !ids.contains(&s.id));
+                return Some(ids);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seeker(id: u32, rating: i32, ping_ms: Option<u64>, since: Instant) -> Seeker {
+        Seeker { id, rating, ping_ms, since }
+    }
+
+    #[test]
+    fn matches_players_close_in_rating_and_ping() {
+        let start = Instant::now();
+        let mut pool = Pool::default();
+        pool.add(seeker(1, 1200, Some(30), start));
+        pool.add(seeker(2, 1900, Some(30), start));
+        pool.add(seeker(3, 1250, Some(400), start));
+        pool.add(seeker(4, 1180, Some(40), start));
+        // 2 is too good and 3 too far away for a full room of three
+        assert_eq!(pool.find_match(3, start), None);
+        pool.add(seeker(5, 1230, Some(50), start));
+        assert_eq!(pool.find_match(3, start), Some(vec![1, 4, 5]));
+        assert_eq!(pool.seekers().len(), 2);
+
+        // in time the bands widen, and two are enough
+        assert_eq!(pool.find_match(3, start + Duration::from_secs(10)), None);
+        assert_eq!(pool.find_match(3, start + Duration::from_secs(120)), Some(vec![2, 3]));
+        assert!(pool.seekers().is_empty());
+    }
+}
@@ -0,0 +1,168 @@
+// Counters for operators, served over HTTP in Prometheus' text format when the server
+// is started with --metrics. Every connection and room updates them as it goes, so they
+// live in a static rather than being handed to each one.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub struct Metrics {
+    ticks: AtomicU64,
+    tick_micros: AtomicU64,
+    // ticks played by all rooms together in the last full second
+    ticks_per_sec: AtomicU64,
+    clients: AtomicU64,
+    rooms: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    // connections that timed out, misbehaved or failed, rather than being closed by the client
+    dropped: AtomicU64,
+}
+
+pub static METRICS: Metrics = Metrics {
+    ticks: AtomicU64::new(0),
+    tick_micros: AtomicU64::new(0),
+    ticks_per_sec: AtomicU64::new(0),
+    clients: AtomicU64::new(0),
+    rooms: AtomicU64::new(0),
+    bytes_sent: AtomicU64::new(0),
+    bytes_received: AtomicU64::new(0),
+    dropped: AtomicU64::new(0),
+};
+
+impl Metrics {
+    pub fn tick(&self, took: Duration) {
+        self.ticks.fetch_add(1, Ordering::Relaxed);
+        self.tick_micros.fetch_add(took.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn connected(&self) {
+        self.clients.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn disconnected(&self) {
+        self.clients.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_rooms(&self, rooms: usize) {
+        self.rooms.store(rooms as u64, Ordering::Relaxed);
+    }
+
+    pub fn sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn received(&self, bytes: usize) {
+        self.bytes_received.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let get = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let metrics = [
+            ("snake_ticks_total", "counter", "Ticks played by all rooms", get(&self.ticks).to_string()),
+            ("snake_tick_duration_seconds_total", "counter", "Time spent running ticks", format!("{:.6}", get(&self.tick_micros) as f64 / 1e6)),
+            ("snake_ticks_per_second", "gauge", "Ticks played by all rooms in the last second", get(&self.ticks_per_sec).to_string()),
+            ("snake_connected_clients", "gauge", "Open client connections", get(&self.clients).to_string()),
+            ("snake_rooms", "gauge", "Open rooms", get(&self.rooms).to_string()),
+            ("snake_bytes_sent_total", "counter", "Bytes sent to clients over TCP and UDP", get(&self.bytes_sent).to_string()),
+            ("snake_bytes_received_total", "counter", "Bytes received from clients over TCP and UDP", get(&self.bytes_received).to_string()),
+            ("snake_dropped_connections_total", "counter", "Connections that timed out, misbehaved or failed", get(&self.dropped).to_string()),
+        ];
+        let mut text = String::new();
+        for (name, kind, help, value) in metrics {
+            text += &format!("# HELP {} {}\n# TYPE {} {}\n{} {}\n", name, help, name, kind, name, value);
+        }
+        text
+    }
+}
+
+// Longest request line read; a GET for /metrics is far shorter
+const MAX_REQUEST_LINE: u64 = 1024;
+// How long a scraper gets to send its whole request, or take the answer
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Reads from a socket until a deadline, however slowly the bytes trickle in
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    until: Instant,
+}
+
+impl Read for Deadline<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+// The request line, cut off at MAX_REQUEST_LINE bytes so a client can't make it grow forever
+fn request_line(stream: impl Read) -> io::Result<String> {
+    let mut request = Vec::new();
+    BufReader::new(stream.take(MAX_REQUEST_LINE)).read_until(b'\n', &mut request)?;
+    Ok(String::from_utf8_lossy(&request).into_owned())
+}
+
+// Answers one scrape; anything but GET /metrics is a 404
+fn serve(stream: TcpStream) -> io::Result<()> {
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let request = request_line(Deadline { stream: &stream, until: Instant::now() + REQUEST_TIMEOUT })?;
+    let (status, body) = match request.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+        ["GET", "/metrics"] => ("200 OK", METRICS.render()),
+        _ => ("404 Not Found", "Not found, try /metrics\n".to_string()),
+    };
+    let mut stream = stream;
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body)
+}
+
+pub fn spawn_endpoint(listener: TcpListener) {
+    thread::spawn(|| {
+        let mut last = 0;
+        loop {
+            thread::sleep(Duration::from_secs(1));
+            let ticks = METRICS.ticks.load(Ordering::Relaxed);
+            METRICS.ticks_per_sec.store(ticks - last, Ordering::Relaxed);
+            last = ticks;
+        }
+    });
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            thread::spawn(move || serve(stream));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_lines_are_cut_short() {
+        assert_eq!(request_line(&b"GET /metrics HTTP/1.1\r\nHost: x\r\n"[..]).unwrap(), "GET /metrics HTTP/1.1\r\n");
+        let endless = io::repeat(b'A');
+        assert_eq!(request_line(endless).unwrap().len(), MAX_REQUEST_LINE as usize);
+    }
+
+    #[test]
+    fn renders_prometheus_text() {
+        METRICS.tick(Duration::from_millis(2));
+        METRICS.sent(100);
+        let text = METRICS.render();
+        assert!(text.contains("# TYPE snake_ticks_total counter\nsnake_ticks_total "));
+        assert!(text.contains("# TYPE snake_rooms gauge\n"));
+        // every sample is a name and a number
+        for line in text.lines().filter(|l| !l.starts_with('#')) {
+            let (name, value) = line.split_once(' ').unwrap();
+            assert!(name.starts_with("snake_"));
+            assert!(value.parse::<f64>().is_ok());
+        }
+    }
+}
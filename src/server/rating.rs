@@ -0,0 +1,53 @@
+// Elo ratings for logged-in players. Every round counts as a match between each pair of rated
+// players in it, won by whoever finished ahead, and a player's change is the average of theirs.
+
+// where a player's first rated round starts them from
+pub const START_RATING: i32 = 1200;
+// the most a single round can move a rating
+const K: f64 = 32.0;
+
+// Each player's rating before the round and how they finished it, a greater `result`
+// finishing ahead; returns how much each rating changes, in the same order
+pub fn changes<R: Ord>(players: &[(i32, R)]) -> Vec<i32> {
+    if players.len() < 2 {
+        return vec![0; players.len()];
+    }
+    let opponents = (players.len() - 1) as f64;
+    players.iter().enumerate()
+        .map(|(i, (rating, result))| {
+            let total: f64 = players.iter().enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, (other_rating, other_result))| {
+                    let expected = 1.0 / (1.0 + 10f64.powf((other_rating - rating) as f64 / 400.0));
+                    let score = match result.cmp(other_result) {
+                        std::cmp::Ordering::Greater => 1.0,
+                        std::cmp::Ordering::Equal => 0.5,
+                        std::cmp::Ordering::Less => 0.0,
+                    };
+                    score - expected
+                })
+                .sum();
+            (K * total / opponents).round() as i32
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn winners_take_from_losers() {
+        // evenly matched: half of K either way
+        assert_eq!(changes(&[(1200, 1), (1200, 0)]), [16, -16]);
+        // beating someone much better pays more than beating someone much worse
+        let upset = changes(&[(1000, 1), (1400, 0)]);
+        let expected = changes(&[(1400, 1), (1000, 0)]);
+        assert!(upset[0] > expected[0] && upset[0] + upset[1] == 0);
+        assert_eq!(changes(&[(1200, 0), (1200, 0)]), [0, 0]);
+        // alone there's nobody to be rated against
+        assert_eq!(changes(&[(1200, 1)]), [0]);
+        // in a bigger round the middle player wins one and loses one
+        assert_eq!(changes(&[(1200, 2), (1200, 1), (1200, 0)]), [16, 0, -16]);
+    }
+}
@@ -0,0 +1,477 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time;
+use tracing::{info, info_span, warn, Instrument};
+use crate::game_core::codec;
+use crate::game_core::{check_name, normalize_name, AccountMsg, AccountToken, ClientMsg, ErrorCode, ErrorMsg, GameMode, MatchPhase, QueueMsg, RoomInfo, ServerMsg, WireFormat, MAX_ROOM_PASSWORD_LEN, ROOM_CODE_LEN};
+
+use super::accounts::{self, account_key, Accounts, Refusal};
+use super::bans::{self, Bans};
+use super::console::AdminCommand;
+use super::limits::PasswordGuesses;
+use super::matchmaking::{Pool, Seeker};
+use super::metrics::METRICS;
+use super::rating::START_RATING;
+use super::stats::Stats;
+use super::udp::UdpPeer;
+use crate::game_core::sim::room_seed;
+
+use super::{lobby, run_match, Inbox, MatchConfig, NetEvent, Outbox, REJOIN_GRACE};
+
+// Letters that can't be mistaken for each other when read out loud or off a screen
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
+// players a leaderboard lists
+const LEADERBOARD_SIZE: usize = 10;
+// players matchmaking puts in a room, when the server takes that many
+const MATCH_SIZE: usize = 4;
+// how often matchmaking looks for matches and tells the queue how it's doing
+const MATCHMAKING_INTERVAL: Duration = Duration::from_secs(1);
+// console commands a room may fall behind on before it misses some
+const ADMIN_BACKLOG: usize = 64;
+
+// A room runs its own lobby and matches on a task; the router feeds it the events
+// of the connections that joined it
+struct Room {
+    name: String,
+    mode: GameMode,
+    tx: mpsc::UnboundedSender<NetEvent>,
+    members: usize,
+    empty_since: Option<Instant>,
+    // set by the room's task while a match is being played
+    in_progress: Arc<AtomicBool>,
+    // joining a private room takes this
+    password: Option<String>,
+}
+
+// A connection that hasn't picked a room yet; its Join/Rejoin waits here
+struct Pending {
+    outbox: Outbox,
+    ip: IpAddr,
+    format: Option<WireFormat>,
+    udp: Option<UdpPeer>,
+    queued: Vec<ClientMsg>,
+    // the account it logged in to
+    account: Option<String>,
+    // round trip its client last measured, for matchmaking
+    ping_ms: Option<u64>,
+}
+
+fn new_room_code(rooms: &HashMap<String, Room>) -> String {
+    let mut rng = rand::thread_rng();
+    loop {
+        let code: String = (0..ROOM_CODE_LEN)
+            .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+            .collect();
+        if !rooms.contains_key(&code) {
+            return code;
+        }
+    }
+}
+
+// Opens a room named `name`, or after its code without one, playing `mode` on a board that
+// wraps around by `wrap`, or the server's defaults
+fn create_room(rooms: &mut HashMap<String, Room>, name: Option<String>, mode: Option<GameMode>, wrap: Option<bool>, config: &MatchConfig, stats: &Stats, admin: &broadcast::Sender<AdminCommand>) -> String {
+    let code = new_room_code(rooms);
+    let name = name.unwrap_or_else(|| format!("Room {}", code));
+    let mut room_config = config.clone();
+    room_config.game.mode = mode.unwrap_or(config.game.mode);
+    room_config.game.wrap = wrap.unwrap_or(config.game.wrap);
+    room_config.seed = config.seed.map(|seed| room_seed(seed, &code));
+    info!(room = %code, %name, mode = %room_config.game.mode, "Room created");
+    rooms.insert(code.clone(), spawn_room(code.clone(), name, room_config, stats.clone(), admin.subscribe()));
+    code
+}
+
+fn spawn_room(code: String, name: String, config: MatchConfig, stats: Stats, admin: broadcast::Receiver<AdminCommand>) -> Room {
+    let (tx, rx) = mpsc::unbounded_channel::<NetEvent>();
+    let mode = config.game.mode;
+    let in_progress = Arc::new(AtomicBool::new(false));
+    let playing = in_progress.clone();
+    let span = info_span!("room", code = %code);
+    tokio::spawn(async move {
+        let mut inbox = Inbox::new(rx, admin);
+        // Gather players in the lobby, play, and go back to the lobby once everyone has left
+        while let Some(clients) = lobby::run_lobby(&mut inbox, &code, config.clone()).await {
+            info!(players = clients.len(), "Match starting");
+            playing.store(true, Ordering::Relaxed);
+            run_match(&mut inbox, &code, clients, config.clone(), &stats).await;
+            playing.store(false, Ordering::Relaxed);
+            info!("Match over, back to the lobby");
+        }
+        info!("Room closed");
+    }.instrument(span));
+    Room { name, mode, tx, members: 0, empty_since: None, in_progress, password: None }
+}
+
+fn room_list(rooms: &HashMap<String, Room>, max_players: usize) -> Vec<RoomInfo> {
+    let mut list: Vec<RoomInfo> = rooms.iter()
+        .map(|(code, room)| RoomInfo {
+            code: code.clone(),
+            name: room.name.clone(),
+            players: room.members as u8,
+            capacity: max_players as u8,
+            phase: if room.in_progress.load(Ordering::Relaxed) { MatchPhase::Playing } else { MatchPhase::Lobby },
+            mode: room.mode,
+            locked: room.password.is_some(),
+        })
+        .collect();
+    list.sort_by(|a, b| a.name.cmp(&b.name));
+    list
+}
+
+impl Pending {
+    // Connections that haven't entered a room yet aren't Clients, so they're answered here
+    fn send(&self, msg: &ServerMsg) {
+        if let Ok(bytes) = codec::encode(self.format.unwrap_or_default(), msg) {
+            let _ = self.outbox.send(bytes.into());
+        }
+    }
+
+    fn send_error(&self, code: ErrorCode, message: String) {
+        self.send(&ServerMsg::Error(ErrorMsg { code, message }));
+    }
+
+    // The name its Join asked for
+    fn joined_as(&self) -> Option<&str> {
+        self.queued.iter().find_map(|m| match m {
+            ClientMsg::Join { name } => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    // Why it can't play under the name it joined with, if it can't
+    fn refusal(&self, accounts: &Accounts) -> Option<Refusal> {
+        let name = self.joined_as()?;
+        if let Err(message) = check_name(name) {
+            return Some((ErrorCode::BadRequest, message));
+        }
+        // in any spelling; a guest playing "ALICE" would take the name from alice otherwise
+        if accounts.registered(name) && self.account.as_deref().map(account_key) != Some(account_key(name)) {
+            return Some((ErrorCode::AuthRequired, format!("{} is a registered name, log in to play as it", name)));
+        }
+        if !accounts.guests_allowed() && self.account.is_none() {
+            return Some((ErrorCode::AuthRequired, "This server only lets registered players in".to_string()));
+        }
+        None
+    }
+}
+
+// Hands a waiting connection over to its room, replaying what it sent so far
+fn enter_room(room: &mut Room, id: u32, pending: Pending) {
+    room.members += 1;
+    room.empty_since = None;
+    let _ = room.tx.send(NetEvent::Connected(id, pending.outbox, pending.ip));
+    if let Some(format) = pending.format {
+        let _ = room.tx.send(NetEvent::Handshake(id, format));
+    }
+    if let Some(peer) = pending.udp {
+        let _ = room.tx.send(NetEvent::UdpReady(id, peer));
+    }
+    if let Some(account) = pending.account {
+        let _ = room.tx.send(NetEvent::LoggedIn(id, account));
+    }
+    for msg in pending.queued {
+        let _ = room.tx.send(NetEvent::Message(id, msg));
+    }
+}
+
+// Tells a connection how its Register or Login went; a refused one is hung up on
+fn log_in(pending: &mut HashMap<u32, Pending>, id: u32, result: Result<(String, AccountToken), Refusal>) {
+    let Some(p) = pending.get_mut(&id) else { return };
+    match result {
+        Ok((name, token)) => {
+            p.send(&ServerMsg::Account(AccountMsg { name: name.clone(), token }));
+            p.account = Some(name);
+        }
+        Err((error, message)) => {
+            info!(id, "Rejected: {}", message);
+            p.send_error(error, message);
+            pending.remove(&id);
+        }
+    }
+}
+
+// Hangs up on the waiting connections `target` names and has every room kick its own
+fn kick(pending: &mut HashMap<u32, Pending>, admin: &broadcast::Sender<AdminCommand>, target: &str, reason: &str) {
+    pending.retain(|id, p| {
+        if !bans::targets(target, p.joined_as(), p.ip) {
+            return true;
+        }
+        info!(id, "Kicked");
+        // dropping the outbox closes the connection once the Kick is out
+        p.send(&ServerMsg::Kick { reason: reason.to_string() });
+        false
+    });
+    // no rooms, nobody to kick in them
+    let _ = admin.send(AdminCommand::Kick { target: target.to_string(), reason: reason.to_string() });
+}
+
+// Puts the players matchmaking found a match for into a room of their own, readied up, and
+// tells the rest how long they've been waiting
+fn matchmake(pool: &mut Pool, pending: &mut HashMap<u32, Pending>, rooms: &mut HashMap<String, Room>, routes: &mut HashMap<u32, String>, config: &MatchConfig, stats: &Stats, admin: &broadcast::Sender<AdminCommand>) {
+    // those that hung up or were kicked
+    pool.retain(|id| pending.contains_key(&id));
+    let now = Instant::now();
+    while let Some(ids) = pool.find_match(MATCH_SIZE.min(config.max_players), now) {
+        let code = create_room(rooms, Some("Matched game".to_string()), None, None, config, stats, admin);
+        info!(room = %code, players = ids.len(), "Match found");
+        for id in ids {
+            let Some(mut p) = pending.remove(&id) else { continue };
+            p.queued.push(ClientMsg::Ready { ready: true });
+            enter_room(rooms.get_mut(&code).unwrap(), id, p);
+            routes.insert(id, code.clone());
+        }
+    }
+    let waiting = pool.seekers().len() as u32;
+    for seeker in pool.seekers() {
+        let status = QueueMsg { waiting, waited_secs: now.duration_since(seeker.since).as_secs() };
+        pending[&seeker.id].send(&ServerMsg::Queue(status));
+    }
+}
+
+// Routes connections into rooms until the acceptor goes away
+pub async fn run_router(mut rx_events: mpsc::UnboundedReceiver<NetEvent>, config: MatchConfig, stats: Stats, accounts: Accounts, bans: Bans) {
+    let mut rooms: HashMap<String, Room> = HashMap::new();
+    let mut pending: HashMap<u32, Pending> = HashMap::new();
+    // which room each routed connection belongs to
+    let mut routes: HashMap<u32, String> = HashMap::new();
+    let mut pool = Pool::default();
+    let mut housekeeping = time::interval(MATCHMAKING_INTERVAL);
+    let mut guesses = PasswordGuesses::default();
+    // console commands for every room; each room listens from the moment it opens
+    let (admin, _) = broadcast::channel::<AdminCommand>(ADMIN_BACKLOG);
+
+    loop {
+        let event = tokio::select! {
+            event = rx_events.recv() => event,
+            _ = housekeeping.tick() => {
+                matchmake(&mut pool, &mut pending, &mut rooms, &mut routes, &config, &stats, &admin);
+                // empty rooms stay around long enough for a dropped player to rejoin;
+                // dropping a room's sender ends its task
+                rooms.retain(|_, room| room.empty_since.is_none_or(|since| since.elapsed() < REJOIN_GRACE));
+                guesses.forget_quiet(Instant::now());
+                METRICS.set_rooms(rooms.len());
+                continue;
+            }
+        };
+        let Some(event) = event else { return };
+        match event {
+            NetEvent::Connected(id, outbox, ip) => {
+                pending.insert(id, Pending { outbox, ip, format: None, udp: None, queued: Vec::new(), account: None, ping_ms: None });
+            }
+            NetEvent::Handshake(id, format) => {
+                if let Some(p) = pending.get_mut(&id) {
+                    p.format = Some(format);
+                } else if let Some(room) = routes.get(&id).and_then(|code| rooms.get(code)) {
+                    let _ = room.tx.send(NetEvent::Handshake(id, format));
+                }
+            }
+            NetEvent::UdpReady(id, peer) => {
+                if let Some(p) = pending.get_mut(&id) {
+                    p.udp = Some(peer);
+                } else if let Some(room) = routes.get(&id).and_then(|code| rooms.get(code)) {
+                    let _ = room.tx.send(NetEvent::UdpReady(id, peer));
+                }
+            }
+            NetEvent::Message(id, msg) => {
+                if let Some(room) = routes.get(&id).and_then(|code| rooms.get(code)) {
+                    // a name is only checked on the way into a room
+                    if !matches!(msg, ClientMsg::Join { .. }) {
+                        let _ = room.tx.send(NetEvent::Message(id, msg));
+                    }
+                    continue;
+                }
+                let Some(p) = pending.get_mut(&id) else { continue };
+                if matches!(msg, ClientMsg::CreateRoom { .. } | ClientMsg::JoinRoom { .. } | ClientMsg::QueueForMatch)
+                    && let Some(ban) = bans.find(p.joined_as(), p.ip)
+                {
+                    warn!(id, target = %ban.target, "Rejected: banned");
+                    p.send(&ServerMsg::Kick { reason: ban.reason });
+                    pending.remove(&id);
+                    continue;
+                }
+                if matches!(msg, ClientMsg::CreateRoom { .. } | ClientMsg::JoinRoom { .. } | ClientMsg::QueueForMatch)
+                    && let Some((error, message)) = p.refusal(&accounts)
+                {
+                    info!(id, "Rejected: {}", message);
+                    p.send_error(error, message);
+                    pending.remove(&id);
+                    continue;
+                }
+                let code = match msg {
+                    ClientMsg::Ping { sent_ms, rtt_ms } => {
+                        p.send(&ServerMsg::Pong { sent_ms });
+                        if let Some(ms) = rtt_ms {
+                            p.ping_ms = Some(ms);
+                            pool.set_ping(id, ms);
+                        }
+                        continue;
+                    }
+                    ClientMsg::ListRooms => {
+                        p.send(&ServerMsg::RoomList(room_list(&rooms, config.max_players)));
+                        continue;
+                    }
+                    ClientMsg::GetStats { name } => {
+                        p.send(&ServerMsg::Stats(stats.get(&name)));
+                        continue;
+                    }
+                    ClientMsg::GetLeaderboard => {
+                        p.send(&ServerMsg::Leaderboard(stats.leaderboard(LEADERBOARD_SIZE)));
+                        continue;
+                    }
+                    ClientMsg::Register { name, password } => {
+                        // hashing the password takes a while, so the runtime's other tasks move off this thread meanwhile
+                        let result = tokio::task::block_in_place(|| accounts.register(&name, &password));
+                        if result.is_ok() {
+                            info!(id, %name, "Registered");
+                        }
+                        log_in(&mut pending, id, result);
+                        continue;
+                    }
+                    ClientMsg::Login { name, credential } => {
+                        let result = tokio::task::block_in_place(|| accounts.login(&name, &credential));
+                        log_in(&mut pending, id, result);
+                        continue;
+                    }
+                    ClientMsg::CreateRoom { mode, wrap, password } => {
+                        // an empty password is none at all
+                        let password = password.filter(|password| !password.is_empty());
+                        if password.as_ref().is_some_and(|password| password.chars().count() > MAX_ROOM_PASSWORD_LEN) {
+                            p.send_error(ErrorCode::BadRequest, format!("A room password can be at most {} characters long", MAX_ROOM_PASSWORD_LEN));
+                            pending.remove(&id);
+                            continue;
+                        }
+                        // named after whoever opened it
+                        let name = p.joined_as().map(|creator| format!("{}'s room", creator));
+                        let code = create_room(&mut rooms, name, mode, wrap, &config, &stats, &admin);
+                        if password.is_some() {
+                            info!(room = %code, "Room made private");
+                        }
+                        rooms.get_mut(&code).unwrap().password = password;
+                        code
+                    }
+                    ClientMsg::QueueForMatch => {
+                        if p.joined_as().is_none() {
+                            p.send_error(ErrorCode::BadRequest, "Join before asking for a match".to_string());
+                            pending.remove(&id);
+                            continue;
+                        }
+                        let rating = p.account.as_deref().and_then(|name| stats.get(name).rating).unwrap_or(START_RATING);
+                        info!(id, rating, "Queued for a match");
+                        pool.add(Seeker { id, rating, ping_ms: p.ping_ms, since: Instant::now() });
+                        continue;
+                    }
+                    ClientMsg::JoinRoom { code, password } => {
+                        let code = code.trim().to_uppercase();
+                        let now = Instant::now();
+                        // whole seconds left, if this address has to wait before guessing again
+                        let wait = guesses.locked_out(p.ip, now).map(|left| left.as_secs_f64().ceil() as u64);
+                        let refusal = match rooms.get(&code) {
+                            None => Some((ErrorCode::NoSuchRoom, format!("There is no room {}", code))),
+                            // the password comes first, so a private room doesn't say how full it is
+                            Some(Room { password: Some(_), .. }) if password.is_none() => {
+                                Some((ErrorCode::WrongPassword, format!("Room {} is private, enter its password", code)))
+                            }
+                            // too many wrong ones, and even the right one waits
+                            Some(Room { password: Some(_), .. }) if wait.is_some() => {
+                                Some((ErrorCode::WrongPassword, format!("Too many wrong passwords, try again in {}s", wait.unwrap_or_default())))
+                            }
+                            Some(Room { password: Some(expected), .. }) if !password.as_deref().is_some_and(|given| accounts::same(given, expected)) => {
+                                guesses.wrong(p.ip, now);
+                                Some((ErrorCode::WrongPassword, format!("Wrong password for room {}", code)))
+                            }
+                            Some(room) if room.members >= config.max_players => {
+                                Some((ErrorCode::RoomFull, format!("Room {} is full", code)))
+                            }
+                            Some(_) => None,
+                        };
+                        if let Some((error, message)) = refusal {
+                            info!(id, "Rejected: {}", message);
+                            p.send_error(error, message);
+                            // dropping the outbox closes the connection once the error is out
+                            pending.remove(&id);
+                            continue;
+                        }
+                        code
+                    }
+                    // names are tidied before anything looks at them
+                    ClientMsg::Join { name } => {
+                        p.queued.push(ClientMsg::Join { name: normalize_name(&name) });
+                        continue;
+                    }
+                    other => {
+                        p.queued.push(other);
+                        continue;
+                    }
+                };
+                let p = pending.remove(&id).unwrap();
+                enter_room(rooms.get_mut(&code).unwrap(), id, p);
+                routes.insert(id, code);
+            }
+            NetEvent::Disconnected(id) => {
+                pending.remove(&id);
+                if let Some(code) = routes.remove(&id)
+                    && let Some(room) = rooms.get_mut(&code)
+                {
+                    let _ = room.tx.send(NetEvent::Disconnected(id));
+                    room.members -= 1;
+                    if room.members == 0 {
+                        room.empty_since = Some(Instant::now());
+                    }
+                }
+            }
+            NetEvent::Admin(command) => match &command {
+                AdminCommand::Rooms => {
+                    for room in room_list(&rooms, config.max_players) {
+                        let status = if room.phase == MatchPhase::Lobby { "lobby" } else { "playing" };
+                        println!("{}  {} - {}, {}/{} players, {}", room.code, room.name, room.mode, room.players, room.capacity, status);
+                    }
+                    if rooms.is_empty() {
+                        println!("No rooms");
+                    }
+                }
+                AdminCommand::TickMs { room: code, .. } | AdminCommand::EndRound { room: code } => match rooms.get(code) {
+                    Some(room) => {
+                        let _ = room.tx.send(NetEvent::Admin(command.clone()));
+                    }
+                    None => println!("There is no room {}", code),
+                },
+                AdminCommand::Ban { target, reason } => {
+                    if bans.ban(target, reason) {
+                        info!(%target, "Banned");
+                    } else {
+                        println!("{} was already banned", target);
+                    }
+                    kick(&mut pending, &admin, target, reason);
+                }
+                AdminCommand::Unban(target) => {
+                    if bans.unban(target) {
+                        info!(%target, "Unbanned");
+                    } else {
+                        println!("{} isn't banned", target);
+                    }
+                }
+                AdminCommand::Bans => {
+                    for ban in bans.list() {
+                        println!("{}  {}", ban.target, ban.reason);
+                    }
+                    if bans.list().is_empty() {
+                        println!("No bans");
+                    }
+                }
+                AdminCommand::Kick { target, reason } => kick(&mut pending, &admin, target, reason),
+                AdminCommand::Players | AdminCommand::Say(_) => {
+                    let _ = admin.send(command.clone());
+                }
+            },
+            // the router logs connections in itself and tells rooms, it's never told
+            NetEvent::LoggedIn(..) => {}
+        }
+    }
+}
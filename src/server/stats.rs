@@ -0,0 +1,189 @@
+// Lifetime statistics of everyone who played on this server, by account, or by name for guests. Rooms count
+// into them as their matches run, and they're kept in a Json file so they outlast the server.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use super::rating;
+use crate::game_core::sim::GameState;
+use crate::game_core::{GameEvent, PlayerId, PlayerState, PlayerStats, RoundEndMsg};
+
+#[derive(Clone)]
+pub struct Stats {
+    path: PathBuf,
+    players: Arc<Mutex<HashMap<String, PlayerStats>>>,
+}
+
+impl Stats {
+    // Without the file everyone starts from zero
+    pub fn load(path: &Path) -> Result<Stats, String> {
+        let list: Vec<PlayerStats> = match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("can't read {}: {}", path.display(), e)),
+        };
+        let players = list.into_iter().map(|stats| (stats.name.clone(), stats)).collect();
+        Ok(Stats { path: path.to_path_buf(), players: Arc::new(Mutex::new(players)) })
+    }
+
+    pub fn get(&self, name: &str) -> PlayerStats {
+        let players = self.players.lock().unwrap();
+        players.get(name).cloned().unwrap_or_else(|| PlayerStats { name: name.to_string(), ..Default::default() })
+    }
+
+    fn update(&self, name: &str, f: impl FnOnce(&mut PlayerStats)) {
+        let mut players = self.players.lock().unwrap();
+        let stats = players.entry(name.to_string()).or_insert_with(|| PlayerStats { name: name.to_string(), ..Default::default() });
+        f(stats);
+    }
+
+    // Counts a tick that took `tick_ms`: what its events say happened, and time alive for
+    // every snake on the board. Only the players in `slots` are counted.
+    pub fn count_tick(&self, state: &GameState, events: &[GameEvent], tick_ms: u64, slots: &[usize]) {
+        let counted = |id: PlayerId| state.slot_of(id).filter(|slot| slots.contains(slot)).map(|slot| stats_name(&state.players[slot]));
+        for event in events {
+            match event {
+                GameEvent::Eat { player, .. } => {
+                    if let Some(name) = counted(*player) {
+                        self.update(name, |s| s.food_eaten += 1);
+                    }
+                }
+                GameEvent::Death(kill) => {
+                    if let Some(name) = counted(kill.victim) {
+                        self.update(name, |s| s.deaths += 1);
+                    }
+                    if let Some(name) = kill.killer.and_then(counted) {
+                        self.update(name, |s| s.kills += 1);
+                    }
+                }
+                GameEvent::PowerUp { .. } => {}
+            }
+        }
+        for &slot in slots {
+            let player = &state.players[slot];
+            if !player.dead && player.connected {
+                self.update(stats_name(player), |s| s.time_alive_ms += tick_ms);
+            }
+        }
+    }
+
+    // Counts a finished round for the players in `slots`, and rates the ones among them logged
+    // in to their account; their new ratings go into the round's standings
+    pub fn count_round(&self, state: &GameState, round_end: &mut RoundEndMsg, slots: &[usize]) {
+        let won = |player: &PlayerState| round_end.winner == Some(player.id) || (round_end.winning_team.is_some() && round_end.winning_team == player.team);
+        for &slot in slots {
+            let player = &state.players[slot];
+            let won = won(player);
+            self.update(stats_name(player), |s| {
+                s.games += 1;
+                s.wins += won as u32;
+            });
+        }
+
+        // the winners finished ahead of everyone, the rest by score
+        let rated: Vec<&PlayerState> = slots.iter().map(|&slot| &state.players[slot]).filter(|p| p.account.is_some()).collect();
+        let before: Vec<(i32, (bool, u32))> = rated.iter()
+            .map(|p| (self.get(stats_name(p)).rating.unwrap_or(rating::START_RATING), (won(p), p.score)))
+            .collect();
+        let changes = rating::changes(&before);
+        for ((player, (old, _)), change) in rated.iter().zip(&before).zip(changes) {
+            self.update(stats_name(player), |s| s.rating = Some(old + change));
+            if let Some(standing) = round_end.standings.iter_mut().find(|s| s.player == player.id) {
+                standing.rating = Some(old + change);
+                standing.rating_change = Some(change);
+            }
+        }
+    }
+
+    // The rated players with the best ratings, ties by name
+    pub fn leaderboard(&self, count: usize) -> Vec<PlayerStats> {
+        let mut rated: Vec<PlayerStats> = self.players.lock().unwrap().values().filter(|s| s.rating.is_some()).cloned().collect();
+        rated.sort_by(|a, b| b.rating.cmp(&a.rating).then_with(|| a.name.cmp(&b.name)));
+        rated.truncate(count);
+        rated
+    }
+
+    // Writes the file, sorted by name; failing only loses what was counted since the last save
+    pub fn save(&self) {
+        let mut list: Vec<PlayerStats> = self.players.lock().unwrap().values().cloned().collect();
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        let written = serde_json::to_string_pretty(&list).map_err(io::Error::other).and_then(|text| fs::write(&self.path, text));
+        if let Err(e) = written {
+            tracing::error!("Can't save stats to {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+// Whom a player's numbers go to: the account they logged in to, so renaming doesn't lose them
+fn stats_name(player: &PlayerState) -> &str {
+    player.account.as_deref().unwrap_or(&player.name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_core::sim::MatchConfig;
+    use crate::game_core::{FoodKind, KillEvent, Pos};
+
+    #[test]
+    fn counts_matches_and_survives_a_restart() {
+        let path = std::env::temp_dir().join(format!("snake-stats-test-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let stats = Stats::load(&path).unwrap();
+        let names = ["alice".to_string(), "bob".to_string(), "Bot-1".to_string()];
+        let mut state = GameState::new(&names, MatchConfig { max_players: 3, ..MatchConfig::default() });
+        state.players[1].dead = true;
+        let events = [
+            GameEvent::Eat { player: 1, pos: Pos::default(), kind: FoodKind::Normal },
+            GameEvent::Death(KillEvent { victim: 2, killer: Some(1) }),
+            GameEvent::Death(KillEvent { victim: 3, killer: Some(1) }),
+        ];
+        // the bot in slot 2 isn't counted
+        stats.count_tick(&state, &events, 150, &[0, 1]);
+        // alice and bob are logged in, so the round is rated
+        state.players[0].account = Some("alice".to_string());
+        state.players[1].account = Some("bob".to_string());
+        let mut round_end = state.end_round().unwrap();
+        round_end.winner = Some(1);
+        stats.count_round(&state, &mut round_end, &[0, 1]);
+        stats.save();
+
+        let stats = Stats::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        let alice = stats.get("alice");
+        assert_eq!((alice.games, alice.wins, alice.kills, alice.deaths, alice.food_eaten, alice.time_alive_ms), (1, 1, 2, 0, 1, 150));
+        let bob = stats.get("bob");
+        assert_eq!((bob.games, bob.wins, bob.kills, bob.deaths, bob.time_alive_ms), (1, 0, 0, 1, 0));
+        assert_eq!(stats.get("Bot-1").games, 0);
+        assert_eq!((alice.rating, bob.rating), (Some(1216), Some(1184)));
+        assert_eq!(round_end.standings.iter().find(|s| s.player == 1).and_then(|s| s.rating_change), Some(16));
+        assert_eq!(stats.leaderboard(1).iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), ["alice"]);
+    }
+
+    #[test]
+    fn players_count_under_their_account() {
+        let path = std::env::temp_dir().join(format!("snake-stats-account-test-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let stats = Stats::load(&path).unwrap();
+        // alice logged in but plays as "Ace", bob is a guest
+        let names = ["Ace".to_string(), "bob".to_string()];
+        let mut state = GameState::new(&names, MatchConfig { max_players: 2, ..MatchConfig::default() });
+        state.players[0].account = Some("alice".to_string());
+        let events = [GameEvent::Eat { player: 1, pos: Pos::default(), kind: FoodKind::Normal }];
+        stats.count_tick(&state, &events, 100, &[0, 1]);
+        let mut round_end = state.end_round().unwrap();
+        round_end.winner = Some(1);
+        stats.count_round(&state, &mut round_end, &[0, 1]);
+        let _ = fs::remove_file(&path);
+
+        let alice = stats.get("alice");
+        assert_eq!((alice.games, alice.wins, alice.food_eaten, alice.time_alive_ms), (1, 1, 1, 100));
+        assert!(alice.rating.is_some());
+        assert_eq!(stats.get("Ace").games, 0);
+        let bob = stats.get("bob");
+        assert_eq!((bob.games, bob.rating), (1, None));
+    }
+}
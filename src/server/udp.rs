@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex};
+
+use rand::Rng;
+use tokio::sync::mpsc;
+
+use super::metrics::METRICS;
+use super::NetEvent;
+
+// Snapshots bigger than this go over TCP anyway, so a datagram never needs IP fragmentation
+pub const MAX_DATAGRAM: usize = 1400;
+
+// Where a client receives its snapshots by datagram
+#[derive(Clone)]
+pub struct UdpPeer {
+    pub socket: Arc<tokio::net::UdpSocket>,
+    pub addr: SocketAddr,
+}
+
+impl UdpPeer {
+    // Best effort: a lost snapshot is replaced by the next one, and so is one the socket had no room for
+    pub fn send(&self, frame: &[u8]) {
+        if let Ok(n) = self.socket.try_send_to(frame, self.addr) {
+            METRICS.sent(n);
+        }
+    }
+}
+
+// Tickets handed out over TCP that haven't been presented over UDP yet, by connection
+#[derive(Clone, Default)]
+pub struct Tickets(Arc<Mutex<HashMap<u64, u32>>>);
+
+impl Tickets {
+    pub fn issue(&self, client_id: u32) -> u64 {
+        let ticket = rand::thread_rng().r#gen::<u64>();
+        self.0.lock().unwrap().insert(ticket, client_id);
+        ticket
+    }
+}
+
+// A client registers its UDP address by sending the ticket it got over TCP from the socket
+// it listens on; the first datagram with a valid ticket wins, the rest are ignored
+pub fn spawn_listener(socket: UdpSocket, tickets: Tickets, tx_events: mpsc::UnboundedSender<NetEvent>) -> std::io::Result<()> {
+    socket.set_nonblocking(true)?;
+    let socket = Arc::new(tokio::net::UdpSocket::from_std(socket)?);
+    tokio::spawn(async move {
+        let mut buf = [0u8; 64];
+        loop {
+            let Ok((n, addr)) = socket.recv_from(&mut buf).await else { continue };
+            METRICS.received(n);
+            let Ok(bytes) = <[u8; 8]>::try_from(&buf[..n]) else { continue };
+            let Some(client_id) = tickets.0.lock().unwrap().remove(&u64::from_le_bytes(bytes)) else { continue };
+            tracing::info!(id = client_id, %addr, "Receives states over UDP");
+            let peer = UdpPeer { socket: socket.clone(), addr };
+            if tx_events.send(NetEvent::UdpReady(client_id, peer)).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,336 @@
+// Load tester: opens many client connections to a running server, plays them in rooms with
+// random inputs, and reports how steadily states arrive, how late they run and what they
+// cost in bandwidth.
+
+use std::io::{self, BufReader, Read};
+use std::net::{Shutdown, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use snake::game_core::codec;
+use snake::game_core::{ClientMsg, Compression, Direction, ServerMsg, WireFormat, PING_INTERVAL_MS, PROTOCOL_VERSION};
+
+const DEFAULT_ADDR: &str = "127.0.0.1:4000";
+// a press every this long, about as often as a busy player
+const INPUT_INTERVAL: Duration = Duration::from_millis(200);
+// a server that sends nothing for this long counts as gone
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+struct StressArgs {
+    addr: String,
+    clients: usize,
+    // clients per room; the last room takes whoever is left
+    room_size: usize,
+    secs: u64,
+    format: WireFormat,
+    compression: Compression,
+}
+
+fn parse_args() -> Result<StressArgs, String> {
+    let mut args = StressArgs { addr: DEFAULT_ADDR.to_string(), clients: 8, room_size: 2, secs: 30, format: WireFormat::Binary, compression: Compression::None };
+    let mut it = std::env::args().skip(1);
+    let number = |flag: &str, value: Option<String>| -> Result<u64, String> {
+        let value = value.ok_or(format!("{} needs a value", flag))?;
+        value.parse().ok().filter(|n| *n > 0).ok_or(format!("invalid {}: {}", flag, value))
+    };
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--addr" => args.addr = it.next().ok_or("--addr needs a value")?,
+            "--clients" => args.clients = number("--clients", it.next())? as usize,
+            "--room-size" => args.room_size = number("--room-size", it.next())? as usize,
+            "--secs" => args.secs = number("--secs", it.next())?,
+            "--json" => args.format = WireFormat::Json,
+            "--compress" => {
+                args.compression = match it.next().as_deref() {
+                    Some("deflate") => Compression::Deflate,
+                    Some("lz4") => Compression::Lz4,
+                    other => return Err(format!("invalid --compress: {} (expected deflate or lz4)", other.unwrap_or(""))),
+                }
+            }
+            other => return Err(format!("unknown argument: {}", other)),
+        }
+    }
+    Ok(args)
+}
+
+// Counts the bytes read through it
+struct Counted<R> {
+    inner: R,
+    bytes: u64,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+}
+
+struct Conn {
+    reader: BufReader<Counted<TcpStream>>,
+    writer: Arc<Mutex<TcpStream>>,
+    format: WireFormat,
+    compression: Compression,
+}
+
+impl Conn {
+    fn open(args: &StressArgs, name: &str) -> Result<Conn, String> {
+        let stream = TcpStream::connect(&args.addr).map_err(|e| format!("can't connect to {}: {}", args.addr, e))?;
+        stream.set_read_timeout(Some(READ_TIMEOUT)).map_err(|e| e.to_string())?;
+        stream.set_nodelay(true).map_err(|e| e.to_string())?;
+        let writer = stream.try_clone().map_err(|e| e.to_string())?;
+        let mut conn = Conn {
+            reader: BufReader::new(Counted { inner: stream, bytes: 0 }),
+            writer: Arc::new(Mutex::new(writer)),
+            format: args.format,
+            compression: Compression::None,
+        };
+        let hello = ClientMsg::Hello { protocol_version: PROTOCOL_VERSION, format: args.format, udp: false, compression: args.compression };
+        codec::write_msg(&mut *conn.writer.lock().unwrap(), WireFormat::Json, &hello).map_err(|e| e.to_string())?;
+        let frame = codec::read_frame(&mut conn.reader, WireFormat::Json).map_err(|e| e.to_string())?.ok_or("closed during the handshake")?;
+        match codec::decode(WireFormat::Json, &frame).map_err(|e| e.to_string())? {
+            ServerMsg::Welcome { format, compression, .. } => {
+                conn.format = format;
+                conn.compression = compression;
+            }
+            ServerMsg::VersionMismatch { server_version } => return Err(format!("the server speaks protocol {}, this build {}", server_version, PROTOCOL_VERSION)),
+            ServerMsg::Kick { reason } => return Err(format!("turned away: {}", reason)),
+            _ => return Err("no Welcome from the server".to_string()),
+        }
+        conn.send(&ClientMsg::Join { name: name.to_string() })?;
+        Ok(conn)
+    }
+
+    fn send(&self, msg: &ClientMsg) -> Result<(), String> {
+        codec::write_msg(&mut *self.writer.lock().unwrap(), self.format, msg).map_err(|e| e.to_string())
+    }
+
+    fn recv(&mut self) -> Result<ServerMsg, String> {
+        let frame = codec::read_packed_frame(&mut self.reader, self.format, self.compression)
+            .map_err(|e| e.to_string())?
+            .ok_or("the server closed the connection")?;
+        codec::decode(self.format, &frame).map_err(|e| e.to_string())
+    }
+
+    // The room code once the server has put the client in a room
+    fn wait_for_session(&mut self) -> Result<String, String> {
+        loop {
+            match self.recv()? {
+                ServerMsg::Session(session) => return Ok(session.room_code),
+                ServerMsg::Error(e) => return Err(e.message),
+                ServerMsg::Kick { reason } => return Err(format!("kicked: {}", reason)),
+                _ => {}
+            }
+        }
+    }
+}
+
+// What one connection saw
+#[derive(Default)]
+struct Measured {
+    states: u64,
+    bytes: u64,
+    // how far the gap between two states strayed from the ticks between them, in microseconds
+    jitter_us: Vec<u64>,
+    // how much later than the earliest state of its round each state arrived, measured
+    // against the round's own tick clock, in microseconds
+    lateness_us: Vec<u64>,
+    rtt_ms: Vec<u64>,
+    // why the connection ended before the run did
+    error: Option<String>,
+}
+
+impl Measured {
+    // Lateness is relative to the best arrival of a round, since the two clocks differ
+    fn close_round(&mut self, offsets: &mut Vec<i64>) {
+        if let Some(&min) = offsets.iter().min() {
+            self.lateness_us.extend(offsets.iter().map(|o| (o - min) as u64));
+        }
+        offsets.clear();
+    }
+}
+
+// Reads the connection until the run stops, answering round ends with a rematch vote
+fn measure(mut conn: Conn, start: Instant, stop: Arc<AtomicBool>) -> Measured {
+    let mut measured = Measured::default();
+    // arrival time, tick and game clock of the last state
+    let mut last: Option<(Instant, u64, i64)> = None;
+    let mut offsets = Vec::new();
+    loop {
+        let msg = match conn.recv() {
+            Ok(msg) => msg,
+            Err(e) => {
+                if !stop.load(Ordering::Relaxed) {
+                    measured.error = Some(e);
+                }
+                break;
+            }
+        };
+        let now = Instant::now();
+        match msg {
+            ServerMsg::State(state) => {
+                measured.states += 1;
+                let clock_us = match last {
+                    // a new round starts its ticks over
+                    Some((at, tick, clock)) if state.tick > tick => {
+                        let expected = (state.tick - tick) * state.tick_ms * 1000;
+                        let took = now.duration_since(at).as_micros() as u64;
+                        measured.jitter_us.push(took.abs_diff(expected));
+                        clock + expected as i64
+                    }
+                    _ => {
+                        measured.close_round(&mut offsets);
+                        0
+                    }
+                };
+                offsets.push(now.duration_since(start).as_micros() as i64 - clock_us);
+                last = Some((now, state.tick, clock_us));
+            }
+            ServerMsg::RoundEnd(_) => {
+                let _ = conn.send(&ClientMsg::Restart);
+            }
+            ServerMsg::Pong { sent_ms } => measured.rtt_ms.push((start.elapsed().as_millis() as u64).saturating_sub(sent_ms)),
+            ServerMsg::Kick { reason } => {
+                measured.error = Some(format!("kicked: {}", reason));
+                break;
+            }
+            _ => {}
+        }
+    }
+    measured.close_round(&mut offsets);
+    measured.bytes = conn.reader.get_ref().bytes;
+    measured
+}
+
+// Presses random directions and pings like a real client, until the run stops
+fn drive(writer: Arc<Mutex<TcpStream>>, format: WireFormat, start: Instant, stop: Arc<AtomicBool>) {
+    let mut rng = rand::thread_rng();
+    let mut seq = 0;
+    let mut next_ping = Instant::now();
+    while !stop.load(Ordering::Relaxed) {
+        seq += 1;
+        let mut msgs = vec![ClientMsg::Input { dir: DIRECTIONS[rng.gen_range(0..DIRECTIONS.len())], seq }];
+        if Instant::now() >= next_ping {
+            msgs.push(ClientMsg::Ping { sent_ms: start.elapsed().as_millis() as u64, rtt_ms: None });
+            next_ping += Duration::from_millis(PING_INTERVAL_MS);
+        }
+        for msg in msgs {
+            if codec::write_msg(&mut *writer.lock().unwrap(), format, &msg).is_err() {
+                return;
+            }
+        }
+        // spread the clients out rather than have them all press at once
+        thread::sleep(INPUT_INTERVAL + Duration::from_millis(rng.gen_range(0..50)));
+    }
+}
+
+// Fills the rooms one at a time: the first client creates each, the others join by its code
+fn connect_all(args: &StressArgs) -> Result<Vec<Conn>, String> {
+    let mut conns = Vec::with_capacity(args.clients);
+    for room in 0..args.clients.div_ceil(args.room_size) {
+        let mut host = Conn::open(args, &format!("stress{}-0", room))?;
+        host.send(&ClientMsg::CreateRoom { mode: None, wrap: None, password: None })?;
+        let code = host.wait_for_session().map_err(|e| format!("room {}: {}", room, e))?;
+        conns.push(host);
+        for i in 1..args.room_size.min(args.clients - room * args.room_size) {
+            let mut guest = Conn::open(args, &format!("stress{}-{}", room, i))?;
+            guest.send(&ClientMsg::JoinRoom { code: code.clone(), password: None })?;
+            guest.wait_for_session().map_err(|e| format!("joining room {}: {}", code, e))?;
+            conns.push(guest);
+        }
+    }
+    for conn in &conns {
+        conn.send(&ClientMsg::Ready { ready: true })?;
+    }
+    Ok(conns)
+}
+
+// The p-th percentile of sorted samples
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    sorted[((sorted.len() - 1) as f64 * p).round() as usize]
+}
+
+fn spread(name: &str, samples: &mut [u64], scale: f64, unit: &str) {
+    samples.sort_unstable();
+    let show = |v: u64| format!("{:.1} {}", v as f64 / scale, unit);
+    println!(
+        "{:<15} p50 {:>10}  p95 {:>10}  p99 {:>10}  max {:>10}",
+        name,
+        show(percentile(samples, 0.5)),
+        show(percentile(samples, 0.95)),
+        show(percentile(samples, 0.99)),
+        show(samples.last().copied().unwrap_or(0))
+    );
+}
+
+fn report(args: &StressArgs, results: Vec<Measured>, elapsed: Duration) {
+    let secs = elapsed.as_secs_f64();
+    let rooms = args.clients.div_ceil(args.room_size);
+    let failed: Vec<&String> = results.iter().filter_map(|m| m.error.as_ref()).collect();
+    let states: u64 = results.iter().map(|m| m.states).sum();
+    let bytes: u64 = results.iter().map(|m| m.bytes).sum();
+    let (mut jitter, mut lateness, mut rtt) = (Vec::new(), Vec::new(), Vec::new());
+    for m in &results {
+        jitter.extend(&m.jitter_us);
+        lateness.extend(&m.lateness_us);
+        rtt.extend(&m.rtt_ms);
+    }
+
+    println!("{} clients in {} rooms on {} for {:.1} s, {} dropped", args.clients, rooms, args.addr, secs, failed.len());
+    println!("{:<15} {} ({:.1}/s per client)", "states", states, states as f64 / secs / args.clients as f64);
+    spread("tick jitter", &mut jitter, 1000.0, "ms");
+    spread("state lateness", &mut lateness, 1000.0, "ms");
+    spread("round trip", &mut rtt, 1.0, "ms");
+    println!("{:<15} {:.1} KB/s in all, {:.2} KB/s per client", "bandwidth", bytes as f64 / 1024.0 / secs, bytes as f64 / 1024.0 / secs / args.clients as f64);
+    for error in failed {
+        println!("dropped: {}", error);
+    }
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("usage: stress [--addr HOST:PORT] [--clients N] [--room-size N] [--secs N] [--json] [--compress deflate|lz4]");
+            std::process::exit(2);
+        }
+    };
+    let conns = match connect_all(&args) {
+        Ok(conns) => conns,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    println!("{} clients connected, playing for {} s", conns.len(), args.secs);
+
+    let start = Instant::now();
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut sockets = Vec::new();
+    let mut readers = Vec::new();
+    for conn in conns {
+        let writer = conn.writer.clone();
+        sockets.push(writer.clone());
+        let (format, stop_driving, stop_reading) = (conn.format, stop.clone(), stop.clone());
+        thread::spawn(move || drive(writer, format, start, stop_driving));
+        readers.push(thread::spawn(move || measure(conn, start, stop_reading)));
+    }
+    thread::sleep(Duration::from_secs(args.secs));
+    stop.store(true, Ordering::Relaxed);
+    let elapsed = start.elapsed();
+    // closing the sockets wakes up the readers
+    for socket in sockets {
+        let _ = socket.lock().unwrap().shutdown(Shutdown::Both);
+    }
+    let results = readers.into_iter().map(|r| r.join().unwrap()).collect();
+    report(&args, results, elapsed);
+}
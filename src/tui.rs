@@ -0,0 +1,535 @@
+// A terminal client: the same game as the windowed one, drawn with characters, for playing
+// over SSH or anywhere without a window system. It joins (or creates) one room and plays in
+// it until the player quits or the connection ends.
+
+use std::collections::VecDeque;
+use std::io;
+use std::time::{Duration, Instant};
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Clear, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use snake::game_core::net::tcp::{self, TcpTransport};
+use snake::game_core::net::{join_messages, version_mismatch_text, NetOptions, NetStatus, RoomChoice};
+use snake::game_core::scene::{self, Piece, Segment};
+use snake::game_core::{
+    check_name, strip_scheme, BoardMap, ClientMsg, Compression, Credential, Direction, FoodKind, GameConfig, GameMode, LobbyMsg, PauseMsg, PlayerId, PowerUpKind, RematchMsg,
+    RoundEndMsg, RoundEndReason, ServerMsg, Standing, StateMsg, WireFormat, MAX_PLAYERS, PING_INTERVAL_MS,
+};
+
+const DEFAULT_ADDR: &str = "127.0.0.1:4000";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// how long to wait for a key before drawing again
+const FRAME: Duration = Duration::from_millis(30);
+// news stays up this long, and only this much of it at once
+const NEWS_SECS: u64 = 5;
+const MAX_NEWS: usize = 5;
+const SIDEBAR_WIDTH: u16 = 30;
+
+// Snake colors by PlayerState::color, and by team in team rooms
+const COLORS: [Color; MAX_PLAYERS] = [Color::Blue, Color::Yellow, Color::Green, Color::Magenta, Color::Cyan, Color::Red, Color::White, Color::LightBlue];
+const TEAM_COLORS: [Color; 4] = [Color::Blue, Color::Yellow, Color::Green, Color::Magenta];
+
+struct TuiArgs {
+    addr: String,
+    name: String,
+    // join this room, or create one without it
+    room: Option<String>,
+    // the room's password, or the one a created room gets
+    room_password: Option<String>,
+    mode: GameMode,
+    wrap: bool,
+    options: NetOptions,
+}
+
+fn parse_args() -> Result<TuiArgs, String> {
+    let user = std::env::var("USER").ok().filter(|name| check_name(name).is_ok());
+    let mut args = TuiArgs {
+        addr: DEFAULT_ADDR.to_string(),
+        name: user.unwrap_or_else(|| "player".to_string()),
+        room: None,
+        room_password: None,
+        mode: GameMode::default(),
+        wrap: true,
+        options: NetOptions { format: WireFormat::Binary, udp: false, compression: Compression::None, state_every: 1, connect_timeout: CONNECT_TIMEOUT },
+    };
+    let mut it = std::env::args().skip(1);
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--addr" => args.addr = it.next().ok_or("--addr needs a value")?,
+            "--name" => args.name = it.next().ok_or("--name needs a value")?,
+            "--room" => args.room = Some(it.next().ok_or("--room needs a value")?),
+            "--room-password" => args.room_password = Some(it.next().ok_or("--room-password needs a value")?),
+            "--mode" => args.mode = it.next().ok_or("--mode needs a value")?.parse()?,
+            "--no-wrap" => args.wrap = false,
+            "--json" => args.options.format = WireFormat::Json,
+            "--udp" => args.options.udp = true,
+            "--compress" => {
+                args.options.compression = match it.next().as_deref() {
+                    Some("deflate") => Compression::Deflate,
+                    Some("lz4") => Compression::Lz4,
+                    other => return Err(format!("invalid --compress: {} (expected deflate or lz4)", other.unwrap_or(""))),
+                }
+            }
+            other => return Err(format!("unknown argument: {}", other)),
+        }
+    }
+    check_name(&args.name)?;
+    Ok(args)
+}
+
+// Rejoins the room's snake with a saved session, and logs in with a saved login, like the
+// windowed client does
+fn connect(args: &TuiArgs) -> TcpTransport {
+    let token = args.room.as_deref().and_then(|code| tcp::load_session(&args.addr, code));
+    let login = tcp::load_account(&args.addr, &args.name).map(|token| ClientMsg::Login { name: args.name.clone(), credential: Credential::Token(token) });
+    // datagrams aren't encrypted, so a secure connection keeps states on the stream
+    let options = NetOptions { udp: args.options.udp && !strip_scheme(&args.addr).0, ..args.options };
+    let room = match &args.room {
+        Some(code) => RoomChoice::Join { code: code.clone(), password: args.room_password.clone() },
+        None => RoomChoice::Create { mode: args.mode, wrap: args.wrap, password: args.room_password.clone() },
+    };
+    let opening = join_messages(args.name.clone(), room, token, login, options);
+    tcp::start_networking(args.addr.clone(), opening, options.connect_timeout)
+}
+
+struct Game {
+    net: TcpTransport,
+    // our snake's id, once the server's Session told it
+    me: Option<PlayerId>,
+    room_code: Option<String>,
+    config: GameConfig,
+    map: Option<BoardMap>,
+    state: Option<StateMsg>,
+    lobby: Option<LobbyMsg>,
+    round_end: Option<RoundEndMsg>,
+    rematch: Option<RematchMsg>,
+    voted_rematch: bool,
+    pause: Option<PauseMsg>,
+    ready: bool,
+    boosting: bool,
+    input_seq: u32,
+    news: VecDeque<(String, Color, Instant)>,
+    // set when the server turned us away; ends the game once the connection closes
+    error: Option<String>,
+    rtt_ms: Option<u64>,
+    started: Instant,
+    last_ping: Instant,
+}
+
+impl Game {
+    fn new(net: TcpTransport, args: &TuiArgs) -> Game {
+        Game {
+            net,
+            me: None,
+            room_code: args.room.clone(),
+            config: GameConfig::default(),
+            map: None,
+            state: None,
+            lobby: None,
+            round_end: None,
+            rematch: None,
+            voted_rematch: false,
+            pause: None,
+            ready: false,
+            boosting: false,
+            input_seq: 0,
+            news: VecDeque::new(),
+            error: None,
+            rtt_ms: None,
+            started: Instant::now(),
+            last_ping: Instant::now(),
+        }
+    }
+
+    fn tell(&mut self, text: String, color: Color) {
+        self.news.push_back((text, color, Instant::now()));
+        if self.news.len() > MAX_NEWS {
+            self.news.pop_front();
+        }
+    }
+
+    // Handles everything the server sent since the last frame. Returns the reason the
+    // game is over, if it is.
+    fn update(&mut self) -> Option<String> {
+        if self.last_ping.elapsed() >= Duration::from_millis(PING_INTERVAL_MS) {
+            self.last_ping = Instant::now();
+            self.net.send(ClientMsg::Ping { sent_ms: self.started.elapsed().as_millis() as u64, rtt_ms: self.rtt_ms });
+        }
+        while let Some(status) = self.net.poll_status() {
+            match status {
+                // the server's own explanation beats "connection closed"
+                NetStatus::Failed(reason) => return Some(self.error.take().unwrap_or(reason)),
+                NetStatus::Degraded(text) => self.tell(text, Color::Yellow),
+                NetStatus::Connected => {}
+            }
+        }
+        self.news.retain(|(_, _, at)| at.elapsed() < Duration::from_secs(NEWS_SECS));
+
+        while let Some(msg) = self.net.try_recv() {
+            match msg {
+                ServerMsg::VersionMismatch { server_version } => self.error = Some(version_mismatch_text(server_version)),
+                // refused before getting anywhere means the game is over
+                ServerMsg::Error(error) if self.state.is_none() && self.lobby.is_none() => self.error = Some(error.message),
+                ServerMsg::Error(error) => self.tell(error.message, Color::Red),
+                ServerMsg::Info(text) => self.tell(text, Color::White),
+                ServerMsg::Kick { reason } => self.error = Some(format!("Kicked: {}", reason)),
+                ServerMsg::Pong { sent_ms } => self.rtt_ms = Some((self.started.elapsed().as_millis() as u64).saturating_sub(sent_ms)),
+                ServerMsg::Session(session) => {
+                    self.room_code = Some(session.room_code);
+                    self.me = Some(session.player_id);
+                }
+                ServerMsg::Account(account) => self.tell(format!("Logged in as {}", account.name), Color::Green),
+                ServerMsg::Config(config) => self.config = config,
+                ServerMsg::Map(map) => self.map = Some(map),
+                ServerMsg::Lobby(lobby) => self.lobby = Some(lobby),
+                // drop snapshots that arrive after a newer one
+                ServerMsg::State(state) if self.state.as_ref().is_some_and(|s| s.seq >= state.seq) => {}
+                ServerMsg::State(state) => {
+                    // a running state after a round ended means the rematch started
+                    if !state.is_over() && self.round_end.is_some() {
+                        self.round_end = None;
+                        self.rematch = None;
+                        self.voted_rematch = false;
+                    }
+                    self.state = Some(state);
+                }
+                ServerMsg::RoundEnd(end) => self.round_end = Some(end),
+                ServerMsg::Rematch(rematch) => self.rematch = Some(rematch),
+                ServerMsg::Pause(pause) => {
+                    if !pause.paused && pause.votes > 0 {
+                        self.tell(format!("Pause requested ({}/{}), press P to agree", pause.votes, pause.needed), Color::Yellow);
+                    }
+                    self.pause = Some(pause);
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn playing(&self) -> bool {
+        self.state.as_ref().is_some_and(|s| !s.is_over()) && !self.pause.as_ref().is_some_and(|p| p.paused)
+    }
+
+    fn turn(&mut self, dir: Direction) {
+        if self.playing() {
+            self.input_seq += 1;
+            self.net.send(ClientMsg::Input { dir, seq: self.input_seq });
+        }
+    }
+
+    // False once the player asked to quit
+    fn key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Up | KeyCode::Char('w') | KeyCode::Char('k') => self.turn(Direction::Up),
+            KeyCode::Down | KeyCode::Char('s') | KeyCode::Char('j') => self.turn(Direction::Down),
+            KeyCode::Left | KeyCode::Char('a') | KeyCode::Char('h') => self.turn(Direction::Left),
+            KeyCode::Right | KeyCode::Char('d') | KeyCode::Char('l') => self.turn(Direction::Right),
+            // terminals don't report keys being let go, so boost toggles
+            KeyCode::Char('b') if self.playing() => {
+                self.boosting = !self.boosting;
+                self.net.send(ClientMsg::Boost { active: self.boosting });
+            }
+            KeyCode::Char('p') if self.state.as_ref().is_some_and(|s| !s.is_over()) => self.net.send(ClientMsg::Pause),
+            KeyCode::Enter | KeyCode::Char('r') if self.round_end.is_some() && !self.voted_rematch => {
+                self.voted_rematch = true;
+                self.net.send(ClientMsg::Restart);
+            }
+            KeyCode::Enter | KeyCode::Char('r') if self.state.is_none() && self.lobby.is_some() => {
+                self.ready = !self.ready;
+                self.net.send(ClientMsg::Ready { ready: self.ready });
+            }
+            KeyCode::Char('t') if self.state.is_none() => {
+                let Some(lobby) = self.lobby.as_ref().filter(|l| l.teams > 0) else { return true };
+                let team = lobby.players.iter().find(|p| Some(p.id) == self.me).and_then(|p| p.team).unwrap_or(0);
+                self.net.send(ClientMsg::PickTeam { team: (team + 1) % lobby.teams });
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, status] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [board, sidebar] = Layout::horizontal([Constraint::Min(0), Constraint::Length(SIDEBAR_WIDTH)]).areas(main);
+        match &self.state {
+            Some(state) => self.draw_board(frame, board, state),
+            None => self.draw_lobby(frame, board),
+        }
+        frame.render_widget(Paragraph::new(self.sidebar()).block(Block::bordered().title(" Snake ")), sidebar);
+        let help = match (&self.state, &self.round_end) {
+            (None, _) => "Enter ready  T team  Q quit",
+            (Some(_), Some(_)) => "Enter rematch  Q quit",
+            (Some(_), None) => "arrows/WASD/HJKL turn  B boost  P pause  Q quit",
+        };
+        frame.render_widget(Paragraph::new(help).style(Style::default().fg(Color::DarkGray)), status);
+    }
+
+    fn draw_lobby(&self, frame: &mut Frame, area: Rect) {
+        let mut lines = Vec::new();
+        match &self.lobby {
+            Some(lobby) => {
+                lines.push(Line::from(format!("Room {}  ({}/{} players)", lobby.room_code, lobby.players.len(), lobby.max_players)));
+                lines.push(Line::default());
+                for player in &lobby.players {
+                    let (mark, color) = if player.ready { ("ready", Color::Green) } else { ("waiting", Color::DarkGray) };
+                    let team = player.team.map(|t| format!("  team {}", t + 1)).unwrap_or_default();
+                    lines.push(Line::from(vec![Span::raw(format!("{:<18}", player.name)), Span::styled(mark, Style::default().fg(color)), Span::raw(team)]));
+                }
+                lines.push(Line::default());
+                lines.push(match lobby.countdown {
+                    Some(secs) => Line::styled(format!("Starting in {}...", secs), Style::default().fg(Color::Yellow)),
+                    None if self.ready => Line::from("Waiting for the others to be ready"),
+                    None => Line::from("Press Enter when you're ready"),
+                });
+            }
+            None => lines.push(Line::from("Connecting...")),
+        }
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" Lobby ")), area);
+    }
+
+    fn draw_board(&self, frame: &mut Frame, area: Rect, state: &StateMsg) {
+        let (width, height) = (self.config.grid_width.max(1) as usize, self.config.grid_height.max(1) as usize);
+        // square cells take two columns; a narrow terminal gets one
+        let cell_w = if area.width as usize >= width * 2 + 2 { 2 } else { 1 };
+        if (area.width as usize) < width * cell_w + 2 || (area.height as usize) < height + 2 {
+            let text = format!("The board needs a terminal of at least {}x{}", width + 2 + SIDEBAR_WIDTH as usize, height + 3);
+            frame.render_widget(Paragraph::new(text).block(Block::bordered()), area);
+            return;
+        }
+
+        let mut cells = vec![(' ', ' ', Style::default()); width * height];
+        let mut put = |x: i32, y: i32, glyph: (char, char), style: Style| {
+            if (0..width as i32).contains(&x) && (0..height as i32).contains(&y) {
+                cells[y as usize * width + x as usize] = (glyph.0, glyph.1, style);
+            }
+        };
+        for (pos, piece) in scene::pieces(state, self.map.as_ref()) {
+            let (glyph, style) = match piece {
+                Piece::FoodZone => continue,
+                Piece::Teleporter { .. } => (('(', ')'), Style::default().fg(Color::LightMagenta)),
+                Piece::Wall => (('█', '█'), Style::default().fg(Color::Gray)),
+                Piece::PowerUp(kind) => {
+                    let glyph = match kind {
+                        PowerUpKind::Speed => ('>', '>'),
+                        PowerUpKind::Slow => ('<', '<'),
+                        PowerUpKind::Ghost => ('?', '?'),
+                        PowerUpKind::Shrink => ('-', '-'),
+                    };
+                    (glyph, Style::default().fg(Color::LightCyan).add_modifier(Modifier::BOLD))
+                }
+                Piece::Body(segment) => (('█', '█'), Style::default().fg(self.segment_color(state, segment))),
+                Piece::Head(segment) => {
+                    let style = Style::default().fg(Color::Black).bg(self.segment_color(state, segment));
+                    ((':', ':'), style.add_modifier(Modifier::BOLD))
+                }
+                Piece::Food(kind) => {
+                    let color = match kind {
+                        FoodKind::Normal => Color::Red,
+                        FoodKind::Golden => Color::LightYellow,
+                        FoodKind::Leftover | FoodKind::Corpse => Color::Magenta,
+                        FoodKind::Poison => Color::LightGreen,
+                    };
+                    let glyph = if kind == FoodKind::Poison { ('>', '<') } else { ('<', '>') };
+                    (glyph, Style::default().fg(color).add_modifier(Modifier::BOLD))
+                }
+            };
+            put(pos.x, pos.y, glyph, style);
+        }
+
+        let lines: Vec<Line> = cells
+            .chunks(width)
+            .map(|row| {
+                let spans = row.iter().map(|&(left, right, style)| {
+                    let text = if cell_w == 2 { format!("{}{}", left, right) } else { left.to_string() };
+                    Span::styled(text, style)
+                });
+                Line::from(spans.collect::<Vec<_>>())
+            })
+            .collect();
+        let board = Rect { width: (width * cell_w + 2) as u16, height: (height + 2) as u16, ..area };
+        let mut title = format!(" {} ", self.config.mode);
+        if let Some(ms) = state.time_left_ms {
+            title += &format!("- {}:{:02} ", ms / 60_000, ms / 1000 % 60);
+        }
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(title)), board);
+
+        if let Some(end) = &self.round_end {
+            self.draw_round_end(frame, board, end);
+        } else if let Some(pause) = self.pause.as_ref().filter(|p| p.paused) {
+            let mut lines = vec![Line::from("Paused")];
+            if !pause.waiting_for.is_empty() {
+                lines.push(Line::from(format!("Waiting for {}", pause.waiting_for.join(", "))));
+            }
+            lines.push(Line::from(format!("P to resume ({}/{})", pause.votes, pause.needed)));
+            popup(frame, board, lines);
+        } else if let Some(ms) = state.countdown_ms {
+            popup(frame, board, vec![Line::from(format!("Starting in {}", ms.div_ceil(1000)))]);
+        }
+    }
+
+    fn segment_color(&self, state: &StateMsg, segment: Segment) -> Color {
+        let player = &state.players[segment.player];
+        if player.dead || segment.faded { Color::DarkGray } else { snake_color(player.color, player.team) }
+    }
+
+    fn draw_round_end(&self, frame: &mut Frame, area: Rect, end: &RoundEndMsg) {
+        let winner = end.standings.iter().find(|s| Some(s.player) == end.winner);
+        let title = match (winner, end.winning_team, end.reason) {
+            (Some(s), _, RoundEndReason::ScoreTarget) => format!("{} reached the target!", s.name),
+            (Some(s), _, RoundEndReason::HighestScore) => format!("{} wins on points!", s.name),
+            (Some(s), _, RoundEndReason::SuddenDeath) => format!("{} survived sudden death!", s.name),
+            (Some(s), _, _) => format!("{} wins!", s.name),
+            (None, Some(t), _) => format!("Team {} wins!", t + 1),
+            (None, None, _) if end.standings.len() > 1 => "Draw!".to_string(),
+            (None, None, _) => "Game Over".to_string(),
+        };
+        let secs = end.duration_ms / 1000;
+        let mut subtitle = format!("Round took {}:{:02}", secs / 60, secs % 60);
+        // a decided series names its champion instead of the round's winner
+        let series = end.series.as_ref();
+        let title = match series.filter(|s| s.is_over()) {
+            Some(series) => {
+                let first = series.champions.first().and_then(|&id| end.standings.iter().find(|s| s.player == id));
+                subtitle = format!("Best of {}, decided in round {}", series.best_of, series.round);
+                match (series.champions.len(), first) {
+                    (1, Some(s)) => format!("{} wins the series!", s.name),
+                    (_, Some(Standing { team: Some(t), .. })) => format!("Team {} wins the series!", t + 1),
+                    _ => "Series over".to_string(),
+                }
+            }
+            None => title,
+        };
+        if let Some(series) = series.filter(|s| !s.is_over()) {
+            subtitle = format!("Round {} of a best of {}   {}", series.round, series.best_of, subtitle);
+        }
+        let rated = end.standings.iter().any(|s| s.rating_change.is_some());
+        let mut lines = vec![
+            Line::styled(title, Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+            Line::from(subtitle),
+            Line::default(),
+            Line::styled(format!("    {:<18}{:>6}{:>6}{:>6}{:>7}{:>7}", "Player", "Score", "Kills", "Peak", if series.is_some() { "Rounds" } else { "" }, if rated { "Rating" } else { "" }), Style::default().fg(Color::DarkGray)),
+        ];
+        for (rank, s) in end.standings.iter().enumerate() {
+            let rounds = series.map_or(String::new(), |series| series.wins_of(s.player).to_string());
+            let rating = s.rating_change.map_or(String::new(), |change| format!("{:+}", change));
+            lines.push(Line::from(format!("{:>2}. {:<18}{:>6}{:>6}{:>6}{:>7}{:>7}", rank + 1, s.name, s.score, s.kills, s.peak_length, rounds, rating)));
+        }
+        lines.push(Line::default());
+        lines.push(Line::from(match (&self.rematch, self.voted_rematch) {
+            (Some(r), true) => format!("Waiting for a rematch ({}/{})", r.votes, r.needed),
+            (Some(r), false) => format!("Enter for a rematch ({}/{})", r.votes, r.needed),
+            (None, true) => "Waiting for a rematch".to_string(),
+            (None, false) => "Enter for a rematch".to_string(),
+        }));
+        popup(frame, area, lines);
+    }
+
+    fn sidebar(&self) -> Vec<Line<'_>> {
+        let mut lines = vec![Line::from(format!("Room {}", self.room_code.as_deref().unwrap_or("-")))];
+        lines.push(Line::from(match self.rtt_ms {
+            Some(ms) => format!("Ping {} ms", ms),
+            None => "Ping -".to_string(),
+        }));
+        if let Some(series) = self.state.as_ref().and_then(|s| s.series.as_ref()) {
+            lines.push(Line::from(format!("Round {}, best of {}", series.round, series.best_of)));
+        }
+        if let Some(state) = &self.state {
+            lines.push(Line::default());
+            let mut players: Vec<_> = state.players.iter().collect();
+            players.sort_by_key(|p| std::cmp::Reverse(p.score));
+            for player in players {
+                let mut style = Style::default();
+                if Some(player.id) == self.me {
+                    style = style.add_modifier(Modifier::BOLD);
+                }
+                if player.dead || !player.connected {
+                    style = style.fg(Color::DarkGray);
+                }
+                lines.push(Line::from(vec![
+                    Span::styled("██ ", Style::default().fg(snake_color(player.color, player.team))),
+                    Span::styled(format!("{:<16}{:>5}", player.name, player.score), style),
+                ]));
+            }
+        }
+        if !self.news.is_empty() {
+            lines.push(Line::default());
+            lines.extend(self.news.iter().map(|(text, color, _)| Line::styled(text.clone(), Style::default().fg(*color))));
+        }
+        lines
+    }
+}
+
+fn snake_color(color: u8, team: Option<u8>) -> Color {
+    match team {
+        Some(team) => TEAM_COLORS[team as usize % TEAM_COLORS.len()],
+        None => COLORS[color as usize % COLORS.len()],
+    }
+}
+
+// A box in the middle of `area` for the lines, over whatever is drawn there
+fn popup(frame: &mut Frame, area: Rect, lines: Vec<Line>) {
+    let width = (lines.iter().map(Line::width).max().unwrap_or(0) as u16 + 4).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let rect = Rect { x: area.x + (area.width - width) / 2, y: area.y + (area.height - height) / 2, width, height };
+    frame.render_widget(Clear, rect);
+    frame.render_widget(Paragraph::new(lines).centered().block(Block::bordered()), rect);
+}
+
+// Plays until the player quits, or returns why the connection ended
+fn run(terminal: &mut DefaultTerminal, game: &mut Game) -> io::Result<Option<String>> {
+    loop {
+        if let Some(reason) = game.update() {
+            return Ok(Some(reason));
+        }
+        terminal.draw(|frame| game.draw(frame))?;
+        if !event::poll(FRAME)? {
+            continue;
+        }
+        // take every key pressed since the last frame
+        loop {
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+                && !game.key(key)
+            {
+                return Ok(None);
+            }
+            if !event::poll(Duration::ZERO)? {
+                break;
+            }
+        }
+    }
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("usage: tui [--addr HOST:PORT] [--name NAME] [--room CODE] [--room-password PASSWORD] [--mode NAME] [--no-wrap] [--json] [--udp] [--compress deflate|lz4]");
+            std::process::exit(2);
+        }
+    };
+    let mut game = Game::new(connect(&args), &args);
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &mut game);
+    ratatui::restore();
+    match result {
+        Ok(None) => {}
+        Ok(Some(reason)) => {
+            eprintln!("{}", reason);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Terminal error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
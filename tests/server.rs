@@ -0,0 +1,453 @@
+// Runs the server binary on a free port, or the server in this process as a hosting client
+// does, and plays scripted clients against it over real sockets, so a change to the protocol
+// or the tick loop that breaks a match shows up here.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use snake::game_core::codec;
+use snake::game_core::sim::MatchConfig;
+use snake::game_core::{ClientMsg, Compression, Direction, ErrorCode, MatchPhase, ServerMsg, StateMsg, WireFormat, PROTOCOL_VERSION};
+
+// A quick tick keeps a few hundred of them within seconds
+const SETTINGS: &str = "\
+tick_ms = 20
+max_players = 2
+food_count = 3
+wrap = true
+mode = \"classic\"
+countdown = 1
+";
+
+struct Server {
+    child: Child,
+    addr: SocketAddr,
+    dir: PathBuf,
+}
+
+impl Server {
+    fn start(settings: &str) -> Server {
+        Server::start_on(settings, "127.0.0.1")
+    }
+
+    // Starts the server with `settings` as its config file, listening on `bind`, and waits
+    // until it listens
+    fn start_on(settings: &str, bind: &str) -> Server {
+        static RUNS: AtomicU32 = AtomicU32::new(0);
+        let run = RUNS.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("snake-server-test-{}-{}", std::process::id(), run));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("server.toml"), settings).unwrap();
+        let path = |file: &str| dir.join(file).to_str().unwrap().to_string();
+        let mut child = Command::new(env!("CARGO_BIN_EXE_server"))
+            .args(["--config", &path("server.toml"), "--bind", bind, "--port", "0", "--seed", "7", "--log-json"])
+            .args(["--stats", &path("stats.json"), "--accounts", &path("accounts.json"), "--bans", &path("bans.json")])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        // with port 0 the log is the only place that says which port it got
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        let addr = loop {
+            let line = lines.next().expect("server exited before listening").unwrap();
+            let log: serde_json::Value = serde_json::from_str(&line).unwrap();
+            if let Some(addr) = log["fields"]["message"].as_str().and_then(|m| m.strip_prefix("Server listening on ")) {
+                break addr.parse().unwrap();
+            }
+        };
+        // keep reading so the server never blocks on a full pipe
+        thread::spawn(move || lines.for_each(drop));
+        Server { child, addr, dir }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    format: WireFormat,
+    compression: Compression,
+    seq: u32,
+}
+
+impl Client {
+    // Connects and shakes hands; the server answers in the format and compression asked for
+    fn connect(addr: SocketAddr, format: WireFormat, compression: Compression) -> Client {
+        let stream = TcpStream::connect(addr).unwrap();
+        // a server that stops answering fails the test rather than hanging it
+        stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        let mut client = Client { reader: BufReader::new(stream.try_clone().unwrap()), writer: stream, format, compression: Compression::None, seq: 0 };
+        let hello = ClientMsg::Hello { protocol_version: PROTOCOL_VERSION, format, udp: false, compression };
+        codec::write_msg(&mut client.writer, WireFormat::Json, &hello).unwrap();
+        let frame = codec::read_frame(&mut client.reader, WireFormat::Json).unwrap().unwrap();
+        match codec::decode(WireFormat::Json, &frame).unwrap() {
+            ServerMsg::Welcome { protocol_version, format: agreed, compression: packed, .. } => {
+                assert_eq!(protocol_version, PROTOCOL_VERSION);
+                assert_eq!(agreed, format);
+                assert_eq!(packed, compression);
+                client.compression = packed;
+            }
+            other => panic!("expected a Welcome, got {:?}", other),
+        }
+        client
+    }
+
+    fn send(&mut self, msg: &ClientMsg) {
+        codec::write_msg(&mut self.writer, self.format, msg).unwrap();
+        self.writer.flush().unwrap();
+    }
+
+    fn input(&mut self, dir: Direction) -> u32 {
+        self.seq += 1;
+        self.send(&ClientMsg::Input { dir, seq: self.seq });
+        self.seq
+    }
+
+    fn recv(&mut self) -> ServerMsg {
+        let frame = codec::read_packed_frame(&mut self.reader, self.format, self.compression).unwrap().expect("server closed the connection");
+        codec::decode(self.format, &frame).unwrap()
+    }
+
+    // Reads until `pick` takes a message, skipping everything else
+    fn wait_for<T>(&mut self, mut pick: impl FnMut(ServerMsg) -> Option<T>) -> T {
+        loop {
+            if let Some(found) = pick(self.recv()) {
+                return found;
+            }
+        }
+    }
+
+    fn next_state(&mut self) -> StateMsg {
+        self.wait_for(|msg| match msg {
+            ServerMsg::State(state) => Some(state),
+            ServerMsg::Error(e) => panic!("server error: {:?}", e),
+            ServerMsg::Kick { reason } => panic!("kicked: {}", reason),
+            _ => None,
+        })
+    }
+}
+
+#[test]
+fn two_players_play_a_match() {
+    let server = Server::start(SETTINGS);
+    let mut alice = Client::connect(server.addr, WireFormat::Binary, Compression::Deflate);
+    let mut bob = Client::connect(server.addr, WireFormat::Json, Compression::None);
+
+    alice.send(&ClientMsg::Join { name: "alice".into() });
+    alice.send(&ClientMsg::CreateRoom { mode: None, wrap: None, password: None });
+    let code = alice.wait_for(|msg| match msg {
+        ServerMsg::Session(session) => Some(session.room_code),
+        _ => None,
+    });
+    bob.send(&ClientMsg::Join { name: "bob".into() });
+    bob.send(&ClientMsg::JoinRoom { code: code.clone(), password: None });
+    let lobby = bob.wait_for(|msg| match msg {
+        ServerMsg::Lobby(lobby) if lobby.players.len() == 2 => Some(lobby),
+        _ => None,
+    });
+    assert_eq!(lobby.room_code, code);
+    alice.send(&ClientMsg::Ready { ready: true });
+    bob.send(&ClientMsg::Ready { ready: true });
+
+    // the round counts down before anything moves
+    let countdown = alice.next_state();
+    assert_eq!(countdown.phase, MatchPhase::Countdown);
+    let first = alice.wait_for(|msg| match msg {
+        ServerMsg::State(s) if s.phase == MatchPhase::Playing => Some(s),
+        _ => None,
+    });
+    assert_eq!(first.players.iter().map(|p| &p.snake).collect::<Vec<_>>(), countdown.players.iter().map(|p| &p.snake).collect::<Vec<_>>());
+
+    // both keep going straight: alice along the top half of the board, bob along the bottom,
+    // so on a wrapping board they never meet and the match runs as long as it's watched
+    let (alice_seq, bob_seq) = (alice.input(Direction::Right), bob.input(Direction::Left));
+    let (mut last_seq, mut last_tick) = (first.seq, first.tick);
+    let mut state = first;
+    while state.tick < 300 {
+        // pings keep the connections from looking idle
+        if state.tick.is_multiple_of(100) {
+            alice.send(&ClientMsg::Ping { sent_ms: state.tick, rtt_ms: None });
+            bob.send(&ClientMsg::Ping { sent_ms: state.tick, rtt_ms: None });
+        }
+        state = alice.next_state();
+        assert!(state.seq > last_seq && state.tick > last_tick, "snapshots go forward");
+        (last_seq, last_tick) = (state.seq, state.tick);
+    }
+    let seen_by_bob = bob.wait_for(|msg| match msg {
+        ServerMsg::State(s) if s.tick >= state.tick => Some(s),
+        _ => None,
+    });
+
+    assert_eq!(state.phase, MatchPhase::Playing);
+    assert_eq!(state.ack, alice_seq);
+    assert_eq!(seen_by_bob.ack, bob_seq);
+    assert_eq!(state.food_items.len(), 3);
+    let names: Vec<&str> = state.players.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["alice", "bob"]);
+    for (player, dir) in state.players.iter().zip([Direction::Right, Direction::Left]) {
+        assert!(!player.dead && player.connected, "{} is still playing", player.name);
+        assert_eq!(player.dir, dir);
+        // a snake starts one cell long and grows a cell per point
+        assert_eq!(player.snake.len() as u32, player.score + 1);
+    }
+    // both snakes stay on their own row the whole match
+    assert!(state.players.iter().all(|p| p.snake.iter().all(|c| c.y == p.snake[0].y)));
+    assert_ne!(state.players[0].snake[0].y, state.players[1].snake[0].y);
+
+    // the server holds the slot of a player who drops out, and tells the others
+    drop(bob);
+    alice.wait_for(|msg| match msg {
+        ServerMsg::Info(news) => (news == "bob lost connection").then_some(()),
+        _ => None,
+    });    // and the match pauses while it waits for them
+    let paused = alice.next_state();
+    assert_eq!(paused.phase, MatchPhase::Paused);
+}
+
+#[test]
+fn held_slots_keep_a_full_match_full() {
+    let server = Server::start(SETTINGS);
+    let mut alice = Client::connect(server.addr, WireFormat::Json, Compression::None);
+    let mut bob = Client::connect(server.addr, WireFormat::Json, Compression::None);
+    let session = |msg| match msg {
+        ServerMsg::Session(session) => Some(session),
+        _ => None,
+    };
+    alice.send(&ClientMsg::Join { name: "alice".into() });
+    alice.send(&ClientMsg::CreateRoom { mode: None, wrap: None, password: None });
+    let code = alice.wait_for(session).room_code;
+    bob.send(&ClientMsg::Join { name: "bob".into() });
+    bob.send(&ClientMsg::JoinRoom { code: code.clone(), password: None });
+    let token = bob.wait_for(session).token;
+    alice.send(&ClientMsg::Ready { ready: true });
+    bob.send(&ClientMsg::Ready { ready: true });
+    alice.next_state();
+
+    // bob's slot is held while bob is gone, so there's no room for carol
+    drop(bob);
+    alice.wait_for(|msg| match msg {
+        ServerMsg::Info(news) => (news == "bob lost connection").then_some(()),
+        _ => None,
+    });
+    let mut carol = Client::connect(server.addr, WireFormat::Json, Compression::None);
+    carol.send(&ClientMsg::Join { name: "carol".into() });
+    carol.send(&ClientMsg::JoinRoom { code: code.clone(), password: None });
+    let error = carol.wait_for(|msg| match msg {
+        ServerMsg::Error(error) => Some(error),
+        ServerMsg::Session(_) => panic!("carol got a third slot in a two player match"),
+        _ => None,
+    });
+    assert_eq!(error.code, ErrorCode::RoomFull);
+
+    // and it's still there for bob
+    let mut bob = Client::connect(server.addr, WireFormat::Json, Compression::None);
+    bob.send(&ClientMsg::Rejoin { token });
+    bob.send(&ClientMsg::JoinRoom { code, password: None });
+    assert_eq!(bob.wait_for(session).name, "bob");
+    let state = bob.next_state();
+    assert_eq!(state.players.len(), 2);
+}
+
+#[test]
+fn players_with_the_same_name_are_told_apart() {
+    let server = Server::start(SETTINGS);
+    let mut first = Client::connect(server.addr, WireFormat::Json, Compression::None);
+    let mut second = Client::connect(server.addr, WireFormat::Json, Compression::None);
+
+    first.send(&ClientMsg::Join { name: "  bob  ".into() });
+    first.send(&ClientMsg::CreateRoom { mode: None, wrap: None, password: None });
+    let session = first.wait_for(|msg| match msg {
+        ServerMsg::Session(session) => Some(session),
+        _ => None,
+    });
+    assert_eq!(session.name, "bob");
+    second.send(&ClientMsg::Join { name: "Bob".into() });
+    second.send(&ClientMsg::JoinRoom { code: session.room_code.clone(), password: None });
+    let renamed = second.wait_for(|msg| match msg {
+        ServerMsg::Session(session) => Some(session),
+        _ => None,
+    });
+    assert_eq!(renamed.name, "Bob (2)");
+    // and the lobby knows them by the ids they were given
+    let lobby = second.wait_for(|msg| match msg {
+        ServerMsg::Lobby(lobby) if lobby.players.len() == 2 => Some(lobby),
+        _ => None,
+    });
+    let ids: Vec<_> = lobby.players.iter().map(|p| (p.name.as_str(), p.id)).collect();
+    assert_eq!(ids, [("bob", session.player_id), ("Bob (2)", renamed.player_id)]);
+    assert_ne!(session.player_id, renamed.player_id);
+}
+
+#[test]
+fn guests_cant_take_a_registered_name_in_another_case() {
+    let server = Server::start(SETTINGS);
+    let mut alice = Client::connect(server.addr, WireFormat::Json, Compression::None);
+    alice.send(&ClientMsg::Register { name: "alice".into(), password: "hunter2".into() });
+    alice.send(&ClientMsg::Join { name: "alice".into() });
+    alice.send(&ClientMsg::CreateRoom { mode: None, wrap: None, password: None });
+    let code = alice.wait_for(|msg| match msg {
+        ServerMsg::Session(session) => Some(session.room_code),
+        _ => None,
+    });
+
+    let mut guest = Client::connect(server.addr, WireFormat::Json, Compression::None);
+    guest.send(&ClientMsg::Join { name: "ALICE".into() });
+    guest.send(&ClientMsg::JoinRoom { code, password: None });
+    let error = guest.wait_for(|msg| match msg {
+        ServerMsg::Error(error) => Some(error),
+        ServerMsg::Session(_) => panic!("a guest got in as ALICE"),
+        _ => None,
+    });
+    assert_eq!(error.code, ErrorCode::AuthRequired);
+}
+
+#[test]
+fn private_rooms_take_their_password() {
+    let server = Server::start(SETTINGS);
+    let mut alice = Client::connect(server.addr, WireFormat::Json, Compression::None);
+    alice.send(&ClientMsg::Join { name: "alice".into() });
+    alice.send(&ClientMsg::CreateRoom { mode: None, wrap: None, password: Some("hunter2".into()) });
+    let code = alice.wait_for(|msg| match msg {
+        ServerMsg::Session(session) => Some(session.room_code),
+        _ => None,
+    });
+
+    // the room list says it's private, and joining without the password or with a wrong one fails
+    let mut bob = Client::connect(server.addr, WireFormat::Json, Compression::None);
+    bob.send(&ClientMsg::ListRooms);
+    let rooms = bob.wait_for(|msg| match msg {
+        ServerMsg::RoomList(rooms) => Some(rooms),
+        _ => None,
+    });
+    assert!(rooms.iter().any(|room| room.code == code && room.locked));
+    for password in [None, Some("hunter3".to_string())] {
+        let mut bob = Client::connect(server.addr, WireFormat::Json, Compression::None);
+        bob.send(&ClientMsg::Join { name: "bob".into() });
+        bob.send(&ClientMsg::JoinRoom { code: code.clone(), password });
+        let error = bob.wait_for(|msg| match msg {
+            ServerMsg::Error(error) => Some(error),
+            _ => None,
+        });
+        assert_eq!(error.code, ErrorCode::WrongPassword);
+    }
+    bob.send(&ClientMsg::Join { name: "bob".into() });
+    bob.send(&ClientMsg::JoinRoom { code: code.clone(), password: Some("hunter2".into()) });
+    let session = bob.wait_for(|msg| match msg {
+        ServerMsg::Session(session) => Some(session),
+        ServerMsg::Error(error) => panic!("server error: {:?}", error),
+        _ => None,
+    });
+    assert_eq!(session.room_code, code);
+}
+
+#[test]
+fn password_guessers_are_locked_out() {
+    let server = Server::start(SETTINGS);
+    let mut alice = Client::connect(server.addr, WireFormat::Json, Compression::None);
+    alice.send(&ClientMsg::Join { name: "alice".into() });
+    alice.send(&ClientMsg::CreateRoom { mode: None, wrap: None, password: Some("hunter2".into()) });
+    let code = alice.wait_for(|msg| match msg {
+        ServerMsg::Session(session) => Some(session.room_code),
+        _ => None,
+    });
+
+    // each try is a new connection, and the right password stops working after a few wrong ones
+    let try_password = |password: &str| {
+        let mut mallory = Client::connect(server.addr, WireFormat::Json, Compression::None);
+        mallory.send(&ClientMsg::Join { name: "mallory".into() });
+        mallory.send(&ClientMsg::JoinRoom { code: code.clone(), password: Some(password.into()) });
+        mallory.wait_for(|msg| match msg {
+            ServerMsg::Error(error) => Some(Err(error)),
+            ServerMsg::Session(_) => Some(Ok(())),
+            _ => None,
+        })
+    };
+    for guess in 0..5 {
+        let error = try_password(&format!("guess{}", guess)).unwrap_err();
+        assert_eq!(error.code, ErrorCode::WrongPassword);
+    }
+    let error = try_password("hunter2").unwrap_err();
+    assert!(error.message.starts_with("Too many wrong passwords"), "{}", error.message);
+}
+
+#[test]
+fn queued_players_are_matched_into_a_room() {
+    let server = Server::start(SETTINGS);
+    let mut alice = Client::connect(server.addr, WireFormat::Json, Compression::None);
+    let mut bob = Client::connect(server.addr, WireFormat::Binary, Compression::None);
+
+    alice.send(&ClientMsg::Join { name: "alice".into() });
+    alice.send(&ClientMsg::QueueForMatch);
+    bob.send(&ClientMsg::Join { name: "bob".into() });
+    bob.send(&ClientMsg::QueueForMatch);
+    let session = |msg| match msg {
+        ServerMsg::Session(session) => Some(session),
+        _ => None,
+    };
+    let (a, b) = (alice.wait_for(session), bob.wait_for(session));
+    assert_eq!(a.room_code, b.room_code);
+    // matchmaking readied them both, so the match starts on its own
+    let state = alice.next_state();
+    assert_eq!(state.players.len(), 2);
+}
+
+#[test]
+fn other_protocol_versions_are_turned_away() {
+    let server = Server::start(SETTINGS);
+    let mut stream = TcpStream::connect(server.addr).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    let hello = ClientMsg::Hello { protocol_version: PROTOCOL_VERSION - 1, format: WireFormat::Json, udp: false, compression: Compression::None };
+    codec::write_msg(&mut stream, WireFormat::Json, &hello).unwrap();
+    let mut reader = BufReader::new(stream);
+    let frame = codec::read_frame(&mut reader, WireFormat::Json).unwrap().unwrap();
+    match codec::decode(WireFormat::Json, &frame).unwrap() {
+        ServerMsg::VersionMismatch { server_version } => assert_eq!(server_version, PROTOCOL_VERSION),
+        other => panic!("expected VersionMismatch, got {:?}", other),
+    }
+    // and the connection is closed after it
+    assert!(codec::read_frame(&mut reader, WireFormat::Json).unwrap().is_none());
+}
+
+#[test]
+fn a_server_on_the_ipv6_wildcard_takes_ipv4_too() {
+    let server = Server::start_on(SETTINGS, "::");
+    let port = server.addr.port();
+    for ip in [IpAddr::from(Ipv4Addr::LOCALHOST), IpAddr::from(Ipv6Addr::LOCALHOST)] {
+        let mut client = Client::connect(SocketAddr::new(ip, port), WireFormat::Json, Compression::None);
+        client.send(&ClientMsg::Join { name: "alice".into() });
+        client.send(&ClientMsg::CreateRoom { mode: None, wrap: None, password: None });
+        client.wait_for(|msg| match msg {
+            ServerMsg::Lobby(_) => Some(()),
+            _ => None,
+        });
+    }
+}
+
+#[test]
+fn a_hosted_server_plays_in_process() {
+    // what a client's Host game starts, on any free port
+    let addr = snake::server::host(0, MatchConfig::default()).unwrap();
+    let mut alice = Client::connect((Ipv4Addr::LOCALHOST, addr.port()).into(), WireFormat::Json, Compression::None);
+    alice.send(&ClientMsg::Join { name: "alice".into() });
+    alice.send(&ClientMsg::CreateRoom { mode: None, wrap: None, password: None });
+    let lobby = alice.wait_for(|msg| match msg {
+        ServerMsg::Lobby(lobby) => Some(lobby),
+        _ => None,
+    });
+    assert_eq!(lobby.players.len(), 1);
+    assert_eq!(lobby.players[0].name, "alice");
+}
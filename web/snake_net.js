@@ -0,0 +1,69 @@
+// miniquad plugin giving the wasm client a WebSocket.
+// The socket carries the same byte stream as the native TCP connection, so point it at
+// a WebSocket-to-TCP bridge in front of the server, e.g.
+//   websockify 4001 127.0.0.1:4000
+// and connect to 127.0.0.1:4001 from the client's address field.
+
+var snake_ws = null;
+var snake_ws_outbox = [];
+var snake_ws_inbox = [];
+
+function snake_ws_bytes(ptr, len) {
+    return new Uint8Array(wasm_memory.buffer, ptr, len).slice();
+}
+
+miniquad_add_plugin({
+    name: "snake_net",
+    version: 1,
+    register_plugin: function (importObject) {
+        importObject.env.snake_ws_connect = function (url_ptr, url_len) {
+            var url = new TextDecoder().decode(snake_ws_bytes(url_ptr, url_len));
+            // one connection at a time: joining a game replaces the room-browser socket
+            if (snake_ws) {
+                snake_ws.close();
+            }
+            snake_ws_inbox = [];
+            snake_ws_outbox = [];
+            snake_ws = new WebSocket(url);
+            snake_ws.binaryType = "arraybuffer";
+            snake_ws.onopen = function () {
+                snake_ws_outbox.forEach(function (data) { snake_ws.send(data); });
+                snake_ws_outbox = [];
+            };
+            snake_ws.onmessage = function (event) {
+                var data = typeof event.data === "string"
+                    ? new TextEncoder().encode(event.data)
+                    : new Uint8Array(event.data);
+                snake_ws_inbox.push(data);
+            };
+        };
+        importObject.env.snake_ws_send = function (ptr, len) {
+            var data = snake_ws_bytes(ptr, len);
+            if (snake_ws && snake_ws.readyState === WebSocket.OPEN) {
+                snake_ws.send(data);
+            } else {
+                snake_ws_outbox.push(data);
+            }
+        };
+        importObject.env.snake_ws_state = function () {
+            if (!snake_ws || snake_ws.readyState === WebSocket.CONNECTING) {
+                return 0;
+            }
+            return snake_ws.readyState === WebSocket.OPEN ? 1 : 2;
+        };
+        importObject.env.snake_ws_recv = function (buf_ptr, cap) {
+            if (snake_ws_inbox.length === 0) {
+                return 0;
+            }
+            var chunk = snake_ws_inbox[0];
+            var n = Math.min(cap, chunk.length);
+            new Uint8Array(wasm_memory.buffer, buf_ptr, n).set(chunk.subarray(0, n));
+            if (n === chunk.length) {
+                snake_ws_inbox.shift();
+            } else {
+                snake_ws_inbox[0] = chunk.subarray(n);
+            }
+            return n;
+        };
+    },
+});